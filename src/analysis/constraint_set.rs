@@ -1,17 +1,25 @@
 //! Implements Constraint and ConstraintSet
 //!
-//! NOTE: Current implementation is limited to work with reference marking.
-//! However, this principle is quite general and can be extended as needed later on.
-//! This needs a bit more engineering (generics) to make it work for the general case.
-
-// This is what the current implementation solves. Extend to any generic lattice.
-// T_a = C
-//     = R
-//     = op1 U op2
-//
-//   C = op1 U op2 => op1 = C /\ op2 = C
-//   R = op1 U op2 => (op1 = R /\ op2 = C) \/ (op1 = C /\ op2 = R)
-//
+//! A `ConstraintSet` binds every variable to an element of a `Lattice` and
+//! solves equality, equivalence and union constraints over these bindings.
+//! Variables start out at the top of the lattice, i.e. nothing is known about
+//! them, and every constraint can only refine a binding by taking the meet
+//! with the value it implies. Once a binding reaches the bottom of the
+//! lattice the constraints are unsatisfiable and the constraint responsible
+//! is reported by `ConstraintSet::conflicts`.
+//!
+//! Constraints are solved with a worklist: whenever a binding changes, every
+//! constraint mentioning the variable is solved again. As bindings only ever
+//! move down, this terminates for lattices of finite height.
+//!
+//! The meaning of a union is given by the lattice, see `Lattice::union`. For
+//! `ValueType`, which is the default lattice and is used for reference marking,
+//! a union is pointer arithmetic: at most one of the operands is a reference.
+//! Subtype constraints are not supported, TIE solves them with
+//! `tie::structs::TypeConstraints`.
+//!
+//!   C = op1 U op2 => op1 = C /\ op2 = C
+//!   R = op1 U op2 => (op1 = R /\ op2 = C) \/ (op1 = C /\ op2 = R)
 
 use middle::ssa::ssa_traits::ValueType;
 use std::collections::hash_map;
@@ -19,31 +27,147 @@ use std::collections::{HashMap, VecDeque};
 use std::fmt::{Debug, Display, Error, Formatter};
 use std::hash::Hash;
 
+/// A lattice whose elements are bound to the variables of a `ConstraintSet`.
+pub trait Lattice: Clone + Debug + PartialEq {
+    /// Element that carries no information. Variables are bound to it until
+    /// a constraint says otherwise.
+    fn top() -> Self;
+
+    /// Element of conflicting information.
+    fn bottom() -> Self;
+
+    /// Greatest lower bound of `self` and `other`.
+    fn meet(&self, other: &Self) -> Self;
+
+    /// Least upper bound of `self` and `other`.
+    fn join(&self, other: &Self) -> Self;
+
+    /// Value of `op1 U op2`. Defaults to the join of both operands.
+    fn union(op1: &Self, op2: &Self) -> Self {
+        op1.join(op2)
+    }
+
+    /// Value of `op1` in `result = op1 U op2`, where `other` is the value of
+    /// `op2`. Nothing can be said about the operands by default.
+    fn union_operand(_result: &Self, _other: &Self) -> Self {
+        Self::top()
+    }
+}
+
+impl Lattice for ValueType {
+    fn top() -> ValueType {
+        ValueType::Unresolved
+    }
+
+    fn bottom() -> ValueType {
+        ValueType::Invalid
+    }
+
+    fn meet(&self, other: &ValueType) -> ValueType {
+        match (*self, *other) {
+            (ValueType::Unresolved, vt) | (vt, ValueType::Unresolved) => vt,
+            (vt1, vt2) if vt1 == vt2 => vt1,
+            _ => ValueType::Invalid,
+        }
+    }
+
+    fn join(&self, other: &ValueType) -> ValueType {
+        match (*self, *other) {
+            (ValueType::Invalid, vt) | (vt, ValueType::Invalid) => vt,
+            (vt1, vt2) if vt1 == vt2 => vt1,
+            _ => ValueType::Unresolved,
+        }
+    }
+
+    fn union(op1: &ValueType, op2: &ValueType) -> ValueType {
+        match (*op1, *op2) {
+            (ValueType::Invalid, _)
+            | (_, ValueType::Invalid)
+            | (ValueType::Reference, ValueType::Reference) => ValueType::Invalid,
+            (ValueType::Reference, _) | (_, ValueType::Reference) => ValueType::Reference,
+            (ValueType::Scalar, ValueType::Scalar) => ValueType::Scalar,
+            _ => ValueType::Unresolved,
+        }
+    }
+
+    fn union_operand(result: &ValueType, other: &ValueType) -> ValueType {
+        match (*result, *other) {
+            (ValueType::Invalid, _) | (_, ValueType::Invalid) => ValueType::Unresolved,
+            // At most one operand is a reference.
+            (_, ValueType::Reference) => ValueType::Scalar,
+            (vt, ValueType::Scalar) => vt,
+            (ValueType::Scalar, _) => ValueType::Scalar,
+            _ => ValueType::Unresolved,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub enum Constraint<I: Debug + Clone + Copy> {
+pub enum Constraint<I: Debug + Clone + Copy, L: Lattice = ValueType> {
     Union(I, I),
     // Solve eq. Equation of form, I = Constraint<I>
-    Equality(I, Box<Constraint<I>>),
+    Equality(I, Box<Constraint<I, L>>),
     // Equivalence. All elements in Vec<I> have the same value.
-    // Solve equivalence
     AssertEquivalence(Vec<I>),
-    // Solve assert
-    Assertion(Box<Constraint<I>>),
-    Or(Box<Constraint<I>>, Box<Constraint<I>>),
-    And(Box<Constraint<I>>, Box<Constraint<I>>),
     // No solve
-    Value(ValueType),
+    Value(L),
+}
+
+impl<I: Debug + Clone + Copy, L: Lattice> Constraint<I, L> {
+    /// Variables mentioned by this constraint.
+    pub fn vars(&self) -> Vec<I> {
+        match *self {
+            Constraint::Union(op1, op2) => vec![op1, op2],
+            Constraint::Equality(op, box ref c) => {
+                let mut vars = c.vars();
+                vars.insert(0, op);
+                vars
+            }
+            Constraint::AssertEquivalence(ref ops) => ops.clone(),
+            Constraint::Value(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConstraintSet<I: Eq + Debug + Hash + Copy, L: Lattice = ValueType> {
+    pub bindings: HashMap<I, L>,
+    // All constraints, in the order they were added.
+    set: Vec<Constraint<I, L>>,
+    // Indices of the constraints that have to be solved (again).
+    worklist: VecDeque<usize>,
+    queued: Vec<bool>,
+    // Indices of the constraints mentioning a variable.
+    uses: HashMap<I, Vec<usize>>,
+    // Indices of the constraints that made a binding bottom.
+    conflicts: Vec<usize>,
 }
 
-#[derive(Default, Debug)]
-pub struct ConstraintSet<I: Eq + Debug + Hash + Copy> {
-    pub bindings: HashMap<I, ValueType>,
-    set: VecDeque<Constraint<I>>,
+impl<I: Eq + Debug + Hash + Copy, L: Lattice> Default for ConstraintSet<I, L> {
+    fn default() -> ConstraintSet<I, L> {
+        ConstraintSet {
+            bindings: HashMap::new(),
+            set: Vec::new(),
+            worklist: VecDeque::new(),
+            queued: Vec::new(),
+            uses: HashMap::new(),
+            conflicts: Vec::new(),
+        }
+    }
 }
 
-impl<T: Clone + Debug + Hash + Eq + Copy> ConstraintSet<T> {
-    pub fn add_constraint(&mut self, c: Constraint<T>) {
-        self.set.push_back(c);
+impl<T: Clone + Debug + Hash + Eq + Copy, L: Lattice> ConstraintSet<T, L> {
+    pub fn add_constraint(&mut self, c: Constraint<T, L>) {
+        let idx = self.set.len();
+        for var in c.vars() {
+            let uses = self.uses.entry(var).or_insert_with(Vec::new);
+            if !uses.contains(&idx) {
+                uses.push(idx);
+            }
+        }
+        self.set.push(c);
+        self.queued.push(true);
+        self.worklist.push_back(idx);
     }
 
     pub fn add_union(&mut self, lhs: T, ops: &[T]) {
@@ -65,7 +189,7 @@ impl<T: Clone + Debug + Hash + Eq + Copy> ConstraintSet<T> {
         }
     }
 
-    pub fn add_eq(&mut self, lhs: T, vt: ValueType) {
+    pub fn add_eq(&mut self, lhs: T, vt: L) {
         self.add_constraint(Constraint::Equality(lhs, Box::new(Constraint::Value(vt))));
     }
 
@@ -74,178 +198,111 @@ impl<T: Clone + Debug + Hash + Eq + Copy> ConstraintSet<T> {
     }
 
     // Retrive binding value for `bind` or insert default if none exist
-    pub fn bvalue(&mut self, bind: T) -> ValueType {
-        *self.bindings.entry(bind).or_insert(ValueType::Unresolved)
+    pub fn bvalue(&mut self, bind: T) -> L {
+        self.bindings.entry(bind).or_insert_with(L::top).clone()
     }
 
+    /// Solves the constraints added since the last call. Returns whether any
+    /// binding changed.
     pub fn solve(&mut self) -> bool {
-        let mut first_false = 0;
         let mut made_progress = false;
-        while !self.set.is_empty() {
-            // See if we went a complete circle. If we did, this means that the equations in the
-            // set are no longer solvable.
-            if first_false > self.set.len() {
-                break;
+        while let Some(idx) = self.worklist.pop_front() {
+            self.queued[idx] = false;
+            // Only report the constraint where the conflict arises, not the ones it is
+            // propagated through.
+            let consistent = self.set[idx]
+                .vars()
+                .iter()
+                .all(|var| self.bindings.get(var) != Some(&L::bottom()));
+            let changed = self.solve_constraint(idx);
+            if consistent
+                && changed.iter().any(|var| self.bindings[var] == L::bottom())
+                && !self.conflicts.contains(&idx)
+            {
+                self.conflicts.push(idx);
             }
-
-            let constraint = self.set.pop_front().expect("Cannot be `None`");
-            // Match to see if this is an Eq constraint or an assert
-            let res = match constraint {
-                Constraint::Equality(_, box _) => self.solve_eq(&constraint),
-                Constraint::Assertion(_) => self.solve_assert(&constraint),
-                Constraint::AssertEquivalence(_) => self.solve_equivalence(&constraint),
-                _ => false,
-            };
-
-            made_progress |= res;
-
-            if !res {
-                // It wasn't solved. Push it back in.
-                self.set.push_back(constraint);
-                first_false += 1;
-            } else {
-                first_false = 0;
+            for var in changed {
+                made_progress = true;
+                for &user in &self.uses[&var] {
+                    if !self.queued[user] {
+                        self.queued[user] = true;
+                        self.worklist.push_back(user);
+                    }
+                }
             }
         }
         made_progress
     }
 
-    fn solve_equivalence(&mut self, constraint: &Constraint<T>) -> bool {
-        if let &Constraint::AssertEquivalence(ref list) = constraint {
-            if let Some(found) = list.iter().find(|&var| {
-                // Check if any var has a known value
-                self.bvalue(*var) != ValueType::Unresolved
-            }) {
-                let vt = self.bvalue(*found);
-                for var in list {
-                    self.bindings.insert(*var, vt);
-                }
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+    /// Returns whether no binding is bottom.
+    pub fn is_satisfiable(&self) -> bool {
+        self.conflicts.is_empty()
     }
 
-    fn solve_eq(&mut self, constraint: &Constraint<T>) -> bool {
-        match *constraint {
-            Constraint::Equality(ni, box ref cs) => {
-                match *cs {
-                    Constraint::Value(vt) => {
-                        self.bindings.insert(ni, vt);
-                        true
-                    }
-                    Constraint::Union(op1, op2) => {
-                        match self.bvalue(ni) {
-                            ValueType::Scalar => {
-                                // Both, op1 and op2, are scalars
-                                self.bindings.insert(op1, ValueType::Scalar);
-                                self.bindings.insert(op2, ValueType::Scalar);
-                                true
-                            }
-                            ValueType::Reference => {
-                                // Assert some facts
-                                let cons = Constraint::Or(
-                                    Box::new(Constraint::And(
-                                        Box::new(Constraint::Equality(
-                                            op1,
-                                            Box::new(Constraint::Value(ValueType::Reference)),
-                                        )),
-                                        Box::new(Constraint::Equality(
-                                            op2,
-                                            Box::new(Constraint::Value(ValueType::Scalar)),
-                                        )),
-                                    )),
-                                    Box::new(Constraint::And(
-                                        Box::new(Constraint::Equality(
-                                            op2,
-                                            Box::new(Constraint::Value(ValueType::Reference)),
-                                        )),
-                                        Box::new(Constraint::Equality(
-                                            op1,
-                                            Box::new(Constraint::Value(ValueType::Scalar)),
-                                        )),
-                                    )),
-                                );
-                                self.set.push_back(Constraint::Assertion(Box::new(cons)));
-                                true
-                            }
-                            ValueType::Unresolved => {
-                                // Look at the Union
-                                let op1_vt = self.bvalue(op1);
-                                let op2_vt = self.bvalue(op2);
-                                let (vt, retval) = match (op1_vt, op2_vt) {
-                                    (ValueType::Invalid, _)
-                                    | (_, ValueType::Invalid)
-                                    | (ValueType::Reference, ValueType::Reference) => {
-                                        (ValueType::Invalid, true)
-                                    }
-
-                                    (ValueType::Reference, _) => {
-                                        self.bindings.insert(op2, ValueType::Scalar);
-                                        (ValueType::Reference, true)
-                                    }
-
-                                    (_, ValueType::Reference) => {
-                                        self.bindings.insert(op1, ValueType::Scalar);
-                                        (ValueType::Reference, true)
-                                    }
-
-                                    (ValueType::Scalar, ValueType::Scalar) => {
-                                        (ValueType::Scalar, true)
-                                    }
-                                    (_, _) => (ValueType::Unresolved, false),
-                                };
-                                self.bindings.insert(ni, vt);
-                                retval
-                            }
-                            _ => false,
-                        }
-                    }
-                    _ => false,
+    /// Constraints that made a binding bottom, in the order they were solved.
+    pub fn conflicts(&self) -> Vec<&Constraint<T, L>> {
+        self.conflicts.iter().map(|&idx| &self.set[idx]).collect()
+    }
+
+    /// Constraints that still mention a variable bound to top.
+    pub fn unresolved(&self) -> Vec<&Constraint<T, L>> {
+        self.set
+            .iter()
+            .filter(|c| {
+                c.vars()
+                    .iter()
+                    .any(|var| self.bindings.get(var).map_or(true, |v| *v == L::top()))
+            })
+            .collect()
+    }
+
+    // Solves the constraint `idx` and returns the variables whose binding changed.
+    fn solve_constraint(&mut self, idx: usize) -> Vec<T> {
+        let mut changed = Vec::new();
+        match self.set[idx].clone() {
+            Constraint::Equality(ni, box Constraint::Value(ref vt)) => {
+                self.refine(ni, vt, &mut changed);
+            }
+            Constraint::Equality(ni, box Constraint::Union(op1, op2)) => {
+                let union = L::union(&self.bvalue(op1), &self.bvalue(op2));
+                self.refine(ni, &union, &mut changed);
+                let result = self.bvalue(ni);
+                let op1_vt = L::union_operand(&result, &self.bvalue(op2));
+                self.refine(op1, &op1_vt, &mut changed);
+                let op2_vt = L::union_operand(&result, &self.bvalue(op1));
+                self.refine(op2, &op2_vt, &mut changed);
+            }
+            Constraint::AssertEquivalence(ref list) => {
+                let vt = list
+                    .iter()
+                    .fold(L::top(), |acc, &var| acc.meet(&self.bvalue(var)));
+                for &var in list {
+                    self.refine(var, &vt, &mut changed);
                 }
             }
-            _ => false,
+            _ => {}
         }
+        changed
     }
 
-    fn solve_assert(&mut self, constraint: &Constraint<T>) -> bool {
-        // Will be of the form, Assertion(<inner>)
-        // inner will be of the form Or(<c1>, <c2>)
-        if let &Constraint::Assertion(box Constraint::Or(box ref c1, box ref c2)) = constraint {
-            // c1, c2 will be of the form And(Eq(ci1), Eq(ci2))
-            [c1, c2].iter().any(|&ci| {
-                if let Constraint::And(
-                    box Constraint::Equality(op1, box Constraint::Value(vt1)),
-                    box Constraint::Equality(op2, box Constraint::Value(vt2)),
-                ) = *ci
-                {
-                    if self.bvalue(op1) == vt1 {
-                        self.bindings.insert(op2, vt2);
-                        true
-                    } else if self.bvalue(op2) == vt2 {
-                        self.bindings.insert(op1, vt1);
-                        true
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            })
-        } else {
-            false
+    // Binds `var` to the meet of its binding and `vt`.
+    fn refine(&mut self, var: T, vt: &L, changed: &mut Vec<T>) {
+        let old = self.bvalue(var);
+        let new = old.meet(vt);
+        if new != old {
+            self.bindings.insert(var, new);
+            if !changed.contains(&var) {
+                changed.push(var);
+            }
         }
     }
 
-    pub fn iter_bindings<'a>(&'a self) -> hash_map::Iter<'a, T, ValueType> {
+    pub fn iter_bindings<'a>(&'a self) -> hash_map::Iter<'a, T, L> {
         self.bindings.iter()
     }
 }
 
-impl<T: Debug + Clone + Copy> Display for Constraint<T> {
+impl<T: Debug + Clone + Copy, L: Lattice> Display for Constraint<T, L> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         let s = match *self {
             Constraint::Union(op1, op2) => format!("{:?} U {:?}", op1, op2),
@@ -256,16 +313,13 @@ impl<T: Debug + Clone + Copy> Display for Constraint<T> {
                     acc
                 })
             }
-            Constraint::Assertion(box ref op) => format!("Assert {}", op),
-            Constraint::Or(box ref op1, box ref op2) => format!("{} \\/ {}", op1, op2),
-            Constraint::And(box ref op1, box ref op2) => format!("{} /\\ {}", op1, op2),
             Constraint::Value(ref vt) => format!("{:?}", vt),
         };
         write!(f, "{}", s)
     }
 }
 
-impl<T: Clone + Debug + Hash + Eq + Copy> Display for ConstraintSet<T> {
+impl<T: Clone + Debug + Hash + Eq + Copy, L: Lattice> Display for ConstraintSet<T, L> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         // First Bindings information.
         let mut display_str = String::new();
@@ -280,6 +334,14 @@ impl<T: Clone + Debug + Hash + Eq + Copy> Display for ConstraintSet<T> {
             display_str.push_str("\n");
         }
 
+        if !self.conflicts.is_empty() {
+            display_str.push_str("-----------------------\nConflicts:\n");
+            for constraint in self.conflicts() {
+                display_str.push_str(&constraint.to_string());
+                display_str.push_str("\n");
+            }
+        }
+
         write!(f, "{}", display_str)
     }
 }
@@ -410,6 +472,20 @@ mod test {
 
         cs.solve();
 
-        assert!(!cs.set.is_empty());
+        assert_eq!(cs.unresolved().len(), 1);
+    }
+
+    #[test]
+    fn conflicting_values_are_reported() {
+        let mut cs = ConstraintSet::<u64>::default();
+
+        cs.add_eq(0, ValueType::Reference);
+        cs.add_equivalence_assertion(&[0, 1]);
+        cs.add_eq(1, ValueType::Scalar);
+        cs.solve();
+
+        assert!(!cs.is_satisfiable());
+        assert_eq!(cs.bindings[&0], ValueType::Invalid);
+        assert_eq!(cs.conflicts().len(), 1);
     }
 }
@@ -0,0 +1,598 @@
+//! Store-to-load forwarding and dead store elimination.
+//!
+//! Memory is a single SSA value threaded through every `OpStore`, `OpLoad` and
+//! `OpCall`, so `dce` has to keep every store. The passes in this module tell
+//! the accessed locations apart, using the stack offsets computed by
+//! `interproc::digstack` for addresses relative to the stack pointer and the
+//! value of constant addresses otherwise.
+//!
+//! Unless the address of a stack slot escapes (is stored, passed to a call in
+//! any register but the stack pointer or used in arithmetic that cannot be
+//! followed), the stack frame can only be accessed through the tracked
+//! addresses. Calls are assumed to access the stack frame of their caller only
+//! through escaped addresses and through the arguments stored right before
+//! them, at or above the stack pointer they are passed.
+
+use analysis::interproc::digstack;
+use middle::ir::MOpcode;
+use middle::ssa::cfg_traits::CFG;
+use middle::ssa::ssa_traits::*;
+use middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::{HashMap, HashSet};
+
+/// Replaces every load that reads a value stored to the same location with the
+/// stored value. Returns the number of loads that were replaced.
+pub fn forward_stores(ssa: &mut SSAStorage, sp_name: &str, bp_name: &str) -> usize {
+    let frame = Frame::new(ssa, sp_name, bp_name);
+    let loads: Vec<NodeIndex> = ssa
+        .inorder_walk()
+        .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+        .collect();
+
+    let mut forwarded = 0;
+    for load in loads {
+        // Values are looked up one at a time, as forwarding a load may change
+        // the value stored by a later store.
+        if let Some(value) = frame.forwarded_value(ssa, load) {
+            radeco_trace!("dead_stores|Forward {:?} to {:?}", value, load);
+            ssa.replace_value(load, value);
+            forwarded += 1;
+        }
+    }
+    forwarded
+}
+
+/// Removes every store whose value can never be read, either because the
+/// location is always overwritten first or because it is a local stack slot
+/// that is not read before the function returns. Returns the number of stores
+/// that were removed.
+pub fn remove_dead_stores(ssa: &mut SSAStorage, sp_name: &str, bp_name: &str) -> usize {
+    let frame = Frame::new(ssa, sp_name, bp_name);
+    let exit_state = ssa.exit_node().and_then(|exit| ssa.registers_in(exit));
+    let dead: Vec<NodeIndex> = ssa
+        .inorder_walk()
+        .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+        .filter(|&n| frame.is_dead(ssa, n, exit_state))
+        .collect();
+
+    for &store in &dead {
+        radeco_trace!("dead_stores|Remove {:?}", store);
+        // Operands are looked up now, as the memory read by this store may
+        // have been a dead store itself.
+        let mem = ssa.operands_of(store)[0];
+        ssa.replace_value(store, mem);
+    }
+    dead.len()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Base {
+    /// Offset from the stack pointer at the entry of the function.
+    Stack,
+    /// Absolute address.
+    Absolute,
+}
+
+/// Range of memory accessed by a load or a store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    base: Base,
+    offset: i64,
+    /// Size in bytes, if known.
+    size: Option<u64>,
+}
+
+impl Location {
+    fn end(&self, size: u64) -> i64 {
+        self.offset.wrapping_add(size as i64)
+    }
+
+    fn overlaps(&self, other: &Location) -> bool {
+        if self.base != other.base {
+            return false;
+        }
+        match (self.size, other.size) {
+            (Some(a), Some(b)) => self.offset < other.end(b) && other.offset < self.end(a),
+            _ => true,
+        }
+    }
+
+    fn covers(&self, other: &Location) -> bool {
+        match (self.size, other.size) {
+            (Some(a), Some(b)) => {
+                self.base == other.base
+                    && self.offset <= other.offset
+                    && other.end(b) <= self.end(a)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this is a slot in the stack frame of the function, which is
+    /// freed when it returns.
+    fn is_local(&self) -> bool {
+        match (self.base, self.size) {
+            (Base::Stack, Some(size)) => self.end(size) <= 0,
+            _ => false,
+        }
+    }
+}
+
+/// Stack layout of a function.
+struct Frame {
+    /// Operand of a call that the stack pointer is passed in.
+    sp_slot: Option<u8>,
+    /// Offset from the stack pointer at the entry of every value that is a
+    /// stack address.
+    offsets: HashMap<NodeIndex, i64>,
+    /// Whether a stack address is used in a way that is not tracked.
+    escaped: bool,
+}
+
+impl Frame {
+    fn new(ssa: &SSAStorage, sp_name: &str, bp_name: &str) -> Frame {
+        let mut offsets = digstack::rounded_analysis(ssa, sp_name.to_owned(), bp_name.to_owned());
+
+        // `digstack` only follows the values of SP and BP, follow the address
+        // arithmetic on them as well.
+        let nodes: Vec<NodeIndex> = ssa.inorder_walk().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &node in &nodes {
+                if offsets.contains_key(&node) {
+                    continue;
+                }
+                if let Some(offset) = derived_offset(ssa, &offsets, node) {
+                    offsets.insert(node, offset);
+                    changed = true;
+                }
+            }
+        }
+
+        // Call operands are numbered after the registers they are passed in.
+        let sp_slot = ssa
+            .regfile
+            .register_id_by_name(sp_name)
+            .map(|id| id.to_u8() + 1);
+        let escaped = offsets.keys().any(|&n| escapes(ssa, &offsets, sp_slot, n));
+        radeco_trace!("dead_stores|Stack addresses escape: {}", escaped);
+        Frame {
+            sp_slot: sp_slot,
+            offsets: offsets,
+            escaped: escaped,
+        }
+    }
+
+    /// Location accessed by the load or store `node`, if it is known.
+    fn location(&self, ssa: &SSAStorage, node: NodeIndex) -> Option<Location> {
+        let operands = ssa.operands_of(node);
+        let addr = *operands.get(1)?;
+        let value = if ssa.opcode(node) == Some(MOpcode::OpStore) {
+            *operands.get(2)?
+        } else {
+            node
+        };
+        let size = ssa
+            .node_data(value)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .map(|w| (w as u64 + 7) / 8);
+        if let Some(&offset) = self.offsets.get(&addr) {
+            Some(Location {
+                base: Base::Stack,
+                offset: offset,
+                size: size,
+            })
+        } else if let Some(addr) = ssa.constant(addr) {
+            Some(Location {
+                base: Base::Absolute,
+                offset: addr as i64,
+                size: size,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn may_alias(&self, a: Option<Location>, b: Option<Location>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.overlaps(&b),
+            (Some(l), None) | (None, Some(l)) => l.base == Base::Absolute || self.escaped,
+            (None, None) => true,
+        }
+    }
+
+    /// Offset of the stack pointer passed to `call`.
+    fn call_sp(&self, ssa: &SSAStorage, call: NodeIndex) -> Option<i64> {
+        let sp_slot = self.sp_slot?;
+        ssa.sparse_operands_of(call)
+            .into_iter()
+            .find(|&(slot, _)| slot == sp_slot)
+            .and_then(|(_, op)| self.offsets.get(&op).cloned())
+    }
+
+    /// Whether `call` may overwrite `loc`.
+    fn call_writes(&self, ssa: &SSAStorage, call: NodeIndex, loc: &Location) -> bool {
+        if loc.base == Base::Absolute || self.escaped {
+            return true;
+        }
+        // Everything below the stack pointer is overwritten by the frame of
+        // the callee.
+        self.call_sp(ssa, call).map_or(true, |sp| loc.offset < sp)
+    }
+
+    /// Whether `call` may read `loc`. `after_call` is set if another call is
+    /// executed between the store to `loc` and `call`.
+    fn call_reads(
+        &self,
+        ssa: &SSAStorage,
+        call: NodeIndex,
+        loc: &Location,
+        after_call: bool,
+    ) -> bool {
+        if loc.base == Base::Absolute || self.escaped {
+            return true;
+        }
+        match self.call_sp(ssa, call) {
+            Some(sp) => !after_call && loc.offset >= sp,
+            None => true,
+        }
+    }
+
+    /// The value stored to the location read by `load`, if it is the same on
+    /// every path.
+    fn forwarded_value(&self, ssa: &SSAStorage, load: NodeIndex) -> Option<NodeIndex> {
+        let loc = self.location(ssa, load)?;
+        loc.size?;
+        let mut mem = *ssa.operands_of(load).get(0)?;
+        loop {
+            match ssa.opcode(mem) {
+                Some(MOpcode::OpStore) => {
+                    let operands = ssa.operands_of(mem);
+                    let stored = self.location(ssa, mem);
+                    if stored == Some(loc) {
+                        return operands.get(2).cloned();
+                    }
+                    if self.may_alias(stored, Some(loc)) {
+                        return None;
+                    }
+                    mem = *operands.get(0)?;
+                }
+                // Memory written by a call. Opaque instructions, memory phis and
+                // the memory at the entry are not looked through.
+                None if ssa.is_comment(mem) => {
+                    let call = *ssa.operands_of(mem).get(0)?;
+                    if ssa.opcode(call) != Some(MOpcode::OpCall)
+                        || self.call_writes(ssa, call, &loc)
+                    {
+                        return None;
+                    }
+                    mem = memory_operand(ssa, call)?;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Whether the value written by `store` is never read.
+    fn is_dead(&self, ssa: &SSAStorage, store: NodeIndex, exit_state: Option<NodeIndex>) -> bool {
+        let loc = match self.location(ssa, store) {
+            Some(loc) => loc,
+            None => return false,
+        };
+
+        let mut visited = HashSet::new();
+        let mut worklist = vec![(store, false)];
+        while let Some((mem, after_call)) = worklist.pop() {
+            if !visited.insert((mem, after_call)) {
+                continue;
+            }
+            for user in ssa.uses_of(mem) {
+                match ssa.opcode(user) {
+                    Some(MOpcode::OpLoad) => {
+                        if self.may_alias(Some(loc), self.location(ssa, user)) {
+                            return false;
+                        }
+                    }
+                    Some(MOpcode::OpStore) => {
+                        if ssa.operands_of(user).get(0) != Some(&mem) {
+                            return false;
+                        }
+                        let overwritten =
+                            self.location(ssa, user).map_or(false, |l| l.covers(&loc));
+                        if !overwritten {
+                            worklist.push((user, after_call));
+                        }
+                    }
+                    Some(MOpcode::OpCall) => {
+                        if self.call_reads(ssa, user, &loc, after_call) {
+                            return false;
+                        }
+                        for out in ssa.uses_of(user) {
+                            if ssa.is_comment(out) && is_memory(ssa, out) {
+                                worklist.push((out, true));
+                            }
+                        }
+                    }
+                    None if ssa.is_phi(user) => worklist.push((user, after_call)),
+                    _ if Some(user) == exit_state => {
+                        if !loc.is_local() {
+                            return false;
+                        }
+                    }
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Offset of `node` from the stack pointer at the entry, if it is computed from
+/// other stack addresses.
+fn derived_offset(
+    ssa: &SSAStorage,
+    offsets: &HashMap<NodeIndex, i64>,
+    node: NodeIndex,
+) -> Option<i64> {
+    let operands = ssa.operands_of(node);
+    let offset = |i: usize| operands.get(i).and_then(|op| offsets.get(op)).cloned();
+    let constant = |i: usize| operands.get(i).and_then(|&op| ssa.constant(op));
+    match ssa.opcode(node) {
+        Some(MOpcode::OpAdd) => match (offset(0), constant(1), constant(0), offset(1)) {
+            (Some(base), Some(c), _, _) | (_, _, Some(c), Some(base)) => {
+                Some(base.wrapping_add(c as i64))
+            }
+            _ => None,
+        },
+        Some(MOpcode::OpSub) => Some(offset(0)?.wrapping_sub(constant(1)? as i64)),
+        Some(MOpcode::OpMov) | Some(MOpcode::OpZeroExt(_)) | Some(MOpcode::OpNarrow(_)) => {
+            offset(0)
+        }
+        // Same as `digstack`, a phi takes the offset its known operands agree on.
+        None if ssa.is_phi(node) => {
+            let mut known = operands.iter().filter_map(|op| offsets.get(op));
+            let first = *known.next()?;
+            if known.all(|&o| o == first) {
+                Some(first)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the stack address `node` is used in a way that is not tracked.
+fn escapes(
+    ssa: &SSAStorage,
+    offsets: &HashMap<NodeIndex, i64>,
+    sp_slot: Option<u8>,
+    node: NodeIndex,
+) -> bool {
+    let exit_state = ssa.exit_node().and_then(|exit| ssa.registers_in(exit));
+    ssa.uses_of(node)
+        .into_iter()
+        .any(|user| match ssa.opcode(user) {
+            Some(MOpcode::OpLoad) => false,
+            // The callee may access anything reachable from an address it is
+            // passed, except through the stack pointer, see `call_sp`.
+            Some(MOpcode::OpCall) => ssa
+                .sparse_operands_of(user)
+                .into_iter()
+                .any(|(slot, op)| op == node && Some(slot) != sp_slot),
+            Some(MOpcode::OpStore) => ssa.operands_of(user).get(2) == Some(&node),
+            _ => !offsets.contains_key(&user) && Some(user) != exit_state,
+        })
+}
+
+fn is_memory(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    ssa.node_data(node)
+        .map(|nd| nd.vt.width().get_width() == Some(0))
+        .unwrap_or(false)
+}
+
+/// The memory read by `call`.
+fn memory_operand(ssa: &SSAStorage, call: NodeIndex) -> Option<NodeIndex> {
+    ssa.operands_of(call)
+        .into_iter()
+        .find(|&op| is_memory(ssa, op))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // Spills rdi to [rsp-8] and reloads it, stores rdi to a global and loads
+    // through rdi.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.spill(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rsp;
+        %2: $Unknown64(*?) = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x8):
+        [@0x000100.0001] %4: $Unknown64(*?) = %1 - #x8;
+        [@0x000100.0002] %5: $Unknown0 = Store(%3, %4, %2);
+        [@0x000100.0003] %6: $Unknown64(*?) = Load(%5, %4);
+        [@0x000100.0004] %7: $Unknown64(*?) = %6 + #x1;
+        [@0x000100.0005] %8: $Unknown0 = Store(%5, #x1000, %2);
+        [@0x000100.0006] %9: $Unknown64(*?) = Load(%8, %2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $rdx = %9;
+        $rsp = %1;
+        $mem = %8;
+}
+";
+
+    // Stores rdi to [rsp-8] and [rsp-16], calls with the stack pointer at
+    // rsp-8 and reloads [rsp-8].
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALL_TXT: &str = "\
+define-fun sym.call(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rsp;
+        %2: $Unknown64(*?) = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x10):
+        [@0x000100.0001] %4: $Unknown64(*?) = %1 - #x8;
+        [@0x000100.0002] %5: $Unknown64(*?) = %1 - #x10;
+        [@0x000100.0003] %6: $Unknown0 = Store(%3, %4, %2);
+        [@0x000100.0004] %7: $Unknown0 = Store(%6, %5, %2);
+        [@0x000100.0005] (%8: $Unknown0 = $mem) = CALL #x2000($rsp=%4, $mem=%7);
+        [@0x000100.0006] %9: $Unknown64(*?) = Load(%8, %4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %9;
+        $rsp = %1;
+        $mem = %8;
+}
+";
+
+    // Stores rdi to [rsp-16] and makes two calls, the first with the stack
+    // pointer at rsp-8, the second at rsp-24.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const AFTER_CALL_TXT: &str = "\
+define-fun sym.after_call(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rsp;
+        %2: $Unknown64(*?) = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x10):
+        [@0x000100.0001] %4: $Unknown64(*?) = %1 - #x8;
+        [@0x000100.0002] %5: $Unknown64(*?) = %1 - #x10;
+        [@0x000100.0003] %6: $Unknown0 = Store(%3, %5, %2);
+        [@0x000100.0004] (%7: $Unknown0 = $mem) = CALL #x2000($rsp=%4, $mem=%6);
+        [@0x000100.0005] %8: $Unknown64(*?) = %1 - #x18;
+        [@0x000100.0006] (%9: $Unknown0 = $mem) = CALL #x3000($rsp=%8, $mem=%7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rsp = %1;
+        $mem = %9;
+}
+";
+
+    fn load_ssa(txt: &str) -> SSAStorage {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        ir_reader::parse_il(txt, regfile)
+    }
+
+    fn count(ssa: &SSAStorage, opc: MOpcode) -> usize {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(opc))
+            .count()
+    }
+
+    #[test]
+    fn spill_is_removed() {
+        let mut ssa = load_ssa(SSA_TXT);
+        assert_eq!(forward_stores(&mut ssa, "rsp", "rbp"), 1);
+        assert_eq!(count(&ssa, MOpcode::OpLoad), 1);
+
+        // The global store stays. The spill is not read anymore, the address in
+        // rdi cannot point into the stack frame.
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 1);
+        let stores: Vec<NodeIndex> = ssa
+            .inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+            .collect();
+        assert_eq!(stores.len(), 1);
+        assert_eq!(ssa.constant(ssa.operands_of(stores[0])[1]), Some(0x1000));
+        assert_eq!(
+            ssa.comment(ssa.operands_of(stores[0])[0]),
+            Some("mem".to_owned())
+        );
+    }
+
+    #[test]
+    fn escaped_stack_is_kept() {
+        let txt = SSA_TXT.replace("Store(%5, #x1000, %2)", "Store(%5, #x1000, %4)");
+        let mut ssa = load_ssa(&txt);
+
+        // The reload is still forwarded, but the spill may be read through rdi
+        // once its address is stored to the global.
+        assert_eq!(forward_stores(&mut ssa, "rsp", "rbp"), 1);
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(count(&ssa, MOpcode::OpStore), 2);
+    }
+
+    fn stores(ssa: &SSAStorage) -> Vec<NodeIndex> {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+            .collect()
+    }
+
+    #[test]
+    fn call_arguments() {
+        let mut ssa = load_ssa(CALL_TXT);
+
+        // The callee only writes below the stack pointer it is passed.
+        assert_eq!(forward_stores(&mut ssa, "rsp", "rbp"), 1);
+        assert_eq!(count(&ssa, MOpcode::OpLoad), 0);
+
+        // [rsp-8] is passed to the callee, [rsp-16] is overwritten by its frame.
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 1);
+        let stores = stores(&ssa);
+        assert_eq!(stores.len(), 1);
+        assert_eq!(
+            ssa.comment(ssa.operands_of(stores[0])[0]),
+            Some("mem".to_owned())
+        );
+    }
+
+    #[test]
+    fn call_writes_passed_slot() {
+        // Like `scanf("%d", &x)`, the address of [rsp-8] is passed in rdi.
+        let txt = CALL_TXT.replace("$rsp=%4, $mem=%7", "$rsp=%4, $rdi=%4, $mem=%7");
+        let mut ssa = load_ssa(&txt);
+
+        // The callee may write the slot, and may read any slot through it.
+        assert_eq!(forward_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(count(&ssa, MOpcode::OpLoad), 1);
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(count(&ssa, MOpcode::OpStore), 2);
+    }
+
+    #[test]
+    fn call_escapes_other_slot() {
+        let txt = CALL_TXT.replace("$rsp=%4, $mem=%7", "$rsp=%4, $rsi=%5, $mem=%7");
+        let mut ssa = load_ssa(&txt);
+
+        assert_eq!(forward_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(count(&ssa, MOpcode::OpStore), 2);
+    }
+
+    #[test]
+    fn store_after_call() {
+        // The first call overwrites [rsp-16] before the second one is made.
+        let mut ssa = load_ssa(AFTER_CALL_TXT);
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 1);
+        assert_eq!(count(&ssa, MOpcode::OpStore), 0);
+
+        // Passed to the first call instead, which reads it.
+        let txt = AFTER_CALL_TXT.replace("$rsp=%4, $mem=%6", "$rsp=%5, $mem=%6");
+        let mut ssa = load_ssa(&txt);
+        assert_eq!(remove_dead_stores(&mut ssa, "rsp", "rbp"), 0);
+        assert_eq!(count(&ssa, MOpcode::OpStore), 1);
+    }
+}
@@ -1,16 +1,17 @@
 use super::CombinableOpConstInfo as COCI;
 use super::CombinableOpInfo as COI;
+use middle::ir::EvalResult;
 use middle::ir::MOpcode::*;
 
 macro_rules! gen_rules {
     (
-        . -> $sub_opinfo:ident -> $cur_opinfo:ident
+        . -> $sub_opinfo:ident -> $cur_opinfo:ident @ $width:ident
         {
             $( $lhs:tt => $rhs:tt ; )*
         }
     ) => {
         match ((0, $sub_opinfo), $cur_opinfo) {
-            $( gen_rules!(@genlhs $lhs) => gen_rules!(@genrhs $rhs) , )*
+            $( gen_rules!(@genlhs $lhs) => gen_rules!(@genrhs $width $rhs) , )*
             _ => None,
         }
     };
@@ -22,13 +23,17 @@ macro_rules! gen_rules {
     (@genlhs ($c:ident $op:tt $sub:tt))
         => { (gen_rules!(@genlhs $sub), &COI(gen_rules!(@opcode $op), COCI::Left($c))) };
 
-    (@genrhs (. $op:tt $sub:tt))
-        => { Some(COI(gen_rules!(@opcode $op), COCI::Right(gen_rules!(@geneval $sub)))) };
-    (@genrhs ($sub:tt $op:tt .))
-        => { Some(COI(gen_rules!(@opcode $op), COCI::Left(gen_rules!(@geneval $sub)))) };
+    (@genrhs $w:ident (. $op:tt $sub:tt))
+        => { Some(COI(gen_rules!(@opcode $op), COCI::Right(gen_rules!(@geneval $w $sub)))) };
+    (@genrhs $w:ident ($sub:tt $op:tt .))
+        => { Some(COI(gen_rules!(@opcode $op), COCI::Left(gen_rules!(@geneval $w $sub)))) };
 
-    (@geneval ($l:ident $op:tt $r:ident))
-        => { gen_rules!(@opcode $op).eval_binop($l, $r).unwrap() };
+    (@geneval $w:ident ($l:ident $op:tt $r:ident))
+        => {
+            gen_rules!(@opcode $op)
+                .eval_binop_width($l, $r, $w, $w)
+                .and_then(EvalResult::value)?
+        };
 
     (@opcode +) => (OpAdd);
     (@opcode -) => (OpSub);
@@ -37,10 +42,11 @@ macro_rules! gen_rules {
     (@opcode ^) => (OpXor);
 }
 
-pub(super) fn combine_opinfo(cur_opinfo: &COI, sub_opinfo: &COI) -> Option<COI> {
+/// Combines `sub_opinfo` into `cur_opinfo`, folding constants as `width` bits wide values.
+pub(super) fn combine_opinfo(cur_opinfo: &COI, sub_opinfo: &COI, width: u16) -> Option<COI> {
     // try to keep put the const on the left like `ssasort` does
     gen_rules!{
-        . -> sub_opinfo -> cur_opinfo
+        . -> sub_opinfo -> cur_opinfo @ width
         {
             // add/add
             ((.+a)+b) => ((a+b)+.);
@@ -1,6 +1,6 @@
 //! Combines sequences of arithmetic or logical instructions into single instructions.
 
-use middle::ir::MOpcode;
+use middle::ir::{self, EvalResult, MOpcode};
 use middle::ssa::ssa_traits::*;
 use middle::ssa::ssastorage::SSAStorage;
 
@@ -127,13 +127,14 @@ impl Combiner {
         sub_node: SSAValue,
         ssa: &mut SSAStorage,
     ) -> Option<Either<(SSAValue, SSAValue, CombinableOpInfo), SSAValue>> {
+        let width = cur_vt.width().get_width().unwrap_or(64);
         let (new_opinfo, new_sub_node) = self
-            .combine_opinfo(cur_opinfo, sub_node)
+            .combine_opinfo(cur_opinfo, sub_node, width)
             .map(|(oi, sn)| (Cow::Owned(oi), sn))
             .unwrap_or((Cow::Borrowed(cur_opinfo), sub_node));
 
         // simplify
-        match simplify_opinfo(&new_opinfo) {
+        match simplify_opinfo(&new_opinfo, width) {
             Some(Some(simpl_new_opinfo)) => {
                 radeco_trace!(
                     "    simplified ({:?}) into ({:?})",
@@ -170,9 +171,10 @@ impl Combiner {
         &self,
         cur_opinfo: &CombinableOpInfo,
         sub_node: SSAValue,
+        width: u16,
     ) -> Option<(CombinableOpInfo, SSAValue)> {
         let &(sub_sub_node, ref sub_opinfo) = self.combine_candidates.get(&sub_node)?;
-        let new_opinfo = combine_rules::combine_opinfo(cur_opinfo, sub_opinfo)?;
+        let new_opinfo = combine_rules::combine_opinfo(cur_opinfo, sub_opinfo, width)?;
         radeco_trace!(
             "    combined ({:?} {:?}) into ({:?})",
             sub_opinfo,
@@ -213,29 +215,38 @@ fn make_opinfo_node(
 /// Returns an equivalent `CombinableOpInfo`, but "simpler" in some sence.
 /// Currently, this converts `OpAdd`s or `OpSub`s with "negative" constants into
 /// equivalent operations with positive constants.
+/// Constants are interpreted as `width` bits wide.
 /// Returns `Some(None)` if `info` is a no-op.
 /// Returns `None` if no simplification exists.
-fn simplify_opinfo(info: &CombinableOpInfo) -> Option<Option<CombinableOpInfo>> {
+fn simplify_opinfo(info: &CombinableOpInfo, width: u16) -> Option<Option<CombinableOpInfo>> {
     use self::CombinableOpConstInfo as COCI;
     use self::CombinableOpInfo as COI;
     use middle::ir::MOpcode::*;
 
+    let all_ones = ir::truncate(u64::max_value(), width);
+    let negate = |c: u64| {
+        OpSub
+            .eval_binop_width(0, c, width, width)
+            .and_then(EvalResult::value)
+    };
+
     match info {
         COI(OpAdd, COCI::Left(0))
         | COI(OpAdd, COCI::Right(0))
         | COI(OpSub, COCI::Right(0))
-        | COI(OpAnd, COCI::Left(0xFFFFFFFFFFFFFFFF))
-        | COI(OpAnd, COCI::Right(0xFFFFFFFFFFFFFFFF))
         | COI(OpOr, COCI::Left(0))
         | COI(OpOr, COCI::Right(0))
         | COI(OpXor, COCI::Left(0))
         | COI(OpXor, COCI::Right(0)) => Some(None),
-        COI(OpAdd, COCI::Left(c)) | COI(OpAdd, COCI::Right(c)) if *c > u64::max_value() / 2 => {
-            let c = OpSub.eval_binop(0, *c).unwrap();
+        COI(OpAnd, COCI::Left(c)) | COI(OpAnd, COCI::Right(c)) if *c & all_ones == all_ones => {
+            Some(None)
+        }
+        COI(OpAdd, COCI::Left(c)) | COI(OpAdd, COCI::Right(c)) if *c > all_ones / 2 => {
+            let c = negate(*c)?;
             Some(Some(COI(OpSub, COCI::Right(c))))
         }
-        COI(OpSub, COCI::Right(c)) if *c > u64::max_value() / 2 => {
-            let c = OpSub.eval_binop(0, *c).unwrap();
+        COI(OpSub, COCI::Right(c)) if *c > all_ones / 2 => {
+            let c = negate(*c)?;
             Some(Some(COI(OpAdd, COCI::Left(c))))
         }
         _ => None,
@@ -262,8 +273,13 @@ fn extract_opinfo(
                 (Some(c1), Some(c2)) => {
                     // this is const_prop's job, but we can do this here too
                     // bail if `cur_opcode` is non-evalable, since that also
-                    // implies it's non-combinable
-                    let res_val = cur_opcode.eval_binop(c1, c2)?;
+                    // implies it's non-combinable; also bail if the result is
+                    // undefined (e.g. division by zero)
+                    let operand_width = width_of(sub_node1, ssa);
+                    let result_width = cur_vt.width().get_width().unwrap_or(64);
+                    let res_val = cur_opcode
+                        .eval_binop_width(c1, c2, operand_width, result_width)?
+                        .value()?;
                     Some(Right(res_val))
                 }
                 (None, Some(c)) => {
@@ -281,6 +297,13 @@ fn extract_opinfo(
     }
 }
 
+fn width_of(node: SSAValue, ssa: &SSAStorage) -> u16 {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+        .unwrap_or(64)
+}
+
 fn extract_opcode(node: SSAValue, ssa: &SSAStorage) -> Option<(MOpcode, ValueInfo)> {
     if let NodeData {
         vt,
@@ -303,7 +303,9 @@ mod test {
         {
             let regfile = SubRegisterFile::new(&reg_profile);
             let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
         {
             dce::collect(&mut ssa);
@@ -329,7 +331,9 @@ mod test {
         {
             let regfile = SubRegisterFile::new(&reg_profile);
             let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
         {
             dce::collect(&mut ssa);
@@ -0,0 +1,1314 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Module that resolves indirect jumps through jump tables.
+//!
+//! An indirect jump is lifted as a block whose only successor is an unexplored block. When
+//! the target of the jump is loaded from a table indexed by a bounded value, the targets can
+//! be read from the binary and the jump becomes a switch: a block with one case edge per
+//! target, annotated with the values of the index that lead to it (see `CASE_EDGE`).
+//!
+//! The recognized targets are `[T + s * i]` and `B + ext([T + s * i])`, where `T` and `B`
+//! are constants after constant propagation and `s` is a constant scale. The index `i` must
+//! be bounded by the conditional branches that dominate the jump. The conditions of these
+//! branches are solved by evaluating them at the points where their outcome may change,
+//! which are the points where a term `±i + k` wraps around, changes sign or meets a constant
+//! of the condition.
+
+use petgraph::graph::{Graph, NodeIndex};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::mem;
+use std::u8;
+
+use analysis::dom::domtree::DomTree;
+use analysis::sccp;
+use frontend::radeco_containers::RadecoFunction;
+use frontend::radeco_source::Source;
+use frontend::ssaconstructor::SSAConstruct;
+use middle::ir::{EvalResult, MOpcode};
+use middle::ssa::cfg_traits::{CFGMod, CASE_EDGE, CFG};
+use middle::ssa::graph_traits::Graph as GraphTrait;
+use middle::ssa::ssa_traits::{SSAMod, SSA};
+use middle::ssa::ssastorage::SSAStorage;
+use r2api::structs::LRegInfo;
+
+/// Bound on the number of values of the index of a jump table.
+const MAX_INDEX_VALUES: u64 = 1024;
+/// Bound on the number of distinct targets of a jump table, one case edge each.
+const MAX_TARGETS: usize = (u8::MAX - CASE_EDGE) as usize + 1;
+/// Bound on the number of points a condition is evaluated at.
+const MAX_CRITICAL_POINTS: usize = 4096;
+/// Bound on the number of times the SSA of a function is reconstructed. Every round may
+/// uncover jump tables in the code reached through the tables of the previous one.
+const MAX_ROUNDS: usize = 4;
+/// Bound on the depth of the expressions that are matched and evaluated.
+const MAX_DEPTH: usize = 32;
+
+/// A resolved jump table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpTable {
+    /// Address of the indirect jump
+    pub address: u64,
+    /// Address of the table
+    pub table: u64,
+    /// Targets of the jump, each along with the values of the index that lead to it
+    pub cases: Vec<(u64, Vec<u64>)>,
+}
+
+/// Finds the jump tables of the indirect jumps of `rfn`, reading the tables from `source`.
+///
+/// Only the jumps whose targets are all instructions of `rfn` are resolved.
+pub fn find_jump_tables<S: Source + ?Sized>(rfn: &RadecoFunction, source: &S) -> Vec<JumpTable> {
+    let ssa = rfn.ssa();
+    let resolver = Resolver::new(ssa);
+    let instructions = rfn
+        .instructions()
+        .iter()
+        .filter_map(|i| i.offset)
+        .collect::<BTreeSet<_>>();
+    let starts = ssa
+        .blocks()
+        .into_iter()
+        .filter_map(|b| ssa.starting_address(b))
+        .map(|a| a.address)
+        .collect::<BTreeSet<_>>();
+
+    let mut tables = Vec::new();
+    for block in ssa.blocks() {
+        let selector = match ssa.selector_in(block) {
+            Some(selector) => selector,
+            None => continue,
+        };
+        if ssa.conditional_blocks(block).is_some() || is_switch(ssa, block) {
+            continue;
+        }
+        // The jump is the last instruction of its block.
+        let start = match ssa.starting_address(block) {
+            Some(start) => start.address,
+            None => continue,
+        };
+        let end = starts
+            .range(start + 1..)
+            .next()
+            .cloned()
+            .unwrap_or(u64::max_value());
+        let address = match instructions.range(start..end).next_back() {
+            Some(&address) => address,
+            None => continue,
+        };
+        let table = match resolver.match_target(selector) {
+            Some(table) => table,
+            None => continue,
+        };
+        let values = match resolver.index_values(block, table.index) {
+            Some(values) => values,
+            None => continue,
+        };
+        if let Some(cases) = table.read(source, &values, &instructions) {
+            radeco_trace!("jump table @ {:#x} for jump @ {:#x}", table.table, address);
+            tables.push(JumpTable {
+                address: address,
+                table: table.table,
+                cases: cases,
+            });
+        }
+    }
+    tables
+}
+
+/// Resolves the jump tables of `rfn` (see `find_jump_tables`) and reconstructs its SSA with a
+/// switch on the index of the table for each of them.
+///
+/// Returns the resolved tables. The SSA is left untouched if there are none.
+pub fn resolve_jump_tables<S: Source + ?Sized>(
+    rfn: &mut RadecoFunction,
+    reg_info: &LRegInfo,
+    source: &S,
+    assume_cc: bool,
+) -> Vec<JumpTable> {
+    let mut tables: Vec<JumpTable> = Vec::new();
+    for _ in 0..MAX_ROUNDS {
+        let new = find_jump_tables(rfn, source)
+            .into_iter()
+            .filter(|t| tables.iter().all(|o| o.address != t.address))
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            break;
+        }
+
+        let switches = tables
+            .iter()
+            .chain(new.iter())
+            .map(|t| (t.address, t.cases.clone()))
+            .collect();
+        let ssa = mem::replace(rfn.ssa_mut(), SSAStorage::new());
+        let diagnostics = rfn.lift_diagnostics.clone();
+        if let Err(e) = SSAConstruct::<SSAStorage>::construct_with_switches(
+            rfn, reg_info, assume_cc, true, switches,
+        ) {
+            // Keep the SSA with the tables resolved so far.
+            radeco_warn!("Unable to lift {} with switches: {}", rfn.name, e);
+            *rfn.ssa_mut() = ssa;
+            rfn.lift_diagnostics = diagnostics;
+            break;
+        }
+        tables.extend(new);
+        select_indices(rfn.ssa_mut());
+    }
+    tables
+}
+
+fn is_switch(ssa: &SSAStorage, block: NodeIndex) -> bool {
+    ssa.outgoing_edges(block)
+        .iter()
+        .any(|&(_, i)| i >= CASE_EDGE)
+}
+
+// The constructor makes the jump target the selector of a switch. Makes the index of the table
+// the selector instead, as the case values are values of the index. If the index cannot be
+// found again, the case values are replaced by the targets themselves.
+fn select_indices(ssa: &mut SSAStorage) {
+    let mut indices = Vec::new();
+    let mut targets = Vec::new();
+    {
+        let resolver = Resolver::new(ssa);
+        for block in ssa.blocks() {
+            if !is_switch(ssa, block) {
+                continue;
+            }
+            match ssa
+                .selector_in(block)
+                .and_then(|s| resolver.match_target(s))
+            {
+                Some(table) => indices.push((block, table.index)),
+                None => {
+                    radeco_warn!("Lost the index of the switch {:?}", block);
+                    for (edge, i) in ssa.outgoing_edges(block) {
+                        if i < CASE_EDGE {
+                            continue;
+                        }
+                        let target = ssa
+                            .edge_info(edge)
+                            .and_then(|e| ssa.starting_address(e.target));
+                        if let Some(target) = target {
+                            targets.push((edge, vec![target.address]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for (block, index) in indices {
+        ssa.set_selector(index, block);
+    }
+    for (edge, values) in targets {
+        ssa.set_case_values(edge, values);
+    }
+}
+
+// A load of a jump target from a table.
+struct TableLoad {
+    // Index into the table
+    index: NodeIndex,
+    // Address of the table
+    table: u64,
+    // Distance between entries
+    scale: u64,
+    // Size of an entry, in bytes
+    size: u64,
+    // Whether entries are sign extended
+    signed: bool,
+    // Added to entries to get the target
+    base: u64,
+    // Width of the target
+    width: u16,
+}
+
+impl TableLoad {
+    // Reads the targets for the index `values` and groups the values by target.
+    fn read<S: Source + ?Sized>(
+        &self,
+        source: &S,
+        values: &[u64],
+        instructions: &BTreeSet<u64>,
+    ) -> Option<Vec<(u64, Vec<u64>)>> {
+        let mut cases: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        let mut order = Vec::new();
+        for &value in values {
+            let address = self.table.wrapping_add(self.scale.wrapping_mul(value));
+            let bytes = source.read_bytes(self.size, address).ok()?;
+            if bytes.len() as u64 != self.size {
+                return None;
+            }
+            // Entries are little endian.
+            let entry = bytes
+                .iter()
+                .rev()
+                .fold(0, |acc: u64, &b| (acc << 8) | u64::from(b));
+            let entry = if self.signed {
+                sign_extend(entry, self.size as u16 * 8)
+            } else {
+                entry
+            };
+            let target = truncate(entry.wrapping_add(self.base), self.width);
+            if !instructions.contains(&target) {
+                return None;
+            }
+            if !cases.contains_key(&target) {
+                order.push(target);
+            }
+            cases.entry(target).or_insert_with(Vec::new).push(value);
+        }
+        if order.is_empty() || order.len() > MAX_TARGETS {
+            return None;
+        }
+        Some(
+            order
+                .into_iter()
+                .map(|t| (t, cases.remove(&t).unwrap_or_default()))
+                .collect(),
+        )
+    }
+}
+
+// Shape of a value as a function of a variable `x`, used to find the points where a condition
+// on `x` may change its outcome.
+#[derive(Clone, Copy, Debug)]
+enum Shape {
+    Const(u64),
+    // `(±x + k) mod 2^w`
+    Affine { neg: bool, k: u64, w: u16 },
+    // Constant between critical points
+    Piecewise,
+}
+
+struct Resolver<'a> {
+    ssa: &'a SSAStorage,
+    sccp: sccp::Analyzer<SSAStorage>,
+    // Dominator tree of the reachable blocks, the graph it was built from and the node of
+    // every block in that graph
+    dom: Option<(DomTree, Graph<NodeIndex, u8>, HashMap<NodeIndex, NodeIndex>)>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(ssa: &'a SSAStorage) -> Resolver<'a> {
+        let mut copy = ssa.clone();
+        let mut sccp = sccp::Analyzer::new(&mut copy);
+        sccp.analyze();
+        Resolver {
+            ssa: ssa,
+            sccp: sccp,
+            dom: dominator_tree(ssa),
+        }
+    }
+
+    fn constant(&self, node: NodeIndex) -> Option<u64> {
+        self.sccp.constant_value(&node)
+    }
+
+    fn width(&self, node: NodeIndex) -> u16 {
+        self.ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .filter(|&w| w > 0 && w <= 64)
+            .unwrap_or(64)
+    }
+
+    fn operand(&self, node: NodeIndex, i: u8) -> Option<NodeIndex> {
+        self.ssa
+            .sparse_operands_of(node)
+            .into_iter()
+            .find(|&(j, _)| j == i)
+            .map(|(_, n)| n)
+    }
+
+    // Width the operands of `node` are evaluated at, that of its first non-constant operand.
+    fn operand_width(&self, node: NodeIndex) -> u16 {
+        self.ssa
+            .operands_of(node)
+            .into_iter()
+            .find(|&o| !self.ssa.is_constant(o))
+            .map_or(64, |o| self.width(o))
+    }
+
+    fn peel(&self, mut node: NodeIndex) -> (NodeIndex, bool) {
+        let mut signed = false;
+        for _ in 0..MAX_DEPTH {
+            match self.ssa.opcode(node) {
+                Some(MOpcode::OpMov) | Some(MOpcode::OpZeroExt(_)) | Some(MOpcode::OpNarrow(_)) => {
+                }
+                Some(MOpcode::OpSignExt(_)) => signed = true,
+                _ => break,
+            }
+            match self.operand(node, 0) {
+                Some(op) => node = op,
+                None => break,
+            }
+        }
+        (node, signed)
+    }
+
+    fn match_target(&self, target: NodeIndex) -> Option<TableLoad> {
+        let width = self.width(target);
+        let (node, signed) = self.peel(target);
+        match self.ssa.opcode(node)? {
+            MOpcode::OpLoad => self.match_load(node, signed, 0, width),
+            MOpcode::OpAdd => {
+                let (a, b) = (self.operand(node, 0)?, self.operand(node, 1)?);
+                [(a, b), (b, a)]
+                    .iter()
+                    .filter_map(|&(base, entry)| {
+                        let base = self.constant(base)?;
+                        let (load, signed) = self.peel(entry);
+                        if self.ssa.opcode(load) != Some(MOpcode::OpLoad) {
+                            return None;
+                        }
+                        self.match_load(load, signed, base, width)
+                    })
+                    .next()
+            }
+            _ => None,
+        }
+    }
+
+    fn match_load(
+        &self,
+        load: NodeIndex,
+        signed: bool,
+        base: u64,
+        width: u16,
+    ) -> Option<TableLoad> {
+        let size = u64::from(self.width(load) / 8);
+        if ![1, 2, 4, 8].contains(&size) {
+            return None;
+        }
+        let (address, _) = self.peel(self.operand(load, 1)?);
+        if self.ssa.opcode(address)? != MOpcode::OpAdd {
+            return None;
+        }
+        let (a, b) = (self.operand(address, 0)?, self.operand(address, 1)?);
+        [(a, b), (b, a)]
+            .iter()
+            .filter_map(|&(table, scaled)| {
+                let table = self.constant(table)?;
+                let (index, scale) = self.match_scaled(scaled)?;
+                Some(TableLoad {
+                    index: index,
+                    table: table,
+                    scale: scale,
+                    size: size,
+                    signed: signed,
+                    base: base,
+                    width: width,
+                })
+            })
+            .next()
+    }
+
+    fn match_scaled(&self, node: NodeIndex) -> Option<(NodeIndex, u64)> {
+        if self.constant(node).is_some() {
+            return None;
+        }
+        let (a, b) = match (self.operand(node, 0), self.operand(node, 1)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Some((node, 1)),
+        };
+        match self.ssa.opcode(node)? {
+            MOpcode::OpMul => match (self.constant(a), self.constant(b)) {
+                (None, Some(s)) if s != 0 => Some((a, s)),
+                (Some(s), None) if s != 0 => Some((b, s)),
+                _ => None,
+            },
+            MOpcode::OpLsl => match self.constant(b) {
+                Some(s) if s < 8 => Some((a, 1 << s)),
+                _ => None,
+            },
+            _ => Some((node, 1)),
+        }
+    }
+
+    // Conditions that hold when `block` is reached, with whether they are non-zero.
+    fn guards(&self, block: NodeIndex) -> Vec<(NodeIndex, bool)> {
+        let (dom, g, index) = match self.dom {
+            Some((ref dom, ref g, ref index)) => (dom, g, index),
+            None => return Vec::new(),
+        };
+        let doms = match index.get(&block) {
+            Some(&b) => dom.doms(b).into_iter().collect::<HashSet<_>>(),
+            None => return Vec::new(),
+        };
+        let mut guards = Vec::new();
+        for &d in &doms {
+            let branch = g[d];
+            if branch == block {
+                continue;
+            }
+            let (info, cond) = match (
+                self.ssa.conditional_blocks(branch),
+                self.ssa.selector_in(branch),
+            ) {
+                (Some(info), Some(cond)) => (info, cond),
+                _ => continue,
+            };
+            if info.true_side == info.false_side {
+                continue;
+            }
+            for &(side, holds) in &[(info.true_side, true), (info.false_side, false)] {
+                // The edge to `side` is taken whenever `side` is, and it leads to `block`.
+                let taken = self.ssa.preds_of(side).len() == 1
+                    && index.get(&side).map_or(false, |s| doms.contains(s));
+                if taken {
+                    guards.push((cond, holds));
+                }
+            }
+        }
+        guards
+    }
+
+    // Values `index` may take in `block`, if they are few.
+    fn index_values(&self, block: NodeIndex, index: NodeIndex) -> Option<Vec<u64>> {
+        let guards = self.guards(block);
+        if guards.is_empty() {
+            return None;
+        }
+        // Any value `index` is computed from through a one-to-one or narrowing operation can
+        // be the one bounded by the guards.
+        let mut var = index;
+        for _ in 0..MAX_DEPTH {
+            let bounds = guards
+                .iter()
+                .filter_map(|&(cond, holds)| self.solve(cond, holds, var))
+                .fold(None, |acc: Option<Vec<(u64, u64)>>, b| {
+                    Some(match acc {
+                        Some(a) => intersect(&a, &b),
+                        None => b,
+                    })
+                });
+            if let Some(bounds) = bounds {
+                // Intervals may cover all 2^64 values.
+                let count = bounds.iter().fold(0u64, |acc, &(lo, hi)| {
+                    acc.saturating_add((hi - lo).saturating_add(1))
+                });
+                if count <= MAX_INDEX_VALUES {
+                    let mut values = BTreeSet::new();
+                    for &(lo, hi) in &bounds {
+                        for x in lo..=hi {
+                            values.insert(self.eval(index, var, x, &mut HashMap::new(), 0)?);
+                        }
+                    }
+                    return Some(values.into_iter().collect());
+                }
+            }
+            var = match self.ssa.opcode(var)? {
+                MOpcode::OpMov
+                | MOpcode::OpZeroExt(_)
+                | MOpcode::OpSignExt(_)
+                | MOpcode::OpNarrow(_) => self.operand(var, 0)?,
+                MOpcode::OpAdd | MOpcode::OpSub | MOpcode::OpAnd => {
+                    let (a, b) = (self.operand(var, 0)?, self.operand(var, 1)?);
+                    match (self.constant(a), self.constant(b)) {
+                        (None, Some(_)) => a,
+                        (Some(_), None) if self.ssa.opcode(var) != Some(MOpcode::OpSub) => b,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            };
+        }
+        None
+    }
+
+    // Values of `var` for which `cond` is non-zero if `holds`, and zero otherwise, as sorted
+    // disjoint inclusive intervals. `None` if `cond` is not understood.
+    fn solve(&self, cond: NodeIndex, holds: bool, var: NodeIndex) -> Option<Vec<(u64, u64)>> {
+        let width = self.width(var);
+        let max = mask(width);
+        let mut points = BTreeSet::new();
+        points.insert(0);
+        points.insert(max);
+        match self.shape(cond, var, width, &mut points, &mut HashMap::new(), 0)? {
+            Shape::Affine { neg, k, w } => {
+                critical_points(neg, k, w, &[0], width, &mut points)?;
+            }
+            Shape::Const(_) | Shape::Piecewise => {}
+        }
+        if points.len() > MAX_CRITICAL_POINTS {
+            return None;
+        }
+
+        // An undefined outcome does not exclude a value.
+        let satisfies = |x| {
+            self.eval(cond, var, x, &mut HashMap::new(), 0)
+                .map_or(true, |c| (c != 0) == holds)
+        };
+        let points = points.into_iter().filter(|&p| p <= max).collect::<Vec<_>>();
+        let mut intervals: Vec<(u64, u64)> = Vec::new();
+        for (i, &p) in points.iter().enumerate() {
+            let mut pieces = vec![(p, p)];
+            if let Some(&next) = points.get(i + 1) {
+                if p + 1 < next {
+                    pieces.push((p + 1, next - 1));
+                }
+            }
+            for (lo, hi) in pieces {
+                if !satisfies(lo) {
+                    continue;
+                }
+                match intervals.last_mut() {
+                    Some(last) if last.1 + 1 == lo => last.1 = hi,
+                    _ => intervals.push((lo, hi)),
+                }
+            }
+        }
+        Some(intervals)
+    }
+
+    fn shape(
+        &self,
+        node: NodeIndex,
+        var: NodeIndex,
+        var_width: u16,
+        points: &mut BTreeSet<u64>,
+        memo: &mut HashMap<NodeIndex, Shape>,
+        depth: usize,
+    ) -> Option<Shape> {
+        if self.same_value(node, var, 0) {
+            return Some(Shape::Affine {
+                neg: false,
+                k: 0,
+                w: var_width,
+            });
+        }
+        if let Some(c) = self.constant(node) {
+            return Some(Shape::Const(c));
+        }
+        if let Some(&shape) = memo.get(&node) {
+            return Some(shape);
+        }
+        if depth > MAX_DEPTH || points.len() > MAX_CRITICAL_POINTS {
+            return None;
+        }
+
+        let opcode = self.ssa.opcode(node)?;
+        let w = self.width(node);
+        let mut operands = Vec::new();
+        for op in self.ssa.operands_of(node) {
+            operands.push(self.shape(op, var, var_width, points, memo, depth + 1)?);
+        }
+        let mut crit = |t: Shape, values: &[u64]| match t {
+            Shape::Affine { neg, k, w } => critical_points(neg, k, w, values, var_width, points),
+            _ => Some(()),
+        };
+
+        let shape = match (opcode, operands.as_slice()) {
+            (MOpcode::OpMov, &[a]) | (MOpcode::OpZeroExt(_), &[a]) => a,
+            (MOpcode::OpNarrow(_), &[a]) => narrow(a, w),
+            (MOpcode::OpSignExt(_), &[a]) => match a {
+                // Not affine across the sign change, unless nothing is extended.
+                Shape::Affine { w: aw, .. } if aw < w => return None,
+                Shape::Affine { .. } => narrow(a, w),
+                _ => Shape::Piecewise,
+            },
+            (MOpcode::OpNot, &[a]) => match a {
+                Shape::Affine { neg, k, w: aw } => Shape::Affine {
+                    neg: !neg,
+                    k: truncate(!k, aw),
+                    w: aw,
+                },
+                _ => Shape::Piecewise,
+            },
+            (MOpcode::OpEq, &[a, b])
+            | (MOpcode::OpLt, &[a, b])
+            | (MOpcode::OpGt, &[a, b])
+            | (MOpcode::OpSLt, &[a, b])
+            | (MOpcode::OpSGt, &[a, b]) => {
+                let ow = self.operand_width(node);
+                let (a, b) = (narrow(a, ow), narrow(b, ow));
+                for &t in &[a, b] {
+                    if let Shape::Affine { w: tw, .. } = t {
+                        crit(t, &[0, mask(tw), sign_bit(tw), sign_bit(tw) - 1])?;
+                    }
+                }
+                match (a, b) {
+                    (t @ Shape::Affine { .. }, Shape::Const(c))
+                    | (Shape::Const(c), t @ Shape::Affine { .. }) => {
+                        crit(t, &[c.wrapping_sub(1), c, c.wrapping_add(1)])?;
+                    }
+                    // With the same slope, the difference is constant.
+                    (
+                        Shape::Affine { neg: n1, w: w1, .. },
+                        Shape::Affine { neg: n2, w: w2, .. },
+                    ) if n1 == n2 && w1 == w2 => {}
+                    (Shape::Affine { .. }, _) | (_, Shape::Affine { .. }) => return None,
+                    _ => {}
+                }
+                Shape::Piecewise
+            }
+            (MOpcode::OpAdd, &[a, b]) | (MOpcode::OpSub, &[a, b]) => {
+                let sub = opcode == MOpcode::OpSub;
+                match (narrow(a, w), narrow(b, w)) {
+                    (Shape::Affine { neg, k, w: tw }, Shape::Const(c)) if tw == w => {
+                        Shape::Affine {
+                            neg: neg,
+                            k: truncate(
+                                if sub {
+                                    k.wrapping_sub(c)
+                                } else {
+                                    k.wrapping_add(c)
+                                },
+                                w,
+                            ),
+                            w: w,
+                        }
+                    }
+                    (Shape::Const(c), Shape::Affine { neg, k, w: tw }) if tw == w => {
+                        Shape::Affine {
+                            neg: neg != sub,
+                            k: truncate(
+                                if sub {
+                                    c.wrapping_sub(k)
+                                } else {
+                                    c.wrapping_add(k)
+                                },
+                                w,
+                            ),
+                            w: w,
+                        }
+                    }
+                    (
+                        Shape::Affine {
+                            neg: n1,
+                            k: k1,
+                            w: w1,
+                        },
+                        Shape::Affine {
+                            neg: n2,
+                            k: k2,
+                            w: w2,
+                        },
+                    ) if w1 == w && w2 == w && (n1 == n2) == sub => Shape::Const(truncate(
+                        if sub {
+                            k1.wrapping_sub(k2)
+                        } else {
+                            k1.wrapping_add(k2)
+                        },
+                        w,
+                    )),
+                    (Shape::Affine { .. }, _) | (_, Shape::Affine { .. }) => return None,
+                    _ => Shape::Piecewise,
+                }
+            }
+            (MOpcode::OpAnd, &[a, b]) => match (narrow(a, w), narrow(b, w)) {
+                (t @ Shape::Affine { .. }, Shape::Const(m))
+                | (Shape::Const(m), t @ Shape::Affine { .. }) => {
+                    let tw = match t {
+                        Shape::Affine { w, .. } => w,
+                        _ => unreachable!(),
+                    };
+                    let m = truncate(m, tw);
+                    if m == mask(tw) {
+                        t
+                    } else if m.wrapping_add(1).is_power_of_two() {
+                        narrow(t, m.count_ones() as u16)
+                    } else if m == sign_bit(tw) {
+                        crit(t, &[0, mask(tw), sign_bit(tw), sign_bit(tw) - 1])?;
+                        Shape::Piecewise
+                    } else {
+                        return None;
+                    }
+                }
+                (Shape::Affine { .. }, _) | (_, Shape::Affine { .. }) => return None,
+                _ => Shape::Piecewise,
+            },
+            (MOpcode::OpLsr, &[a, b]) | (MOpcode::OpAsr, &[a, b]) => match (narrow(a, w), b) {
+                // Only the sign is left.
+                (t @ Shape::Affine { .. }, Shape::Const(n)) => {
+                    let tw = match t {
+                        Shape::Affine { w, .. } => w,
+                        _ => unreachable!(),
+                    };
+                    if n + 1 < u64::from(tw) {
+                        return None;
+                    }
+                    crit(t, &[0, mask(tw), sign_bit(tw), sign_bit(tw) - 1])?;
+                    Shape::Piecewise
+                }
+                (Shape::Affine { .. }, _) | (_, Shape::Affine { .. }) => return None,
+                _ => Shape::Piecewise,
+            },
+            (MOpcode::OpOr, &[t @ Shape::Affine { .. }, Shape::Const(0)])
+            | (MOpcode::OpOr, &[Shape::Const(0), t @ Shape::Affine { .. }])
+            | (MOpcode::OpXor, &[t @ Shape::Affine { .. }, Shape::Const(0)])
+            | (MOpcode::OpXor, &[Shape::Const(0), t @ Shape::Affine { .. }]) => t,
+            (MOpcode::OpLoad, _)
+            | (MOpcode::OpStore, _)
+            | (MOpcode::OpCall, _)
+            | (MOpcode::OpCustom(_), _)
+            | (MOpcode::OpITE, _) => return None,
+            (_, operands) => {
+                if operands.iter().any(|s| match *s {
+                    Shape::Affine { .. } => true,
+                    _ => false,
+                }) {
+                    return None;
+                }
+                Shape::Piecewise
+            }
+        };
+        memo.insert(node, shape);
+        Some(shape)
+    }
+
+    // Evaluates `node` with `var` set to `x`.
+    fn eval(
+        &self,
+        node: NodeIndex,
+        var: NodeIndex,
+        x: u64,
+        memo: &mut HashMap<NodeIndex, Option<u64>>,
+        depth: usize,
+    ) -> Option<u64> {
+        if self.same_value(node, var, 0) {
+            return Some(truncate(x, self.width(var)));
+        }
+        if let Some(c) = self.constant(node) {
+            return Some(c);
+        }
+        if let Some(&v) = memo.get(&node) {
+            return v;
+        }
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let opcode = self.ssa.opcode(node)?;
+        let mut values = Vec::new();
+        for op in self.ssa.operands_of(node) {
+            values.push(self.eval(op, var, x, memo, depth + 1)?);
+        }
+        let result = match values.as_slice() {
+            &[v] => opcode.eval_unop_width(v, self.operand_width(node), self.width(node)),
+            &[a, b] => opcode.eval_binop_width(a, b, self.operand_width(node), self.width(node)),
+            _ => None,
+        };
+        let value = result.and_then(EvalResult::value);
+        memo.insert(node, value);
+        value
+    }
+
+    // Whether `a` and `b` are computed the same way from the same values.
+    fn same_value(&self, a: NodeIndex, b: NodeIndex, depth: usize) -> bool {
+        if a == b {
+            return true;
+        }
+        if depth > 4 {
+            return false;
+        }
+        match (self.ssa.opcode(a), self.ssa.opcode(b)) {
+            (Some(oa), Some(ob)) if oa == ob && self.width(a) == self.width(b) => {
+                let (ops_a, ops_b) = (self.ssa.operands_of(a), self.ssa.operands_of(b));
+                !ops_a.is_empty()
+                    && ops_a.len() == ops_b.len()
+                    && ops_a
+                        .iter()
+                        .zip(ops_b.iter())
+                        .all(|(&x, &y)| self.same_value(x, y, depth + 1))
+            }
+            _ => false,
+        }
+    }
+}
+
+// Builds the dominator tree of the blocks reachable from the entry of `ssa`, along with the
+// graph of these blocks it is built from and the node of every block in it.
+fn dominator_tree(
+    ssa: &SSAStorage,
+) -> Option<(DomTree, Graph<NodeIndex, u8>, HashMap<NodeIndex, NodeIndex>)> {
+    let entry = ssa.entry_node()?;
+    // `DomTree` cannot handle unreachable blocks, so only the reachable ones are copied.
+    let mut g = Graph::<NodeIndex, u8>::new();
+    let mut index = HashMap::new();
+    let mut worklist = vec![entry];
+    index.insert(entry, g.add_node(entry));
+    while let Some(block) = worklist.pop() {
+        for succ in ssa.succs_of(block) {
+            if !index.contains_key(&succ) {
+                index.insert(succ, g.add_node(succ));
+                worklist.push(succ);
+            }
+            g.add_edge(index[&block], index[&succ], 0);
+        }
+    }
+    let dom = DomTree::build_dom_tree(&g, index[&entry]);
+    Some((dom, g, index))
+}
+
+// Adds the values of `x` (of width `var_width`) for which `(±x + k) mod 2^w` is one of
+// `values`. Fails if there are too many of them.
+fn critical_points(
+    neg: bool,
+    k: u64,
+    w: u16,
+    values: &[u64],
+    var_width: u16,
+    points: &mut BTreeSet<u64>,
+) -> Option<()> {
+    // Every solution modulo 2^w has 2^(var_width - w) lifts.
+    let lifts = if w < var_width {
+        1u64 << (var_width - w)
+    } else {
+        1
+    };
+    if lifts > 16 {
+        return None;
+    }
+    for &v in values {
+        let v = truncate(v, w);
+        let base = truncate(
+            if neg {
+                k.wrapping_sub(v)
+            } else {
+                v.wrapping_sub(k)
+            },
+            w,
+        );
+        for j in 0..lifts {
+            let x = base.wrapping_add(j << w.min(63));
+            if x <= mask(var_width) {
+                points.insert(x);
+            }
+        }
+    }
+    if points.len() > MAX_CRITICAL_POINTS {
+        return None;
+    }
+    Some(())
+}
+
+fn narrow(s: Shape, width: u16) -> Shape {
+    match s {
+        Shape::Affine { neg, k, w } if width < w => Shape::Affine {
+            neg: neg,
+            k: truncate(k, width),
+            w: width,
+        },
+        Shape::Const(c) => Shape::Const(truncate(c, width)),
+        s => s,
+    }
+}
+
+fn intersect(a: &[(u64, u64)], b: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut res = Vec::new();
+    for &(alo, ahi) in a {
+        for &(blo, bhi) in b {
+            let (lo, hi) = (alo.max(blo), ahi.min(bhi));
+            if lo <= hi {
+                res.push((lo, hi));
+            }
+        }
+    }
+    res.sort();
+    res
+}
+
+fn mask(width: u16) -> u64 {
+    if width >= 64 {
+        u64::max_value()
+    } else {
+        (1 << width) - 1
+    }
+}
+
+fn sign_bit(width: u16) -> u64 {
+    1 << (width.max(1).min(64) - 1)
+}
+
+fn truncate(value: u64, width: u16) -> u64 {
+    value & mask(width)
+}
+
+fn sign_extend(value: u64, width: u16) -> u64 {
+    if width == 0 || width >= 64 {
+        value
+    } else if value & sign_bit(width) != 0 {
+        value | !mask(width)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use frontend::radeco_source::SourceErr;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use r2api::structs::{LOpInfo, LRegInfo};
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // A source that only holds the bytes of a jump table.
+    struct TableSource {
+        address: u64,
+        bytes: Vec<u8>,
+    }
+
+    impl Source for TableSource {
+        fn functions(&self) -> Result<Vec<::r2api::structs::FunctionInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn instructions_at(&self, _: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            Ok(LRegInfo::default())
+        }
+        fn flags(&self) -> Result<Vec<::r2api::structs::LFlagInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn sections(&self) -> Result<Vec<::r2api::structs::LSectionInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+            let start = at.wrapping_sub(self.address) as usize;
+            self.bytes
+                .get(start..start + n as usize)
+                .map(|b| b.to_vec())
+                .ok_or(SourceErr::SrcErr("Out of the table"))
+        }
+    }
+
+    fn function(ssa_txt: &str, instructions: &[u64]) -> RadecoFunction {
+        let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile: LRegInfo = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(ssa_txt, regfile);
+        rfn.instructions = instructions
+            .iter()
+            .map(|&a| LOpInfo {
+                offset: Some(a),
+                ..Default::default()
+            })
+            .collect();
+        rfn
+    }
+
+    fn table(entries: &[u64], size: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for e in entries {
+            for i in 0..size {
+                bytes.push((e >> (8 * i)) as u8);
+            }
+        }
+        bytes
+    }
+
+    // `cmp edi, 3; ja 0x1030; jmp qword [rdi*8 + 0x2000]`
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ABSOLUTE_TXT: &str = "\
+define-fun sym.switch(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0000] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0000] %4: $Unknown1 = #x3 < %3;
+        JMP IF %4 0x001030.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x0):
+        [@0x001008.0000] %5: $Unknown32 = Narrow32(%1);
+        [@0x001008.0000] %6: $Unknown64 = ZeroExt64(%5);
+        [@0x001008.0000] %7: $Unknown64 = %6 * #x8;
+        [@0x001008.0000] %8: $Unknown64 = #x2000 + %7;
+        [@0x001008.0000] %9: $Unknown64 = Load(%2, %8);
+        JMP TO %9
+    bb_0x001010.0000(sz 0x0):
+        RETURN
+    bb_0x001018.0000(sz 0x0):
+        RETURN
+    bb_0x001020.0000(sz 0x0):
+        RETURN
+    bb_0x001030.0000(sz 0x0):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    const INSTRUCTIONS: &[u64] = &[0x1000, 0x1008, 0x1010, 0x1018, 0x1020, 0x1030];
+
+    fn absolute_source() -> TableSource {
+        TableSource {
+            address: 0x2000,
+            bytes: table(&[0x1010, 0x1018, 0x1010, 0x1020], 8),
+        }
+    }
+
+    #[test]
+    fn absolute_table() {
+        let rfn = function(ABSOLUTE_TXT, INSTRUCTIONS);
+        let tables = find_jump_tables(&rfn, &absolute_source());
+        assert_eq!(
+            tables,
+            vec![JumpTable {
+                address: 0x1008,
+                table: 0x2000,
+                cases: vec![(0x1010, vec![0, 2]), (0x1018, vec![1]), (0x1020, vec![3])],
+            }]
+        );
+    }
+
+    #[test]
+    fn unbounded_index() {
+        // Negative values of `edi` pass a signed check.
+        let txt = ABSOLUTE_TXT.replace("#x3 < %3", "%3 >s #x3");
+        let rfn = function(&txt, INSTRUCTIONS);
+        assert!(find_jump_tables(&rfn, &absolute_source()).is_empty());
+    }
+
+    #[test]
+    fn unbounded_64_bit_guard() {
+        // The guard never excludes any value of `rdi`.
+        let txt = ABSOLUTE_TXT.replace("#x3 < %3", "%1 < #x0");
+        let rfn = function(&txt, INSTRUCTIONS);
+        assert!(find_jump_tables(&rfn, &absolute_source()).is_empty());
+    }
+
+    #[test]
+    fn target_outside_function() {
+        let rfn = function(ABSOLUTE_TXT, &[0x1000, 0x1008, 0x1010, 0x1018, 0x1030]);
+        assert!(find_jump_tables(&rfn, &absolute_source()).is_empty());
+    }
+
+    // `lea rdx, [0x2000]; lea eax, [rdi - 1]; cmp eax, 1; ja 0x1030;
+    // movsxd rax, dword [rdx + rdi*4]; add rax, rdx; jmp rax`
+    #[test]
+    fn relative_table() {
+        let txt = ABSOLUTE_TXT
+            .replace(
+                "%4: $Unknown1 = #x3 < %3;",
+                "%10: $Unknown32 = %3 - #x1;
+        [@0x001000.0000] %4: $Unknown1 = #x1 < %10;",
+            )
+            .replace("%6 * #x8", "%6 << #x2")
+            .replace(
+                "%9: $Unknown64 = Load(%2, %8);
+        JMP TO %9",
+                "%9: $Unknown32 = Load(%2, %8);
+        [@0x001008.0000] %11: $Unknown64 = SignExt64(%9);
+        [@0x001008.0000] %12: $Unknown64 = %11 + #x2000;
+        JMP TO %12",
+            );
+        let rfn = function(&txt, INSTRUCTIONS);
+        let source = TableSource {
+            address: 0x2000,
+            bytes: table(&[0, 0xffff_f010, 0xffff_f018, 0xffff_f010], 4),
+        };
+        let tables = find_jump_tables(&rfn, &source);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].cases, vec![(0x1010, vec![1]), (0x1018, vec![2])]);
+    }
+
+    // `cmp rdi, 3; ja 0x1030; jmp qword [rdi*8 + 0x2000]`, where the case for 3 holds
+    // `cmp rsi, 1; ja 0x1030; jmp qword [rsi*8 + 0x2020]`.
+    const SWITCH_ESIL: &[(u64, &str)] = &[
+        (0x1000, "3,rdi,>,?{,0x1030,rip,=,}"),
+        (0x1008, "rdi,8,*,0x2000,+,[8],rip,="),
+        (0x1010, "1,rax,="),
+        (0x1018, "rsp,[8],rip,=,8,rsp,+="),
+        (0x1020, "1,rsi,>,?{,0x1030,rip,=,}"),
+        (0x1028, "rsi,8,*,0x2020,+,[8],rip,="),
+        (0x1030, "0,rax,="),
+        (0x1038, "rsp,[8],rip,=,8,rsp,+="),
+    ];
+
+    fn lifted_switch() -> (RadecoFunction, Vec<JumpTable>) {
+        let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile: LRegInfo = serde_json::from_str(&*s).unwrap();
+        let mut rfn = RadecoFunction::new();
+        rfn.instructions = SWITCH_ESIL
+            .iter()
+            .map(|&(offset, esil)| LOpInfo {
+                offset: Some(offset),
+                size: Some(8),
+                esil: Some(esil.to_owned()),
+                opcode: Some(esil.to_owned()),
+                optype: if esil.starts_with("rsp,[8],rip,=") {
+                    Some("ret".to_owned())
+                } else {
+                    None
+                },
+                ..Default::default()
+            })
+            .collect();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, false, true).unwrap();
+        let source = TableSource {
+            address: 0x2000,
+            bytes: table(&[0x1010, 0x1018, 0x1010, 0x1020, 0x1030, 0x1010], 8),
+        };
+        let tables = resolve_jump_tables(&mut rfn, &reg_profile, &source, false);
+        (rfn, tables)
+    }
+
+    fn block_at(ssa: &SSAStorage, address: u64) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b).map(|a| (a.address, a.offset)) == Some((address, 0)))
+            .expect("No block at the address")
+    }
+
+    // Start of every target of `block` that is reached through a case edge, along with the
+    // values that lead to it.
+    fn cases_of(ssa: &SSAStorage, block: NodeIndex) -> Vec<(u64, Vec<u64>)> {
+        let mut cases = ssa
+            .outgoing_edges(block)
+            .into_iter()
+            .filter(|&(_, i)| i >= CASE_EDGE)
+            .map(|(e, _)| {
+                let target = ssa.edge_info(e).unwrap().target;
+                let start = ssa.starting_address(target).unwrap().address;
+                (start, ssa.case_values(e).unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+        cases.sort();
+        cases
+    }
+
+    // Makes every expression using `node`, and the block it selects, use `value` instead.
+    fn set_constant(ssa: &mut SSAStorage, node: NodeIndex, value: u64) {
+        let c = ssa.insert_const(value).unwrap();
+        for user in ssa.uses_of(node) {
+            if !ssa.is_expr(user) {
+                continue;
+            }
+            let indices = ssa
+                .sparse_operands_of(user)
+                .into_iter()
+                .filter(|&(_, o)| o == node)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            ssa.op_unuse(user, node);
+            for i in indices {
+                ssa.op_use(user, i, c);
+            }
+        }
+        if let Some(block) = ssa.selector_for(node) {
+            ssa.set_selector(c, block);
+        }
+    }
+
+    #[test]
+    fn resolve_nested_tables() {
+        let (rfn, tables) = lifted_switch();
+        // The inner table is only reachable once the outer one is resolved.
+        assert_eq!(
+            tables,
+            vec![
+                JumpTable {
+                    address: 0x1008,
+                    table: 0x2000,
+                    cases: vec![(0x1010, vec![0, 2]), (0x1018, vec![1]), (0x1020, vec![3])],
+                },
+                JumpTable {
+                    address: 0x1028,
+                    table: 0x2020,
+                    cases: vec![(0x1030, vec![0]), (0x1010, vec![1])],
+                },
+            ]
+        );
+
+        let ssa = rfn.ssa();
+        let outer = block_at(ssa, 0x1008);
+        assert_eq!(
+            cases_of(ssa, outer),
+            vec![(0x1010, vec![0, 2]), (0x1018, vec![1]), (0x1020, vec![3])]
+        );
+        let selector = ssa.selector_in(outer).unwrap();
+        assert_eq!(ssa.comment(selector), Some("rdi".to_owned()));
+        let inner = block_at(ssa, 0x1028);
+        assert_eq!(
+            cases_of(ssa, inner),
+            vec![(0x1010, vec![1]), (0x1030, vec![0])]
+        );
+        let selector = ssa.selector_in(inner).unwrap();
+        assert_eq!(ssa.comment(selector), Some("rsi".to_owned()));
+
+        // Case 1 skips the assignment to rax.
+        let merge = block_at(ssa, 0x1018);
+        assert_eq!(ssa.preds_of(merge).len(), 2);
+        let mut rax = ssa
+            .phis_in(merge)
+            .into_iter()
+            .map(|phi| {
+                let mut operands = ssa
+                    .operands_of(phi)
+                    .into_iter()
+                    .map(|n| match ssa.constant(n) {
+                        Some(c) => format!("#{}", c),
+                        None => ssa.comment(n).unwrap_or_default(),
+                    })
+                    .collect::<Vec<_>>();
+                operands.sort();
+                operands
+            })
+            .filter(|operands| operands.contains(&"#1".to_owned()));
+        assert_eq!(rax.next(), Some(vec!["#1".to_owned(), "rax".to_owned()]));
+    }
+
+    #[test]
+    fn constant_selector() {
+        let (mut rfn, _) = lifted_switch();
+        let ssa = rfn.ssa_mut();
+        let outer = block_at(ssa, 0x1008);
+        let selector = ssa.selector_in(outer).unwrap();
+        set_constant(ssa, selector, 2);
+        let ssa = {
+            let mut analyzer = sccp::Analyzer::new(ssa);
+            analyzer.analyze();
+            analyzer.emit_ssa()
+        };
+        // Only the case for 2 is left, and it is taken unconditionally.
+        let outer = block_at(&ssa, 0x1008);
+        assert!(ssa.selector_in(outer).is_none());
+        let outgoing = ssa.outgoing_edges(outer);
+        assert_eq!(outgoing.len(), 1);
+        assert!(outgoing[0].1 < CASE_EDGE);
+        let target = ssa.edge_info(outgoing[0].0).unwrap().target;
+        assert_eq!(ssa.starting_address(target).unwrap().address, 0x1010);
+        assert!(ssa.case_values(outgoing[0].0).is_none());
+        // The inner switch is unreachable.
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .all(|b| ssa.starting_address(b).map(|a| a.address) != Some(0x1028)));
+    }
+
+    #[test]
+    fn remove_case_target() {
+        let (mut rfn, _) = lifted_switch();
+        let ssa = rfn.ssa_mut();
+        let default = block_at(ssa, 0x1030);
+        ssa.remove_block(default);
+        // The inner switch is left with a single target, which it jumps to.
+        let inner = block_at(ssa, 0x1028);
+        assert!(ssa.selector_in(inner).is_none());
+        let outgoing = ssa.outgoing_edges(inner);
+        assert_eq!(outgoing.len(), 1);
+        assert!(outgoing[0].1 < CASE_EDGE);
+        assert!(ssa.case_values(outgoing[0].0).is_none());
+        let target = ssa.edge_info(outgoing[0].0).unwrap().target;
+        assert_eq!(ssa.starting_address(target).unwrap().address, 0x1010);
+        // The outer switch keeps its targets.
+        let outer = block_at(ssa, 0x1008);
+        assert_eq!(cases_of(ssa, outer).len(), 3);
+        assert!(ssa.selector_in(outer).is_some());
+    }
+}
@@ -203,6 +203,11 @@ where
                     MOpcode::OpLt => "OpLt".to_owned(),
                     MOpcode::OpLsl => "OpLsl".to_owned(),
                     MOpcode::OpLsr => "OpLsr".to_owned(),
+                    MOpcode::OpAsr => "OpAsr".to_owned(),
+                    MOpcode::OpSDiv => "OpSDiv".to_owned(),
+                    MOpcode::OpSMod => "OpSMod".to_owned(),
+                    MOpcode::OpSGt => "OpSGt".to_owned(),
+                    MOpcode::OpSLt => "OpSLt".to_owned(),
                     MOpcode::OpLoad => "OpLoad".to_owned(),
                     MOpcode::OpStore => "OpStore".to_owned(),
                     MOpcode::OpNarrow(w) => format!("OpNarrow{}", w),
@@ -357,6 +362,11 @@ where
                 "OpLt" => Some(MOpcode::OpLt),
                 "OpLsl" => Some(MOpcode::OpLsl),
                 "OpLsr" => Some(MOpcode::OpLsr),
+                "OpAsr" => Some(MOpcode::OpAsr),
+                "OpSDiv" => Some(MOpcode::OpSDiv),
+                "OpSMod" => Some(MOpcode::OpSMod),
+                "OpSGt" => Some(MOpcode::OpSGt),
+                "OpSLt" => Some(MOpcode::OpSLt),
                 "OpLoad" => Some(MOpcode::OpLoad),
                 "OpStore" => Some(MOpcode::OpStore),
                 "OpCall" => Some(MOpcode::OpCall),
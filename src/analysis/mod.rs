@@ -25,9 +25,12 @@ pub mod matcher {
 
 pub mod constraint_set;
 pub mod copy_propagation;
+pub mod dead_stores;
 pub mod functions;
 pub mod inst_combine;
+pub mod jump_tables;
 pub mod interproc;
 pub mod reference_marking;
+pub mod struct_recovery;
 pub mod tie;
 pub mod vsa;
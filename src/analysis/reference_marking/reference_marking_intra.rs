@@ -82,7 +82,12 @@ impl ReferenceMarker {
                         | &MOpcode::OpRol
                         | &MOpcode::OpRor
                         | &MOpcode::OpSub
-                        | &MOpcode::OpXor => {
+                        | &MOpcode::OpXor
+                        | &MOpcode::OpAsr
+                        | &MOpcode::OpSDiv
+                        | &MOpcode::OpSMod
+                        | &MOpcode::OpSGt
+                        | &MOpcode::OpSLt => {
                             // All operands are allowed to be references
                             let operands = ssa.operands_of(idx);
                             // Setup a union constraint
@@ -194,6 +199,10 @@ impl ReferenceMarker {
         }
     }
 
+    /// Solves the constraints added so far and sets the value type of every
+    /// constrained node. Nodes with conflicting constraints, e.g. a value that
+    /// is both a known scalar and an address, are marked `ValueType::Invalid`.
+    /// Returns whether a fixpoint was reached.
     pub fn resolve_references_iterative(&mut self, rfn: &mut RadecoFunction) -> bool {
         let progress = self.cs.solve();
         for (ni, vt) in self.cs.iter_bindings() {
@@ -230,7 +239,70 @@ impl ReferenceMarker {
 #[cfg(test)]
 mod test {
     use super::*;
+    use middle::ir_reader;
+    use serde_json;
+    use std::fs;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // Loads from rdi and from rip+0x10.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.refs(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rip;
+        %2: $Unknown64(*?) = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x8):
+        [@0x000100.0001] %4: $Unknown64(*?) = Load(%3, %2);
+        [@0x000100.0002] %5: $Unknown64(*?) = %1 + #x10;
+        [@0x000100.0003] %6: $Unknown64(*?) = Load(%3, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rdx = %4;
+}
+";
+
+    fn value_type(rfn: &RadecoFunction, node: NodeIndex) -> ValueType {
+        *rfn.ssa().node_data(node).unwrap().vt.value_type()
+    }
 
     #[test]
     fn mark_refs_1() {}
+
+    #[test]
+    fn conflicts_are_invalid() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(SSA_TXT, regfile.clone());
+
+        let nodes: Vec<NodeIndex> = rfn.ssa().inorder_walk().collect();
+        let loads: Vec<NodeIndex> = nodes
+            .iter()
+            .cloned()
+            .filter(|&n| rfn.ssa().opcode(n) == Some(MOpcode::OpLoad))
+            .collect();
+        let add = nodes
+            .iter()
+            .cloned()
+            .find(|&n| rfn.ssa().opcode(n) == Some(MOpcode::OpAdd))
+            .unwrap();
+        let rdi = rfn.ssa().operands_of(loads[0])[1];
+        let rip = rfn.ssa().operands_of(add)[0];
+
+        let refmarker =
+            ReferenceMarker::resolve_references(&mut rfn, regfile, Arc::new(Vec::new()));
+
+        assert_eq!(value_type(&rfn, rdi), ValueType::Reference);
+        assert_eq!(value_type(&rfn, rip), ValueType::Scalar);
+        // The sum of two scalars is used as an address. The conflict is
+        // reported and marks the sum invalid instead of picking either type.
+        assert!(!refmarker.cs.is_satisfiable());
+        assert_eq!(refmarker.cs.conflicts().len(), 1);
+        assert_eq!(value_type(&rfn, add), ValueType::Invalid);
+    }
 }
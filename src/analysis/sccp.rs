@@ -12,12 +12,12 @@
 //!    * https://www.cs.utexas.edu/~lin/cs380c/wegman.pdf.
 //!
 
-use middle::ir::{MAddress, MArity, MOpcode, WidthSpec};
+use middle::ir::{EvalResult, MAddress, MArity, MOpcode, WidthSpec};
+use middle::ssa::cfg_traits::CASE_EDGE;
 use middle::ssa::graph_traits::{ConditionInfo, Graph};
 use middle::ssa::ssa_traits::{NodeData, NodeType, ValueInfo, ValueType};
 use middle::ssa::ssa_traits::{SSAMod, SSA};
 use std::collections::{HashMap, VecDeque};
-use std::u64;
 
 #[macro_export]
 macro_rules! node_data_from_g {
@@ -112,6 +112,17 @@ where
         println!("{:?}", self.expr_val);
     }
 
+    /// Returns the constant that `i` was found to evaluate to by `analyze`, if any.
+    pub fn constant_value(&self, i: &T::ValueRef) -> Option<u64> {
+        if let Some(c) = self.g.constant(*i) {
+            return Some(c);
+        }
+        match self.expr_val.get(i) {
+            Some(&LatticeValue::Const(c)) => Some(c),
+            _ => None,
+        }
+    }
+
     fn visit_phi(&mut self, i: &T::ValueRef) -> LatticeValue {
         let operands = self.g.operands_of(*i);
         let mut phi_val = self.get_value(i);
@@ -165,6 +176,36 @@ where
             radeco_err!("Victim value is not a selector");
             self.g.invalid_action().unwrap()
         });
+
+        // Switch, only the case whose values contain the selector is taken.
+        let cases: Vec<T::CFEdgeRef> = self
+            .g
+            .outgoing_edges(block)
+            .into_iter()
+            .filter(|&(_, idx)| idx >= CASE_EDGE)
+            .map(|(e, _)| e)
+            .collect();
+        if !cases.is_empty() {
+            let taken = if let LatticeValue::Const(cval) = cond_val {
+                cases.iter().cloned().find(|e| {
+                    self.g
+                        .case_values(*e)
+                        .map_or(false, |values| values.contains(&cval))
+                })
+            } else {
+                None
+            };
+            match taken {
+                Some(edge) => self.cfgwl_push(&edge),
+                None => {
+                    for edge in &cases {
+                        self.cfgwl_push(edge);
+                    }
+                }
+            }
+            return;
+        }
+
         let invalid_edge = self.g.invalid_edge().expect("Invalid Edge is not defined");
         let conditional_branches = if let Some(branches) = self.g.conditional_edges(block) {
             branches
@@ -201,6 +242,10 @@ where
             operand[0]
         };
 
+        if opcode == MOpcode::OpCall {
+            return LatticeValue::Bottom;
+        }
+
         let val = self.get_value(&operand);
         let const_val = if let LatticeValue::Const(cval) = val {
             cval
@@ -208,33 +253,12 @@ where
             return val;
         };
 
-        let mut val: u64 = match opcode {
-            MOpcode::OpZeroExt(_) | MOpcode::OpSignExt(_) => {
-                // Nothing to do in case of widen as the value cannot change.
-                const_val
-            }
-            MOpcode::OpNarrow(size) => {
-                // Max size is 64. Therefore, we can _never_ narrow to 64.
-                assert!(size < 64);
-                let mask = (1 << (size)) - 1;
-                const_val & mask
-            }
-            MOpcode::OpMov => const_val as u64,
-            MOpcode::OpNot => !const_val as u64,
-            MOpcode::OpCall => {
-                return LatticeValue::Bottom;
-            }
-            _ => unreachable!(),
-        };
-
-        // We should consider width.
-        let ndata = node_data_from_g!(self, i);
-        let w = ndata.vt.width().get_width().unwrap_or(64);
-        if w < 64 {
-            val = val & ((1 << (w)) - 1);
+        let operand_width = self.width_of(&operand);
+        let result_width = self.width_of(i);
+        match opcode.eval_unop_width(const_val, operand_width, result_width) {
+            Some(EvalResult::Value(v)) => LatticeValue::Const(v),
+            _ => LatticeValue::Bottom,
         }
-
-        LatticeValue::Const(val)
     }
 
     fn evaluate_binary_op(&mut self, i: &T::ValueRef, opcode: MOpcode) -> LatticeValue {
@@ -244,15 +268,14 @@ where
             _ => {}
         }
 
-        let operands = self
-            .g
-            .operands_of(*i)
+        let operands = self.g.operands_of(*i);
+        let values = operands
             .iter()
             .map(|x| self.get_value(x))
             .collect::<Vec<_>>();
 
-        let lhs = operands[0];
-        let rhs = operands[1];
+        let lhs = values[0];
+        let rhs = values[1];
 
         let lhs_val = if let LatticeValue::Const(cval) = lhs {
             cval
@@ -265,51 +288,15 @@ where
             return rhs;
         };
 
-        let mut val: u64 = match opcode {
-            MOpcode::OpAdd => {
-                // lhs_val + rhs_val
-                // we should avoid integer overflow panic
-                let remained: u64 = u64::MAX - lhs_val;
-                if remained >= rhs_val {
-                    lhs_val + rhs_val
-                } else {
-                    (rhs_val - remained - 1) as u64
-                }
-            }
-            MOpcode::OpSub => {
-                if lhs_val >= rhs_val {
-                    lhs_val - rhs_val
-                } else {
-                    // rhs_val - lhs_val
-                    // This will never integer overflow
-                    (u64::MAX - rhs_val + lhs_val + 1) as u64
-                }
-            }
-            MOpcode::OpMul => {
-                // TODO: how to handle integer overflow.
-                lhs_val * rhs_val
-            }
-            MOpcode::OpDiv => lhs_val / rhs_val,
-            MOpcode::OpMod => lhs_val % rhs_val,
-            MOpcode::OpAnd => lhs_val & rhs_val,
-            MOpcode::OpOr => lhs_val | rhs_val,
-            MOpcode::OpXor => lhs_val ^ rhs_val,
-            MOpcode::OpEq => (lhs_val == rhs_val) as u64,
-            MOpcode::OpGt => (lhs_val > rhs_val) as u64,
-            MOpcode::OpLt => (lhs_val < rhs_val) as u64,
-            MOpcode::OpLsl => lhs_val << rhs_val,
-            MOpcode::OpLsr => lhs_val >> rhs_val,
-            _ => unreachable!(),
-        };
-
-        // We should consider width.
-        let ndata = node_data_from_g!(self, i);
-        let w = ndata.vt.width().get_width().unwrap_or(64);
-        if w < 64 {
-            val = val & ((1 << (w)) - 1);
+        let operand_width = self.width_of(&operands[0]);
+        let result_width = self.width_of(i);
+        match opcode.eval_binop_width(lhs_val, rhs_val, operand_width, result_width) {
+            Some(EvalResult::Value(v)) => LatticeValue::Const(v),
+            // Undefined results (such as division by zero) are not folded, as the actual
+            // behavior is up to the hardware.
+            Some(EvalResult::Undefined) => LatticeValue::Bottom,
+            None => LatticeValue::Bottom,
         }
-
-        LatticeValue::Const(val)
     }
 
     fn evaluate_ternary_op(&mut self, i: &T::ValueRef, opcode: MOpcode) -> LatticeValue {
@@ -561,6 +548,11 @@ where
         false
     }
 
+    // Width of the value, assuming 64 bits if it is not known.
+    fn width_of(&self, i: &T::ValueRef) -> u16 {
+        node_data_from_g!(self, i).vt.width().get_width().unwrap_or(64)
+    }
+
     fn ssawl_push(&mut self, i: &T::ValueRef) {
         if !self.g.is_expr(*i) {
             return;
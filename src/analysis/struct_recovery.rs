@@ -0,0 +1,304 @@
+//! Recovers the layout of structs and arrays from memory accesses.
+//!
+//! Addresses of `OpLoad` and `OpStore` are split into a base, an optional
+//! scaled index and a constant offset, i.e. `base + const` or
+//! `base + i*stride + const`. Accesses are then clustered by their base, which
+//! has to be marked as a reference. A base accessed at two or more distinct
+//! offsets is a pointer to a struct, and a base accessed through an index is an
+//! array whose elements are either scalars or structs themselves.
+//!
+//! The stack and base pointer are never taken as a base, as the stack frame is
+//! described by the local variables of the function.
+
+use middle::ir::MOpcode;
+use middle::ssa::ssa_traits::*;
+use middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::{hash_map, BTreeMap, HashMap};
+
+// Larger offsets are rather negative offsets or absolute addresses.
+const MAX_OFFSET: u64 = 0x10000;
+
+/// Memory accessed at `base + index * stride + offset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub base: NodeIndex,
+    /// Index and stride of an array access.
+    pub index: Option<(NodeIndex, u64)>,
+    pub offset: u64,
+    /// Width of the accessed value in bits.
+    pub width: u16,
+}
+
+/// Layout of the memory a base points to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// Width in bits of the fields by their offset. Offsets of an array are
+    /// relative to the start of an element.
+    pub fields: BTreeMap<u64, u16>,
+    /// Size of an element in bytes, if the base is an array.
+    pub stride: Option<u64>,
+}
+
+impl Layout {
+    pub fn is_array(&self) -> bool {
+        self.stride.is_some()
+    }
+
+    /// Whether the base points to structs, as opposed to scalars. Array
+    /// elements larger than their only field are structs with tail padding.
+    pub fn is_struct(&self) -> bool {
+        let padded = match (self.stride, self.fields.get(&0)) {
+            (Some(stride), Some(&width)) => (u64::from(width) + 7) / 8 != stride,
+            _ => false,
+        };
+        padded || self.fields.len() > 1 || self.fields.keys().any(|&offset| offset != 0)
+    }
+
+    // Fields do not overlap and fit into an element.
+    fn is_consistent(&self) -> bool {
+        let mut end = 0;
+        for (&offset, &width) in &self.fields {
+            if offset < end {
+                return false;
+            }
+            end = offset + (u64::from(width) + 7) / 8;
+        }
+        self.stride.map_or(true, |stride| end <= stride)
+    }
+}
+
+/// Structs and arrays accessed by a function.
+#[derive(Clone, Debug, Default)]
+pub struct Aggregates {
+    // Accesses by the address they use.
+    accesses: HashMap<NodeIndex, Access>,
+    layouts: HashMap<NodeIndex, Layout>,
+}
+
+impl Aggregates {
+    pub fn recover(ssa: &SSAStorage) -> Aggregates {
+        let frame_regs: Vec<&String> = ["SP", "BP"]
+            .iter()
+            .filter_map(|alias| ssa.regfile.alias_info.get(*alias))
+            .collect();
+        let mut accesses = HashMap::new();
+        for node in ssa.inorder_walk() {
+            let (addr, value) = match ssa.opcode(node) {
+                Some(MOpcode::OpLoad) => (ssa.operands_of(node)[1], node),
+                Some(MOpcode::OpStore) => {
+                    let ops = ssa.operands_of(node);
+                    (ops[1], ops[2])
+                }
+                _ => continue,
+            };
+            let width = match width_of(ssa, value) {
+                Some(width) if width > 0 => width,
+                _ => continue,
+            };
+            if let Some(access) = split_address(ssa, addr, width) {
+                let is_frame = ssa
+                    .comment(access.base)
+                    .map_or(false, |c| frame_regs.iter().any(|r| **r == c));
+                if !is_frame {
+                    accesses.insert(addr, access);
+                }
+            }
+        }
+
+        let mut layouts: HashMap<NodeIndex, Layout> = HashMap::new();
+        for access in accesses.values() {
+            let layout = layouts.entry(access.base).or_insert_with(Layout::default);
+            if let Some((_, stride)) = access.index {
+                // Accesses of an array with different strides are ambiguous.
+                if layout.stride.map_or(false, |s| s != stride) {
+                    layout.fields.clear();
+                    layout.stride = Some(0);
+                    continue;
+                }
+                layout.stride = Some(stride);
+            }
+        }
+        for access in accesses.values() {
+            let layout = layouts
+                .get_mut(&access.base)
+                .expect("Layout of every base exists");
+            let offset = match layout.stride {
+                Some(0) => continue,
+                Some(stride) => access.offset % stride,
+                None => access.offset,
+            };
+            let width = layout.fields.entry(offset).or_insert(0);
+            *width = (*width).max(access.width);
+        }
+        layouts.retain(|_, l| {
+            l.stride != Some(0) && l.is_consistent() && (l.is_array() || l.fields.len() > 1)
+        });
+        // Constant offsets into an array are fine, but a base that is only used
+        // with constant offsets has to look like a struct.
+        accesses.retain(|_, a| layouts.contains_key(&a.base));
+
+        for (base, layout) in &layouts {
+            radeco_trace!("struct_recovery|{:?}: {:?}", base, layout);
+        }
+        Aggregates {
+            accesses: accesses,
+            layouts: layouts,
+        }
+    }
+
+    /// Returns how the address `addr` accesses a struct or an array.
+    pub fn access(&self, addr: NodeIndex) -> Option<&Access> {
+        self.accesses.get(&addr)
+    }
+
+    pub fn layout(&self, base: NodeIndex) -> Option<&Layout> {
+        self.layouts.get(&base)
+    }
+
+    pub fn layouts(&self) -> hash_map::Iter<NodeIndex, Layout> {
+        self.layouts.iter()
+    }
+}
+
+fn width_of(ssa: &SSAStorage, node: NodeIndex) -> Option<u16> {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+}
+
+// Splits `addr` into a reference marked base, a scaled index and an offset.
+fn split_address(ssa: &SSAStorage, addr: NodeIndex, width: u16) -> Option<Access> {
+    let mut offset = 0u64;
+    let mut scaled = Vec::new();
+    let mut bases = Vec::new();
+    let mut terms = vec![(addr, false)];
+    while let Some((node, negated)) = terms.pop() {
+        let ops = ssa.operands_of(node);
+        if let Some(c) = ssa.constant(node) {
+            offset = if negated {
+                offset.wrapping_sub(c)
+            } else {
+                offset.wrapping_add(c)
+            };
+            continue;
+        }
+        match ssa.opcode(node) {
+            Some(MOpcode::OpAdd) if !negated => {
+                terms.push((ops[0], false));
+                terms.push((ops[1], false));
+            }
+            Some(MOpcode::OpSub) if !negated && ssa.constant(ops[1]).is_some() => {
+                terms.push((ops[0], false));
+                terms.push((ops[1], true));
+            }
+            Some(MOpcode::OpMul) if !negated => {
+                match (ssa.constant(ops[0]), ssa.constant(ops[1])) {
+                    (Some(stride), None) => scaled.push((ops[1], stride)),
+                    (None, Some(stride)) => scaled.push((ops[0], stride)),
+                    _ => return None,
+                }
+            }
+            Some(MOpcode::OpLsl) if !negated => match ssa.constant(ops[1]) {
+                Some(shift) if shift < 16 => scaled.push((ops[0], 1 << shift)),
+                _ => return None,
+            },
+            _ if !negated => bases.push(node),
+            _ => return None,
+        }
+    }
+
+    if bases.len() != 1 || scaled.len() > 1 || offset >= MAX_OFFSET {
+        return None;
+    }
+    let base = bases[0];
+    let is_reference = ssa
+        .node_data(base)
+        .map(|nd| nd.vt.is_reference())
+        .unwrap_or(false);
+    if !is_reference {
+        return None;
+    }
+    let index = scaled.pop();
+    if index.map_or(false, |(_, stride)| stride == 0) {
+        return None;
+    }
+    Some(Access {
+        base: base,
+        index: index,
+        offset: offset,
+        width: width,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // Reads two fields of the struct rdi points to and stores the first into
+    // the array rsi points to, at the index read from the struct.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.fields(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*) = $rdi;
+        %2: $Unknown64(*) = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x8):
+        [@0x000100.0001] %4: $Unknown32 = Load(%3, %1);
+        [@0x000100.0002] %5: $Unknown64 = %1 + #x8;
+        [@0x000100.0003] %6: $Unknown64 = Load(%3, %5);
+        [@0x000100.0004] %7: $Unknown64 = %6 * #x4;
+        [@0x000100.0005] %8: $Unknown64(*) = %2 + %7;
+        [@0x000100.0006] %9: $Unknown0 = Store(%3, %8, %4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %9;
+}
+";
+
+    #[test]
+    fn struct_and_array() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(SSA_TXT, regfile);
+        let aggregates = Aggregates::recover(&ssa);
+
+        let store = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+            .unwrap();
+        let ops = ssa.operands_of(store);
+        let array = aggregates.access(ops[1]).unwrap();
+        let layout = aggregates.layout(array.base).unwrap();
+        assert_eq!(layout.stride, Some(4));
+        assert!(!layout.is_struct());
+
+        let load = ssa.operands_of(ops[2])[1];
+        let field = aggregates.access(load).unwrap();
+        assert_eq!(field.offset, 0);
+        let layout = aggregates.layout(field.base).unwrap();
+        assert!(!layout.is_array());
+        assert_eq!(
+            layout.fields.iter().collect::<Vec<_>>(),
+            vec![(&0, &32), (&8, &64)]
+        );
+
+        // Elements wider than their only field are padded structs.
+        let mut padded = Layout::default();
+        padded.fields.insert(0, 32);
+        padded.stride = Some(8);
+        assert!(padded.is_struct());
+    }
+}
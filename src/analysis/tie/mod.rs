@@ -3,7 +3,380 @@
 //! For more details about the algorithm and working, please read original
 //! paper titled "TIE: Principled Reverse Engineering of Types in Binary
 //! Programs" by Brumley et al.
-
-#![allow(dead_code)]
+//!
+//! `TypeInference` generates type constraints for the values of a function
+//! from the width of every value, the signedness implied by operations such as
+//! `OpSDiv` or `OpZeroExt`, the addresses read by `OpLoad` and written by
+//! `OpStore` and the prototypes of called functions. The constraints are
+//! solved to an upper and a lower bound for every value, see
+//! `structs::TypeConstraints`.
 
 pub mod structs;
+
+use self::structs::{RType, TypeConstraints};
+use frontend::radeco_containers::{FunctionType, RadecoFunction, RadecoModule};
+use middle::ir::MOpcode;
+use middle::regfile::RegisterId;
+use middle::ssa::ssa_traits::{NodeType, SSAWalk, SSA};
+use middle::ssa::ssastorage::SSAStorage;
+use middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use std::collections::HashMap;
+
+/// Types of the arguments and of the return value of a function, by the
+/// register they are passed in.
+#[derive(Clone, Debug, Default)]
+pub struct Prototype {
+    pub args: Vec<(RegisterId, RType)>,
+    pub ret: Option<(RegisterId, RType)>,
+}
+
+impl Prototype {
+    /// Prototype made of the argument and return bindings of `rfn` that have a
+    /// known type.
+    pub fn of(rfn: &RadecoFunction) -> Prototype {
+        let mut proto = Prototype::default();
+        for vb in rfn.bindings() {
+            let (reg, ty) = match (vb.ridx, RType::from_c_type(&vb.type_str)) {
+                (Some(ridx), Some(ty)) => (RegisterId::from_usize(ridx as usize), ty),
+                _ => continue,
+            };
+            if vb.btype.is_argument() {
+                proto.args.push((reg, ty));
+            } else if vb.btype.is_return() {
+                proto.ret = Some((reg, ty));
+            }
+        }
+        proto
+    }
+}
+
+/// Generates and solves the type constraints of a single function.
+pub struct TypeInference<'a> {
+    ssa: &'a SSAStorage,
+    cs: TypeConstraints,
+    // Type variable of every SSA node.
+    vars: HashMap<NodeIndex, NodeIndex>,
+}
+
+impl<'a> TypeInference<'a> {
+    pub fn new(ssa: &'a SSAStorage) -> TypeInference<'a> {
+        TypeInference {
+            ssa: ssa,
+            cs: TypeConstraints::default(),
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Generates the constraints implied by the operations of the SSA.
+    pub fn generate(&mut self) {
+        let ssa = self.ssa;
+        for node in ssa.inorder_walk() {
+            let nd = match ssa.node_data(node) {
+                Ok(nd) => nd,
+                Err(_) => continue,
+            };
+            // Memory has a width of 0 and no type.
+            let width = nd.vt.width().get_width().unwrap_or(0);
+            if width != 0 {
+                self.subtype_of(node, RType::reg(width));
+            }
+            match nd.nt {
+                NodeType::Op(ref opc) => self.op_constraints(node, opc),
+                NodeType::Phi if width != 0 => {
+                    for op in ssa.operands_of(node) {
+                        self.equal(node, op);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `node` is a value of type `ty`, e.g. an argument of the function.
+    pub fn assume(&mut self, node: NodeIndex, ty: RType) {
+        let var = self.var(node);
+        let ty = self.cs.insert_base_type(ty);
+        self.cs.equal(&[var, ty]);
+    }
+
+    /// Constrains the arguments and the return value of the call `call` with
+    /// the prototype of the callee.
+    pub fn call_prototype(&mut self, call: NodeIndex, proto: &Prototype) {
+        if self.ssa.opcode(call) != Some(MOpcode::OpCall) {
+            return;
+        }
+        if let Some(info) = utils::call_info(call, self.ssa) {
+            for &(reg, ref ty) in &proto.args {
+                if let Some(&arg) = info.register_args.get(reg) {
+                    self.subtype_of(arg, ty.clone());
+                }
+            }
+        }
+        if let Some((reg, ref ty)) = proto.ret {
+            if let Some(&(ret, _)) = utils::call_rets(call, self.ssa).get(reg) {
+                self.supertype_of(ret, ty.clone());
+            }
+        }
+    }
+
+    /// Solves the constraints and returns the type of every node that was
+    /// constrained. Constants are not typed, as their uses may differ.
+    pub fn solve(mut self) -> HashMap<NodeIndex, RType> {
+        if let Err(e) = self.cs.solve() {
+            radeco_warn!("{}", e);
+        }
+        let cs = &self.cs;
+        self.vars
+            .iter()
+            .filter_map(|(&node, var)| cs.solution(var).map(|ty| (node, ty.clone())))
+            .collect()
+    }
+
+    fn op_constraints(&mut self, node: NodeIndex, opc: &MOpcode) {
+        let ops = self.ssa.operands_of(node);
+        match *opc {
+            MOpcode::OpMul => self.sized(&ops, node, RType::num),
+            MOpcode::OpDiv | MOpcode::OpMod => self.sized(&ops, node, RType::uint),
+            MOpcode::OpSDiv | MOpcode::OpSMod => self.sized(&ops, node, RType::int),
+            // The shift amount is not constrained.
+            MOpcode::OpLsl => self.sized(&ops[..1], node, RType::num),
+            MOpcode::OpLsr => self.sized(&ops[..1], node, RType::uint),
+            MOpcode::OpAsr => self.sized(&ops[..1], node, RType::int),
+            MOpcode::OpSignExt(_) => self.sized(&ops, node, RType::int),
+            MOpcode::OpZeroExt(_) => self.sized(&ops, node, RType::uint),
+            MOpcode::OpSGt | MOpcode::OpSLt => {
+                self.sized(&ops, node, RType::int);
+                self.equal(ops[0], ops[1]);
+            }
+            MOpcode::OpGt | MOpcode::OpLt | MOpcode::OpEq => self.equal(ops[0], ops[1]),
+            MOpcode::OpMov => self.equal(node, ops[0]),
+            MOpcode::OpITE => {
+                self.equal(node, ops[1]);
+                self.equal(node, ops[2]);
+            }
+            MOpcode::OpLoad => self.points_to(ops[1], node),
+            MOpcode::OpStore => self.points_to(ops[1], ops[2]),
+            MOpcode::OpCall => self.subtype_of(ops[0], RType::Ptr(Box::new(RType::Code))),
+            _ => {}
+        }
+    }
+
+    // Constants are shared between all their uses, which may have unrelated
+    // types. Every use of a constant gets a type variable of its own.
+    fn var(&mut self, node: NodeIndex) -> NodeIndex {
+        if let Some(&var) = self.vars.get(&node) {
+            return var;
+        }
+        let var = self.cs.insert_type_var(None);
+        if self.ssa.constant(node).is_none() {
+            self.vars.insert(node, var);
+        }
+        var
+    }
+
+    fn subtype_of(&mut self, node: NodeIndex, ty: RType) {
+        if ty == RType::Undefined {
+            return;
+        }
+        let var = self.var(node);
+        let ty = self.cs.insert_base_type(ty);
+        self.cs.subtype(&var, &ty);
+    }
+
+    fn supertype_of(&mut self, node: NodeIndex, ty: RType) {
+        let var = self.var(node);
+        let ty = self.cs.insert_base_type(ty);
+        self.cs.subtype(&ty, &var);
+    }
+
+    // Constrains `node` and `ops` to the type `ty` of their width.
+    fn sized(&mut self, ops: &[NodeIndex], node: NodeIndex, ty: fn(u16) -> RType) {
+        for &n in ops.iter().chain(Some(&node)) {
+            let width = self
+                .ssa
+                .node_data(n)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width());
+            if let Some(width) = width {
+                self.subtype_of(n, ty(width));
+            }
+        }
+    }
+
+    // Constants are not unified with other values, see `var`.
+    fn equal(&mut self, a: NodeIndex, b: NodeIndex) {
+        if a == b || self.ssa.constant(a).is_some() || self.ssa.constant(b).is_some() {
+            return;
+        }
+        let (a, b) = (self.var(a), self.var(b));
+        self.cs.equal(&[a, b]);
+    }
+
+    // `addr` points to values of the type of `value`.
+    fn points_to(&mut self, addr: NodeIndex, value: NodeIndex) {
+        let value = self.var(value);
+        let ptr = self.cs.insert_ptr(&value);
+        let addr = self.var(addr);
+        self.cs.subtype(&addr, &ptr);
+    }
+}
+
+/// Infers the types of every function of `rmod`, using the prototypes of the
+/// functions and imports called, and stores them with
+/// `RadecoFunction::set_types`.
+pub fn run(rmod: &mut RadecoModule) {
+    let mut prototypes: HashMap<u64, Prototype> = rmod
+        .functions
+        .iter()
+        .map(|(&offset, rfn)| (offset, Prototype::of(rfn)))
+        .collect();
+    prototypes.extend(
+        rmod.imports
+            .iter()
+            .map(|(&offset, import)| (offset, Prototype::of(&import.rfn.borrow()))),
+    );
+
+    let callgraph = &rmod.callgraph;
+    for rfn in rmod.functions.values_mut() {
+        if rfn.ftype != FunctionType::Function {
+            continue;
+        }
+        let types = {
+            let mut ti = TypeInference::new(rfn.ssa());
+            ti.generate();
+            for vb in rfn.bindings() {
+                if vb.btype.is_argument() && rfn.ssa().node_data(vb.idx).is_ok() {
+                    if let Some(ty) = RType::from_c_type(&vb.type_str) {
+                        ti.assume(vb.idx, ty);
+                    }
+                }
+            }
+            for edge in callgraph.edges_directed(rfn.cgid(), Direction::Outgoing) {
+                if let Some(proto) = prototypes.get(&callgraph[edge.target()]) {
+                    ti.call_prototype(edge.weight().csite_node, proto);
+                }
+            }
+            ti.solve()
+        };
+        rfn.set_types(types);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // Reads an int from rdi and writes it sign extended and halved to rsi.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.half(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Unknown64(*?) = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x8):
+        [@0x000100.0001] %4: $Unknown32 = Load(%3, %1);
+        [@0x000100.0002] %5: $Unknown32 = %4 /s #x2;
+        [@0x000100.0003] %6: $Unknown64 = SignExt64(%5);
+        [@0x000100.0004] %7: $Unknown0 = Store(%3, %2, %6);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %7;
+}
+";
+
+    // Clears an int and a size_t, both halved first, through rdi and rsi.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CLEAR_TXT: &str = "\
+define-fun sym.clear(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Unknown64(*?) = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x8):
+        [@0x000100.0001] %4: $Unknown32 = Load(%3, %1);
+        [@0x000100.0002] %5: $Unknown32 = %4 /s #x2;
+        [@0x000100.0003] %6: $Unknown64 = Load(%3, %2);
+        [@0x000100.0004] %7: $Unknown64 = %6 / #x2;
+        [@0x000100.0005] %8: $Unknown0 = Store(%3, %1, #x0);
+        [@0x000100.0006] %9: $Unknown0 = Store(%8, %2, #x0);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %9;
+}
+";
+
+    fn node(ssa: &SSAStorage, opc: MOpcode) -> NodeIndex {
+        ssa.inorder_walk()
+            .find(|&n| ssa.opcode(n).as_ref() == Some(&opc))
+            .unwrap()
+    }
+
+    #[test]
+    fn infer_pointers_and_signedness() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(SSA_TXT, regfile);
+
+        let mut ti = TypeInference::new(&ssa);
+        ti.generate();
+        let types = ti.solve();
+
+        let load = node(&ssa, MOpcode::OpLoad);
+        let div = node(&ssa, MOpcode::OpSDiv);
+        let sext = node(&ssa, MOpcode::OpSignExt(64));
+        let ops = ssa.operands_of(node(&ssa, MOpcode::OpStore));
+        assert_eq!(types[&load], RType::Int32);
+        assert_eq!(types[&div], RType::Int32);
+        assert_eq!(types[&sext], RType::Int64);
+        assert_eq!(
+            types[&ssa.operands_of(load)[1]],
+            RType::Ptr(Box::new(RType::Int32))
+        );
+        assert_eq!(types[&ops[1]], RType::Ptr(Box::new(RType::Int64)));
+    }
+
+    #[test]
+    fn shared_constants() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(CLEAR_TXT, regfile);
+
+        let mut ti = TypeInference::new(&ssa);
+        ti.generate();
+        let types = ti.solve();
+
+        // Storing the same constant through both pointers does not relate
+        // their pointees, nor does dividing by the same constant.
+        let int = node(&ssa, MOpcode::OpSDiv);
+        let uint = node(&ssa, MOpcode::OpDiv);
+        let (x, y) = (ssa.operands_of(int)[0], ssa.operands_of(uint)[0]);
+        assert_eq!(types[&x], RType::Int32);
+        assert_eq!(types[&y], RType::UInt64);
+        assert_eq!(
+            types[&ssa.operands_of(x)[1]],
+            RType::Ptr(Box::new(RType::Int32))
+        );
+        assert_eq!(
+            types[&ssa.operands_of(y)[1]],
+            RType::Ptr(Box::new(RType::UInt64))
+        );
+        assert!(types.keys().all(|&n| ssa.constant(n).is_none()));
+    }
+}
@@ -1,4 +1,19 @@
-//! Defines valid types.
+//! Defines valid types and the constraints between them.
+//!
+//! Types form a lattice ordered by subtyping. `Undefined` is the top element,
+//! any value has this type, and `Overdefined` is the bottom element, no value
+//! has this type. Below the top, types of the same width are ordered as
+//!
+//! ```text
+//!        regN
+//!       /    \
+//!    numN    ptr(t)     (pointers are 64 bits wide)
+//!    /  \
+//!  intN uintN
+//! ```
+//!
+//! with `ptr(t) <: ptr(u)` whenever `t <: u`. `bool` and `code` are only below
+//! the top.
 
 use std::collections::HashMap;
 use std::fmt;
@@ -9,7 +24,11 @@ use petgraph::EdgeDirection;
 
 type TIEResult<T> = Result<T, String>;
 
-#[derive(Clone, Debug)]
+// Solving stops after this many rounds, types of recursive data structures
+// would otherwise nest pointers forever.
+const MAX_ITERATIONS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RType {
     Overdefined,
     Reg8,
@@ -89,6 +108,198 @@ impl fmt::Display for RType {
     }
 }
 
+impl RType {
+    /// Most general type of a value that is `width` bits wide.
+    pub fn reg(width: u16) -> RType {
+        match width {
+            1 => RType::Bool,
+            8 => RType::Reg8,
+            16 => RType::Reg16,
+            32 => RType::Reg32,
+            64 => RType::Reg64,
+            _ => RType::Undefined,
+        }
+    }
+
+    /// Number of unknown signedness that is `width` bits wide.
+    pub fn num(width: u16) -> RType {
+        match width {
+            1 => RType::Bool,
+            8 => RType::Num8,
+            16 => RType::Num16,
+            32 => RType::Num32,
+            64 => RType::Num64,
+            _ => RType::Undefined,
+        }
+    }
+
+    /// Signed integer that is `width` bits wide.
+    pub fn int(width: u16) -> RType {
+        match width {
+            1 => RType::Bool,
+            8 => RType::Int8,
+            16 => RType::Int16,
+            32 => RType::Int32,
+            64 => RType::Int64,
+            _ => RType::Undefined,
+        }
+    }
+
+    /// Unsigned integer that is `width` bits wide.
+    pub fn uint(width: u16) -> RType {
+        match width {
+            1 => RType::Bool,
+            8 => RType::UInt8,
+            16 => RType::UInt16,
+            32 => RType::UInt32,
+            64 => RType::UInt64,
+            _ => RType::Undefined,
+        }
+    }
+
+    /// Parses a C type as used in function prototypes, e.g. `unsigned int` or
+    /// `const char *`. Assumes the LP64 data model.
+    pub fn from_c_type(ty: &str) -> Option<RType> {
+        let ty = ty.trim();
+        if ty.ends_with('*') {
+            let inner = RType::from_c_type(&ty[..ty.len() - 1]).unwrap_or(RType::Undefined);
+            return Some(RType::Ptr(Box::new(inner)));
+        }
+        let ty = ty.trim_left_matches("const ").trim();
+        let rty = match ty {
+            "bool" | "_Bool" => RType::Bool,
+            "char" | "signed char" | "int8_t" => RType::Int8,
+            "unsigned char" | "uint8_t" => RType::UInt8,
+            "short" | "short int" | "int16_t" => RType::Int16,
+            "unsigned short" | "unsigned short int" | "uint16_t" => RType::UInt16,
+            "int" | "signed int" | "int32_t" => RType::Int32,
+            "unsigned" | "unsigned int" | "uint32_t" => RType::UInt32,
+            "long" | "long int" | "long long" | "ssize_t" | "int64_t" => RType::Int64,
+            "unsigned long" | "unsigned long long" | "size_t" | "uint64_t" => RType::UInt64,
+            _ => return None,
+        };
+        Some(rty)
+    }
+
+    /// Width in bits of the values of this type, if it is known.
+    pub fn width(&self) -> Option<u16> {
+        match *self {
+            RType::Bool => Some(1),
+            RType::Reg8 | RType::Num8 | RType::UInt8 | RType::Int8 => Some(8),
+            RType::Reg16 | RType::Num16 | RType::UInt16 | RType::Int16 => Some(16),
+            RType::Reg32 | RType::Num32 | RType::UInt32 | RType::Int32 => Some(32),
+            RType::Reg64 | RType::Num64 | RType::UInt64 | RType::Int64 | RType::Ptr(_) => Some(64),
+            _ => None,
+        }
+    }
+
+    pub fn is_ptr(&self) -> bool {
+        match *self {
+            RType::Ptr(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether values of this type are known to be numbers.
+    pub fn is_num(&self) -> bool {
+        *self != RType::Overdefined
+            && (self.is_subtype_of(&RType::Num8)
+                || self.is_subtype_of(&RType::Num16)
+                || self.is_subtype_of(&RType::Num32)
+                || self.is_subtype_of(&RType::Num64)
+                || *self == RType::Bool)
+    }
+
+    /// Whether every value of this type is also of type `other`.
+    pub fn is_subtype_of(&self, other: &RType) -> bool {
+        self.normalized().subtype_of(&other.normalized())
+    }
+
+    /// Least upper bound of both types.
+    pub fn join(&self, other: &RType) -> RType {
+        self.normalized().lub(&other.normalized())
+    }
+
+    /// Greatest lower bound of both types.
+    pub fn meet(&self, other: &RType) -> RType {
+        self.normalized().glb(&other.normalized())
+    }
+
+    // Replaces `Union` and `Intersect` by the join and meet of their members.
+    fn normalized(&self) -> RType {
+        match *self {
+            RType::Union(ref tys) => tys
+                .iter()
+                .fold(RType::Overdefined, |acc, ty| acc.lub(&ty.normalized())),
+            RType::Intersect(ref tys) => tys
+                .iter()
+                .fold(RType::Undefined, |acc, ty| acc.glb(&ty.normalized())),
+            RType::Ptr(ref ty) => RType::Ptr(Box::new(ty.normalized())),
+            ref ty => ty.clone(),
+        }
+    }
+
+    // Direct supertype of a normalized type.
+    fn parent(&self) -> Option<RType> {
+        match *self {
+            RType::Int8 | RType::UInt8 => Some(RType::Num8),
+            RType::Int16 | RType::UInt16 => Some(RType::Num16),
+            RType::Int32 | RType::UInt32 => Some(RType::Num32),
+            RType::Int64 | RType::UInt64 => Some(RType::Num64),
+            RType::Num8 => Some(RType::Reg8),
+            RType::Num16 => Some(RType::Reg16),
+            RType::Num32 => Some(RType::Reg32),
+            RType::Num64 | RType::Ptr(_) => Some(RType::Reg64),
+            RType::Undefined | RType::Overdefined => None,
+            _ => Some(RType::Undefined),
+        }
+    }
+
+    fn subtype_of(&self, other: &RType) -> bool {
+        match (self, other) {
+            (&RType::Overdefined, _) | (_, &RType::Undefined) => true,
+            (&RType::Ptr(ref a), &RType::Ptr(ref b)) => a.subtype_of(b),
+            _ => {
+                let mut ty = Some(self.clone());
+                while let Some(t) = ty {
+                    if t == *other {
+                        return true;
+                    }
+                    ty = t.parent();
+                }
+                false
+            }
+        }
+    }
+
+    fn lub(&self, other: &RType) -> RType {
+        match (self, other) {
+            (&RType::Ptr(ref a), &RType::Ptr(ref b)) => RType::Ptr(Box::new(a.lub(b))),
+            _ if self.subtype_of(other) => other.clone(),
+            _ if other.subtype_of(self) => self.clone(),
+            _ => {
+                let mut ty = self.parent();
+                while let Some(t) = ty {
+                    if other.subtype_of(&t) {
+                        return t;
+                    }
+                    ty = t.parent();
+                }
+                RType::Undefined
+            }
+        }
+    }
+
+    fn glb(&self, other: &RType) -> RType {
+        match (self, other) {
+            (&RType::Ptr(ref a), &RType::Ptr(ref b)) => RType::Ptr(Box::new(a.glb(b))),
+            _ if self.subtype_of(other) => self.clone(),
+            _ if other.subtype_of(self) => other.clone(),
+            _ => RType::Overdefined,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ConstraintNode {
     Type(RType),
@@ -112,82 +323,21 @@ pub enum ConstraintEdge {
     Ptr,
 }
 
-#[derive(Clone, Debug)]
-pub enum SubTypeNode {
-    // Index in the original constaints set.
-    OrigIdx(NodeIndex),
-}
-
-#[derive(Clone, Debug)]
-pub enum SubTypeEdge {
-    SubType,
-}
-
-#[derive(Clone, Debug, Default)]
-pub struct SubTypeSet {
-    g: Graph<SubTypeNode, SubTypeEdge>,
-    // Map from NodeIndex in ConstraintSet to NodeIndex in SubTypeSet.
-    map: HashMap<NodeIndex, NodeIndex>,
-}
-
-impl SubTypeSet {
-    // Insert LHS <: RHS
-    pub fn insert_relation(&mut self, lhs: &NodeIndex, rhs: &NodeIndex) {
-        let sub_lhs = if let Some(idx) = self.map.get(lhs).cloned() {
-            idx
-        } else {
-            let new_node = self.g.add_node(SubTypeNode::OrigIdx(*lhs));
-            self.map.insert(*lhs, new_node);
-            new_node
-        };
-
-        let sub_rhs = if let Some(idx) = self.map.get(rhs).cloned() {
-            idx
-        } else {
-            let new_node = self.g.add_node(SubTypeNode::OrigIdx(*rhs));
-            self.map.insert(*rhs, new_node);
-            new_node
-        };
-        self.g.add_edge(sub_lhs, sub_rhs, SubTypeEdge::SubType);
-    }
-
-    // For all ( alpha <: S ) return alpha
-    pub fn subtypes_of(&self, idx_s: &NodeIndex) -> Vec<NodeIndex> {
-        let idx = self.map[idx_s];
-        let mut result = Vec::new();
-        let mut traverse_list = Vec::new();
-        traverse_list.extend(self.g.neighbors_directed(idx, EdgeDirection::Incoming));
-        while let Some(node) = traverse_list.pop() {
-            traverse_list.extend(self.g.neighbors_directed(node, EdgeDirection::Incoming));
-            match self.g[node] {
-                SubTypeNode::OrigIdx(ni) => result.push(ni),
-            }
-        }
-        result
-    }
-
-    // For all ( T <: beta ) return beta
-    pub fn supertypes_of(&self, idx_t: &NodeIndex) -> Vec<NodeIndex> {
-        let idx = self.map[idx_t];
-        let mut result = Vec::new();
-        let mut traverse_list = Vec::new();
-        traverse_list.extend(self.g.neighbors_directed(idx, EdgeDirection::Outgoing));
-        while let Some(node) = traverse_list.pop() {
-            traverse_list.extend(self.g.neighbors_directed(node, EdgeDirection::Outgoing));
-            match self.g[node] {
-                SubTypeNode::OrigIdx(ni) => result.push(ni),
-            }
-        }
-        result
-    }
-}
-
+/// Type constraints, solved to an upper and a lower bound for every node.
+///
+/// Nodes are type variables, base types, pointers to other nodes and unions
+/// and intersections of other nodes. Disjunctive and conjunctive joins are
+/// solved like unions and intersections.
+///
+/// Unlike `analysis::constraint_set::ConstraintSet`, which binds every
+/// variable to a single value that constraints can only lower, every node has
+/// two bounds: `a <: b` raises the lower bound of `b` as well as lowering the
+/// upper bound of `a`. Constraints between pointers are decomposed into
+/// constraints between their pointees.
 #[derive(Clone, Debug, Default)]
-pub struct ConstraintSet {
+pub struct TypeConstraints {
     g: Graph<ConstraintNode, ConstraintEdge>,
     type_vars: HashMap<String, NodeIndex>,
-    // S<:
-    subty: SubTypeSet,
     // S=
     binding_map: HashMap<NodeIndex, RType>,
     // B^
@@ -196,14 +346,16 @@ pub struct ConstraintSet {
     lower_bound: HashMap<NodeIndex, RType>,
 }
 
-impl ConstraintSet {
+impl TypeConstraints {
     pub fn operands(&self, n: &NodeIndex) -> Vec<NodeIndex> {
         let mut result = Vec::new();
         for edge in self.g.edges_directed(*n, EdgeDirection::Outgoing) {
-            result.push((edge.id(), n));
+            if let ConstraintEdge::EdgeIdx(i) = *edge.weight() {
+                result.push((i, edge.target()));
+            }
         }
         result.sort_by(|a, b| a.0.cmp(&b.0));
-        result.iter().map(|a| *a.1).collect()
+        result.iter().map(|a| a.1).collect()
     }
 
     pub fn insert_type_var(&mut self, named: Option<String>) -> NodeIndex {
@@ -213,6 +365,10 @@ impl ConstraintSet {
         var
     }
 
+    pub fn type_var(&self, name: &str) -> Option<NodeIndex> {
+        self.type_vars.get(name).cloned()
+    }
+
     pub fn insert_abstract_type(&mut self) -> NodeIndex {
         self.g.add_node(ConstraintNode::AbstractType)
     }
@@ -221,8 +377,15 @@ impl ConstraintSet {
         self.g.add_node(ConstraintNode::Type(ty))
     }
 
+    /// Inserts a pointer to values of the type of `inner`.
+    pub fn insert_ptr(&mut self, inner: &NodeIndex) -> NodeIndex {
+        let ptr = self.g.add_node(ConstraintNode::Ptr);
+        self.g.update_edge(ptr, *inner, ConstraintEdge::Ptr);
+        ptr
+    }
+
     pub fn subtype(&mut self, lhs: &NodeIndex, rhs: &NodeIndex) {
-        self.g.update_edge(*lhs, *rhs, SUBTYPE_EDGE);
+        self.add_subtype_edge(*lhs, *rhs);
     }
 
     pub fn supertype(&mut self, lhs: &NodeIndex, rhs: &NodeIndex) {
@@ -269,16 +432,212 @@ impl ConstraintSet {
         intersection
     }
 
-    pub fn solve(&mut self) {
-        unimplemented!()
+    /// Upper bound of `n`, the most general type it may have.
+    pub fn upper_bound(&self, n: &NodeIndex) -> Option<&RType> {
+        self.upper_bound.get(n)
+    }
+
+    /// Lower bound of `n`, the most specific type it is known to have.
+    pub fn lower_bound(&self, n: &NodeIndex) -> Option<&RType> {
+        self.lower_bound.get(n)
+    }
+
+    /// Type chosen for `n` by `solve`. This is the lower bound, or the upper
+    /// bound if nothing is known from below, and `Overdefined` if both bounds
+    /// contradict each other.
+    pub fn solution(&self, n: &NodeIndex) -> Option<&RType> {
+        self.binding_map.get(n)
+    }
+
+    /// Nodes whose lower bound is not a subtype of their upper bound.
+    pub fn conflicts(&self) -> Vec<NodeIndex> {
+        self.g
+            .node_indices()
+            .filter(
+                |n| match (self.lower_bound.get(n), self.upper_bound.get(n)) {
+                    (Some(lower), Some(upper)) => !lower.is_subtype_of(upper),
+                    _ => false,
+                },
+            )
+            .collect()
+    }
+
+    /// Computes the bounds and the solution of every node. Fails if the bounds
+    /// did not converge, the bounds computed so far are kept.
+    pub fn solve(&mut self) -> TIEResult<()> {
+        // Decomposition may add more subtype edges that have to be decomposed.
+        let mut edge_count = 0;
+        while edge_count != self.g.edge_count() {
+            edge_count = self.g.edge_count();
+            let subtype_edges = self
+                .g
+                .edge_indices()
+                .filter(|&e| match self.g[e] {
+                    ConstraintEdge::SubType => true,
+                    _ => false,
+                })
+                .collect::<Vec<_>>();
+            for edge in subtype_edges {
+                self.decompose_subtype_relation(edge);
+            }
+        }
+
+        self.upper_bound.clear();
+        self.lower_bound.clear();
+        for n in self.g.node_indices() {
+            let (upper, lower) = match self.g[n] {
+                ConstraintNode::Type(ref ty) => (ty.clone(), ty.clone()),
+                _ => (RType::Undefined, RType::Overdefined),
+            };
+            self.upper_bound.insert(n, upper);
+            self.lower_bound.insert(n, lower);
+        }
+
+        let mut converged = false;
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = false;
+            for n in self.g.node_indices().collect::<Vec<_>>() {
+                changed |= self.update_node(n)?;
+            }
+            for edge in self.g.edge_indices().collect::<Vec<_>>() {
+                changed |= match self.g[edge] {
+                    ConstraintEdge::Equal => self.solve_equality_constraint(&edge)?,
+                    ConstraintEdge::SubType => self.solve_subtype_constraint(&edge)?,
+                    _ => false,
+                };
+            }
+            if !changed {
+                converged = true;
+                break;
+            }
+        }
+
+        self.binding_map.clear();
+        for n in self.g.node_indices() {
+            let upper = &self.upper_bound[&n];
+            let lower = &self.lower_bound[&n];
+            let solution = if !lower.is_subtype_of(upper) {
+                RType::Overdefined
+            } else if *lower == RType::Overdefined {
+                upper.clone()
+            } else {
+                lower.clone()
+            };
+            self.binding_map.insert(n, solution);
+        }
+
+        if converged {
+            Ok(())
+        } else {
+            Err(format!(
+                "Type constraints did not converge after {} iterations",
+                MAX_ITERATIONS
+            ))
+        }
+    }
+
+    // Derives the bounds of unions, intersections and pointers from the nodes
+    // they refer to. Returns whether a bound changed.
+    fn update_node(&mut self, n: NodeIndex) -> bool {
+        let (upper, lower) = match self.g[n] {
+            ConstraintNode::Union | ConstraintNode::DisjunctiveJoin => {
+                let ops = self.operands(&n);
+                self.fold_bounds(&ops, RType::join)
+            }
+            ConstraintNode::Intersect | ConstraintNode::ConjunctiveJoin => {
+                let ops = self.operands(&n);
+                self.fold_bounds(&ops, RType::meet)
+            }
+            ConstraintNode::Ptr => {
+                // Pointer and pointee constrain each other.
+                let inner = self.inner_type(n);
+                let upper = RType::Ptr(Box::new(self.upper_bound[&inner].clone()));
+                let lower = RType::Ptr(Box::new(self.lower_bound[&inner].clone()));
+                let mut changed = self.set_upper(n, &upper) | self.set_lower(n, &lower);
+                if let RType::Ptr(ty) = self.upper_bound[&n].clone() {
+                    changed |= self.set_upper(inner, &ty);
+                }
+                if let RType::Ptr(ty) = self.lower_bound[&n].clone() {
+                    changed |= self.set_lower(inner, &ty);
+                }
+                return changed;
+            }
+            _ => return false,
+        };
+        let changed = self.upper_bound[&n] != upper || self.lower_bound[&n] != lower;
+        self.upper_bound.insert(n, upper);
+        self.lower_bound.insert(n, lower);
+        changed
+    }
+
+    fn fold_bounds(&self, ops: &[NodeIndex], f: fn(&RType, &RType) -> RType) -> (RType, RType) {
+        let mut bounds = ops
+            .iter()
+            .map(|op| (self.upper_bound[op].clone(), self.lower_bound[op].clone()));
+        match bounds.next() {
+            Some(first) => bounds.fold(first, |(upper, lower), (op_upper, op_lower)| {
+                (f(&upper, &op_upper), f(&lower, &op_lower))
+            }),
+            None => (RType::Undefined, RType::Overdefined),
+        }
+    }
+
+    // Bounds of base types are fixed and those of unions and intersections are
+    // derived from their operands.
+    fn is_solved(&self, n: NodeIndex) -> bool {
+        match self.g[n] {
+            ConstraintNode::TypeVar | ConstraintNode::AbstractType | ConstraintNode::Ptr => true,
+            _ => false,
+        }
+    }
+
+    fn set_upper(&mut self, n: NodeIndex, ty: &RType) -> bool {
+        if !self.is_solved(n) {
+            return false;
+        }
+        let upper = self.upper_bound[&n].meet(ty);
+        let changed = self.upper_bound[&n] != upper;
+        self.upper_bound.insert(n, upper);
+        changed
+    }
+
+    fn set_lower(&mut self, n: NodeIndex, ty: &RType) -> bool {
+        if !self.is_solved(n) {
+            return false;
+        }
+        let lower = self.lower_bound[&n].join(ty);
+        let changed = self.lower_bound[&n] != lower;
+        self.lower_bound.insert(n, lower);
+        changed
+    }
+
+    fn solve_equality_constraint(&mut self, constraint_edge: &EdgeIndex) -> TIEResult<bool> {
+        let (lhs, rhs) = self.endpoints(constraint_edge)?;
+        let (lhs_upper, lhs_lower) = (
+            self.upper_bound[&lhs].clone(),
+            self.lower_bound[&lhs].clone(),
+        );
+        let (rhs_upper, rhs_lower) = (
+            self.upper_bound[&rhs].clone(),
+            self.lower_bound[&rhs].clone(),
+        );
+        Ok(self.set_upper(lhs, &rhs_upper)
+            | self.set_lower(lhs, &rhs_lower)
+            | self.set_upper(rhs, &lhs_upper)
+            | self.set_lower(rhs, &lhs_lower))
     }
 
-    fn solve_equality_constraint(&mut self, _constraint_edge: &EdgeIndex) -> TIEResult<()> {
-        unimplemented!()
+    fn solve_subtype_constraint(&mut self, constraint_edge: &EdgeIndex) -> TIEResult<bool> {
+        let (lhs, rhs) = self.endpoints(constraint_edge)?;
+        let rhs_upper = self.upper_bound[&rhs].clone();
+        let lhs_lower = self.lower_bound[&lhs].clone();
+        Ok(self.set_upper(lhs, &rhs_upper) | self.set_lower(rhs, &lhs_lower))
     }
 
-    fn solve_subtype_constraint(&mut self, _constraint_edge: &EdgeIndex) -> TIEResult<()> {
-        unimplemented!()
+    fn endpoints(&self, edge: &EdgeIndex) -> TIEResult<(NodeIndex, NodeIndex)> {
+        self.g
+            .edge_endpoints(*edge)
+            .ok_or_else(|| format!("Invalid constraint {:?}", edge))
     }
 
     fn inner_type(&self, ptr_node: NodeIndex) -> NodeIndex {
@@ -289,55 +648,45 @@ impl ConstraintSet {
                 _ => false,
             })
             .expect("Inner type of `Ptr` cannot be `None`")
-            .source() // TODO .source() or .target() ?
+            .target()
+    }
+
+    // Adds `lhs <: rhs`, unless it is already implied by another constraint.
+    fn add_subtype_edge(&mut self, lhs: NodeIndex, rhs: NodeIndex) {
+        let exists = self
+            .g
+            .edges_directed(lhs, EdgeDirection::Outgoing)
+            .any(|e| {
+                e.target() == rhs
+                    && match *e.weight() {
+                        ConstraintEdge::SubType | ConstraintEdge::Equal => true,
+                        _ => false,
+                    }
+            });
+        if !exists {
+            self.g.add_edge(lhs, rhs, SUBTYPE_EDGE);
+        }
     }
 
-    // This function decomposes the first node and adds the appropriate edges to
-    // the other node.
+    // This function decomposes both nodes of `lhs <: rhs` and adds the implied
+    // constraints between their operands.
     fn decompose_constraint_node(&mut self, lhs: NodeIndex, rhs: NodeIndex) {
-        let rhs_is_ptr = match self.g[rhs] {
-            ConstraintNode::Intersect => {
-                let operands = self.operands(&rhs);
-                for op in &operands {
-                    self.g.update_edge(lhs, *op, ConstraintEdge::SubType);
-                }
-                false
+        if let ConstraintNode::Intersect = self.g[rhs] {
+            for op in self.operands(&rhs) {
+                self.add_subtype_edge(lhs, op);
             }
-            ConstraintNode::Ptr => true,
-            _ => false,
-        };
+        }
 
-        let lhs_is_ptr = match self.g[lhs] {
-            ConstraintNode::Union => {
-                let lhs_operands = self.operands(&lhs);
-                let mut subtype_relations = Vec::new();
-                let mut edges = self
-                    .g
-                    .neighbors_directed(lhs, EdgeDirection::Incoming)
-                    .detach();
-                while let Some(edge) = edges.next_edge(&self.g) {
-                    if let ConstraintEdge::SubType = self.g[edge] {
-                        let (_, target) = self.g.edge_endpoints(edge).expect("");
-                        self.g.remove_edge(edge);
-                        subtype_relations.push(target);
-                    }
-                }
-                for rhs_op in subtype_relations {
-                    for lhs_op in &lhs_operands {
-                        self.g.update_edge(*lhs_op, rhs_op, ConstraintEdge::SubType);
-                    }
-                }
-                false
+        if let ConstraintNode::Union = self.g[lhs] {
+            for op in self.operands(&lhs) {
+                self.add_subtype_edge(op, rhs);
             }
-            ConstraintNode::Ptr => true,
-            _ => false,
-        };
+        }
 
-        if lhs_is_ptr && rhs_is_ptr {
+        if let (&ConstraintNode::Ptr, &ConstraintNode::Ptr) = (&self.g[lhs], &self.g[rhs]) {
             let lhs_inner = self.inner_type(lhs);
             let rhs_inner = self.inner_type(rhs);
-            self.g
-                .update_edge(lhs_inner, rhs_inner, ConstraintEdge::SubType);
+            self.add_subtype_edge(lhs_inner, rhs_inner);
         }
     }
 
@@ -349,12 +698,70 @@ impl ConstraintSet {
             .expect("This cannot be None");
         self.decompose_constraint_node(lhs, rhs);
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lattice() {
+        let ptr = RType::Ptr(Box::new(RType::Int32));
+        assert!(RType::Int32.is_subtype_of(&RType::Reg32));
+        assert!(!RType::Int32.is_subtype_of(&RType::UInt32));
+        assert!(ptr.is_subtype_of(&RType::Reg64));
+        assert!(ptr.is_subtype_of(&RType::Ptr(Box::new(RType::Num32))));
+        assert_eq!(RType::Int32.join(&RType::UInt32), RType::Num32);
+        assert_eq!(RType::Int64.join(&ptr), RType::Reg64);
+        assert_eq!(RType::Int64.join(&RType::Int32), RType::Undefined);
+        assert_eq!(RType::Num32.meet(&RType::Int32), RType::Int32);
+        assert_eq!(RType::Int32.meet(&RType::UInt32), RType::Overdefined);
+        assert_eq!(
+            RType::Union(vec![RType::Int8, RType::UInt8]).meet(&RType::Reg8),
+            RType::Num8
+        );
+    }
 
-    fn meet(&mut self, _operands: &[RType]) -> RType {
-        unimplemented!()
+    #[test]
+    fn c_types() {
+        assert_eq!(RType::from_c_type("unsigned int"), Some(RType::UInt32));
+        assert_eq!(
+            RType::from_c_type("const char *"),
+            Some(RType::Ptr(Box::new(RType::Int8)))
+        );
+        assert_eq!(
+            RType::from_c_type("void *"),
+            Some(RType::Ptr(Box::new(RType::Undefined)))
+        );
+        assert_eq!(RType::from_c_type("struct stat"), None);
     }
 
-    fn join(&mut self, _operands: &[RType]) -> RType {
-        unimplemented!()
+    #[test]
+    fn solve_bounds() {
+        let mut cs = TypeConstraints::default();
+        let a = cs.insert_type_var(None);
+        let b = cs.insert_type_var(None);
+        let p = cs.insert_type_var(None);
+        let reg = cs.insert_base_type(RType::Reg32);
+        let int = cs.insert_base_type(RType::Int32);
+        // a <: reg32, int32 <: a, a = b, p <: ptr(b)
+        cs.subtype(&a, &reg);
+        cs.subtype(&int, &a);
+        cs.equal(&[a, b]);
+        let ptr_b = cs.insert_ptr(&b);
+        cs.subtype(&p, &ptr_b);
+        assert_eq!(cs.solve(), Ok(()));
+
+        assert_eq!(cs.upper_bound(&a), Some(&RType::Reg32));
+        assert_eq!(cs.lower_bound(&a), Some(&RType::Int32));
+        assert_eq!(cs.solution(&b), Some(&RType::Int32));
+        assert_eq!(cs.solution(&p), Some(&RType::Ptr(Box::new(RType::Reg32))));
+        assert!(cs.conflicts().is_empty());
+
+        let uint = cs.insert_base_type(RType::UInt32);
+        cs.subtype(&b, &uint);
+        assert_eq!(cs.solve(), Ok(()));
+        assert_eq!(cs.solution(&a), Some(&RType::Overdefined));
+        assert!(cs.conflicts().contains(&a));
     }
 }
@@ -7,9 +7,1381 @@
 
 //! Module that implements math operation on the BDD.
 //!
+//! A k-bits BDD set is a reduced ordered binary decision diagram over the k bits of its
+//! members, the most significant bit being tested first. A number belongs to the set iff
+//! the path selected by its bits ends in the `TRUE` terminal. Unlike strided intervals, BDDs
+//! keep track of every bit independently, thus masks and flag computations stay precise.
+//!
+//! Bitwise operations, addition and subtraction are computed exactly by walking both
+//! diagrams at once. Multiplication, division and remainder are exact when the operands are
+//! small enough to be enumerated, and over-approximated otherwise.
+//!
+//! Nodes are hash-consed in a table shared by all the BDDs of a thread, so a `BDD` is only a
+//! handle to its root and two BDDs are equal iff they contain the same numbers. Hence BDDs
+//! are neither `Send` nor `Sync`. The results of the operations on the nodes are cached as
+//! well. The cache is emptied when it gets too big. The node table is not bounded: nodes are
+//! only freed by `BDD::collect_garbage`, which has to be given every BDD still in use. Using
+//! any other BDD of the thread afterwards panics.
+//!
 //! More information are available from "BDD-based Value Analysis for X86 Executables".
 //! Please refer here:
 //!     * https://tubdok.tub.tuhh.de/bitstream/11420/1510/1/dis.pdf
 //!
 
-// TODO
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
+
+use super::abstract_set::{_bits, inum, unum};
+use super::abstract_set::{AbstractSet, Container};
+
+// Operations whose operands contain at most this many pairs of numbers are computed by
+// enumerating them.
+const ENUM_LIMIT: u128 = 4096;
+
+// Number of results of operations on nodes that are cached before the cache is emptied.
+const CACHE_LIMIT: usize = 1 << 20;
+
+type NodeId = u32;
+
+const FALSE: NodeId = 0;
+const TRUE: NodeId = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Node {
+    var: u8,
+    lo: NodeId,
+    hi: NodeId,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum BoolOp {
+    And,
+    Or,
+    Diff,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum BitOp {
+    And,
+    Or,
+    Xor,
+}
+
+impl BitOp {
+    fn eval(&self, x: u8, y: u8) -> u8 {
+        match *self {
+            BitOp::And => x & y,
+            BitOp::Or => x | y,
+            BitOp::Xor => x ^ y,
+        }
+    }
+}
+
+/// Table holding the nodes of all the BDDs of a thread.
+struct Manager {
+    nodes: Vec<Node>,
+    unique: HashMap<Node, NodeId>,
+    cache: HashMap<(BoolOp, NodeId, NodeId), NodeId>,
+}
+
+thread_local! {
+    static MANAGER: RefCell<Manager> = RefCell::new(Manager::new());
+    // Number of garbage collections of `MANAGER`. Kept out of the manager so that handles can
+    // be checked while it is borrowed.
+    static GENERATION: Cell<u32> = Cell::new(0);
+}
+
+fn with_manager<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Manager) -> R,
+{
+    MANAGER.with(|m| f(&mut m.borrow_mut()))
+}
+
+impl Manager {
+    fn new() -> Manager {
+        // Terminals never get looked up, their content does not matter
+        let terminal = Node {
+            var: 0,
+            lo: FALSE,
+            hi: FALSE,
+        };
+        Manager {
+            nodes: vec![terminal, terminal],
+            unique: HashMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    // Level of the variable tested by `n`, terminals are below every variable.
+    fn level(&self, n: NodeId) -> i16 {
+        if n <= TRUE {
+            -1
+        } else {
+            self.nodes[n as usize].var as i16
+        }
+    }
+
+    // Returns the cofactors of `n` with respect to variable `v`, `n` must not test a
+    // variable above `v`.
+    fn cofactors(&self, n: NodeId, v: i16) -> (NodeId, NodeId) {
+        if self.level(n) == v {
+            let node = self.nodes[n as usize];
+            (node.lo, node.hi)
+        } else {
+            (n, n)
+        }
+    }
+
+    fn mk(&mut self, var: i16, lo: NodeId, hi: NodeId) -> NodeId {
+        if lo == hi {
+            return lo;
+        }
+        let node = Node {
+            var: var as u8,
+            lo: lo,
+            hi: hi,
+        };
+        if let Some(&id) = self.unique.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        self.unique.insert(node, id);
+        id
+    }
+
+    fn apply(&mut self, op: BoolOp, a: NodeId, b: NodeId) -> NodeId {
+        match op {
+            BoolOp::And => {
+                if a == FALSE || b == FALSE {
+                    return FALSE;
+                } else if a == TRUE || a == b {
+                    return b;
+                } else if b == TRUE {
+                    return a;
+                }
+            }
+            BoolOp::Or => {
+                if a == TRUE || b == TRUE {
+                    return TRUE;
+                } else if a == FALSE || a == b {
+                    return b;
+                } else if b == FALSE {
+                    return a;
+                }
+            }
+            BoolOp::Diff => {
+                if a == FALSE || b == TRUE || a == b {
+                    return FALSE;
+                } else if b == FALSE {
+                    return a;
+                }
+            }
+        }
+        if let Some(&r) = self.cache.get(&(op, a, b)) {
+            return r;
+        }
+        let v = cmp::max(self.level(a), self.level(b));
+        let (a0, a1) = self.cofactors(a, v);
+        let (b0, b1) = self.cofactors(b, v);
+        let lo = self.apply(op, a0, b0);
+        let hi = self.apply(op, a1, b1);
+        let r = self.mk(v, lo, hi);
+        if self.cache.len() >= CACHE_LIMIT {
+            self.cache.clear();
+        }
+        self.cache.insert((op, a, b), r);
+        r
+    }
+
+    // Drops every node that is not reachable from `roots`. The remaining nodes are renumbered,
+    // `roots` are updated accordingly.
+    fn collect(&mut self, roots: &mut [NodeId]) {
+        let old = mem::replace(self, Manager::new());
+        let mut renumbered = HashMap::new();
+        renumbered.insert(FALSE, FALSE);
+        renumbered.insert(TRUE, TRUE);
+        for root in roots.iter_mut() {
+            *root = self.copy_from(&old, *root, &mut renumbered);
+        }
+    }
+
+    fn copy_from(
+        &mut self,
+        old: &Manager,
+        n: NodeId,
+        renumbered: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if let Some(&id) = renumbered.get(&n) {
+            return id;
+        }
+        let node = old.nodes[n as usize];
+        let lo = self.copy_from(old, node.lo, renumbered);
+        let hi = self.copy_from(old, node.hi, renumbered);
+        let id = self.mk(node.var as i16, lo, hi);
+        renumbered.insert(n, id);
+        id
+    }
+
+    fn and(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(BoolOp::And, a, b)
+    }
+
+    fn or(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(BoolOp::Or, a, b)
+    }
+
+    fn diff(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(BoolOp::Diff, a, b)
+    }
+
+    // Returns the set containing the k-bits number n.
+    fn cube(&mut self, k: u8, n: inum) -> NodeId {
+        let mut r = TRUE;
+        for v in 0..k as i16 {
+            r = if (n >> v) & 1 == 1 {
+                self.mk(v, FALSE, r)
+            } else {
+                self.mk(v, r, FALSE)
+            };
+        }
+        r
+    }
+
+    // Returns the set of numbers whose bits `lo..=hi` are all `bit`.
+    fn bits_set_to(&mut self, lo: u8, hi: u8, bit: bool) -> NodeId {
+        let mut r = TRUE;
+        for v in lo as i16..hi as i16 + 1 {
+            r = if bit {
+                self.mk(v, FALSE, r)
+            } else {
+                self.mk(v, r, FALSE)
+            };
+        }
+        r
+    }
+
+    // Returns the set of numbers n such that `bits v..=0 of n` <= `bits v..=0 of c` (or >= if
+    // `greater` is set), compared as unsigned numbers.
+    fn unsigned_cmp(&mut self, c: inum, v: i16, greater: bool) -> NodeId {
+        if v < 0 {
+            return TRUE;
+        }
+        let rest = self.unsigned_cmp(c, v - 1, greater);
+        match ((c >> v) & 1 == 1, greater) {
+            (true, false) => self.mk(v, TRUE, rest),
+            (false, false) => self.mk(v, rest, FALSE),
+            (true, true) => self.mk(v, FALSE, rest),
+            (false, true) => self.mk(v, rest, TRUE),
+        }
+    }
+
+    // Returns the set of k-bits numbers in [lb, ub], compared as signed numbers.
+    fn range(&mut self, k: u8, lb: inum, ub: inum) -> NodeId {
+        if lb > ub {
+            return FALSE;
+        }
+        let top = k as i16 - 1;
+        let ge = self.unsigned_cmp(lb, top - 1, true);
+        let ge = if lb < 0 {
+            self.mk(top, TRUE, ge)
+        } else {
+            self.mk(top, ge, FALSE)
+        };
+        let le = self.unsigned_cmp(ub, top - 1, false);
+        let le = if ub < 0 {
+            self.mk(top, FALSE, le)
+        } else {
+            self.mk(top, le, TRUE)
+        };
+        self.and(ge, le)
+    }
+
+    // Returns the set of numbers whose bits `v..=0` are the bits of the members of `n`, the
+    // other bits are left untouched.
+    fn smooth_below(&mut self, n: NodeId, v: i16, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if n == FALSE {
+            return FALSE;
+        }
+        if self.level(n) <= v {
+            // Every reduced node but FALSE has a path to TRUE
+            return TRUE;
+        }
+        if let Some(&r) = memo.get(&n) {
+            return r;
+        }
+        let node = self.nodes[n as usize];
+        let lo = self.smooth_below(node.lo, v, memo);
+        let hi = self.smooth_below(node.hi, v, memo);
+        let r = self.mk(node.var as i16, lo, hi);
+        memo.insert(n, r);
+        r
+    }
+
+    // Existentially quantifies every variable from `v` upward.
+    fn smooth_above(&mut self, n: NodeId, v: i16, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if self.level(n) < v {
+            return n;
+        }
+        if let Some(&r) = memo.get(&n) {
+            return r;
+        }
+        let node = self.nodes[n as usize];
+        let lo = self.smooth_above(node.lo, v, memo);
+        let hi = self.smooth_above(node.hi, v, memo);
+        let r = self.or(lo, hi);
+        memo.insert(n, r);
+        r
+    }
+
+    // Moves every variable of `n` by `delta`.
+    fn rename(&mut self, n: NodeId, delta: i16, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if n <= TRUE {
+            return n;
+        }
+        if let Some(&r) = memo.get(&n) {
+            return r;
+        }
+        let node = self.nodes[n as usize];
+        let lo = self.rename(node.lo, delta, memo);
+        let hi = self.rename(node.hi, delta, memo);
+        let r = self.mk(node.var as i16 + delta, lo, hi);
+        memo.insert(n, r);
+        r
+    }
+
+    // Complements every member of `n`.
+    fn complement(&mut self, n: NodeId, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if n <= TRUE {
+            return n;
+        }
+        if let Some(&r) = memo.get(&n) {
+            return r;
+        }
+        let node = self.nodes[n as usize];
+        let lo = self.complement(node.lo, memo);
+        let hi = self.complement(node.hi, memo);
+        let r = self.mk(node.var as i16, hi, lo);
+        memo.insert(n, r);
+        r
+    }
+
+    // Returns {x op y | x in a, y in b} on bits v..=0.
+    fn bitwise(
+        &mut self,
+        op: BitOp,
+        a: NodeId,
+        b: NodeId,
+        v: i16,
+        memo: &mut HashMap<(NodeId, NodeId, i16), NodeId>,
+    ) -> NodeId {
+        if a == FALSE || b == FALSE {
+            return FALSE;
+        }
+        if v < 0 || (a == TRUE && b == TRUE) {
+            return TRUE;
+        }
+        if let Some(&r) = memo.get(&(a, b, v)) {
+            return r;
+        }
+        let (ac, bc) = (self.cofactors(a, v), self.cofactors(b, v));
+        let (mut lo, mut hi) = (FALSE, FALSE);
+        for &(x, ax) in &[(0, ac.0), (1, ac.1)] {
+            for &(y, by) in &[(0, bc.0), (1, bc.1)] {
+                let r = self.bitwise(op, ax, by, v - 1, memo);
+                if op.eval(x, y) == 1 {
+                    hi = self.or(hi, r);
+                } else {
+                    lo = self.or(lo, r);
+                }
+            }
+        }
+        let r = self.mk(v, lo, hi);
+        memo.insert((a, b, v), r);
+        r
+    }
+
+    // Returns the sets of (x + y) mod 2^(v + 1) for x in a, y in b on bits v..=0, for the
+    // sums without and with a carry out of bit v.
+    fn add(
+        &mut self,
+        a: NodeId,
+        b: NodeId,
+        v: i16,
+        memo: &mut HashMap<(NodeId, NodeId, i16), (NodeId, NodeId)>,
+    ) -> (NodeId, NodeId) {
+        if a == FALSE || b == FALSE {
+            return (FALSE, FALSE);
+        }
+        if v < 0 {
+            return (TRUE, FALSE);
+        }
+        if let Some(&r) = memo.get(&(a, b, v)) {
+            return r;
+        }
+        let (ac, bc) = (self.cofactors(a, v), self.cofactors(b, v));
+        let mut res = [FALSE, FALSE];
+        for &(x, ax) in &[(0, ac.0), (1, ac.1)] {
+            for &(y, by) in &[(0, bc.0), (1, bc.1)] {
+                let low = self.add(ax, by, v - 1, memo);
+                for &(cin, r) in &[(0, low.0), (1, low.1)] {
+                    if r == FALSE {
+                        continue;
+                    }
+                    let sum = x ^ y ^ cin;
+                    let cout = (x & y) | (x & cin) | (y & cin);
+                    let n = if sum == 1 {
+                        self.mk(v, FALSE, r)
+                    } else {
+                        self.mk(v, r, FALSE)
+                    };
+                    res[cout] = self.or(res[cout], n);
+                }
+            }
+        }
+        memo.insert((a, b, v), (res[0], res[1]));
+        (res[0], res[1])
+    }
+
+    fn sum(&mut self, k: u8, a: NodeId, b: NodeId) -> NodeId {
+        let (r0, r1) = self.add(a, b, k as i16 - 1, &mut HashMap::new());
+        self.or(r0, r1)
+    }
+
+    fn negate(&mut self, k: u8, a: NodeId) -> NodeId {
+        let not = self.complement(a, &mut HashMap::new());
+        let one = self.cube(k, 1);
+        self.sum(k, not, one)
+    }
+
+    fn shl_const(&mut self, k: u8, a: NodeId, s: u8) -> NodeId {
+        if s == 0 {
+            return a;
+        }
+        if s >= k {
+            return self.cube(k, 0);
+        }
+        let low = self.smooth_above(a, (k - s) as i16, &mut HashMap::new());
+        let moved = self.rename(low, s as i16, &mut HashMap::new());
+        let zeros = self.bits_set_to(0, s - 1, false);
+        self.and(moved, zeros)
+    }
+
+    fn shr_const(&mut self, k: u8, a: NodeId, s: u8) -> NodeId {
+        if s == 0 {
+            return a;
+        }
+        if s >= k {
+            return self.cube(k, 0);
+        }
+        let high = self.smooth_below(a, s as i16 - 1, &mut HashMap::new());
+        let moved = self.rename(high, -(s as i16), &mut HashMap::new());
+        let zeros = self.bits_set_to(k - s, k - 1, false);
+        self.and(moved, zeros)
+    }
+
+    // Number of k-bits numbers in n, saturated to unum::max_value()
+    fn count(&self, n: NodeId, v: i16, memo: &mut HashMap<NodeId, u128>) -> u128 {
+        if n == FALSE {
+            return 0;
+        }
+        let level = self.level(n);
+        // Variables skipped between v and the level of n take any value
+        let skipped = 1u128 << (v - level) as u32;
+        if n == TRUE {
+            return skipped;
+        }
+        let c = if let Some(&c) = memo.get(&n) {
+            c
+        } else {
+            let node = self.nodes[n as usize];
+            let c = self.count(node.lo, level - 1, memo) + self.count(node.hi, level - 1, memo);
+            memo.insert(n, c);
+            c
+        };
+        c.saturating_mul(skipped)
+    }
+
+    // Returns up to `limit` members of n, sign extended from k bits.
+    fn members(&self, k: u8, n: NodeId, limit: usize) -> Vec<inum> {
+        let mut members = Vec::new();
+        let mut stack = vec![(n, k as i16 - 1, 0 as unum)];
+        while let Some((n, v, prefix)) = stack.pop() {
+            if n == FALSE {
+                continue;
+            }
+            if v < 0 {
+                members.push(sign_extend(prefix as inum, k));
+                if members.len() >= limit {
+                    break;
+                }
+                continue;
+            }
+            let (lo, hi) = self.cofactors(n, v);
+            stack.push((hi, v - 1, prefix | (1 << v)));
+            stack.push((lo, v - 1, prefix));
+        }
+        members
+    }
+
+    // Returns the smallest (or biggest) member of n as a signed k-bits number.
+    fn extreme(&self, k: u8, n: NodeId, biggest: bool) -> Option<inum> {
+        if n == FALSE {
+            return None;
+        }
+        let mut n = n;
+        let mut value: unum = 0;
+        for v in (0..k as i16).rev() {
+            let (lo, hi) = self.cofactors(n, v);
+            // The sign bit is the only one where 1 means smaller
+            let prefer_one = (v == k as i16 - 1) != biggest;
+            let one = if prefer_one { hi != FALSE } else { lo == FALSE };
+            if one {
+                value |= 1 << v;
+                n = hi;
+            } else {
+                n = lo;
+            }
+        }
+        Some(sign_extend(value as inum, k))
+    }
+
+    fn contains(&self, k: u8, n: NodeId, x: inum) -> bool {
+        let mut n = n;
+        for v in (0..k as i16).rev() {
+            let (lo, hi) = self.cofactors(n, v);
+            n = if (x >> v) & 1 == 1 { hi } else { lo };
+        }
+        n == TRUE
+    }
+
+    // Number of low bits in which n takes every value, whatever its other bits are.
+    fn free_low_bits(&mut self, k: u8, n: NodeId) -> u8 {
+        let mut t = 0;
+        while t < k && self.smooth_below(n, t as i16, &mut HashMap::new()) == n {
+            t += 1;
+        }
+        t
+    }
+
+    // Number of low bits which are zero in every member of n.
+    fn zero_low_bits(&mut self, k: u8, n: NodeId) -> u8 {
+        let mut t = 0;
+        while t < k {
+            let zeros = self.bits_set_to(0, t, false);
+            if self.diff(n, zeros) != FALSE {
+                break;
+            }
+            t += 1;
+        }
+        t
+    }
+}
+
+// Sign extends the k-bits number n.
+fn sign_extend(n: inum, k: u8) -> inum {
+    if k >= _bits {
+        n
+    } else {
+        (n << (_bits - k)) >> (_bits - k)
+    }
+}
+
+/// A k-bits set of integers represented as a BDD.
+///
+/// k == 0 means this BDD is an empty set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BDD {
+    k: u8,
+    root: NodeId,
+    // Garbage collections that happened before `root` was created, 0 for terminals which
+    // are never freed.
+    generation: u32,
+    // The root is only meaningful in the thread it was created in.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl BDD {
+    /// Returns the empty set of BDD.
+    pub fn null() -> Self {
+        BDD {
+            k: 0,
+            root: FALSE,
+            generation: 0,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the BDD containing all the numbers in k bits.
+    pub fn default_k(k: u8) -> Self {
+        BDD::from_root(k, TRUE)
+    }
+
+    /// Returns the BDD containing all the numbers in [lb, ub] in k bits.
+    pub fn range(k: u8, lb: inum, ub: inum) -> Self {
+        let k = cmp::min(k, _bits);
+        if k == 0 {
+            return BDD::null();
+        }
+        let (lb, ub) = (
+            cmp::max(lb, min_in_k_bits(k)),
+            cmp::min(ub, max_in_k_bits(k)),
+        );
+        BDD::from_root(k, with_manager(|m| m.range(k, lb, ub)))
+    }
+
+    /// Returns the BDD containing the numbers in `members` in k bits.
+    pub fn from_members(k: u8, members: &[inum]) -> Self {
+        let k = cmp::min(k, _bits);
+        if k == 0 {
+            return BDD::null();
+        }
+        let root = with_manager(|m| {
+            members.iter().fold(FALSE, |acc, &n| {
+                let c = m.cube(k, n);
+                m.or(acc, c)
+            })
+        });
+        BDD::from_root(k, root)
+    }
+
+    /// Frees the nodes of the BDDs of this thread that are not part of a BDD in `live`. The
+    /// BDDs in `live` are updated and stay usable, every other BDD of this thread becomes
+    /// invalid: operations on it panic, except for the empty and full sets.
+    pub fn collect_garbage(live: &mut [BDD]) {
+        let mut roots = live.iter().map(|b| b.id()).collect::<Vec<_>>();
+        with_manager(|m| m.collect(&mut roots));
+        GENERATION.with(|g| g.set(g.get().wrapping_add(1)));
+        for (bdd, root) in live.iter_mut().zip(roots) {
+            *bdd = BDD::from_root(bdd.k, root);
+        }
+    }
+
+    /// Empties the cache of results of operations of this thread. BDDs stay valid.
+    pub fn clear_cache() {
+        with_manager(|m| m.cache.clear());
+    }
+
+    /// Number of nodes held for the BDDs of this thread.
+    pub fn node_count() -> usize {
+        with_manager(|m| m.nodes.len())
+    }
+
+    /// Radix of the BDD.
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// Returns the members of the BDD, up to `limit` of them, in no particular order.
+    pub fn members(&self, limit: usize) -> Vec<inum> {
+        with_manager(|m| m.members(self.k, self.id(), limit))
+    }
+
+    /// Returns the smallest member of the BDD.
+    pub fn min(&self) -> Option<inum> {
+        with_manager(|m| m.extreme(self.k, self.id(), false))
+    }
+
+    /// Returns the biggest member of the BDD.
+    pub fn max(&self) -> Option<inum> {
+        with_manager(|m| m.extreme(self.k, self.id(), true))
+    }
+
+    fn from_root(k: u8, root: NodeId) -> Self {
+        if k == 0 || root == FALSE {
+            BDD::null()
+        } else {
+            BDD {
+                k: cmp::min(k, _bits),
+                root: root,
+                generation: if root == TRUE {
+                    0
+                } else {
+                    GENERATION.with(|g| g.get())
+                },
+                _not_send: PhantomData,
+            }
+        }
+    }
+
+    // Root of the BDD in the node table. Panics if its nodes have been freed since.
+    fn id(&self) -> NodeId {
+        if self.root > TRUE && self.generation != GENERATION.with(|g| g.get()) {
+            panic!("BDD used after its nodes were freed by `BDD::collect_garbage`");
+        }
+        self.root
+    }
+
+    fn count(&self) -> u128 {
+        with_manager(|m| m.count(self.id(), self.k as i16 - 1, &mut HashMap::new()))
+    }
+
+    // Checks both operands of an arithmetical operation, returns the result to use if they
+    // cannot be operated on.
+    fn check_operands(&self, other: &Self) -> Option<Self> {
+        if self.is_empty() || other.is_empty() {
+            radeco_err!("Empty set cannot be used in arithmetical operation");
+            Some(BDD::default())
+        } else if self.k != other.k {
+            radeco_err!("Two BDDs with different radices cannot be operated");
+            Some(BDD::default())
+        } else {
+            None
+        }
+    }
+
+    // Returns {f(x, y) | x in self, y in other} by enumerating both operands, if they are
+    // small enough. Pairs for which f returns None are ignored.
+    fn enumerate<F>(&self, other: &Self, f: F) -> Option<Self>
+    where
+        F: Fn(inum, inum) -> Option<inum>,
+    {
+        if self.count().saturating_mul(other.count()) > ENUM_LIMIT {
+            return None;
+        }
+        let (xs, ys) = (
+            self.members(ENUM_LIMIT as usize),
+            other.members(ENUM_LIMIT as usize),
+        );
+        let results = xs
+            .iter()
+            .flat_map(|&x| ys.iter().filter_map(|&y| f(x, y)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Some(BDD::from_members(self.k, &results))
+    }
+
+    fn bitwise(self, other: Self, op: BitOp) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        let root = with_manager(|m| {
+            m.bitwise(
+                op,
+                self.id(),
+                other.id(),
+                self.k as i16 - 1,
+                &mut HashMap::new(),
+            )
+        });
+        BDD::from_root(self.k, root)
+    }
+
+    // Returns the shift amounts in other (as numbers in [0, k)), and whether other contains
+    // amounts out of that range.
+    fn shift_amounts(&self, other: &Self) -> (Vec<u8>, bool) {
+        let amounts = (0..self.k)
+            .filter(|&s| other.contains(&(s as inum)))
+            .collect();
+        let in_range = BDD::range(other.k, 0, self.k as inum - 1);
+        let out_of_range = with_manager(|m| m.diff(other.id(), in_range.id())) != FALSE;
+        (amounts, out_of_range)
+    }
+
+    fn shift<F>(self, other: Self, f: F) -> Self
+    where
+        F: Fn(&mut Manager, u8, NodeId, u8) -> NodeId,
+    {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        let (amounts, out_of_range) = self.shift_amounts(&other);
+        let root = with_manager(|m| {
+            let mut root = if out_of_range {
+                m.cube(self.k, 0)
+            } else {
+                FALSE
+            };
+            for s in amounts {
+                let shifted = f(m, self.k, self.id(), s);
+                root = m.or(root, shifted);
+            }
+            root
+        });
+        BDD::from_root(self.k, root)
+    }
+}
+
+fn min_in_k_bits(k: u8) -> inum {
+    inum::min_value() >> (_bits - k)
+}
+
+fn max_in_k_bits(k: u8) -> inum {
+    inum::max_value() >> (_bits - k)
+}
+
+impl fmt::Display for BDD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.count();
+        if count <= 8 {
+            let mut members = self.members(8);
+            members.sort();
+            write!(f, "{}-bits: {:?}", self.k, members)
+        } else {
+            write!(
+                f,
+                "{}-bits: {} numbers in [{}, {}]",
+                self.k,
+                count,
+                self.min().unwrap_or(0),
+                self.max().unwrap_or(0)
+            )
+        }
+    }
+}
+
+//
+// Operations for BDD go here
+//
+
+impl Default for BDD {
+    fn default() -> Self {
+        BDD::default_k(_bits)
+    }
+}
+
+// Generate BDD by a constant
+impl From<inum> for BDD {
+    fn from(number: inum) -> Self {
+        BDD::from((_bits, number))
+    }
+}
+
+impl From<(u8, inum)> for BDD {
+    fn from(number_k: (u8, inum)) -> Self {
+        let (k, n) = number_k;
+        let k = cmp::min(k, _bits);
+        if k == 0 {
+            BDD::null()
+        } else {
+            if n < min_in_k_bits(k) || n > max_in_k_bits(k) {
+                radeco_warn!("{:?} cannot be hold by {:?} bits", n, k);
+            }
+            BDD::from_root(k, with_manager(|m| m.cube(k, n)))
+        }
+    }
+}
+
+impl Container<inum> for BDD {
+    fn contains(&self, object: &inum) -> bool {
+        if self.is_empty() || *object < min_in_k_bits(self.k) || *object > max_in_k_bits(self.k) {
+            false
+        } else {
+            with_manager(|m| m.contains(self.k, self.id(), *object))
+        }
+    }
+}
+
+impl Container<BDD> for BDD {
+    fn contains(&self, object: &BDD) -> bool {
+        if object.is_empty() {
+            true
+        } else if self.is_empty() || self.k != object.k {
+            false
+        } else {
+            with_manager(|m| m.diff(object.id(), self.id())) == FALSE
+        }
+    }
+}
+
+impl Container<Vec<inum>> for BDD {
+    fn contains(&self, object: &Vec<inum>) -> bool {
+        object.iter().all(|x| self.contains(x))
+    }
+}
+
+impl Neg for BDD {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        BDD::from_root(self.k, with_manager(|m| m.negate(self.k, self.id())))
+    }
+}
+
+impl Add for BDD {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        BDD::from_root(
+            self.k,
+            with_manager(|m| m.sum(self.k, self.id(), other.id())),
+        )
+    }
+}
+
+impl Sub for BDD {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
+impl Mul for BDD {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        let k = self.k;
+        if let Some(res) = self.enumerate(&other, |x, y| Some(x.wrapping_mul(y))) {
+            return res;
+        }
+        // Multiplication by a constant is a sum of shifts
+        let (set, c) = match (self.constant(), other.constant()) {
+            (_, Some(c)) => (self, c),
+            (Some(c), _) => (other, c),
+            (None, None) => {
+                // Trailing zeros of both operands are kept
+                let root = with_manager(|m| {
+                    let zeros = m.zero_low_bits(k, self.id()) + m.zero_low_bits(k, other.id());
+                    if zeros == 0 {
+                        TRUE
+                    } else {
+                        m.bits_set_to(0, cmp::min(zeros, k) - 1, false)
+                    }
+                });
+                return BDD::from_root(k, root);
+            }
+        };
+        let root = with_manager(|m| {
+            let mut root = m.cube(k, 0);
+            for s in (0..k).filter(|&s| (c >> s) & 1 == 1) {
+                let shifted = m.shl_const(k, set.id(), s);
+                root = m.sum(k, root, shifted);
+            }
+            root
+        });
+        BDD::from_root(k, root)
+    }
+}
+
+impl Div for BDD {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        if other.constant() == Some(0) {
+            radeco_warn!("Divisor cannot be zero");
+            return BDD::null();
+        }
+        let div = |x: inum, y: inum| {
+            if y == 0 {
+                None
+            } else {
+                Some(x.wrapping_div(y))
+            }
+        };
+        self.enumerate(&other, div)
+            .unwrap_or_else(|| BDD::default_k(self.k))
+    }
+}
+
+impl Rem for BDD {
+    type Output = Self;
+
+    fn rem(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        if other.constant() == Some(0) {
+            radeco_warn!("Divisor cannot be zero");
+            return BDD::null();
+        }
+        let rem = |x: inum, y: inum| {
+            if y == 0 {
+                None
+            } else {
+                Some(x.wrapping_rem(y))
+            }
+        };
+        if let Some(res) = self.enumerate(&other, rem) {
+            return res;
+        }
+        // |x % y| < |y| and x % y has the sign of x
+        let (min, max) = (other.min().unwrap_or(0), other.max().unwrap_or(0));
+        let abs = |x: inum| x.checked_abs().unwrap_or(inum::max_value());
+        let bound = cmp::max(abs(min), abs(max)) - 1;
+        let lb = if self.min().unwrap_or(0) >= 0 {
+            0
+        } else {
+            -bound
+        };
+        let ub = if self.max().unwrap_or(0) <= 0 {
+            0
+        } else {
+            bound
+        };
+        BDD::range(self.k, lb, ub)
+    }
+}
+
+impl BitAnd for BDD {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.bitwise(other, BitOp::And)
+    }
+}
+
+impl BitOr for BDD {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.bitwise(other, BitOp::Or)
+    }
+}
+
+impl BitXor for BDD {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self {
+        self.bitwise(other, BitOp::Xor)
+    }
+}
+
+impl Not for BDD {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        BDD::from_root(
+            self.k,
+            with_manager(|m| m.complement(self.id(), &mut HashMap::new())),
+        )
+    }
+}
+
+impl Shr for BDD {
+    type Output = Self;
+
+    // All shift right in ESIl is logical shift
+    fn shr(self, other: Self) -> Self {
+        self.shift(other, |m, k, n, s| m.shr_const(k, n, s))
+    }
+}
+
+impl Shl for BDD {
+    type Output = Self;
+
+    fn shl(self, other: Self) -> Self {
+        self.shift(other, |m, k, n, s| m.shl_const(k, n, s))
+    }
+}
+
+impl AbstractSet for BDD {
+    fn meet(&self, other: &Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            BDD::null()
+        } else if self.k != other.k {
+            radeco_err!("Meet two BDDs with different radices");
+            BDD::null()
+        } else {
+            BDD::from_root(self.k, with_manager(|m| m.and(self.id(), other.id())))
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        if self.is_empty() {
+            *other
+        } else if other.is_empty() {
+            *self
+        } else if self.k != other.k {
+            radeco_err!("Join two BDDs with different radices");
+            BDD::default()
+        } else {
+            BDD::from_root(self.k, with_manager(|m| m.or(self.id(), other.id())))
+        }
+    }
+
+    // Every time the set grows, one more low bit is allowed to take any value. Thus an
+    // increasing chain of widenings stabilizes after at most k + 1 steps.
+    fn widen(&self, other: &Self) -> Self {
+        let joined = self.join(other);
+        if self.is_empty() || other.is_empty() || joined == *self || self.k != other.k {
+            return joined;
+        }
+        let k = self.k;
+        let root = with_manager(|m| {
+            let free = m.free_low_bits(k, self.id());
+            m.smooth_below(joined.id(), free as i16, &mut HashMap::new())
+        });
+        BDD::from_root(k, root)
+    }
+
+    fn remove_lower_bound(&self) -> Self {
+        match self.max() {
+            Some(ub) => BDD::range(self.k, min_in_k_bits(self.k), ub),
+            None => *self,
+        }
+    }
+
+    fn set_lower_bound(&self, x: inum) -> Self {
+        if self.is_empty() {
+            *self
+        } else {
+            self.meet(&BDD::range(self.k, x, max_in_k_bits(self.k)))
+        }
+    }
+
+    fn remove_upper_bound(&self) -> Self {
+        match self.min() {
+            Some(lb) => BDD::range(self.k, lb, max_in_k_bits(self.k)),
+            None => *self,
+        }
+    }
+
+    fn set_upper_bound(&self, x: inum) -> Self {
+        if self.is_empty() {
+            *self
+        } else {
+            self.meet(&BDD::range(self.k, min_in_k_bits(self.k), x))
+        }
+    }
+
+    fn narrow(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty BDD cannot be narrow");
+            *self
+        } else if k > self.k {
+            radeco_warn!("BDD cannot be narrowed to a bigger bits");
+            *self
+        } else if k == self.k {
+            *self
+        } else {
+            let root = with_manager(|m| m.smooth_above(self.id(), k as i16, &mut HashMap::new()));
+            BDD::from_root(k, root)
+        }
+    }
+
+    fn sign_extend(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty BDD cannot be extended");
+            *self
+        } else if k < self.k {
+            radeco_warn!("BDD cannot be extended to a smaller bits");
+            *self
+        } else if k == self.k {
+            *self
+        } else {
+            let (old, k) = (self.k, cmp::min(k, _bits));
+            let root = with_manager(|m| {
+                let sign = (old - 1) as i16;
+                let (pos, neg) = (m.mk(sign, TRUE, FALSE), m.mk(sign, FALSE, TRUE));
+                let (zeros, ones) = (
+                    m.bits_set_to(old, k - 1, false),
+                    m.bits_set_to(old, k - 1, true),
+                );
+                let pos = m.and(pos, zeros);
+                let neg = m.and(neg, ones);
+                let ext = m.or(pos, neg);
+                m.and(self.id(), ext)
+            });
+            BDD::from_root(k, root)
+        }
+    }
+
+    fn zero_extend(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty BDD cannot be extended");
+            *self
+        } else if k < self.k {
+            radeco_warn!("BDD cannot be extended to a smaller bits");
+            *self
+        } else if k == self.k {
+            *self
+        } else {
+            let (old, k) = (self.k, cmp::min(k, _bits));
+            let root = with_manager(|m| {
+                let zeros = m.bits_set_to(old, k - 1, false);
+                m.and(self.id(), zeros)
+            });
+            BDD::from_root(k, root)
+        }
+    }
+
+    fn constant(&self) -> Option<inum> {
+        if !self.is_empty() && self.count() == 1 {
+            self.min()
+        } else {
+            None
+        }
+    }
+
+    fn capacity(&self) -> inum {
+        cmp::min(self.count(), inum::max_value() as u128) as inum
+    }
+
+    fn is_empty(&self) -> bool {
+        self.k == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set(k: u8, members: &[inum]) -> BDD {
+        BDD::from_members(k, members)
+    }
+
+    fn sorted(bdd: BDD) -> Vec<inum> {
+        let mut members = bdd.members(1024);
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn bdd_test_basicfn() {
+        let op = set(8, &[-3, 1, 5, 100]);
+        assert_eq!(vec![-3, 1, 5, 100], sorted(op));
+        assert_eq!(4, op.capacity());
+        assert_eq!(Some(-3), op.min());
+        assert_eq!(Some(100), op.max());
+        assert!(op.contains(&5));
+        assert!(!op.contains(&6));
+        assert!(!op.contains(&1000));
+        assert!(op.contains(&set(8, &[1, 100])));
+        assert!(!op.contains(&set(8, &[1, 2])));
+        assert_eq!(Some(5), BDD::from((8, 5)).constant());
+        assert_eq!(None, op.constant());
+        assert_eq!(set(8, &[-3, 1, 5, 100]), set(8, &[100, 5, 1, -3]));
+
+        assert!(BDD::null().is_empty());
+        assert_eq!(256, BDD::default_k(8).capacity());
+        assert_eq!(vec![-2, -1, 0, 1], sorted(BDD::range(8, -2, 1)));
+        assert_eq!(51, BDD::range(8, -100, -50).capacity());
+        assert_eq!(BDD::range(8, 10, 20), BDD::range(8, 10, 20));
+    }
+
+    #[test]
+    fn bdd_test_arith() {
+        let op1 = set(8, &[1, 2, 3]);
+        let op2 = set(8, &[10, 20]);
+        assert_eq!(vec![11, 12, 13, 21, 22, 23], sorted(op1 + op2));
+        assert_eq!(vec![-19, -18, -17, -9, -8, -7], sorted(op1 - op2));
+        assert_eq!(vec![-3, -2, -1], sorted(-op1));
+        assert_eq!(vec![10, 20, 30, 40, 60], sorted(op1 * op2));
+        assert_eq!(vec![3, 5, 6, 10, 20], sorted(op2 / op1));
+        assert_eq!(vec![0, 1, 2], sorted(op2 % op1));
+
+        // Wrap around in k bits
+        assert_eq!(vec![-128], sorted(BDD::from((8, 127)) + BDD::from((8, 1))));
+
+        // Big operands are not enumerated
+        let big = BDD::range(16, 0, 1000);
+        assert_eq!(BDD::range(16, 1, 1001), big + BDD::from((16, 1)));
+        let even = big * BDD::from((16, 2));
+        assert!(even.contains(&2000));
+        assert!(!even.contains(&1));
+        let rem = big % BDD::range(16, 1, 100);
+        assert!(rem.contains(&BDD::range(16, 0, 99)));
+        assert!(!rem.contains(&100));
+    }
+
+    #[test]
+    fn bdd_test_bitop() {
+        let op1 = set(8, &[0x0f, 0x3c]);
+        let op2 = set(8, &[0x33, 0x01]);
+        assert_eq!(vec![0x00, 0x01, 0x03, 0x30], sorted(op1 & op2));
+        assert_eq!(vec![0x0f, 0x3d, 0x3f], sorted(op1 | op2));
+        assert_eq!(vec![0x0e, 0x0f, 0x3c, 0x3d], sorted(op1 ^ op2));
+        assert_eq!(vec![-61, -16], sorted(!op1));
+        assert_eq!(vec![0x1e, 0x78], sorted(op1 << BDD::from((8, 1))));
+        assert_eq!(vec![0x03, 0x0f], sorted(op1 >> BDD::from((8, 2))));
+        assert_eq!(
+            vec![0x01, 0x03, 0x07, 0x0f, 0x1e, 0x3c],
+            sorted(op1 >> BDD::range(8, 0, 3))
+        );
+
+        // Masks are precise even on sets that cannot be enumerated
+        let masked = BDD::default_k(32) & BDD::from((32, 0xff0));
+        assert_eq!(256, masked.capacity());
+        assert!(masked.contains(&0xab0));
+        assert!(!masked.contains(&0xab1));
+        assert_eq!(
+            vec![0, 1],
+            sorted((BDD::default_k(32) >> BDD::from((32, 31))) & BDD::from((32, 1)))
+        );
+    }
+
+    #[test]
+    fn bdd_test_setop() {
+        let op1 = set(8, &[1, 2, 3]);
+        let op2 = set(8, &[3, 4]);
+        assert_eq!(vec![1, 2, 3, 4], sorted(op1.join(&op2)));
+        assert_eq!(vec![3], sorted(op1.meet(&op2)));
+        assert_eq!(op1, op1.join(&BDD::null()));
+        assert_eq!(BDD::null(), op1.meet(&BDD::null()));
+        assert_eq!(vec![2, 3], sorted(op1.set_lower_bound(2)));
+        assert_eq!(vec![1, 2], sorted(op1.set_upper_bound(2)));
+        assert_eq!(BDD::range(8, -128, 3), op1.remove_lower_bound());
+        assert_eq!(BDD::range(8, 1, 127), op1.remove_upper_bound());
+
+        // Widening terminates
+        let mut w = BDD::from((8, 0));
+        for _ in 0..10 {
+            w = w.widen(&(w + BDD::from((8, 4))));
+        }
+        assert_eq!(w, w.widen(&(w + BDD::from((8, 4)))));
+        assert!(w.contains(&vec![0, 4, 8, 12, 124]));
+        assert_eq!(op1, op1.widen(&set(8, &[2])));
+    }
+
+    #[test]
+    fn bdd_test_resize() {
+        let op = set(16, &[-1, 0x1ff, 0x7f]);
+        assert_eq!(vec![-1, 0x7f], sorted(op.narrow(8)));
+        let op = set(8, &[-1, 0x7f]);
+        assert_eq!(vec![-1, 0x7f], sorted(op.sign_extend(16)));
+        assert_eq!(vec![0x7f, 0xff], sorted(op.zero_extend(16)));
+        assert_eq!(vec![-1, 0x7f], sorted(op.sign_extend(64)));
+    }
+
+    #[test]
+    fn bdd_test_collect_garbage() {
+        // Every test runs in a thread of its own, with a manager of its own.
+        let mut live = [set(16, &[1, 300, -7]), BDD::range(16, -100, 100)];
+        let members = live.iter().map(|&b| sorted(b)).collect::<Vec<_>>();
+        let _dead = set(16, &[2, 4, 8, 16, 32, 64]) + BDD::range(16, 1000, 2000);
+        let before = BDD::node_count();
+        BDD::collect_garbage(&mut live);
+        assert!(BDD::node_count() < before);
+        assert_eq!(members, live.iter().map(|&b| sorted(b)).collect::<Vec<_>>());
+        // Hash-consing still finds the nodes that were kept.
+        assert_eq!(live[0], set(16, &[-7, 300, 1]));
+        assert_eq!(live[1], BDD::range(16, -100, 100));
+
+        BDD::clear_cache();
+        assert_eq!(vec![-6, 2, 301], sorted(live[0] + BDD::from((16, 1))));
+    }
+
+    #[test]
+    #[should_panic(expected = "BDD::collect_garbage")]
+    fn bdd_test_collected_handle() {
+        let mut live = [set(16, &[1, 300, -7])];
+        let dead = BDD::range(16, -100, 100);
+        // The empty and full sets have no nodes to free.
+        let full = BDD::default_k(16);
+        BDD::collect_garbage(&mut live);
+        assert_eq!(full, full & BDD::default_k(16));
+        dead.min();
+    }
+}
@@ -39,9 +39,7 @@ pub const _bits: u8 = 64;
 /// Trait used to indicate this is a container which contains something
 pub trait Container<T: Clone> {
     // Returns true if the Container `self` contains `object`, false otherwise.
-    fn contains(&self, object: &T) -> bool {
-        unimplemented!();
-    }
+    fn contains(&self, object: &T) -> bool;
 }
 
 /// Type of abstract set.
@@ -60,18 +58,18 @@ pub trait AbstractSet:
     + PartialEq
     + Eq
     + Hash
-    + Neg
-    + Add
-    + Sub
-    + Div
-    + Rem
-    + Mul
-    + BitAnd
-    + BitOr
-    + BitXor
-    + Not
-    + Shl
-    + Shr
+    + Neg<Output = Self>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Mul<Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<Output = Self>
+    + Shr<Output = Self>
     + Container<inum>
     + Container<Self>
     + Container<Vec<inum>>
@@ -80,67 +78,41 @@ pub trait AbstractSet:
     + Default
 {
     /// Returns the meet (intersection) of AbstractSet `self` and `other`.
-    fn meet(&self, other: &Self) -> Self {
-        unimplemented!();
-    }
+    fn meet(&self, other: &Self) -> Self;
 
     /// Returns the join (union) of AbstractSet `self` and `other`.
-    fn join(&self, other: &Self) -> Self {
-        unimplemented!();
-    }
+    fn join(&self, other: &Self) -> Self;
 
     /// Returns the AbstractSet obtained by widening `self` with respect to `other`
-    fn widen(&self, other: &Self) -> Self {
-        unimplemented!();
-    }
+    fn widen(&self, other: &Self) -> Self;
 
     /// Returns the AbstractSet obtained by remoing lower bound for `self`
-    fn remove_lower_bound(&self) -> Self {
-        unimplemented!()
-    }
+    fn remove_lower_bound(&self) -> Self;
 
     /// Returns the AbstractSet obtained by setting lower bound as x for `self`
-    fn set_lower_bound(&self, x: inum) -> Self {
-        unimplemented!()
-    }
+    fn set_lower_bound(&self, x: inum) -> Self;
 
     /// Returns the AbstractSet obtained by remoing upper bound for `self`
-    fn remove_upper_bound(&self) -> Self {
-        unimplemented!()
-    }
+    fn remove_upper_bound(&self) -> Self;
 
     /// Returns the AbstractSet obtained by setting upper bound as x for `self`
-    fn set_upper_bound(&self, x: inum) -> Self {
-        unimplemented!()
-    }
+    fn set_upper_bound(&self, x: inum) -> Self;
 
     /// Returns the AbstractSet narrowed into a new k-bit-filed, for OpNarrow.
-    fn narrow(&self, k: u8) -> Self {
-        unimplemented!();
-    }
+    fn narrow(&self, k: u8) -> Self;
 
     /// Returns the AbstractSet sign extended into a new k-bit-filed, for OpSignExt.
-    fn sign_extend(&self, k: u8) -> Self {
-        unimplemented!();
-    }
+    fn sign_extend(&self, k: u8) -> Self;
 
     /// Returns the AbstractSet zero extended into a new k-bit-filed, for OpZeroExt.
-    fn zero_extend(&self, k: u8) -> Self {
-        unimplemented!();
-    }
+    fn zero_extend(&self, k: u8) -> Self;
 
     /// Returns Some(cons) if the AbstractSet only contains one constant, None otherwise.
-    fn constant(&self) -> Option<inum> {
-        unimplemented!()
-    }
+    fn constant(&self) -> Option<inum>;
 
     /// Returns capacity of AbstractSet.
-    fn capacity(&self) -> inum {
-        unimplemented!()
-    }
+    fn capacity(&self) -> inum;
 
     /// Returns whether the AbstractSet is empty.
-    fn is_empty(&self) -> bool {
-        unimplemented!()
-    }
+    fn is_empty(&self) -> bool;
 }
@@ -11,5 +11,1063 @@
 //!     base + [a1]x1 + [a2]x2 + ... + [an]xn
 //! which means:
 //!     {base + k1 * x1 + k2 * x2 + ... + kn * xn | 0 <= ki <= ai}
+//!
+//! All the computations are done modulo 2^k, as the machine does. Thus addition,
+//! subtraction, negation, multiplication and left shift by a constant, and narrowing are
+//! exact. For instance, the addresses of `a[i][j].f` stay `&a + off(f) + [n]s1 + [m]s2`
+//! instead of collapsing into a single strided interval. The other operations are exact on
+//! constants and over-approximated otherwise.
+//!
+//! Strides are kept in (0, 2^(k - 1)] and terms are sorted by stride, so that equal
+//! polynomials have the same representation in most cases.
+
+use std::cmp;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
+
+use super::abstract_set::{_bits, inum, unum};
+use super::abstract_set::{AbstractSet, Container};
+
+/// Maximum number of terms of a polynomial. Additional terms are merged together.
+pub const MAX_TERMS: usize = 4;
+
+// Membership is decided exactly as long as at most this many combinations of terms need to
+// be enumerated.
+const ENUM_LIMIT: u128 = 0x10000;
+
+//
+// Utility functions go here
+//
+
+// Mask of the k low bits, k may be _bits.
+fn mask(k: u8) -> unum {
+    if k >= _bits {
+        unum::max_value()
+    } else {
+        (1 << k) - 1
+    }
+}
+
+// Sign extends the k-bits number n.
+fn sign_extend(n: unum, k: u8) -> inum {
+    if k >= _bits {
+        n as inum
+    } else {
+        ((n << (_bits - k)) as inum) >> (_bits - k)
+    }
+}
+
+fn gcd(x: unum, y: unum) -> unum {
+    let (mut x, mut y) = (x, y);
+    while y != 0 {
+        let t = y;
+        y = x % y;
+        x = t;
+    }
+    x
+}
+
+// Inverse of the odd number x modulo 2^64, by Newton's iteration.
+fn inverse(x: unum) -> unum {
+    let mut inv = x;
+    for _ in 0..6 {
+        inv = inv.wrapping_mul(2u64.wrapping_sub(x.wrapping_mul(inv)));
+    }
+    inv
+}
+
+/// A k-bits polynomial `base + [a1]x1 + ... + [an]xn`.
+///
+/// k == 0 means this polynomial is an empty set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Polynomial {
+    pub k: u8,
+    pub base: inum,
+    // Number of used terms
+    n: usize,
+    // (stride, count) of every term
+    terms: [(unum, unum); MAX_TERMS],
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-bits: {}", self.k, self.base)?;
+        for &(x, a) in self.terms() {
+            write!(f, " + [{}]{}", a, x)?;
+        }
+        Ok(())
+    }
+}
+
+impl Polynomial {
+    /// Returns the empty set of Polynomial.
+    pub fn null() -> Self {
+        Polynomial {
+            k: 0,
+            base: 0,
+            n: 0,
+            terms: [(0, 0); MAX_TERMS],
+        }
+    }
+
+    /// Returns a polynomial containing all the numbers in k bits.
+    pub fn default_k(k: u8) -> Self {
+        if k == 0 {
+            Polynomial::null()
+        } else {
+            let k = cmp::min(k, _bits);
+            Polynomial::new(k, inum::min_value() >> (_bits - k), &[(1, mask(k))])
+        }
+    }
+
+    /// Returns the polynomial `base + [a1]x1 + ... + [an]xn` in k bits, where `terms` are
+    /// the pairs `(xi, ai)`.
+    pub fn new(k: u8, base: inum, terms: &[(unum, unum)]) -> Self {
+        if k == 0 {
+            return Polynomial::null();
+        }
+        let mut p = Polynomial {
+            k: cmp::min(k, _bits),
+            base: base,
+            n: 0,
+            terms: [(0, 0); MAX_TERMS],
+        };
+        p.normalize(terms.to_vec());
+        p
+    }
+
+    /// Returns the terms `(xi, ai)` of the polynomial.
+    pub fn terms(&self) -> &[(unum, unum)] {
+        &self.terms[..self.n]
+    }
+
+    /// Returns the members of the polynomial, up to `limit` of them.
+    pub fn members(&self, limit: usize) -> Vec<inum> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut members = vec![self.base as unum];
+        for &(x, a) in self.terms() {
+            let mut next = Vec::new();
+            'outer: for &m in &members {
+                let mut v = m;
+                for _ in 0..(a as u128 + 1) {
+                    next.push(v);
+                    if next.len() >= limit {
+                        break 'outer;
+                    }
+                    v = v.wrapping_add(x);
+                }
+            }
+            members = next;
+        }
+        let mut members = members
+            .iter()
+            .map(|&m| sign_extend(m & mask(self.k), self.k))
+            .collect::<Vec<_>>();
+        members.sort();
+        members.dedup();
+        members
+    }
+
+    /// Returns the bounds of the polynomial if its members do not wrap around in k bits.
+    pub fn bounds(&self) -> Option<(inum, inum)> {
+        if self.is_empty() {
+            return None;
+        }
+        let ub = self
+            .terms()
+            .iter()
+            .fold(Some(self.base as i128), |acc, &(x, a)| {
+                acc.and_then(|acc| (x as i128).checked_mul(a as i128).map(|s| acc + s))
+            });
+        match ub {
+            Some(ub) if ub <= (inum::max_value() >> (_bits - self.k)) as i128 => {
+                Some((self.base, ub as inum))
+            }
+            _ => None,
+        }
+    }
+
+    // Number of low bits which are zero in every member.
+    fn trailing_zeros(&self) -> u32 {
+        self.terms()
+            .iter()
+            .fold(self.base.trailing_zeros(), |acc, &(x, _)| {
+                cmp::min(acc, x.trailing_zeros())
+            })
+    }
+
+    // Greatest common divisor of all the strides, 0 for constants.
+    fn stride(&self) -> unum {
+        self.terms().iter().fold(0, |acc, &(x, _)| gcd(acc, x))
+    }
+
+    // Number of combinations of the counts, saturated.
+    fn combinations(&self, terms: &[(unum, unum)]) -> u128 {
+        terms
+            .iter()
+            .fold(1u128, |acc, &(_, a)| acc.saturating_mul(a as u128 + 1))
+    }
+
+    // Brings the polynomial in its normal form after its terms are changed.
+    fn normalize(&mut self, terms: Vec<(unum, unum)>) {
+        let k = self.k;
+        let m = mask(k);
+        let mut base = self.base as unum;
+        let mut norm: Vec<(unum, unum)> = Vec::new();
+        for (x, a) in terms {
+            let mut x = x & m;
+            if x == 0 || a == 0 {
+                continue;
+            }
+            // -[a]x = -a * x + [a]x, keep the smallest stride
+            if x > (m >> 1) + 1 {
+                x = x.wrapping_neg() & m;
+                base = base.wrapping_sub(x.wrapping_mul(a));
+            }
+            // x has period 2^(k - ntz(x)), more counts are useless
+            let period = mask(k - x.trailing_zeros() as u8);
+            let a = cmp::min(a, period);
+            match norm.iter().position(|&(y, _)| y == x) {
+                Some(i) => norm[i].1 = cmp::min(norm[i].1.saturating_add(a), period),
+                None => norm.push((x, a)),
+            }
+        }
+        norm.sort();
+
+        // [a]x + [b]((a + 1) * x) == [a + (a + 1) * b]x
+        let mut i = 0;
+        while i < norm.len() {
+            let (x, a) = norm[i];
+            let next = (x as u128) * (a as u128 + 1);
+            let merged = norm
+                .iter()
+                .position(|&(y, _)| y as u128 == next)
+                .map(|j| (j, norm[j].1));
+            match merged {
+                Some((j, b)) => {
+                    let period = mask(k - x.trailing_zeros() as u8) as u128;
+                    let count = cmp::min(a as u128 + (a as u128 + 1) * b as u128, period);
+                    norm[i].1 = count as unum;
+                    norm.remove(j);
+                    i = 0;
+                }
+                None => i += 1,
+            }
+        }
+
+        // Too many terms, [a]x + [b]y is in [a * x / g + b * y / g]g for g = gcd(x, y)
+        while norm.len() > MAX_TERMS {
+            let (x, a) = norm.remove(0);
+            let (y, b) = norm.remove(0);
+            let g = gcd(x, y);
+            let count = (a as u128 * (x / g) as u128).saturating_add(b as u128 * (y / g) as u128);
+            let count = cmp::min(count, mask(k - g.trailing_zeros() as u8) as u128);
+            norm.push((g, count as unum));
+            norm.sort();
+        }
+
+        self.base = sign_extend(base & m, k);
+        self.n = norm.len();
+        self.terms = [(0, 0); MAX_TERMS];
+        for (i, t) in norm.into_iter().enumerate() {
+            self.terms[i] = t;
+        }
+    }
+
+    // Returns the smallest k such that d == k * x (mod 2^self.k), if any.
+    fn solve(&self, d: unum, x: unum) -> Option<unum> {
+        let d = d & mask(self.k);
+        if d == 0 {
+            return Some(0);
+        }
+        let tz = x.trailing_zeros() as u8;
+        if d.trailing_zeros() < tz as u32 {
+            return None;
+        }
+        let bits = self.k - tz;
+        Some(((d >> tz).wrapping_mul(inverse(x >> tz))) & mask(bits))
+    }
+
+    // Applies `f` on the constants `self` and `other`, or returns the k-bits top.
+    fn on_constants<F>(&self, other: &Self, f: F) -> Self
+    where
+        F: Fn(inum, inum) -> inum,
+    {
+        match (self.constant(), other.constant()) {
+            (Some(x), Some(y)) => Polynomial::from((self.k, f(x, y))),
+            _ => Polynomial::default_k(self.k),
+        }
+    }
+
+    // Decides whether `n` is a member. This is exact as long as the combinations of all the
+    // terms but the biggest one can be enumerated, otherwise `None` is returned.
+    fn membership(&self, n: inum) -> Option<bool> {
+        if self.is_empty() || sign_extend(n as unum & mask(self.k), self.k) != n {
+            return Some(false);
+        }
+        let d = (n as unum).wrapping_sub(self.base as unum) & mask(self.k);
+        let terms = self.terms();
+        if terms.is_empty() {
+            return Some(d == 0);
+        }
+        let last = (0..terms.len()).max_by_key(|&i| terms[i].1).unwrap_or(0);
+        let others = terms
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != last)
+            .map(|(_, &t)| t)
+            .collect::<Vec<_>>();
+        // Members are congruent to the base modulo the stride.
+        if d.trailing_zeros() < self.stride().trailing_zeros() {
+            return Some(false);
+        }
+        if self.combinations(&others) > ENUM_LIMIT {
+            return None;
+        }
+        let mut offsets = vec![0 as unum];
+        for &(x, a) in &others {
+            offsets = offsets
+                .iter()
+                .flat_map(|&o| (0..a + 1).map(move |i| o.wrapping_add(x.wrapping_mul(i))))
+                .collect();
+        }
+        let (x, a) = terms[last];
+        Some(
+            offsets
+                .iter()
+                .any(|&o| self.solve(d.wrapping_sub(o), x).map_or(false, |k| k <= a)),
+        )
+    }
+
+    // Whether every member of `self` is known to be a member of `other`. False when this
+    // cannot be decided, so that `join` never drops members.
+    fn is_subset(&self, other: &Self) -> bool {
+        if self.is_empty() {
+            true
+        } else if other.is_empty() || self.k != other.k {
+            false
+        } else if self.combinations(self.terms()) <= ENUM_LIMIT {
+            self.members(ENUM_LIMIT as usize)
+                .iter()
+                .all(|&m| other.membership(m) == Some(true))
+        } else {
+            // Only decided for single terms, self.base + [b]y is in other.base + [a]x if y is
+            // a multiple of x and the counts needed to reach every member fit in a.
+            if self.n != 1 || other.n != 1 {
+                return false;
+            }
+            let ((x, a), (y, b)) = (other.terms[0], self.terms[0]);
+            if y % x != 0 {
+                return false;
+            }
+            let d = (self.base as unum).wrapping_sub(other.base as unum);
+            match other.solve(d, x) {
+                Some(k) => {
+                    a == mask(other.k - x.trailing_zeros() as u8)
+                        || k as u128 + (y / x) as u128 * b as u128 <= a as u128
+                }
+                None => false,
+            }
+        }
+    }
+
+    // Checks both operands of an arithmetical operation, returns the result to use if they
+    // cannot be operated on.
+    fn check_operands(&self, other: &Self) -> Option<Self> {
+        if self.is_empty() || other.is_empty() {
+            radeco_err!("Empty set cannot be used in arithmetical operation");
+            Some(Polynomial::default())
+        } else if self.k != other.k {
+            radeco_err!("Two polynomials with different radices cannot be operated");
+            Some(Polynomial::default())
+        } else {
+            None
+        }
+    }
+
+    // Returns self * c (mod 2^k).
+    fn scale(&self, c: unum) -> Self {
+        let terms = self
+            .terms()
+            .iter()
+            .map(|&(x, a)| (x.wrapping_mul(c), a))
+            .collect::<Vec<_>>();
+        Polynomial::new(self.k, (self.base as unum).wrapping_mul(c) as inum, &terms)
+    }
+
+    // Returns {x | lb <= x <= ub, x = self.base (mod g)} for g the stride of self.
+    fn aligned_range(&self, lb: inum, ub: inum) -> Self {
+        let g = self.stride() as i128;
+        if g == 0 {
+            return if lb <= self.base && self.base <= ub {
+                *self
+            } else {
+                Polynomial::null()
+            };
+        }
+        let (lb, ub, base) = (lb as i128, ub as i128, self.base as i128);
+        let lb = lb + ((base - lb) % g + g) % g;
+        if lb > ub {
+            Polynomial::null()
+        } else {
+            Polynomial::new(self.k, lb as inum, &[(g as unum, ((ub - lb) / g) as unum)])
+        }
+    }
+}
+
+//
+// Operations for Polynomial go here
+//
+
+impl Default for Polynomial {
+    fn default() -> Self {
+        Polynomial::default_k(_bits)
+    }
+}
+
+// Generate Polynomial by a constant
+impl From<inum> for Polynomial {
+    fn from(number: inum) -> Self {
+        Polynomial::from((_bits, number))
+    }
+}
+
+impl From<(u8, inum)> for Polynomial {
+    fn from(number_k: (u8, inum)) -> Self {
+        let (k, n) = number_k;
+        if k == 0 {
+            Polynomial::null()
+        } else {
+            let k = cmp::min(k, _bits);
+            if sign_extend(n as unum & mask(k), k) != n {
+                radeco_warn!("{:?} cannot be hold by {:?} bits", n, k);
+            }
+            Polynomial::new(k, n, &[])
+        }
+    }
+}
+
+impl Container<inum> for Polynomial {
+    // Returns true when membership cannot be decided, see `membership`.
+    fn contains(&self, object: &inum) -> bool {
+        self.membership(*object).unwrap_or(true)
+    }
+}
+
+impl Container<Polynomial> for Polynomial {
+    // Returns false when inclusion cannot be decided.
+    fn contains(&self, object: &Polynomial) -> bool {
+        object.is_subset(self)
+    }
+}
+
+impl Container<Vec<inum>> for Polynomial {
+    fn contains(&self, object: &Vec<inum>) -> bool {
+        object.iter().all(|x| self.contains(x))
+    }
+}
+
+impl Neg for Polynomial {
+    type Output = Self;
+
+    // -(base + [a1]x1 + ...) = -base - a1 * x1 - ... + [a1]x1 + ...
+    fn neg(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        let base = self
+            .terms()
+            .iter()
+            .fold((self.base as unum).wrapping_neg(), |acc, &(x, a)| {
+                acc.wrapping_sub(x.wrapping_mul(a))
+            });
+        Polynomial::new(self.k, base as inum, self.terms())
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        let base = (self.base as unum).wrapping_add(other.base as unum);
+        let mut terms = self.terms().to_vec();
+        terms.extend_from_slice(other.terms());
+        Polynomial::new(self.k, base as inum, &terms)
+    }
+}
+
+impl Sub for Polynomial {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        match (self.constant(), other.constant()) {
+            (_, Some(c)) => self.scale(c as unum),
+            (Some(c), _) => other.scale(c as unum),
+            _ => {
+                // Only the trailing zeros of both operands are kept
+                let t = cmp::min(
+                    self.trailing_zeros() + other.trailing_zeros(),
+                    self.k as u32,
+                );
+                if t >= self.k as u32 {
+                    Polynomial::from((self.k, 0))
+                } else {
+                    Polynomial::new(self.k, 0, &[(1 << t, mask(self.k - t as u8))])
+                }
+            }
+        }
+    }
+}
+
+impl Div for Polynomial {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        if other.contains(&0) {
+            radeco_err!("Divied by zero");
+            return Polynomial::default_k(self.k);
+        }
+        if let (Some(c), Some((lb, _))) = (other.constant(), self.bounds()) {
+            // Non-negative members whose strides are multiple of c are divided exactly
+            if lb >= 0 && c > 0 && self.terms().iter().all(|&(x, _)| x % c as unum == 0) {
+                let terms = self
+                    .terms()
+                    .iter()
+                    .map(|&(x, a)| (x / c as unum, a))
+                    .collect::<Vec<_>>();
+                return Polynomial::new(self.k, lb / c, &terms);
+            }
+        }
+        self.on_constants(&other, |x, y| x.wrapping_div(y))
+    }
+}
+
+impl Rem for Polynomial {
+    type Output = Self;
+
+    fn rem(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        if other.contains(&0) {
+            radeco_err!("Divied by zero");
+            return Polynomial::default_k(self.k);
+        }
+        if let (Some(c), Some((lb, _))) = (other.constant(), self.bounds()) {
+            // Non-negative members whose strides are multiple of c have the same remainder
+            if lb >= 0 && c > 0 && self.terms().iter().all(|&(x, _)| x % c as unum == 0) {
+                return Polynomial::from((self.k, lb % c));
+            }
+        }
+        self.on_constants(&other, |x, y| x.wrapping_rem(y))
+    }
+}
+
+impl BitAnd for Polynomial {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        self.on_constants(&other, |x, y| x & y)
+    }
+}
+
+impl BitOr for Polynomial {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        self.on_constants(&other, |x, y| x | y)
+    }
+}
+
+impl BitXor for Polynomial {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        self.on_constants(&other, |x, y| x ^ y)
+    }
+}
+
+impl Not for Polynomial {
+    type Output = Self;
+
+    // !x = -x - 1
+    fn not(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        -self - Polynomial::from((self.k, 1))
+    }
+}
+
+impl Shr for Polynomial {
+    type Output = Self;
+
+    // All shift right in ESIl is logical shift
+    fn shr(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        let k = self.k;
+        self.on_constants(&other, |x, y| {
+            if y < 0 || y >= k as inum {
+                0
+            } else {
+                ((x as unum & mask(k)) >> y) as inum
+            }
+        })
+    }
+}
+
+impl Shl for Polynomial {
+    type Output = Self;
+
+    fn shl(self, other: Self) -> Self {
+        if let Some(res) = self.check_operands(&other) {
+            return res;
+        }
+        match other.constant() {
+            Some(s) if s >= 0 && s < self.k as inum => self.scale(1 << s),
+            Some(_) => Polynomial::from((self.k, 0)),
+            None => Polynomial::default_k(self.k),
+        }
+    }
+}
+
+impl AbstractSet for Polynomial {
+    // Intersection is over-approximated by the smallest operand.
+    fn meet(&self, other: &Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            Polynomial::null()
+        } else if self.k != other.k {
+            radeco_err!("Meet two polynomials with different radices");
+            Polynomial::null()
+        } else if let Some(c) = other.constant() {
+            if self.contains(&c) {
+                *other
+            } else {
+                Polynomial::null()
+            }
+        } else if let Some(c) = self.constant() {
+            if other.contains(&c) {
+                *self
+            } else {
+                Polynomial::null()
+            }
+        } else if self.capacity() <= other.capacity() {
+            *self
+        } else {
+            *other
+        }
+    }
+
+    // base1 + P1 join base2 + P2 is in base1 + [1](base2 - base1) + (P1 join P2), where
+    // terms with the same stride keep the biggest count.
+    fn join(&self, other: &Self) -> Self {
+        if self.is_empty() {
+            *other
+        } else if other.is_empty() {
+            *self
+        } else if self.k != other.k {
+            radeco_err!("Join two polynomials with different radices");
+            Polynomial::default()
+        } else if other.is_subset(self) {
+            *self
+        } else if self.is_subset(other) {
+            *other
+        } else {
+            let mut terms = self.terms().to_vec();
+            for &(y, b) in other.terms() {
+                match terms.iter().position(|&(x, _)| x == y) {
+                    Some(i) => terms[i].1 = cmp::max(terms[i].1, b),
+                    None => terms.push((y, b)),
+                }
+            }
+            terms.push(((other.base as unum).wrapping_sub(self.base as unum), 1));
+            Polynomial::new(self.k, self.base, &terms)
+        }
+    }
+
+    // Terms that grow are given all their possible counts. As the number of terms is bounded
+    // and merging terms strictly decreases their strides, widening terminates.
+    fn widen(&self, other: &Self) -> Self {
+        let joined = self.join(other);
+        if self.is_empty() || other.is_empty() || self.k != other.k || joined == *self {
+            return joined;
+        }
+        let k = self.k;
+        let terms = joined
+            .terms()
+            .iter()
+            .map(|&(x, a)| {
+                if self.terms().contains(&(x, a)) {
+                    (x, a)
+                } else {
+                    (x, mask(k))
+                }
+            })
+            .collect::<Vec<_>>();
+        Polynomial::new(k, joined.base, &terms)
+    }
+
+    fn remove_lower_bound(&self) -> Self {
+        match self.bounds() {
+            Some((_, ub)) => self.aligned_range(inum::min_value() >> (_bits - self.k), ub),
+            None => *self,
+        }
+    }
+
+    fn set_lower_bound(&self, x: inum) -> Self {
+        match self.bounds() {
+            Some((lb, ub)) if x > lb => self.aligned_range(x, ub),
+            _ => *self,
+        }
+    }
+
+    fn remove_upper_bound(&self) -> Self {
+        match self.bounds() {
+            Some((lb, _)) => self.aligned_range(lb, inum::max_value() >> (_bits - self.k)),
+            None => *self,
+        }
+    }
+
+    fn set_upper_bound(&self, x: inum) -> Self {
+        match self.bounds() {
+            Some((lb, ub)) if x < ub => self.aligned_range(lb, x),
+            _ => *self,
+        }
+    }
+
+    // Computing modulo 2^k, then truncating, is the same as computing modulo 2^k'.
+    fn narrow(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty Polynomial cannot be narrow");
+            *self
+        } else if k > self.k {
+            radeco_warn!("Polynomial cannot be narrowed to a bigger bits");
+            *self
+        } else {
+            Polynomial::new(k, self.base, self.terms())
+        }
+    }
+
+    fn sign_extend(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty Polynomial cannot be extended");
+            *self
+        } else if k < self.k {
+            radeco_warn!("Polynomial cannot be extended to a smaller bits");
+            *self
+        } else if self.bounds().is_some() {
+            Polynomial::new(k, self.base, self.terms())
+        } else {
+            // Members wrap around, all the k-bits numbers may be reached
+            let min = inum::min_value() >> (_bits - self.k);
+            Polynomial::new(k, min, &[(1, mask(self.k))])
+        }
+    }
+
+    fn zero_extend(&self, k: u8) -> Self {
+        if self.is_empty() {
+            radeco_warn!("Empty Polynomial cannot be extended");
+            *self
+        } else if k < self.k {
+            radeco_warn!("Polynomial cannot be extended to a smaller bits");
+            *self
+        } else {
+            let base = self.base as unum & mask(self.k);
+            let ub = self
+                .terms()
+                .iter()
+                .fold(base as u128, |acc, &(x, a)| acc + (x as u128) * (a as u128));
+            if ub <= mask(self.k) as u128 {
+                Polynomial::new(k, base as inum, self.terms())
+            } else {
+                Polynomial::new(k, 0, &[(1, mask(self.k))])
+            }
+        }
+    }
+
+    fn constant(&self) -> Option<inum> {
+        if !self.is_empty() && self.n == 0 {
+            Some(self.base)
+        } else {
+            None
+        }
+    }
+
+    // Upper bound of the number of members.
+    fn capacity(&self) -> inum {
+        if self.is_empty() {
+            0
+        } else {
+            let c = cmp::min(self.combinations(self.terms()), (mask(self.k) as u128) + 1);
+            cmp::min(c, inum::max_value() as u128) as inum
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.k == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn polynomial_test_basicfn() {
+        assert_eq!(4, gcd(12, 8));
+        assert_eq!(1, 3u64.wrapping_mul(inverse(3)));
+        assert_eq!(-1, sign_extend(0xff, 8));
+        assert_eq!(
+            Polynomial::new(8, -128, &[(1, 255)]),
+            Polynomial::default_k(8)
+        );
+        assert_eq!(Polynomial::null(), Polynomial::new(0, 3, &[(4, 1)]));
+        assert_eq!(Some(0xdeadbeef), Polynomial::from(0xdeadbeef).constant());
+
+        // Normal form
+        assert_eq!(
+            Polynomial::new(16, 0, &[(4, 3)]),
+            Polynomial::new(16, 0, &[(4, 1), (8, 1)])
+        );
+        assert_eq!(
+            Polynomial::new(16, -12, &[(4, 3)]),
+            Polynomial::new(16, 0, &[(0xfffc, 3)])
+        );
+        assert_eq!(
+            Polynomial::new(16, 0, &[(4, 5), (64, 2)]),
+            Polynomial::new(16, 0, &[(64, 1), (4, 2), (4, 3), (64, 1), (0, 4)])
+        );
+        assert_eq!(
+            Polynomial::new(8, 0, &[(2, 127)]),
+            Polynomial::new(8, 0, &[(2, 1000)])
+        );
+
+        let p = Polynomial::new(64, 0x1000, &[(4, 9), (48, 2)]);
+        assert_eq!(30, p.capacity());
+        assert_eq!(Some((0x1000, 0x1000 + 36 + 96)), p.bounds());
+        assert!(p.contains(&0x1000));
+        assert!(p.contains(&(0x1000 + 4 * 3 + 48 * 2)));
+        assert!(p.contains(&(0x1000 + 132)));
+        assert!(!p.contains(&(0x1000 + 2)));
+        assert!(!p.contains(&(0x1000 + 136)));
+        assert!(!p.contains(&0xffc));
+        assert!(p.contains(&vec![0x1000, 0x1004, 0x1030]));
+        assert!(p.contains(&Polynomial::new(64, 0x1030, &[(4, 9)])));
+        assert!(!p.contains(&Polynomial::new(64, 0x1030, &[(4, 10)])));
+        assert!(p.contains(&Polynomial::null()));
+        assert!(!Polynomial::null().contains(&p));
+        assert!(Polynomial::default().contains(&p));
+
+        // Members wrap around in k bits
+        let p = Polynomial::new(8, 120, &[(4, 3)]);
+        assert_eq!(vec![-128, -124, 120, 124], p.members(10));
+        assert!(p.contains(&-124));
+        assert_eq!(None, p.bounds());
+    }
+
+    #[test]
+    fn polynomial_test_undecided() {
+        // Too many combinations to decide membership exactly
+        let terms = [(4, 300), (1000, 300), (100000, 300)];
+        let p = Polynomial::new(64, 0, &terms);
+        let big: inum = 1_000_000_000_000;
+        // Undecided members may be in the set, undecided subsets are not known to be in it
+        assert!(p.contains(&big));
+        assert!(!p.contains(&Polynomial::from(big)));
+        assert_ne!(p, p.join(&Polynomial::from(big)));
+        assert_ne!(p, Polynomial::from(big).join(&p));
+        // Operations that must not miss a member still assume it
+        let q = Polynomial::new(64, -8, &terms);
+        assert_eq!(Polynomial::from(8), q.meet(&Polynomial::from(8)));
+        assert_eq!(Polynomial::default_k(64), Polynomial::from(100) / q);
+    }
+
+    #[test]
+    fn polynomial_test_add() {
+        // a[i][j].f with a at 0x1000, 10 rows of 3 ints padded to 16 bytes
+        let a = Polynomial::from(0x1000);
+        let i = Polynomial::new(64, 0, &[(1, 9)]);
+        let j = Polynomial::new(64, 0, &[(1, 2)]);
+        let addr = a + i * Polynomial::from(16) + j * Polynomial::from(4);
+        assert_eq!(Polynomial::new(64, 0x1000, &[(4, 2), (16, 9)]), addr);
+        assert_eq!(2, addr.terms().len());
+        // Accessing a field keeps both strides
+        let field = addr + Polynomial::from(8);
+        assert_eq!(Polynomial::new(64, 0x1008, &[(4, 2), (16, 9)]), field);
+        assert!(field.contains(&(0x1008 + 16 * 9 + 4 * 2)));
+        assert!(!field.contains(&(0x1008 + 12)));
+        // Without padding, the rows are contiguous
+        let addr = a + i * Polynomial::from(12) + j * Polynomial::from(4);
+        assert_eq!(Polynomial::new(64, 0x1000, &[(4, 29)]), addr);
+
+        let op1 = Polynomial::new(8, 100, &[(10, 3)]);
+        let op2 = Polynomial::from((8, 100));
+        assert_eq!(Polynomial::new(8, -56, &[(10, 3)]), op1 + op2);
+    }
+
+    #[test]
+    fn polynomial_test_neg() {
+        let op = Polynomial::new(16, 10, &[(4, 3), (100, 1)]);
+        assert_eq!(Polynomial::new(16, -122, &[(4, 3), (100, 1)]), -op);
+        assert_eq!(op, -(-op));
+        assert_eq!(Polynomial::from((8, -128)), -Polynomial::from((8, -128)));
+        assert_eq!(Polynomial::new(16, -123, &[(4, 3), (100, 1)]), !op);
+    }
+
+    #[test]
+    fn polynomial_test_sub() {
+        let op1 = Polynomial::new(32, 0x1000, &[(8, 4)]);
+        let op2 = Polynomial::from((32, 0x1000));
+        assert_eq!(Polynomial::new(32, 0, &[(8, 4)]), op1 - op2);
+        assert_eq!(Polynomial::new(32, -32, &[(8, 4)]), op2 - op1);
+        assert_eq!(Polynomial::new(32, -32, &[(8, 8)]), op1 - op1);
+    }
+
+    #[test]
+    fn polynomial_test_mul() {
+        let op = Polynomial::new(16, 3, &[(2, 5)]);
+        assert_eq!(
+            Polynomial::new(16, 12, &[(8, 5)]),
+            op * Polynomial::from((16, 4))
+        );
+        assert_eq!(-op, op * Polynomial::from((16, -1)));
+        assert_eq!(Polynomial::new(16, -13, &[(2, 5)]), -op);
+        let even = Polynomial::new(16, 0, &[(2, 10)]) * Polynomial::new(16, 4, &[(4, 10)]);
+        assert!(even.contains(&(2 * 4 * 7)));
+        assert!(!even.contains(&4));
+        assert_eq!(
+            Polynomial::new(16, 6, &[(4, 5)]),
+            op << Polynomial::from((16, 1))
+        );
+    }
+
+    #[test]
+    fn polynomial_test_div() {
+        let op = Polynomial::new(16, 24, &[(8, 5), (64, 3)]);
+        assert_eq!(
+            Polynomial::new(16, 3, &[(1, 5), (8, 3)]),
+            op / Polynomial::from((16, 8))
+        );
+        assert_eq!(Polynomial::from((16, 0)), op % Polynomial::from((16, 8)));
+        assert_eq!(
+            Polynomial::from((16, -3)),
+            Polynomial::from((16, -7)) / Polynomial::from((16, 2))
+        );
+        assert_eq!(
+            Polynomial::from((16, -1)),
+            Polynomial::from((16, -7)) % Polynomial::from((16, 2))
+        );
+        assert_eq!(
+            Polynomial::default_k(16),
+            op / Polynomial::new(16, -1, &[(1, 2)])
+        );
+        assert_eq!(
+            Polynomial::default_k(16),
+            op / Polynomial::new(16, 3, &[(2, 2)])
+        );
+    }
+
+    #[test]
+    fn polynomial_test_bitop() {
+        let op1 = Polynomial::from((8, 0x3c));
+        let op2 = Polynomial::from((8, 0x0f));
+        assert_eq!(Polynomial::from((8, 0x0c)), op1 & op2);
+        assert_eq!(Polynomial::from((8, 0x3f)), op1 | op2);
+        assert_eq!(Polynomial::from((8, 0x33)), op1 ^ op2);
+        assert_eq!(Polynomial::from((8, 0x0f)), op1 >> Polynomial::from((8, 2)));
+        assert_eq!(
+            Polynomial::from((8, 0x7f)),
+            Polynomial::from((8, -1)) >> Polynomial::from((8, 1))
+        );
+        assert_eq!(
+            Polynomial::default_k(8),
+            Polynomial::new(8, 0, &[(1, 3)]) & op2
+        );
+    }
+
+    #[test]
+    fn polynomial_test_setop() {
+        let op1 = Polynomial::new(32, 0x1000, &[(4, 9)]);
+        let op2 = Polynomial::new(32, 0x2000, &[(4, 9)]);
+        let joined = op1.join(&op2);
+        assert_eq!(Polynomial::new(32, 0x1000, &[(4, 9), (0x1000, 1)]), joined);
+        assert!(joined.contains(&op1) && joined.contains(&op2));
+        assert_eq!(op1, op1.join(&Polynomial::from((32, 0x1004))));
+        assert_eq!(op1, op1.meet(&Polynomial::new(32, 0x1000, &[(4, 100)])));
+        assert_eq!(
+            Polynomial::from((32, 0x1004)),
+            op1.meet(&Polynomial::from((32, 0x1004)))
+        );
+        assert_eq!(
+            Polynomial::null(),
+            op1.meet(&Polynomial::from((32, 0x1002)))
+        );
+
+        // Widening terminates
+        let mut w = Polynomial::from((32, 0));
+        for _ in 0..3 {
+            w = w.widen(&(w + Polynomial::from((32, 4))));
+        }
+        assert_eq!(Polynomial::new(32, 0, &[(4, 0x3fffffff)]), w);
+        assert_eq!(w, w.widen(&(w + Polynomial::from((32, 4)))));
+
+        assert_eq!(
+            Polynomial::new(32, 0x1008, &[(4, 7)]),
+            op1.set_lower_bound(0x1005)
+        );
+        assert_eq!(
+            Polynomial::new(32, 0x1000, &[(4, 2)]),
+            op1.set_upper_bound(0x100b)
+        );
+        assert_eq!(
+            Polynomial::new(32, -0x80000000, &[(4, 0x20000409)]),
+            op1.remove_lower_bound()
+        );
+        assert_eq!(
+            Polynomial::new(32, 0x1000, &[(4, 0x1ffffbff)]),
+            op1.remove_upper_bound()
+        );
+    }
 
-// TODO
+    #[test]
+    fn polynomial_test_resize() {
+        let op = Polynomial::new(16, 0x1234, &[(0x100, 2)]);
+        assert_eq!(Polynomial::from((8, 0x34)), op.narrow(8));
+        let op = Polynomial::new(8, -2, &[(1, 3)]);
+        assert_eq!(Polynomial::new(16, -2, &[(1, 3)]), op.sign_extend(16));
+        assert_eq!(Polynomial::new(16, 0, &[(1, 255)]), op.zero_extend(16));
+        let op = Polynomial::new(8, 2, &[(1, 3)]);
+        assert_eq!(Polynomial::new(16, 2, &[(1, 3)]), op.zero_extend(16));
+    }
+}
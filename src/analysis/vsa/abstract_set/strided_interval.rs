@@ -352,7 +352,8 @@ impl StridedInterval {
             );
             1
         };
-        if (s != n_in_k_bits!(s, k)) {
+        // The distance between two k-bits numbers can be up to 2^k - 1
+        if (s as unum) > (mask_in_k_bits!(k) as unum) {
             radeco_warn!("Invalid stride({:?})", s);
             s = 1;
         }
@@ -461,6 +462,9 @@ impl Container<StridedInterval> for StridedInterval {
             true
         } else if object.constant().is_some() {
             self.contains(&object.constant().unwrap_or(0))
+        } else if self.constant().is_some() {
+            // a constant cannot contain a set
+            false
         } else if object.s % self.s != 0 {
             false
         } else {
@@ -622,7 +626,8 @@ impl Mul for StridedInterval {
                 {
                     StridedInterval::new(
                         self.k,
-                        n_in_k_bits!(set_si.s * n.wrapping_abs(), self.k),
+                        // validate falls back to 1 if the stride cannot be hold by k bits
+                        set_si.s.checked_mul(n.wrapping_abs()).unwrap_or(1),
                         n_in_k_bits!(set_si.lb.wrapping_mul(n), self.k),
                         n_in_k_bits!(set_si.ub.wrapping_mul(n), self.k),
                     )
@@ -900,10 +905,12 @@ impl BitAnd for StridedInterval {
                     (&other, &self)
                 };
                 let n = cons_si.constant().unwrap();
-                if (n & (n.wrapping_add(1))) == 0 {
+                // The mask only works as a modulo on non-negative numbers
+                if (n & (n.wrapping_add(1))) == 0 && (n == -1 || set_si.lb >= 0) {
                     if n == 0 {
                         return StridedInterval::from((self.k, 0));
-                    } else if (n == -1) {
+                    } else if (n == -1) || (n == max_in_k_bits!(self.k)) {
+                        // set_si is non-negative if n is max_in_k_bits
                         return set_si.clone();
                     } else {
                         return *set_si % StridedInterval::from((self.k, n + 1));
@@ -983,9 +990,9 @@ impl BitOr for StridedInterval {
                 (false, false, true, true) => (minOr(a, b, c, d), maxOr(a, b, c, d)),
                 (false, false, true, false) => (minOr(a, b, c, -1), maxOr(a, b, 0, d)),
                 (false, false, false, false) => (minOr(a, b, c, d), maxOr(a, b, c, d)),
-                _ => {
-                    unimplemented!();
-                }
+                // a > b or c > d, only possible for an interval that was built
+                // without going through `validate`. Nothing is known about it.
+                _ => return StridedInterval::default_k(self.k),
             };
 
             StridedInterval::new(
@@ -1035,49 +1042,19 @@ impl Shr for StridedInterval {
         } else if other.lb < 0 {
             radeco_err!("Bitwise shift's operation cannot be negative");
             StridedInterval::default_k(self.k)
-        } else if (self.lb < 0) && (self.ub > 0) {
+        } else if (self.lb < 0) && (self.ub >= 0) {
             // Logical shift will distroy the stride when there are negative and
-            // positive numbers in self at one time
-
-            let lb = if other.contains(&0) {
-                // self.lb will be the smallest number in result strided interval
-                self.lb
-            } else {
-                // _lb_shr_1 is the smallest number in self >> 1
-                let _lb_shr_1 = cmp::min(
-                    (self.lb >> 1) ^ min_in_k_bits!(self.k),
-                    (self.ub - (self.ub / self.s) * self.s) >> 1,
-                );
-                _lb_shr_1 >> (other.ub - 1)
-            };
-            let ub = if other.ub == 0 {
-                // other == 0[0, 0]
-                self.ub
-            } else {
-                // _ub_shr_1 is the biggest number in self >> 1
-                let _ub_shr_1 = cmp::max(
-                    ((self.ub - (self.ub / self.s) * self.s - self.s) >> 1)
-                        ^ min_in_k_bits!(self.k),
-                    (self.ub >> 1),
-                );
-                if other.lb == 0 {
-                    cmp::max(self.ub, _ub_shr_1 >> (other.s - 1))
-                } else {
-                    _ub_shr_1 >> (other.lb - 1)
-                }
-            };
-            StridedInterval::new(
-                self.k,
-                1,
-                n_in_k_bits!(lb, self.k),
-                n_in_k_bits!(ub, self.k),
-            )
+            // positive numbers in self at one time, thus we shift them separately
+            let neg = self.set_upper_bound(-1);
+            let pos = self.set_lower_bound(0);
+            (neg >> other).join(&(pos >> other))
         } else {
             // all numbers in self are with the same sign
             let mut res: Option<StridedInterval> = None;
+            // An odd stride cannot be kept after shifting
             let _shr_one = StridedInterval::new(
                 self.k,
-                cmp::max(self.s / 2, 1),
+                if self.s % 2 == 0 { self.s / 2 } else { 1 },
                 (self.lb >> 1) & (!min_in_k_bits!(self.k)) & mask_in_k_bits!(self.k),
                 (self.ub >> 1) & (!min_in_k_bits!(self.k)) & mask_in_k_bits!(self.k),
             );
@@ -1095,10 +1072,12 @@ impl Shr for StridedInterval {
                     }
                 }
             }
-            if other.ub >= self.k as inum {
-                res.unwrap().join(&StridedInterval::from((self.k, 0)))
-            } else {
-                res.unwrap()
+            // Shifting by k bits or more results in 0
+            let zero = StridedInterval::from((self.k, 0));
+            match res {
+                Some(res) if other.ub >= self.k as inum => res.join(&zero),
+                Some(res) => res,
+                None => zero,
             }
         }
     }
@@ -1126,10 +1105,12 @@ impl Shl for StridedInterval {
                     }
                 }
             }
-            if other.ub >= self.k as inum {
-                res.unwrap().join(&StridedInterval::from((self.k, 0)))
-            } else {
-                res.unwrap()
+            // Shifting by k bits or more results in 0
+            let zero = StridedInterval::from((self.k, 0));
+            match res {
+                Some(res) if other.ub >= self.k as inum => res.join(&zero),
+                Some(res) => res,
+                None => zero,
             }
         }
     }
@@ -1163,96 +1144,43 @@ impl AbstractSet for StridedInterval {
             // It comes to a very insteresting math problem.
 
             // self.s and othr.s cannot be zero in this branch
-            let s = self.s / gcd(self.s, other.s) * other.s;
-
-            // At beginning, we need to find the smallest number in result set
-            if self.lb == other.lb {
-                // self.lb is the smallest number in result
-                StridedInterval::new(self.k, s, self.lb, cmp::max(self.ub, other.ub))
+            let (min_lb, min_s, max_lb, max_s) = if self.lb < other.lb {
+                (self.lb, self.s, other.lb, other.s)
             } else {
-                let (min_lb, min_s, max_lb, max_s) = if self.lb < other.lb {
-                    (self.lb, self.s, other.lb, other.s)
-                } else {
-                    (other.lb, other.s, self.lb, self.s)
-                };
-                assert!(min_lb < max_lb && min_s > 0 && max_s > 0);
-                // For the smallest number n:
-                //      min_lb + x * min_s = max_lb + y * max_s = n
-                //      ( x >= 0, y >= 0)
-                //  =>  max_lb - min_lb = x * min_s - y * max_s
-                //  let: s_ = gcd(min_s, max_s)
-                //  if (max_lb - min_lb) % s_ != 0
-                //      then there is no number in the result set
-                //  let: lb_ = (max_lb - min_lb) / s_
-                //      --> lb_ != 0
-                //  let: min_s_ = min_s / s_
-                //  let: max_s_ = max_s / s_
-                //      --> gcd(min_s_, max_s_) = 1
-                //  =>  lb_ = x * min_s_ - y * max_s_
-                //  =>  lb_ % max_s_ = (x * min_s_) % max_s_
-                //      because gcd(min_s_, max_s_) = 1, thus we have
-                //          (rev_min_s_ * min_s_) % max_s_ = 1
-                //  => x % max_s_ = ((lb_ % max_s_) * rev_min_s_) % max_s_
-                //  As we want to find the smallest number, which means
-                //      x should be as small as it can
-                //  let x_ = x % max_s_, then we solve the equation:
-                //      lb_ = x_ * min_s_ - y_ * max_s_
-                //  thus:
-                //      x = x_ + i * max_s_
-                //      y = y_ + i * min_s_
-                //  then we found the smallest positive i, which make x > 0 && y > 0
-                //  n = min_lb + x * min_s
-
-                let s_ = gcd(min_s, max_s);
-                if (max_lb - min_lb) % s_ != 0 {
-                    StridedInterval::null()
-                } else {
-                    // lb_ = x * min_s_ - y * max_s_
-                    let lb_ = (max_lb - min_lb) / s_;
-                    let max_s_ = max_s / s_;
-                    let min_s_ = min_s / s_;
-                    assert!(lb_ != 0);
-                    assert!(gcd(max_s_, min_s_) == 1);
-
-                    // x_ = x % max_s_ = ((lb_ % max_s_) * rev_min_s_) % max_s_
-                    let (a, b) = exgcd(min_s_, max_s_);
-                    // a * min_s_ + b * min_s_ = gcd(max_s_, min_s_) = 1
-                    assert_eq!(a * min_s_ + b * max_s_, gcd(min_s_, max_s_));
-                    let rev_min_s_ = a - periodNr(a, max_s_) * max_s_;
-                    assert_eq!((rev_min_s_ * min_s_) % max_s_, 1);
-                    let x_ = ((lb_ % max_s_) * rev_min_s_) % max_s_;
-                    assert!(x_ > 0);
-
-                    // let x_ = x % max_s_, then we solve the equation:
-                    //     lb_ = x_ * min_s_ - y_ * max_s_
-                    if (x_ * min_s_ - lb_) % max_s_ != 0 {
-                        // Cannot find the integer ans for the equation
-                        return StridedInterval::null();
-                    }
-                    let y_ = (x_ * min_s_ - lb_) / max_s_;
-
-                    // thus:
-                    //     x = x_ + i * max_s_
-                    //     y = y_ + i * min_s_
-                    // then we found the smallest positive i, which make x > 0 && y > 0
-                    // n = min_lb + x * min_s
-                    let (x, y) = if y_ < 0 {
-                        let y = y_ - periodNr(y_, min_s_) * min_s_;
-                        let i = (y - y_) / min_s_;
-                        (x_ + i * max_s_, y)
-                    } else {
-                        (x_, y_)
-                    };
-
-                    assert_eq!(min_lb + x * min_s, max_lb + y * max_s);
-                    assert!(x > 0 && y > 0);
-                    let n = min_lb + x * min_s;
-                    if self.contains(&n) && other.contains(&n) {
-                        StridedInterval::new(self.k, s, n, cmp::max(self.ub, other.ub))
-                    } else {
-                        StridedInterval::null()
-                    }
-                }
+                (other.lb, other.s, self.lb, self.s)
+            };
+            assert!(min_lb <= max_lb && min_s > 0 && max_s > 0);
+            // The smallest number n in result set goes like:
+            //      n = min_lb + x * min_s = max_lb + y * max_s
+            //      ( x >= 0, y >= 0)
+            //  =>  y * max_s = min_lb - max_lb (mod min_s)
+            //  let: s_ = gcd(min_s, max_s)
+            //  if (max_lb - min_lb) % s_ != 0
+            //      then there is no number in the result set
+            //  otherwise, divide everything by s_, then max_s_ is invertible
+            //  modulo min_s_, and the smallest y is:
+            //      y = ((min_lb - max_lb) / s_) * rev_max_s_ (mod min_s_)
+            // All the computation is done in i128 to avoid overflow.
+            let s_ = gcd(min_s, max_s);
+            let d = max_lb as i128 - min_lb as i128;
+            let ub = cmp::min(self.ub, other.ub);
+            if d % s_ as i128 != 0 {
+                return StridedInterval::null();
+            }
+            let (min_s_, max_s_) = ((min_s / s_) as i128, (max_s / s_) as i128);
+            // a * max_s_ + b * min_s_ = gcd(max_s_, min_s_) = 1
+            let (a, _) = exgcd(max_s_ as inum, min_s_ as inum);
+            let rev_max_s_ = ((a as i128) % min_s_ + min_s_) % min_s_;
+            let y = ((-d / s_ as i128) % min_s_ + min_s_) % min_s_ * rev_max_s_ % min_s_;
+            let n = max_lb as i128 + y * max_s as i128;
+            // s = lcm(self.s, other.s)
+            let s = min_s_ * max_s as i128;
+            if n > ub as i128 {
+                StridedInterval::null()
+            } else if s > ub as i128 - n {
+                StridedInterval::from((self.k, n as inum))
+            } else {
+                StridedInterval::new(self.k, s as inum, n as inum, ub)
             }
         }
     }
@@ -1269,8 +1197,13 @@ impl AbstractSet for StridedInterval {
             let mut s = gcd(self.s, other.s);
             // Trick to avoid overflow
             let (min_lb, max_lb) = (cmp::min(self.lb, other.lb), cmp::max(self.lb, other.lb));
-            // add 2 * s to make sure all arguments in gcd is positive
-            s = gcd(s, max_lb % s - min_lb % s + 2 * s);
+            s = if s == 0 {
+                // Both are constants, the distance between them is the stride
+                max_lb.checked_sub(min_lb).unwrap_or(1)
+            } else {
+                // add 2 * s to make sure all arguments in gcd is positive
+                gcd(s, max_lb % s - min_lb % s + 2 * s)
+            };
             StridedInterval::new(
                 self.k,
                 s,
@@ -1289,18 +1222,15 @@ impl AbstractSet for StridedInterval {
             radeco_err!("Widen two strided intervals with different radices");
             StridedInterval::default()
         } else {
-            let s = gcd(self.s, other.s);
-            let lb = if self.lb <= other.lb {
-                self.lb
-            } else {
-                min_in_k_bits!(self.k)
-            };
-            let ub = if self.ub >= other.ub {
-                self.ub
-            } else {
-                max_in_k_bits!(self.k)
-            };
-            StridedInterval::new(self.k, s, lb, ub)
+            // The stride of the join keeps all numbers of both sets aligned.
+            let mut si = self.join(other);
+            if self.lb > other.lb {
+                si = si.remove_lower_bound();
+            }
+            if self.ub < other.ub {
+                si = si.remove_upper_bound();
+            }
+            si
         }
     }
 
@@ -1309,11 +1239,13 @@ impl AbstractSet for StridedInterval {
             self.clone()
         } else {
             let min = min_in_k_bits!(self.k);
-            let ub_mod_s = self.ub % self.s;
-            let min_mod_s = min % self.s; // min_mod_s must be negative
-            let offset = (ub_mod_s - min_mod_s + self.s) % self.s;
+            // A constant does not have a stride, so any number below ub might be in
+            let s = cmp::max(self.s, 1);
+            let ub_mod_s = self.ub % s;
+            let min_mod_s = min % s; // min_mod_s must be negative
+            let offset = (ub_mod_s - min_mod_s + s) % s;
             assert!(min_mod_s <= 0, "min_in_k_bits must be non-positive");
-            StridedInterval::new(self.k, self.s, n_in_k_bits!(min + offset, self.k), self.ub)
+            StridedInterval::new(self.k, s, n_in_k_bits!(min + offset, self.k), self.ub)
         }
     }
 
@@ -1325,12 +1257,15 @@ impl AbstractSet for StridedInterval {
             radeco_warn!("Set a lower bound which is bigger than upper bound");
             StridedInterval::null()
         } else {
-            let offset = self.ub - periodNr(self.ub, self.s) * self.s;
-            let offset_ = x - periodNr(x, self.s) * self.s;
+            // A constant does not have a stride, so any number below ub might be in
+            let s = cmp::max(self.s, 1);
+            si.s = s;
+            let offset = self.ub - periodNr(self.ub, s) * s;
+            let offset_ = x - periodNr(x, s) * s;
             si.lb = if offset >= offset_ {
                 x + offset - offset_
             } else {
-                x + offset - offset_ + self.s
+                x + offset - offset_ + s
             };
             si.validate();
             si
@@ -1700,7 +1635,8 @@ mod test {
 
         let op1 = StridedInterval::new(4, 2, 4, 6);
         let op2 = StridedInterval::new(4, 3, -4, -3);
-        assert_eq!(StridedInterval::new(4, 1, -8, 0), op1 * op2);
+        // {4, 6} * {-4} --> {-16, -24} --> {0, -8}
+        assert_eq!(StridedInterval::new(4, 8, -8, 0), op1 * op2);
 
         let op1 = StridedInterval::new(4, 2, 4, 6);
         let op2 = StridedInterval::new(4, 1, -4, -3);
@@ -1898,7 +1834,8 @@ mod test {
 
         let op1 = StridedInterval::new(4, 1, -1, 1);
         let op2 = StridedInterval::from((4, 1));
-        assert_eq!(StridedInterval::new(4, 1, 0, 7), op1 >> op2);
+        // {-1, 0, 1} >> 1 --> {7, 0, 0}
+        assert_eq!(StridedInterval::new(4, 7, 0, 7), op1 >> op2);
 
         let op1 = StridedInterval::new(4, 1, -1, 1);
         let op2 = StridedInterval::new(4, 1, 0, 1);
@@ -1933,24 +1870,21 @@ mod test {
         let op1 = StridedInterval::new(16, 30, 1, 901);
         let op2 = StridedInterval::new(16, 15, 6, 606);
         let op3 = StridedInterval::null();
-        assert_eq!(StridedInterval::new(16, 15, 1, 901), op1.widen(&op2));
-        assert_eq!(
-            StridedInterval::new(16, 15, min_in_k_bits!(16), max_in_k_bits!(16)),
-            op2.widen(&op1)
-        );
+        assert_eq!(StridedInterval::new(16, 5, 1, 901), op1.widen(&op2));
+        // Widening keeps the stride of the join, which aligns with all members.
+        assert_eq!(StridedInterval::new(16, 5, -32764, 32766), op2.widen(&op1));
         assert_eq!(op1, op1.widen(&op3));
         assert_eq!(op1, op3.widen(&op1));
 
+        let op1 = StridedInterval::from((16, 42));
+        let op2 = StridedInterval::from((16, 48));
+        assert_eq!(StridedInterval::new(16, 6, 42, 48), op1.join(&op2));
+        assert_eq!(op1, op1.join(&op1));
+
         let op1 = StridedInterval::new(16, 30, 1, 901);
         let op2 = StridedInterval::new(16, 15, -4, 696);
-        assert_eq!(
-            StridedInterval::new(16, 15, min_in_k_bits!(16), 901),
-            op1.widen(&op2)
-        );
-        assert_eq!(
-            StridedInterval::new(16, 15, -4, max_in_k_bits!(16)),
-            op2.widen(&op1)
-        );
+        assert_eq!(StridedInterval::new(16, 5, -32764, 901), op1.widen(&op2));
+        assert_eq!(StridedInterval::new(16, 5, -4, 32766), op2.widen(&op1));
 
         let op1 = StridedInterval::new(16, 30, 1, 901);
         let op2 = StridedInterval::new(16, 15, 6, 606);
@@ -1978,8 +1912,8 @@ mod test {
 
         let op1 = StridedInterval::new(16, 30, 1, 901);
         let op2 = StridedInterval::new(16, 15, 1, 601);
-        assert_eq!(StridedInterval::new(16, 30, 1, 901), op1.meet(&op2));
-        assert_eq!(StridedInterval::new(16, 30, 1, 901), op2.meet(&op1));
+        assert_eq!(StridedInterval::new(16, 30, 1, 601), op1.meet(&op2));
+        assert_eq!(StridedInterval::new(16, 30, 1, 601), op2.meet(&op1));
 
         let op1 = StridedInterval::new(16, 12, 1, 121);
         let op2 = StridedInterval::new(16, 15, 6, 606);
@@ -1988,13 +1922,13 @@ mod test {
 
         let op1 = StridedInterval::new(16, 12, 1, 121);
         let op2 = StridedInterval::new(16, 15, 10, 610);
-        assert_eq!(StridedInterval::new(16, 60, 25, 565), op1.meet(&op2));
-        assert_eq!(StridedInterval::new(16, 60, 25, 565), op2.meet(&op1));
+        assert_eq!(StridedInterval::new(16, 60, 25, 85), op1.meet(&op2));
+        assert_eq!(StridedInterval::new(16, 60, 25, 85), op2.meet(&op1));
 
         let op1 = StridedInterval::new(16, 12, 1, 121);
         let op2 = StridedInterval::new(16, 15, 40, 610);
-        assert_eq!(StridedInterval::new(16, 60, 85, 565), op1.meet(&op2));
-        assert_eq!(StridedInterval::new(16, 60, 85, 565), op2.meet(&op1));
+        assert_eq!(StridedInterval::from((16, 85)), op1.meet(&op2));
+        assert_eq!(StridedInterval::from((16, 85)), op2.meet(&op1));
 
         let op1 = StridedInterval::new(16, 12, 1, 73);
         let op2 = StridedInterval::new(16, 15, 40, 610);
@@ -2043,4 +1977,306 @@ mod test {
         //      {8, 11, 14, 1, 3, 7}
         assert_eq!(StridedInterval::new(8, 1, 1, 0xe), op1.zero_extend(8));
     }
+
+    //
+    // Soundness checks against concrete evaluation.
+    //
+    // Every property builds (an) arbitrary strided interval(s), picks arbitrary
+    // members of them and checks that the result of the concrete operation
+    // (`MOpcode::eval_binop_width`) is a member of the abstract result.
+    //
+
+    use middle::ir::{self, EvalResult, MOpcode};
+    use quickcheck::TestResult;
+
+    // Arbitrary k in [1, _bits]
+    fn arbitrary_k(k: u8) -> u8 {
+        k % _bits + 1
+    }
+
+    // Builds a k-bits strided interval out of arbitrary numbers.
+    fn arbitrary_si(k: u8, si: (inum, inum, inum)) -> StridedInterval {
+        let (s, lb, ub) = si;
+        let s = n_in_k_bits!(s, k).checked_abs().unwrap_or(1);
+        StridedInterval::new(k, s, n_in_k_bits!(lb, k), n_in_k_bits!(ub, k))
+    }
+
+    // Picks the (pick % capacity)th member of `si`.
+    fn member(si: &StridedInterval, pick: unum) -> inum {
+        if si.s == 0 {
+            si.lb
+        } else {
+            let n = (si.ub as i128 - si.lb as i128) / si.s as i128 + 1;
+            (si.lb as i128 + (pick as i128 % n) * si.s as i128) as inum
+        }
+    }
+
+    // Concrete value of `res` as a k-bits signed number.
+    fn concrete(res: Option<EvalResult>, k: u8) -> Option<inum> {
+        match res {
+            Some(EvalResult::Value(v)) => Some(ir::sign_extend(v, k as u16) as inum),
+            _ => None,
+        }
+    }
+
+    fn binop_is_sound<F>(
+        op: MOpcode,
+        f: F,
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult
+    where
+        F: Fn(StridedInterval, StridedInterval) -> StridedInterval,
+    {
+        let k = arbitrary_k(k);
+        let (si1, si2) = (arbitrary_si(k, si1), arbitrary_si(k, si2));
+        let (x, y) = (member(&si1, picks.0), member(&si2, picks.1));
+        let res = if let Some(res) = concrete(
+            op.eval_binop_width(x as unum, y as unum, k as u16, k as u16),
+            k,
+        ) {
+            res
+        } else {
+            return TestResult::discard();
+        };
+        let si = f(si1, si2);
+        if si.contains(&res) {
+            TestResult::passed()
+        } else {
+            TestResult::error(format!(
+                "{} {} {} = {}, but {} {} {} = {}",
+                si1, op, si2, si, x, op, y, res
+            ))
+        }
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_add(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpAdd, |a, b| a + b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_sub(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpSub, |a, b| a - b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_mul(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpMul, |a, b| a * b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_div(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpSDiv, |a, b| a / b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_rem(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpSMod, |a, b| a % b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_and(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpAnd, |a, b| a & b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_or(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpOr, |a, b| a | b, k, si1, si2, picks)
+    }
+
+    #[test]
+    fn strided_interval_test_or_signs() {
+        // One pair of intervals for every row of the signed minOr/maxOr table.
+        let rows = [
+            ((1, -8, -2), (2, -6, -2)),
+            ((1, -8, -2), (2, -6, 4)),
+            ((1, -8, -2), (2, 2, 6)),
+            ((1, -8, 3), (2, -6, -2)),
+            ((1, -8, 3), (2, -6, 4)),
+            ((1, -8, 3), (2, 2, 6)),
+            ((1, 1, 5), (2, -6, -2)),
+            ((1, 1, 5), (2, -6, 4)),
+            ((1, 1, 5), (2, 2, 6)),
+        ];
+        for &(si1, si2) in rows.iter() {
+            for x in 0..8 {
+                for y in 0..8 {
+                    let res = binop_is_sound(MOpcode::OpOr, |a, b| a | b, 7, si1, si2, (x, y));
+                    assert!(!res.is_failure(), "{:?} | {:?}", si1, si2);
+                }
+            }
+        }
+
+        // Intervals with lb > ub fall back to top.
+        let op1 = StridedInterval {
+            k: 8,
+            s: 1,
+            lb: 3,
+            ub: -3,
+        };
+        let op2 = StridedInterval::new(8, 2, 2, 6);
+        assert_eq!(StridedInterval::default_k(8), op1 | op2);
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_xor(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpXor, |a, b| a ^ b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_shl(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpLsl, |a, b| a << b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_shr(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        binop_is_sound(MOpcode::OpLsr, |a, b| a >> b, k, si1, si2, picks)
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_neg(k: u8, si: (inum, inum, inum), pick: unum) -> TestResult {
+        let k = arbitrary_k(k);
+        let si = arbitrary_si(k, si);
+        let x = member(&si, pick);
+        let res = concrete(
+            MOpcode::OpSub.eval_binop_width(0, x as unum, k as u16, k as u16),
+            k,
+        );
+        TestResult::from_bool(res.map_or(false, |res| (-si).contains(&res)))
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_not(k: u8, si: (inum, inum, inum), pick: unum) -> TestResult {
+        let k = arbitrary_k(k);
+        let si = arbitrary_si(k, si);
+        let x = member(&si, pick);
+        let res = concrete(
+            MOpcode::OpNot.eval_unop_width(x as unum, k as u16, k as u16),
+            k,
+        );
+        TestResult::from_bool(res.map_or(false, |res| (!si).contains(&res)))
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_resize(
+        k: u8,
+        new_k: u8,
+        si: (inum, inum, inum),
+        pick: unum,
+    ) -> TestResult {
+        let (k, new_k) = (arbitrary_k(k), arbitrary_k(new_k));
+        let si = arbitrary_si(k, si);
+        let x = member(&si, pick) as unum;
+        let (k16, new_k16) = (k as u16, new_k as u16);
+        if new_k <= k {
+            let res = MOpcode::OpNarrow(new_k16).eval_unop_width(x, k16, new_k16);
+            let res = concrete(res, new_k).unwrap();
+            TestResult::from_bool(si.narrow(new_k).contains(&res))
+        } else {
+            let sext = MOpcode::OpSignExt(new_k16).eval_unop_width(x, k16, new_k16);
+            let sext = concrete(sext, new_k).unwrap();
+            let zext = MOpcode::OpZeroExt(new_k16).eval_unop_width(x, k16, new_k16);
+            let zext = concrete(zext, new_k).unwrap();
+            TestResult::from_bool(
+                si.sign_extend(new_k).contains(&sext) && si.zero_extend(new_k).contains(&zext),
+            )
+        }
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_setop(
+        k: u8,
+        si1: (inum, inum, inum),
+        si2: (inum, inum, inum),
+        picks: (unum, unum),
+    ) -> TestResult {
+        let k = arbitrary_k(k);
+        let (si1, si2) = (arbitrary_si(k, si1), arbitrary_si(k, si2));
+        let (x, y) = (member(&si1, picks.0), member(&si2, picks.1));
+        let join = si1.join(&si2);
+        let widen = si1.widen(&si2);
+        let meet = si1.meet(&si2);
+        TestResult::from_bool(
+            join.contains(&x)
+                && join.contains(&y)
+                && join.contains(&si1)
+                && join.contains(&si2)
+                && widen.contains(&x)
+                && widen.contains(&y)
+                && (!si2.contains(&x) || meet.contains(&x))
+                && (!si1.contains(&y) || meet.contains(&y))
+                && si1.contains(&meet)
+                && si2.contains(&meet),
+        )
+    }
+
+    #[quickcheck]
+    fn qc_strided_interval_bound(k: u8, si: (inum, inum, inum), pick: unum, n: inum) -> TestResult {
+        let k = arbitrary_k(k);
+        let si = arbitrary_si(k, si);
+        let n = n_in_k_bits!(n, k);
+        let x = member(&si, pick);
+        TestResult::from_bool(
+            si.remove_lower_bound().contains(&x)
+                && si.remove_upper_bound().contains(&x)
+                && (x < n || si.set_lower_bound(n).contains(&x))
+                && (x > n || si.set_upper_bound(n).contains(&x))
+                && si.contains(&si)
+                && (si.capacity() == 0 || si.capacity() >= 1)
+                && (si.constant().is_none() || si.constant() == Some(x)),
+        )
+    }
 }
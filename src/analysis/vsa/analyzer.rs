@@ -0,0 +1,516 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Module that drives the value set analysis over a function in SSA form.
+//!
+//! The analysis computes a value-set for every value node and an abstract store for every
+//! memory node of the function. It iterates over the reachable blocks in reverse post order
+//! until a fixpoint is reached, widening at loop headers to guarantee termination.
+//!
+//! The stack pointer at the entry of the function points to offset 0 of the stack region and
+//! the return value of every call points into a heap region of its own. Global memory is
+//! addressed by plain numbers.
+
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::EdgeDirection;
+use std::collections::{HashMap, HashSet};
+
+use analysis::dom::domtree::{DFSVisitor, DomTree};
+use middle::ir::{EvalResult, MOpcode, WidthSpec};
+use middle::ssa::cfg_traits::CFG;
+use middle::ssa::ssa_traits::{NodeType, SSA};
+use middle::ssa::ssastorage::SSAStorage;
+
+use super::abstract_set::abstract_set::AbstractSet;
+use super::value_set::{self, ALoc, AbstractStore, MemRegion, ValueSet};
+
+/// Bound on the number of passes over the function, in case widening does not converge.
+const MAX_PASSES: usize = 64;
+
+/// Result of the value set analysis of a function.
+#[derive(Clone, Debug)]
+pub struct ValueSetAnalysis<A: AbstractSet> {
+    values: HashMap<NodeIndex, ValueSet<A>>,
+    stores: HashMap<NodeIndex, AbstractStore<A>>,
+    addresses: HashMap<NodeIndex, ValueSet<A>>,
+}
+
+impl<A: AbstractSet> ValueSetAnalysis<A> {
+    /// Returns the value-set of the value `node`.
+    ///
+    /// Returns `None` for memory nodes and for nodes that were never reached.
+    pub fn value_set(&self, node: NodeIndex) -> Option<&ValueSet<A>> {
+        self.values.get(&node)
+    }
+
+    /// Returns the abstract store after the memory node `node` (an `OpStore`, a memory phi
+    /// or a memory comment).
+    pub fn store_at(&self, node: NodeIndex) -> Option<&AbstractStore<A>> {
+        self.stores.get(&node)
+    }
+
+    /// Returns the value-set of the address accessed by the `OpLoad` or `OpStore` `node`.
+    pub fn address_of(&self, node: NodeIndex) -> Option<&ValueSet<A>> {
+        self.addresses.get(&node)
+    }
+
+    /// Returns the a-loc accessed by the `OpLoad` or `OpStore` `node`, if it accesses a
+    /// single one. `size` is the size of the access in bytes.
+    pub fn a_loc_of(&self, node: NodeIndex, size: u8) -> Option<ALoc> {
+        self.address_of(node)
+            .and_then(|a| a.as_address())
+            .map(|(region, offset)| ALoc::new(region, offset, size))
+    }
+
+    /// Iterates over the value nodes along with their value-set.
+    pub fn value_sets(&self) -> impl Iterator<Item = (NodeIndex, &ValueSet<A>)> {
+        self.values.iter().map(|(&n, vs)| (n, vs))
+    }
+}
+
+/// Runs the value set analysis over `ssa` using `A` as the abstract domain for numbers.
+pub fn analyze<A: AbstractSet>(ssa: &SSAStorage) -> ValueSetAnalysis<A> {
+    let mut analyzer = Analyzer::new(ssa);
+    analyzer.run();
+    ValueSetAnalysis {
+        values: analyzer.values,
+        stores: analyzer.stores,
+        addresses: analyzer.addresses,
+    }
+}
+
+struct Analyzer<'a, A: AbstractSet> {
+    ssa: &'a SSAStorage,
+    values: HashMap<NodeIndex, ValueSet<A>>,
+    stores: HashMap<NodeIndex, AbstractStore<A>>,
+    addresses: HashMap<NodeIndex, ValueSet<A>>,
+    // Blocks where values are widened
+    widen_at: HashSet<NodeIndex>,
+    // Name of the stack pointer
+    sp: Option<String>,
+    // Name of the register holding return values
+    retn: Option<String>,
+    changed: bool,
+}
+
+impl<'a, A: AbstractSet> Analyzer<'a, A> {
+    fn new(ssa: &'a SSAStorage) -> Analyzer<'a, A> {
+        Analyzer {
+            ssa: ssa,
+            values: HashMap::new(),
+            stores: HashMap::new(),
+            addresses: HashMap::new(),
+            widen_at: HashSet::new(),
+            sp: ssa.regfile.alias_info.get("SP").cloned(),
+            retn: ssa.regfile.alias_info.get("SN").cloned(),
+            changed: false,
+        }
+    }
+
+    fn run(&mut self) {
+        let rpo = self.reverse_post_order();
+        for pass in 0.. {
+            if pass == MAX_PASSES {
+                radeco_warn!("Value set analysis did not converge after {} passes", pass);
+                break;
+            }
+            self.changed = false;
+            for &block in &rpo {
+                let widen = self.widen_at.contains(&block);
+                for phi in self.ssa.phis_in(block) {
+                    self.visit_phi(phi, widen);
+                }
+                for expr in self.ssa.exprs_in(block) {
+                    self.visit_expr(expr);
+                }
+            }
+            if !self.changed {
+                break;
+            }
+        }
+
+        // Constants and comments are not part of any block, record them as well
+        for node in self.ssa.values() {
+            if !self.values.contains_key(&node) && !self.is_memory(node) {
+                let vs = self.value_of(node);
+                if !vs.is_bottom() {
+                    self.values.insert(node, vs);
+                }
+            }
+        }
+    }
+
+    // Returns the reachable blocks in reverse post order and finds the blocks to widen at.
+    fn reverse_post_order(&mut self) -> Vec<NodeIndex> {
+        let entry = match self.ssa.entry_node() {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+
+        // `DomTree` cannot handle unreachable blocks, so only the reachable ones are copied.
+        let mut g = Graph::<NodeIndex, u8>::new();
+        let mut index = HashMap::new();
+        let mut worklist = vec![entry];
+        index.insert(entry, g.add_node(entry));
+        while let Some(block) = worklist.pop() {
+            for succ in self.ssa.succs_of(block) {
+                if !index.contains_key(&succ) {
+                    index.insert(succ, g.add_node(succ));
+                    worklist.push(succ);
+                }
+                g.add_edge(index[&block], index[&succ], 0);
+            }
+        }
+
+        let mut dfs = DFSVisitor::new();
+        dfs.dfs(&g, index[&entry]);
+        let mut rpo = dfs.post_order();
+        rpo.reverse();
+        let order = rpo
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (n, i))
+            .collect::<HashMap<_, _>>();
+
+        // Loop headers dominate one of their predecessors. Blocks entered by a retreating
+        // edge are widened too, as irreducible loops have no such header.
+        let dom = DomTree::build_dom_tree(&g, index[&entry]);
+        for &n in &rpo {
+            for pred in g.neighbors_directed(n, EdgeDirection::Incoming) {
+                if dom.doms(pred).contains(&n) || order[&pred] >= order[&n] {
+                    self.widen_at.insert(g[n]);
+                }
+            }
+        }
+
+        rpo.iter().map(|&n| g[n]).collect()
+    }
+
+    fn visit_phi(&mut self, phi: NodeIndex, widen: bool) {
+        let operands = self.ssa.operands_of(phi);
+        if self.is_memory(phi) {
+            let mut joined: Option<AbstractStore<A>> = None;
+            for op in operands {
+                if let Some(store) = self.store_of(op) {
+                    joined = Some(match joined {
+                        Some(j) => j.join(&store),
+                        None => store,
+                    });
+                }
+            }
+            if let Some(new) = joined {
+                let new = match self.stores.get(&phi) {
+                    Some(old) if widen => old.widen(&old.join(&new)),
+                    _ => new,
+                };
+                self.set_store(phi, new);
+            }
+        } else {
+            let width = self.width(phi);
+            let mut joined = ValueSet::bottom(width);
+            for op in operands {
+                joined = joined.join(&self.value_of(op));
+            }
+            let new = match self.values.get(&phi) {
+                Some(old) if widen => old.widen(&old.join(&joined)),
+                _ => joined,
+            };
+            self.set_value(phi, new);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: NodeIndex) {
+        let opcode = match self.ssa.opcode(expr) {
+            Some(opcode) => opcode,
+            None => return,
+        };
+        let operands = self.ssa.operands_of(expr);
+        match opcode {
+            MOpcode::OpStore => {
+                if operands.len() < 3 {
+                    radeco_warn!("Malformed OpStore {:?}", expr);
+                    return;
+                }
+                let mut store = self.store_of(operands[0]).unwrap_or_default();
+                let address = self.value_of(operands[1]);
+                let value = self.value_of(operands[2]);
+                let size = (self.width(operands[2]) + 7) / 8;
+                store.store(&address, size, value);
+                self.set_address(expr, address);
+                self.set_store(expr, store);
+            }
+            MOpcode::OpLoad => {
+                if operands.len() < 2 {
+                    radeco_warn!("Malformed OpLoad {:?}", expr);
+                    return;
+                }
+                let width = self.width(expr);
+                let address = self.value_of(operands[1]);
+                let value = match (self.store_of(operands[0]), address.as_address()) {
+                    (Some(store), Some((region, offset))) => {
+                        store.load(&ALoc::new(region, offset, (width + 7) / 8), width)
+                    }
+                    _ => ValueSet::top(width),
+                };
+                self.set_address(expr, address);
+                self.set_value(expr, value);
+            }
+            MOpcode::OpCall | MOpcode::OpCJmp | MOpcode::OpJmp | MOpcode::OpNop => {}
+            _ => {
+                if !self.is_memory(expr) {
+                    let value = self.eval(expr, &opcode, &operands);
+                    self.set_value(expr, value);
+                }
+            }
+        }
+    }
+
+    fn eval(&self, expr: NodeIndex, opcode: &MOpcode, operands: &[NodeIndex]) -> ValueSet<A> {
+        let width = self.width(expr);
+        let values = operands
+            .iter()
+            .map(|&op| self.value_of(op))
+            .collect::<Vec<_>>();
+
+        if values.iter().any(|v| v.is_bottom()) {
+            return ValueSet::bottom(width);
+        }
+
+        // Operations on constants are evaluated exactly
+        let constants = operands
+            .iter()
+            .zip(values.iter())
+            .map(|(&op, v)| v.as_constant().map(|c| (c as u64, self.width(op) as u16)))
+            .collect::<Option<Vec<_>>>();
+        if let Some(c) = constants {
+            let result = match c.len() {
+                1 => opcode.eval_unop_width(c[0].0, c[0].1, width as u16),
+                2 => opcode.eval_binop_width(c[0].0, c[1].0, c[0].1, width as u16),
+                _ => None,
+            };
+            match result {
+                Some(EvalResult::Value(v)) => return ValueSet::constant(width, v),
+                Some(EvalResult::Undefined) => return ValueSet::bottom(width),
+                None => {}
+            }
+        }
+
+        match (opcode, values.len()) {
+            (&MOpcode::OpConst(c), _) => ValueSet::constant(width, c),
+            (&MOpcode::OpMov, 1) => values[0].resize(width, false),
+            (&MOpcode::OpNarrow(_), 1) => values[0].resize(width, false),
+            (&MOpcode::OpZeroExt(_), 1) => values[0].resize(width, false),
+            (&MOpcode::OpSignExt(_), 1) => values[0].resize(width, true),
+            (&MOpcode::OpNot, 1) => values[0].apply_numbers(&values[0], |a, _| !a),
+            (&MOpcode::OpAdd, 2) => values[0].add(&values[1].resize(width, false)),
+            (&MOpcode::OpSub, 2) => values[0].sub(&values[1].resize(width, false)),
+            (_, 2) => {
+                let (l, r) = (&values[0], values[1].resize(values[0].width(), false));
+                let result = match *opcode {
+                    MOpcode::OpMul => l.apply_numbers(&r, |a, b| a * b),
+                    MOpcode::OpAnd => l.apply_numbers(&r, |a, b| a & b),
+                    MOpcode::OpOr => l.apply_numbers(&r, |a, b| a | b),
+                    MOpcode::OpXor => l.apply_numbers(&r, |a, b| a ^ b),
+                    MOpcode::OpLsl => l.apply_numbers(&r, |a, b| a << b),
+                    MOpcode::OpLsr => l.apply_numbers(&r, |a, b| a >> b),
+                    MOpcode::OpEq
+                    | MOpcode::OpGt
+                    | MOpcode::OpLt
+                    | MOpcode::OpSGt
+                    | MOpcode::OpSLt => {
+                        return ValueSet::constant(width, 0).join(&ValueSet::constant(width, 1));
+                    }
+                    // Division and modulo, signed or not, are not evaluated on abstract numbers.
+                    _ => return ValueSet::top(width),
+                };
+                result.resize(width, false)
+            }
+            _ => ValueSet::top(width),
+        }
+    }
+
+    // Returns the value-set of `node`, evaluating constants and comments which are not part of
+    // the blocks walked by the analysis.
+    fn value_of(&self, node: NodeIndex) -> ValueSet<A> {
+        if let Some(vs) = self.values.get(&node) {
+            return vs.clone();
+        }
+        let width = self.width(node);
+        match self.ssa.node_data(node).map(|nd| nd.nt) {
+            Ok(NodeType::Op(MOpcode::OpConst(c))) => ValueSet::constant(width, c),
+            Ok(NodeType::Comment(ref name)) => self.comment_value(node, name, width),
+            Ok(NodeType::Undefined) => ValueSet::top(width),
+            // Not reached yet
+            Ok(_) => ValueSet::bottom(width),
+            Err(_) => ValueSet::top(width),
+        }
+    }
+
+    fn comment_value(&self, node: NodeIndex, name: &str, width: u8) -> ValueSet<A> {
+        if Some(name) == self.sp.as_ref().map(|s| s.as_str()) {
+            return ValueSet::from_region(width, MemRegion::Stack, value_set::number(width, 0));
+        }
+        // The return register after a call points to memory owned by that call
+        let is_retn = self
+            .retn
+            .as_ref()
+            .map_or(false, |r| name.starts_with(&format!("{}@", r)));
+        if is_retn {
+            let call = self
+                .ssa
+                .operands_of(node)
+                .into_iter()
+                .find(|&op| self.ssa.opcode(op) == Some(MOpcode::OpCall));
+            if let Some(call) = call {
+                return ValueSet::from_region(
+                    width,
+                    MemRegion::Heap(call),
+                    value_set::number(width, 0),
+                );
+            }
+        }
+        ValueSet::top(width)
+    }
+
+    fn store_of(&self, node: NodeIndex) -> Option<AbstractStore<A>> {
+        if let Some(store) = self.stores.get(&node) {
+            return Some(store.clone());
+        }
+        // Memory comments ("mem" at the entry and "mem@addr" after calls) may hold anything
+        match self.ssa.comment(node) {
+            Some(ref name) if name == "mem" || name.starts_with("mem@") => {
+                Some(AbstractStore::new())
+            }
+            _ => None,
+        }
+    }
+
+    fn is_memory(&self, node: NodeIndex) -> bool {
+        match self.ssa.node_data(node) {
+            Ok(nd) => *nd.vt.width() == WidthSpec::Known(0),
+            Err(_) => false,
+        }
+    }
+
+    // Width of `node` in bits. Unknown widths are assumed to be 64 bits.
+    fn width(&self, node: NodeIndex) -> u8 {
+        match self.ssa.node_data(node).map(|nd| *nd.vt.width()) {
+            Ok(WidthSpec::Known(w)) if w > 0 => ::std::cmp::min(w, 64) as u8,
+            _ => 64,
+        }
+    }
+
+    fn set_value(&mut self, node: NodeIndex, vs: ValueSet<A>) {
+        if self.values.get(&node) != Some(&vs) {
+            self.values.insert(node, vs);
+            self.changed = true;
+        }
+    }
+
+    fn set_store(&mut self, node: NodeIndex, store: AbstractStore<A>) {
+        if self.stores.get(&node) != Some(&store) {
+            self.stores.insert(node, store);
+            self.changed = true;
+        }
+    }
+
+    fn set_address(&mut self, node: NodeIndex, address: ValueSet<A>) {
+        if self.addresses.get(&node) != Some(&address) {
+            self.addresses.insert(node, address);
+            self.changed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use analysis::vsa::abstract_set::strided_interval::StridedInterval;
+    use frontend::radeco_containers::ModuleLoader;
+    use frontend::radeco_source::FileSource;
+    use middle::ir::MAddress;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SIGNED_TXT: &str = "\
+define-fun sym.signed(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x3):
+        [@0x000100.0001] %3: $Unknown64 = %1 | #xfffffffffffffff0;
+        [@0x000100.0002] %4: $Unknown64 = %3 /s #x4;
+        [@0x000100.0003] %5: $Unknown64 = %3 %s #x4;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $rdx = %5;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn vsa_signed_test() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(SIGNED_TXT, regfile);
+        let vsa = analyze::<StridedInterval>(&ssa);
+        let value_at = |offset| {
+            let addr = MAddress::new(0x100, offset);
+            let node = ssa
+                .values()
+                .into_iter()
+                .find(|&n| ssa.address(n) == Some(addr))
+                .unwrap();
+            vsa.value_set(node).expect("No value-set").clone()
+        };
+        // %3 is negative, -16 /s 4 = -4 and -15 %s 4 = -3
+        assert!(value_at(2).contains_number(-4i64 as u64));
+        assert!(value_at(3).contains_number(-3i64 as u64));
+    }
+
+    #[test]
+    fn vsa_bin1_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .load(Rc::new(FileSource::open(path.to_str().unwrap())));
+        let main = rmod.function(0x40059d).expect("No main in bin1");
+        let ssa = main.ssa();
+        let vsa = analyze::<StridedInterval>(ssa);
+
+        let sp = ssa.regfile.alias_info["SP"].clone();
+        let mut saw_stack = false;
+        for node in ssa.values() {
+            if let Some(MOpcode::OpConst(c)) = ssa.opcode(node) {
+                let vs = vsa.value_set(node).expect("Constant without a value-set");
+                assert!(vs.contains_number(c));
+                assert!(vs.as_constant().is_some());
+            }
+            if ssa.comment(node).as_ref() == Some(&sp) {
+                let vs = vsa.value_set(node);
+                assert_eq!(vs.and_then(|v| v.as_address()), Some((MemRegion::Stack, 0)));
+            }
+            if let Some(a) = vsa.address_of(node) {
+                saw_stack |= a.region(MemRegion::Stack).is_some();
+            }
+        }
+        // main spills its frame to the stack
+        assert!(saw_stack);
+    }
+}
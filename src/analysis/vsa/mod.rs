@@ -16,3 +16,6 @@ pub mod abstract_set {
     pub mod polynomial;
     pub mod strided_interval;
 }
+
+pub mod analyzer;
+pub mod value_set;
@@ -0,0 +1,501 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Module that implements value-sets and abstract stores used by the value set analysis.
+//!
+//! A value-set maps every memory region to the set of offsets a value may have in that
+//! region. Plain numbers are offsets in the global region. An abstract store maps a-locs
+//! (abstract locations, roughly the variables of the program) to the value-set they hold.
+//!
+//! As for Gogul Balakrishnan's Ph.D. Thesis, chapter 3.
+//! Please refer here:
+//!     * https://research.cs.wisc.edu/wpis/papers/balakrishnan_thesis.pdf
+
+use petgraph::graph::NodeIndex;
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+
+use middle::ir;
+
+use super::abstract_set::abstract_set::{inum, AbstractSet, Container};
+
+/// Memory region a value-set points into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MemRegion {
+    /// Global memory. Plain numbers are offsets into this region.
+    Global,
+    /// Stack frame of the analyzed function. Offsets are relative to the stack pointer at
+    /// the entry of the function.
+    Stack,
+    /// Memory returned by the call at the `OpCall` node.
+    Heap(NodeIndex),
+}
+
+impl fmt::Display for MemRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MemRegion::Global => write!(f, "global"),
+            MemRegion::Stack => write!(f, "stack"),
+            MemRegion::Heap(call) => write!(f, "heap_{}", call.index()),
+        }
+    }
+}
+
+/// An abstract location: `size` bytes at `offset` in `region`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ALoc {
+    pub region: MemRegion,
+    pub offset: inum,
+    pub size: u8,
+}
+
+impl ALoc {
+    pub fn new(region: MemRegion, offset: inum, size: u8) -> ALoc {
+        ALoc {
+            region: region,
+            offset: offset,
+            size: size,
+        }
+    }
+
+    /// Returns true if `self` and `other` share at least one byte.
+    pub fn overlaps(&self, other: &ALoc) -> bool {
+        self.region == other.region
+            && self.offset < other.offset.saturating_add(other.size as inum)
+            && other.offset < self.offset.saturating_add(self.size as inum)
+    }
+}
+
+impl fmt::Display for ALoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{:#x}; {}]", self.region, self.offset, self.size)
+    }
+}
+
+/// Set of values a `width` bits wide SSA value may have.
+///
+/// All the abstract sets in a value-set are `width` bits wide. A value-set without regions
+/// is the empty set (bottom), which is the value of nodes that have not been reached yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueSet<A: AbstractSet> {
+    width: u8,
+    offsets: BTreeMap<MemRegion, A>,
+    top: bool,
+}
+
+impl<A: AbstractSet> ValueSet<A> {
+    /// Returns the empty value-set.
+    pub fn bottom(width: u8) -> ValueSet<A> {
+        ValueSet {
+            width: width,
+            offsets: BTreeMap::new(),
+            top: false,
+        }
+    }
+
+    /// Returns the value-set that contains every value.
+    pub fn top(width: u8) -> ValueSet<A> {
+        ValueSet {
+            width: width,
+            offsets: BTreeMap::new(),
+            top: true,
+        }
+    }
+
+    /// Returns the value-set containing only the number `value`, truncated to `width` bits.
+    pub fn constant(width: u8, value: u64) -> ValueSet<A> {
+        ValueSet::numbers(width, number(width, value))
+    }
+
+    /// Returns the value-set containing the numbers in `set`.
+    pub fn numbers(width: u8, set: A) -> ValueSet<A> {
+        ValueSet::from_region(width, MemRegion::Global, set)
+    }
+
+    /// Returns the value-set pointing at `offsets` in `region`.
+    pub fn from_region(width: u8, region: MemRegion, offsets: A) -> ValueSet<A> {
+        let mut vs = ValueSet::bottom(width);
+        if !offsets.is_empty() {
+            vs.offsets.insert(region, offsets);
+        }
+        vs
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn is_top(&self) -> bool {
+        self.top
+    }
+
+    pub fn is_bottom(&self) -> bool {
+        !self.top && self.offsets.is_empty()
+    }
+
+    /// Returns the offsets of `self` in `region`, if `self` may point into `region`.
+    pub fn region(&self, region: MemRegion) -> Option<&A> {
+        if self.top {
+            None
+        } else {
+            self.offsets.get(&region)
+        }
+    }
+
+    /// Iterates over the regions `self` may point into, along with the offsets in them.
+    pub fn regions(&self) -> btree_map::Iter<MemRegion, A> {
+        self.offsets.iter()
+    }
+
+    /// Returns the numbers in `self` if `self` holds plain numbers only.
+    pub fn as_numbers(&self) -> Option<&A> {
+        if self.offsets.len() == 1 {
+            self.region(MemRegion::Global)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number in `self` if it is the only value in `self`.
+    pub fn as_constant(&self) -> Option<inum> {
+        self.as_numbers().and_then(|n| n.constant())
+    }
+
+    /// Returns the region and offset `self` points to, if it is the only value in `self`.
+    pub fn as_address(&self) -> Option<(MemRegion, inum)> {
+        if self.top || self.offsets.len() != 1 {
+            return None;
+        }
+        self.offsets
+            .iter()
+            .next()
+            .and_then(|(&r, o)| o.constant().map(|o| (r, o)))
+    }
+
+    /// Returns true if `self` contains the number `value`.
+    pub fn contains_number(&self, value: u64) -> bool {
+        let value = ir::sign_extend(value, self.width as u16) as inum;
+        self.top
+            || self
+                .region(MemRegion::Global)
+                .map_or(false, |n| Container::<inum>::contains(n, &value))
+    }
+
+    /// Returns the union of `self` and `other`.
+    pub fn join(&self, other: &ValueSet<A>) -> ValueSet<A> {
+        self.merge(other, |a, b| a.join(b))
+    }
+
+    /// Returns `self` widened with respect to `other`.
+    pub fn widen(&self, other: &ValueSet<A>) -> ValueSet<A> {
+        self.merge(other, |a, b| a.widen(b))
+    }
+
+    fn merge<F>(&self, other: &ValueSet<A>, f: F) -> ValueSet<A>
+    where
+        F: Fn(&A, &A) -> A,
+    {
+        if self.top || other.top {
+            return ValueSet::top(self.width);
+        }
+        let other = other.resize(self.width, false);
+        let mut offsets = self.offsets.clone();
+        for (region, o) in other.offsets {
+            let merged = match offsets.get(&region) {
+                Some(s) => f(s, &o),
+                None => o,
+            };
+            offsets.insert(region, merged);
+        }
+        ValueSet {
+            width: self.width,
+            offsets: offsets,
+            top: false,
+        }
+    }
+
+    /// Returns `self` converted to `width` bits, as done by `OpNarrow`, `OpSignExt` and
+    /// `OpZeroExt`.
+    ///
+    /// Narrowed addresses are no longer meaningful, so they become top.
+    pub fn resize(&self, width: u8, signed: bool) -> ValueSet<A> {
+        if self.top {
+            return ValueSet::top(width);
+        }
+        if width == self.width {
+            return self.clone();
+        }
+        let mut vs = ValueSet::bottom(width);
+        for (&region, o) in &self.offsets {
+            let resized = if width < self.width {
+                if region != MemRegion::Global {
+                    return ValueSet::top(width);
+                }
+                o.narrow(width)
+            } else if signed || region != MemRegion::Global {
+                o.sign_extend(width)
+            } else {
+                o.zero_extend(width)
+            };
+            vs.offsets.insert(region, resized);
+        }
+        vs
+    }
+
+    /// Returns the value-set of `self + other`.
+    ///
+    /// Adding a number to an address moves the address inside of its region.
+    pub fn add(&self, other: &ValueSet<A>) -> ValueSet<A> {
+        if let Some(n) = other.as_numbers() {
+            return self.map_offsets(|o| o + *n);
+        }
+        if let Some(n) = self.as_numbers() {
+            return other.map_offsets(|o| *n + o);
+        }
+        self.apply_numbers(other, |a, b| a + b)
+    }
+
+    /// Returns the value-set of `self - other`.
+    ///
+    /// The difference of two addresses in the same region is a number.
+    pub fn sub(&self, other: &ValueSet<A>) -> ValueSet<A> {
+        if let Some(n) = other.as_numbers() {
+            return self.map_offsets(|o| o - *n);
+        }
+        if !self.top && !other.top && self.offsets.len() == 1 && other.offsets.len() == 1 {
+            let (r1, o1) = self.offsets.iter().next().expect("Cannot fail");
+            let (r2, o2) = other.offsets.iter().next().expect("Cannot fail");
+            if r1 == r2 {
+                return ValueSet::numbers(self.width, *o1 - *o2);
+            }
+        }
+        ValueSet::top(self.width)
+    }
+
+    /// Returns the value-set of `op(self, other)` for an operation that is only meaningful on
+    /// numbers. Any other value-set yields top.
+    pub fn apply_numbers<F>(&self, other: &ValueSet<A>, op: F) -> ValueSet<A>
+    where
+        F: Fn(A, A) -> A,
+    {
+        if self.is_bottom() || other.is_bottom() {
+            return ValueSet::bottom(self.width);
+        }
+        match (self.as_numbers(), other.as_numbers()) {
+            (Some(a), Some(b)) => ValueSet::numbers(self.width, op(*a, *b)),
+            _ => ValueSet::top(self.width),
+        }
+    }
+
+    /// Applies `f` to the offsets in every region.
+    pub fn map_offsets<F>(&self, f: F) -> ValueSet<A>
+    where
+        F: Fn(A) -> A,
+    {
+        if self.top {
+            return self.clone();
+        }
+        let mut vs = ValueSet::bottom(self.width);
+        for (&region, o) in &self.offsets {
+            vs.offsets.insert(region, f(*o));
+        }
+        vs
+    }
+}
+
+impl<A: AbstractSet + fmt::Display> fmt::Display for ValueSet<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.top {
+            return write!(f, "T");
+        }
+        write!(f, "{{")?;
+        for (i, (region, o)) in self.offsets.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", region, o)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Returns the abstract set containing only `value`, truncated to `width` bits.
+pub fn number<A: AbstractSet>(width: u8, value: u64) -> A {
+    A::from((width, ir::sign_extend(value, width as u16) as inum))
+}
+
+/// Map from a-locs to the value-sets they hold at a point of the program.
+///
+/// A-locs missing from the store may hold any value, so the store at the entry of a function
+/// is empty. Stored a-locs never overlap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AbstractStore<A: AbstractSet> {
+    alocs: BTreeMap<ALoc, ValueSet<A>>,
+}
+
+impl<A: AbstractSet> Default for AbstractStore<A> {
+    fn default() -> AbstractStore<A> {
+        AbstractStore {
+            alocs: BTreeMap::new(),
+        }
+    }
+}
+
+impl<A: AbstractSet> AbstractStore<A> {
+    pub fn new() -> AbstractStore<A> {
+        AbstractStore::default()
+    }
+
+    /// Iterates over the a-locs whose value is known, along with their value.
+    pub fn iter(&self) -> btree_map::Iter<ALoc, ValueSet<A>> {
+        self.alocs.iter()
+    }
+
+    pub fn get(&self, aloc: &ALoc) -> Option<&ValueSet<A>> {
+        self.alocs.get(aloc)
+    }
+
+    /// Returns the value-set of a `width` bits wide load from `aloc`.
+    pub fn load(&self, aloc: &ALoc, width: u8) -> ValueSet<A> {
+        if let Some(vs) = self.alocs.get(aloc) {
+            return vs.resize(width, false);
+        }
+        // A narrower load from the start of a bigger a-loc reads its low bytes
+        let bigger = self.alocs.iter().find(|&(l, _)| {
+            l.region == aloc.region && l.offset == aloc.offset && l.size > aloc.size
+        });
+        match bigger {
+            Some((_, vs)) => vs.resize(width, false),
+            None => ValueSet::top(width),
+        }
+    }
+
+    /// Updates the store for a write of `value` (`size` bytes) to `address`.
+    ///
+    /// A write to a single a-loc replaces its value. Writes to more than one a-loc forget
+    /// the a-locs of the regions written to, as they may be partially overwritten.
+    pub fn store(&mut self, address: &ValueSet<A>, size: u8, value: ValueSet<A>) {
+        if address.is_top() {
+            self.alocs.clear();
+            return;
+        }
+        if let Some((region, offset)) = address.as_address() {
+            let aloc = ALoc::new(region, offset, size);
+            self.forget(|l| l.overlaps(&aloc));
+            if !value.is_top() && !value.is_bottom() {
+                self.alocs.insert(aloc, value);
+            }
+            return;
+        }
+        for (&region, _) in address.regions() {
+            self.forget(|l| l.region == region);
+        }
+    }
+
+    fn forget<F>(&mut self, f: F)
+    where
+        F: Fn(&ALoc) -> bool,
+    {
+        let forgotten = self
+            .alocs
+            .keys()
+            .filter(|l| f(l))
+            .cloned()
+            .collect::<Vec<_>>();
+        for aloc in forgotten {
+            self.alocs.remove(&aloc);
+        }
+    }
+
+    /// Returns the store holding the values of either `self` or `other`.
+    pub fn join(&self, other: &AbstractStore<A>) -> AbstractStore<A> {
+        self.merge(other, |a, b| a.join(b))
+    }
+
+    /// Returns `self` widened with respect to `other`.
+    pub fn widen(&self, other: &AbstractStore<A>) -> AbstractStore<A> {
+        self.merge(other, |a, b| a.widen(b))
+    }
+
+    fn merge<F>(&self, other: &AbstractStore<A>, f: F) -> AbstractStore<A>
+    where
+        F: Fn(&ValueSet<A>, &ValueSet<A>) -> ValueSet<A>,
+    {
+        // An a-loc missing from either store may hold anything
+        let alocs = self
+            .alocs
+            .iter()
+            .filter_map(|(l, a)| other.alocs.get(l).map(|b| (*l, f(a, b))))
+            .filter(|&(_, ref vs)| !vs.is_top())
+            .collect();
+        AbstractStore { alocs: alocs }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use analysis::vsa::abstract_set::strided_interval::StridedInterval;
+
+    type VS = ValueSet<StridedInterval>;
+
+    #[test]
+    fn value_set_arith_test() {
+        let sp = VS::from_region(64, MemRegion::Stack, StridedInterval::from((64, 0)));
+        let local = sp.sub(&VS::constant(64, 8));
+        assert_eq!(local.as_address(), Some((MemRegion::Stack, -8)));
+        assert_eq!(local.sub(&sp).as_constant(), Some(-8));
+        assert!(local.add(&sp).is_top());
+        assert!(local
+            .apply_numbers(&VS::constant(64, 2), |a, b| a * b)
+            .is_top());
+        assert_eq!(
+            VS::constant(32, 6)
+                .apply_numbers(&VS::constant(32, 7), |a, b| a * b)
+                .as_constant(),
+            Some(42)
+        );
+        assert_eq!(VS::constant(8, 0xff).as_constant(), Some(-1));
+        assert!(VS::constant(8, 0xff).contains_number(0xff));
+        assert_eq!(
+            VS::constant(8, 0xff).resize(16, false).as_constant(),
+            Some(0xff)
+        );
+    }
+
+    #[test]
+    fn abstract_store_test() {
+        let sp = VS::from_region(64, MemRegion::Stack, StridedInterval::from((64, 0)));
+        let mut store = AbstractStore::new();
+        store.store(&sp.sub(&VS::constant(64, 8)), 8, VS::constant(64, 42));
+        store.store(&sp.sub(&VS::constant(64, 16)), 4, VS::constant(32, 1));
+        let l8 = ALoc::new(MemRegion::Stack, -8, 8);
+        assert_eq!(store.load(&l8, 64).as_constant(), Some(42));
+        assert_eq!(
+            store
+                .load(&ALoc::new(MemRegion::Stack, -8, 4), 32)
+                .as_constant(),
+            Some(42)
+        );
+
+        // Joining with a store where -8 holds another value keeps both values
+        let mut other = store.clone();
+        other.store(&sp.sub(&VS::constant(64, 8)), 8, VS::constant(64, 43));
+        let joined = store.join(&other);
+        let v = joined.load(&l8, 64);
+        assert!(v.contains_number(42) && v.contains_number(43));
+
+        // Partially overwriting an a-loc forgets it
+        store.store(&sp.sub(&VS::constant(64, 4)), 4, VS::constant(32, 0));
+        assert!(store.load(&l8, 64).is_top());
+        assert!(store.get(&ALoc::new(MemRegion::Stack, -16, 4)).is_some());
+
+        // Writing to an unknown address forgets everything
+        store.store(&VS::top(64), 8, VS::constant(64, 0));
+        assert_eq!(store.iter().count(), 0);
+    }
+}
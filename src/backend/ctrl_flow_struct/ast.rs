@@ -1,3 +1,5 @@
+use std::iter::FromIterator;
+
 // B: basic block
 // C: condition
 // V: variable
@@ -18,11 +20,141 @@ pub enum LoopType<C> {
     Endless,
 }
 
-pub type ValueSet = (); // XXX
-
 impl<B, C, V> Default for AstNode<B, C, V> {
     /// Creates a no-op node.
     fn default() -> Self {
         AstNode::Seq(Vec::new())
     }
 }
+
+/// A set of values of a switch variable, stored as a sorted list of disjoint,
+/// non-adjacent inclusive ranges.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ValueSet {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl ValueSet {
+    /// Creates a set containing no values.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Creates a set containing every value.
+    pub fn full() -> Self {
+        Self::range(0, u64::max_value())
+    }
+
+    /// Creates a set containing only `val`.
+    pub fn single(val: u64) -> Self {
+        Self::range(val, val)
+    }
+
+    /// Creates a set containing all values from `lo` to `hi`, inclusive.
+    /// The set is empty if `lo > hi`.
+    pub fn range(lo: u64, hi: u64) -> Self {
+        if lo <= hi {
+            ValueSet {
+                ranges: vec![(lo, hi)],
+            }
+        } else {
+            Self::empty()
+        }
+    }
+
+    fn from_ranges(mut ranges: Vec<(u64, u64)>) -> Self {
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (lo, hi) in ranges {
+            if let Some(last) = merged.last_mut() {
+                if lo <= last.1.saturating_add(1) {
+                    last.1 = last.1.max(hi);
+                    continue;
+                }
+            }
+            merged.push((lo, hi));
+        }
+        ValueSet { ranges: merged }
+    }
+
+    /// The inclusive ranges that make up this set, in ascending order.
+    pub fn ranges(&self) -> &[(u64, u64)] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.ranges == [(0, u64::max_value())]
+    }
+
+    pub fn contains(&self, val: u64) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= val && val <= hi)
+    }
+
+    /// Returns the smallest value in this set.
+    pub fn min(&self) -> Option<u64> {
+        self.ranges.first().map(|&(lo, _)| lo)
+    }
+
+    /// Returns the number of values in this set. This is a `u128` because
+    /// a full set has 2^64 values.
+    pub fn len(&self) -> u128 {
+        self.ranges
+            .iter()
+            .map(|&(lo, hi)| u128::from(hi - lo) + 1)
+            .sum()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_ranges(self.ranges.iter().chain(&other.ranges).cloned().collect())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (alo, ahi) = self.ranges[i];
+            let (blo, bhi) = other.ranges[j];
+            let (lo, hi) = (alo.max(blo), ahi.min(bhi));
+            if lo <= hi {
+                ranges.push((lo, hi));
+            }
+            if ahi < bhi {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        ValueSet { ranges }
+    }
+
+    pub fn complement(&self) -> Self {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        let mut next = Some(0);
+        for &(lo, hi) in &self.ranges {
+            if let Some(n) = next {
+                if n < lo {
+                    ranges.push((n, lo - 1));
+                }
+            }
+            next = hi.checked_add(1);
+        }
+        if let Some(n) = next {
+            ranges.push((n, u64::max_value()));
+        }
+        ValueSet { ranges }
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).is_empty()
+    }
+}
+
+impl FromIterator<u64> for ValueSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        Self::from_ranges(iter.into_iter().map(|v| (v, v)).collect())
+    }
+}
@@ -1,6 +1,8 @@
+use super::ast::ValueSet;
+
 pub trait AstContext {
     type Block;
-    type Variable;
+    type Variable: Clone + Eq;
     type BoolVariable;
     type Condition: 'static;
 }
@@ -20,6 +22,17 @@ pub trait AstContextMut: AstContext {
     /// Returns a `Condition` that represents `var` being equal to `val`.
     fn mk_cond_equals(&mut self, var: &Self::Variable, val: u64) -> Self::Condition;
 
+    /// If `cond` only tests the value of a single `Variable`, returns that
+    /// variable and the set of values for which `cond` is true. This is used
+    /// to find `switch` statements.
+    fn cond_value_set(&self, cond: &Self::Condition) -> Option<(Self::Variable, ValueSet)>;
+
+    /// Returns the set of values `var` can hold. Value sets are limited to it,
+    /// and a `switch` whose cases cover it needs no `default`.
+    fn var_value_set(&self, _var: &Self::Variable) -> ValueSet {
+        ValueSet::full()
+    }
+
     /// Returns a `Condition` that represents the value of `var`.
     fn mk_cond_from_bool_var(&mut self, var: &Self::BoolVariable) -> Self::Condition;
 
@@ -355,7 +355,7 @@ impl<'cd, T> Condition<'cd, T> {
 
     pub fn fold<F: Folder<T>>(self, mut folder: F) -> F::Output {
         match self.0 {
-            &Var(inv, vr) => folder.var(inv == Negation::Normal, vr.0),
+            &Var(inv, vr) => folder.var(inv == Negation::Negated, vr.0),
             &Expr(Op::And, ref opn_v) => folder.and(opn_v.iter().cloned()),
             &Expr(Op::Or, ref opn_v) => folder.or(opn_v.iter().cloned()),
         }
@@ -304,3 +304,55 @@ fn nested_complementation() {
         a_and_b
     );
 }
+
+struct Printer;
+
+impl Folder<&'static str> for Printer {
+    type Output = String;
+    fn var(&mut self, negated: bool, var: &&'static str) -> String {
+        if negated {
+            format!("!{}", var)
+        } else {
+            var.to_string()
+        }
+    }
+    fn and<'a, I>(&mut self, operands: I) -> String
+    where
+        I: IntoIterator<Item = Condition<'a, &'static str>>,
+    {
+        print_expr(operands, " && ", "1")
+    }
+    fn or<'a, I>(&mut self, operands: I) -> String
+    where
+        I: IntoIterator<Item = Condition<'a, &'static str>>,
+    {
+        print_expr(operands, " || ", "0")
+    }
+}
+
+fn print_expr<'a, I>(operands: I, op: &str, empty: &str) -> String
+where
+    I: IntoIterator<Item = Condition<'a, &'static str>>,
+{
+    let mut operands: Vec<_> = operands.into_iter().map(|c| c.fold(Printer)).collect();
+    if operands.is_empty() {
+        return empty.to_owned();
+    }
+    operands.sort();
+    format!("({})", operands.join(op))
+}
+
+#[test]
+fn fold() {
+    let cstore = Storage::new();
+    let cctx = cstore.cctx();
+    let a = cctx.mk_var(cctx.new_var("a"));
+    let b = cctx.mk_var(cctx.new_var("b"));
+
+    assert_eq!("a", a.fold(Printer));
+    assert_eq!("!a", cctx.mk_not(a).fold(Printer));
+    assert_eq!("1", cctx.mk_true().fold(Printer));
+    assert_eq!("0", cctx.mk_false().fold(Printer));
+    assert_eq!("(!b && a)", cctx.mk_and(a, cctx.mk_not(b)).fold(Printer));
+    assert_eq!("(!a || !b)", cctx.mk_not(cctx.mk_and(a, b)).fold(Printer));
+}
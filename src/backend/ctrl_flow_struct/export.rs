@@ -64,10 +64,28 @@ impl<'a> Exporter<'a> {
                 Ok(vec![self.conv.ast_mut().new_while(c, b)])
             }
             Break => Ok(vec![self.conv.ast_mut().insert_break()]),
-            Switch(_, _, _) => unimplemented!(), // TODO
+            Switch(v, cases, default) => {
+                let sel = self.conv.get_node(v).ok_or("unconverted switch variable")?;
+                let cases = cases
+                    .into_iter()
+                    .map(|(vs, a)| Ok((vs.ranges().to_vec(), self.go_case(a)?)))
+                    .collect::<Result<Vec<_>, &'static str>>()?;
+                let default = match *default {
+                    Seq(ref seq) if seq.is_empty() => None,
+                    default => Some(self.go_case(default)?),
+                };
+                Ok(vec![self.conv.ast_mut().new_switch(sel, cases, default)])
+            }
         }
     }
 
+    /// Exports the body of a `switch` case, which must not fall through.
+    fn go_case<'cd>(&mut self, ast: AstNode<'cd, CCFG>) -> Result<Vec<CASTRef>, &'static str> {
+        let mut body = self.go(ast)?;
+        body.push(self.conv.ast_mut().insert_break());
+        Ok(body)
+    }
+
     fn bool_expr<'c, I: IntoIterator<Item = Condition<'c, CCFG>>>(
         &mut self,
         empty: CASTRef,
@@ -107,7 +125,7 @@ impl<'a, 'b> condition::Folder<CCFGRef> for &'a mut Exporter<'b> {
     where
         I: IntoIterator<Item = Condition<'c, CCFG>>,
     {
-        let empty = self.const_zero;
+        let empty = self.const_one;
         self.bool_expr(empty, || c_ast::Expr::And, operands)
     }
 
@@ -115,7 +133,7 @@ impl<'a, 'b> condition::Folder<CCFGRef> for &'a mut Exporter<'b> {
     where
         I: IntoIterator<Item = Condition<'c, CCFG>>,
     {
-        let empty = self.const_one;
+        let empty = self.const_zero;
         self.bool_expr(empty, || c_ast::Expr::Or, operands)
     }
 }
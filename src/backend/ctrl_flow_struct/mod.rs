@@ -4,11 +4,11 @@
 
 #![allow(dead_code)]
 
+pub mod ast;
 pub mod ast_context;
 pub mod condition;
 pub mod export;
 
-mod ast;
 mod dedup_conds;
 mod graph_utils;
 mod refinement;
@@ -245,6 +245,10 @@ impl<'cd, A: AstContextMut> ControlFlowGraph<'cd, A> {
 
         let ast = refinement::refine::<RegionAstContext<A>>(
             self.cctx,
+            refinement::ValueSetFns {
+                cond: &|c| self.actx.cond_value_set(c),
+                var: &|v| self.actx.var_value_set(v),
+            },
             region_graph,
             old_new_map[&header],
         );
@@ -2,8 +2,9 @@
 //!
 //! Everything in this module does not impact correctness, only readability.
 
-use super::ast::LoopType;
+use super::ast::{LoopType, ValueSet};
 use super::ast_context::AstContext;
+use super::condition;
 use super::graph_utils;
 use super::{AstNode, AstNodeC, CondContext, Condition, NodeSet};
 
@@ -14,29 +15,50 @@ use petgraph::visit::{IntoNodeReferences, Topo, Walker};
 use std::collections::HashMap;
 use std::iter::FromIterator;
 
-pub(super) struct Refiner<'cd, A: AstContext> {
+pub(super) struct Refiner<'a, 'cd, A: AstContext + 'a> {
     pub cctx: CondContext<'cd, A>,
+    pub value_sets: ValueSetFns<'a, A>,
     pub graph: StableDiGraph<RefinementAstNode<'cd, A>, ()>,
 }
 
 pub(super) type RefinementAstNode<'cd, A> = (Condition<'cd, A>, Option<AstNode<'cd, A>>);
 
+/// Looks inside conditions; see `AstContextMut::cond_value_set` and
+/// `AstContextMut::var_value_set`.
+pub(super) struct ValueSetFns<'a, A: AstContext + 'a> {
+    pub cond: &'a Fn(&A::Condition) -> Option<(A::Variable, ValueSet)>,
+    pub var: &'a Fn(&A::Variable) -> ValueSet,
+}
+impl<'a, A: AstContext> Copy for ValueSetFns<'a, A> {}
+impl<'a, A: AstContext> Clone for ValueSetFns<'a, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
 /// Perform all refinements and return the resulting AST.
-pub(super) fn refine<'cd, A: AstContext>(
+pub(super) fn refine<'a, 'cd, A: AstContext + 'a>(
     cctx: CondContext<'cd, A>,
+    value_sets: ValueSetFns<'a, A>,
     graph: StableDiGraph<RefinementAstNode<'cd, A>, ()>,
     entry: NodeIndex,
 ) -> AstNode<'cd, A> {
-    let mut refiner = Refiner::<A> { cctx, graph };
+    let mut refiner = Refiner::<A> {
+        cctx,
+        value_sets,
+        graph,
+    };
     refiner.combine_breaks(entry);
     refiner.refine()
 }
 
-impl<'cd, A: AstContext> Refiner<'cd, A> {
+impl<'a, 'cd, A: AstContext> Refiner<'a, 'cd, A> {
     fn refine(mut self) -> AstNode<'cd, A> {
+        // look for `switch`es first, since grouping by condition would
+        // otherwise pull apart the cases
+        self.try_find_switch();
         self.try_find_if_else_pair();
         self.try_find_if();
-        // TODO: find switch
         self.try_find_if_else_cascade();
 
         // move all nodes into a vec in topological order
@@ -161,6 +183,7 @@ impl<'cd, A: AstContext> Refiner<'cd, A> {
     /// condition.
     fn try_group_by_cond(&mut self, cond: Condition<'cd, A>, not_cond: Condition<'cd, A>) -> bool {
         let cctx = self.cctx;
+        let value_sets = self.value_sets;
 
         if cond.is_true() {
             return false;
@@ -209,6 +232,7 @@ impl<'cd, A: AstContext> Refiner<'cd, A> {
                         Some(
                             Refiner::<A> {
                                 cctx,
+                                value_sets,
                                 graph: else_graph,
                             }
                             .refine(),
@@ -229,6 +253,7 @@ impl<'cd, A: AstContext> Refiner<'cd, A> {
                         Some(
                             Refiner::<A> {
                                 cctx,
+                                value_sets,
                                 graph: then_graph,
                             }
                             .refine(),
@@ -249,6 +274,7 @@ impl<'cd, A: AstContext> Refiner<'cd, A> {
                             Some(
                                 Refiner::<A> {
                                     cctx,
+                                    value_sets,
                                     graph: else_graph,
                                 }
                                 .refine(),
@@ -299,6 +325,107 @@ impl<'cd, A: AstContext> Refiner<'cd, A> {
         true
     }
 
+    /// Repeatedly look for code nodes whose reaching conditions only test the
+    /// value of one variable and group them into a `switch` statement.
+    fn try_find_switch(&mut self) {
+        let cctx = self.cctx;
+        let value_sets = self.value_sets;
+
+        loop {
+            // make a topological order of the candidate code nodes
+            let mut order = Vec::new();
+            for n in Topo::new(&self.graph).iter(&self.graph) {
+                let (cond, ref opt_ast) = self.graph[n];
+                if let Some(ast) = opt_ast {
+                    // a `break` in a case would only break out of the `switch`
+                    if !cond.is_true() && !contains_break(ast) {
+                        if let Some((var, vs)) = cond.fold(ValueSetFolder::<A>(value_sets)) {
+                            order.push((n, var, vs));
+                        }
+                    }
+                }
+            }
+            let order = order;
+
+            if order.len() < 2 {
+                // can't make switch with only one node
+                return;
+            }
+
+            let trans_clos = graph_utils::dag_transitive_closure(&self.graph);
+
+            let mut opt_cands = None;
+            // for each variable, in order of first appearance ...
+            for (i, &(_, ref var, _)) in order.iter().enumerate() {
+                if order[..i].iter().any(|&(_, ref v, _)| v == var) {
+                    // already tried this variable
+                    continue;
+                }
+                // ... add every node that can't run together with the others
+                let mut cands = HashMap::new();
+                let mut covered = ValueSet::empty();
+                for &(n, ref v, ref vs) in &order[i..] {
+                    // since `order` is topological, `n` can't reach any of the
+                    // candidates before it
+                    if v == var
+                        && vs.is_disjoint(&covered)
+                        && cands.keys().all(|c| !trans_clos[c].contains(n))
+                    {
+                        covered = covered.union(vs);
+                        cands.insert(n, vs.clone());
+                    }
+                }
+                if cands.len() >= 2 {
+                    opt_cands = Some((var.clone(), cands, covered));
+                    break;
+                }
+            }
+
+            if let Some((var, mut cands, covered)) = opt_cands {
+                let nodes: NodeSet = cands.keys().collect();
+                graph_utils::contract_nodes_and_map(
+                    &mut self.graph,
+                    &nodes,
+                    |n, (_, ast)| (cands.remove(&n).unwrap(), ast.unwrap()),
+                    |_, _| (),
+                    |mut switch_graph| {
+                        debug_assert!(switch_graph.edge_count() == 0);
+                        debug_assert!(switch_graph.node_count() >= 2);
+
+                        // remove all nodes
+                        let mut cases = Vec::new();
+                        while let Some(n) = switch_graph.node_indices().next() {
+                            cases.push(switch_graph.remove_node(n).unwrap());
+                        }
+
+                        // if the cases cover every value, the largest one can
+                        // become the `default`
+                        let default = if covered == (value_sets.var)(&var) {
+                            let (i, _) = cases
+                                .iter()
+                                .enumerate()
+                                .max_by_key(|&(_, &(ref vs, _))| vs.len())
+                                .unwrap();
+                            cases.swap_remove(i).1
+                        } else {
+                            AstNodeC::default()
+                        };
+
+                        cases.sort_unstable_by_key(|&(ref vs, _)| vs.min());
+
+                        (
+                            cctx.mk_true(),
+                            Some(AstNodeC::Switch(var, cases, Box::new(default))),
+                        )
+                    },
+                );
+            } else {
+                // no more groupings
+                return;
+            }
+        }
+    }
+
     /// Tries to find a set of code where exactly one of them will run.
     fn try_find_if_else_cascade(&mut self) {
         let cctx = self.cctx;
@@ -446,6 +573,69 @@ pub fn simplify_ast_node<'cd, A: AstContext>(
     }
 }
 
+/// Computes the variable a condition tests and the set of values for which
+/// the condition is true, if there is exactly one such variable.
+struct ValueSetFolder<'a, A: AstContext + 'a>(ValueSetFns<'a, A>);
+
+impl<'a, A: AstContext> ValueSetFolder<'a, A> {
+    fn combine<'cd, I, F>(
+        self,
+        operands: I,
+        init: ValueSet,
+        f: F,
+    ) -> Option<(A::Variable, ValueSet)>
+    where
+        I: IntoIterator<Item = condition::Condition<'cd, A::Condition>>,
+        F: Fn(&ValueSet, &ValueSet) -> ValueSet,
+    {
+        let mut ret: Option<(A::Variable, ValueSet)> = None;
+        for opn in operands {
+            let (var, vs) = opn.fold(ValueSetFolder::<A>(self.0))?;
+            ret = Some(match ret {
+                Some((ret_var, ret_vs)) => {
+                    if ret_var != var {
+                        return None;
+                    }
+                    (ret_var, f(&ret_vs, &vs))
+                }
+                None => (var, f(&init, &vs)),
+            });
+        }
+        ret
+    }
+}
+
+impl<'a, A: AstContext> condition::Folder<A::Condition> for ValueSetFolder<'a, A> {
+    type Output = Option<(A::Variable, ValueSet)>;
+
+    fn var(&mut self, negated: bool, cond: &A::Condition) -> Self::Output {
+        let (var, vs) = (self.0.cond)(cond)?;
+        // the complement is limited to the values `var` can hold
+        let range = (self.0.var)(&var);
+        if negated {
+            Some((var, vs.complement().intersection(&range)))
+        } else {
+            Some((var, vs.intersection(&range)))
+        }
+    }
+
+    fn and<'cd, I>(&mut self, operands: I) -> Self::Output
+    where
+        I: IntoIterator<Item = condition::Condition<'cd, A::Condition>>,
+        A::Condition: 'cd,
+    {
+        ValueSetFolder::<A>(self.0).combine(operands, ValueSet::full(), ValueSet::intersection)
+    }
+
+    fn or<'cd, I>(&mut self, operands: I) -> Self::Output
+    where
+        I: IntoIterator<Item = condition::Condition<'cd, A::Condition>>,
+        A::Condition: 'cd,
+    {
+        ValueSetFolder::<A>(self.0).combine(operands, ValueSet::empty(), ValueSet::union)
+    }
+}
+
 struct LoopRefiner<'cd, A: AstContext> {
     cctx: CondContext<'cd, A>,
 }
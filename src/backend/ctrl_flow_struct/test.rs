@@ -1,4 +1,4 @@
-use super::ast::LoopType;
+use super::ast::{LoopType, ValueSet};
 use super::condition;
 use super::*;
 
@@ -45,6 +45,35 @@ impl AstContextMut for StringAst {
         format!("{} == {}", var, val)
     }
 
+    fn cond_value_set(&self, cond: &String) -> Option<(String, ValueSet)> {
+        // conditions look like "n <= 7"
+        let parts: Vec<_> = cond.split_whitespace().collect();
+        if let [var, op, val] = parts.as_slice() {
+            let val: u64 = val.parse().ok()?;
+            let vs = match *op {
+                "==" => ValueSet::single(val),
+                "!=" => ValueSet::single(val).complement(),
+                "<=" => ValueSet::range(0, val),
+                ">" => ValueSet::range(0, val).complement(),
+                _ => return None,
+            };
+            let var = var.to_string();
+            let range = self.var_value_set(&var);
+            Some((var, vs.intersection(&range)))
+        } else {
+            None
+        }
+    }
+
+    fn var_value_set(&self, var: &String) -> ValueSet {
+        // `b` is a byte
+        if var == "b" {
+            ValueSet::range(0, 0xff)
+        } else {
+            ValueSet::full()
+        }
+    }
+
     fn mk_cond_from_bool_var(&mut self, var: &String) -> String {
         format!("{}", var)
     }
@@ -365,14 +394,13 @@ fn ast_nmg_r3() {
 }
 
 #[test]
-#[ignore] // TODO
 fn ast_switchy() {
     /*
      * switch (n) {
      * case 1:
      * case 4:
      * case 7:
-     * case 94:
+     * case 98:
      *   puts("1");
      *   break;
      * case 34:
@@ -432,14 +460,19 @@ fn ast_switchy() {
     println!("{:#?}", ast);
 
     use self::AstNodeC::*;
-    // XXX: need actual value set impl
     assert_eq!(
         Seq(vec![
             Switch(
                 "n".to_owned(),
                 vec![
-                    ((), BasicBlock("n1".to_owned())),
-                    ((), BasicBlock("n2".to_owned())),
+                    (
+                        [1, 4, 7, 98].iter().cloned().collect(),
+                        BasicBlock("n1".to_owned()),
+                    ),
+                    (
+                        [34, 88].iter().cloned().collect(),
+                        BasicBlock("n2".to_owned()),
+                    ),
                 ],
                 Box::new(BasicBlock("n3".to_owned())),
             ),
@@ -449,6 +482,192 @@ fn ast_switchy() {
     );
 }
 
+#[test]
+fn ast_switch_without_default() {
+    /*
+     * if (n == 1) {
+     *   puts("1");
+     * } else if (n == 2 || n == 3) {
+     *   puts("2");
+     * }
+     */
+    let cstore = condition::Storage::new();
+    let cctx = cstore.cctx();
+
+    let v_c1 = cond_s(cctx, "n == 1");
+    let v_c2 = cond_s(cctx, "n == 2");
+    let v_c3 = cond_s(cctx, "n == 3");
+
+    let mut graph = StableDiGraph::new();
+    let c1 = graph.add_node(cnode(v_c1));
+    let c2 = graph.add_node(cnode(v_c2));
+    let c3 = graph.add_node(cnode(v_c3));
+    let n1 = graph.add_node(node("n1"));
+    let n2 = graph.add_node(node("n2"));
+    let exit = graph.add_node(node("return"));
+
+    graph.add_edge(c1, n1, CETrue);
+    graph.add_edge(c1, c2, CEFalse);
+    graph.add_edge(c2, n2, CETrue);
+    graph.add_edge(c2, c3, CEFalse);
+    graph.add_edge(c3, n2, CETrue);
+    graph.add_edge(c3, exit, CEFalse);
+    graph.add_edge(n1, exit, CETrue);
+    graph.add_edge(n2, exit, CETrue);
+
+    let actx = StringAst::default();
+    let cfg = ControlFlowGraph::new(graph, c1, cctx, actx);
+    let ast = cfg.structure_whole().0;
+    println!("{:#?}", ast);
+
+    use self::AstNodeC::*;
+    assert_eq!(
+        Seq(vec![
+            Switch(
+                "n".to_owned(),
+                vec![
+                    (ValueSet::single(1), BasicBlock("n1".to_owned())),
+                    (ValueSet::range(2, 3), BasicBlock("n2".to_owned())),
+                ],
+                Box::new(Seq(Vec::new())),
+            ),
+            BasicBlock("return".to_owned()),
+        ]),
+        ast
+    );
+}
+
+#[test]
+fn ast_switch_narrow_selector() {
+    /*
+     * if (b == 1) {
+     *   puts("1");
+     * } else if (b != 2) {
+     *   puts("not 2");
+     * }
+     */
+    let cstore = condition::Storage::new();
+    let cctx = cstore.cctx();
+
+    let v_c1 = cond_s(cctx, "b == 1");
+    let v_c2 = cond_s(cctx, "b == 2");
+
+    let mut graph = StableDiGraph::new();
+    let c1 = graph.add_node(cnode(v_c1));
+    let c2 = graph.add_node(cnode(v_c2));
+    let n1 = graph.add_node(node("n1"));
+    let n2 = graph.add_node(node("n2"));
+    let exit = graph.add_node(node("return"));
+
+    graph.add_edge(c1, n1, CETrue);
+    graph.add_edge(c1, c2, CEFalse);
+    graph.add_edge(c2, exit, CETrue);
+    graph.add_edge(c2, n2, CEFalse);
+    graph.add_edge(n1, exit, CETrue);
+    graph.add_edge(n2, exit, CETrue);
+
+    let actx = StringAst::default();
+    let cfg = ControlFlowGraph::new(graph, c1, cctx, actx);
+    let ast = cfg.structure_whole().0;
+    println!("{:#?}", ast);
+
+    // negated conditions do not reach beyond the values of a byte
+    use self::AstNodeC::*;
+    assert_eq!(
+        Seq(vec![
+            Switch(
+                "b".to_owned(),
+                vec![
+                    (
+                        ValueSet::single(0).union(&ValueSet::range(3, 0xff)),
+                        BasicBlock("n2".to_owned()),
+                    ),
+                    (ValueSet::single(1), BasicBlock("n1".to_owned())),
+                ],
+                Box::new(Seq(Vec::new())),
+            ),
+            BasicBlock("return".to_owned()),
+        ]),
+        ast
+    );
+}
+
+#[test]
+fn ast_switch_narrow_selector_exhaustive() {
+    /*
+     * if (b <= 1) {
+     *   puts("small");
+     * } else if (b > 1) {
+     *   puts("big");
+     * }
+     */
+    let cstore = condition::Storage::new();
+    let cctx = cstore.cctx();
+
+    let v_c1 = cond_s(cctx, "b <= 1");
+    let v_c2 = cond_s(cctx, "b > 1");
+
+    let mut graph = StableDiGraph::new();
+    let c1 = graph.add_node(cnode(v_c1));
+    let c2 = graph.add_node(cnode(v_c2));
+    let n1 = graph.add_node(node("n1"));
+    let n2 = graph.add_node(node("n2"));
+    let exit = graph.add_node(node("return"));
+
+    graph.add_edge(c1, n1, CETrue);
+    graph.add_edge(c1, c2, CEFalse);
+    graph.add_edge(c2, n2, CETrue);
+    graph.add_edge(c2, exit, CEFalse);
+    graph.add_edge(n1, exit, CETrue);
+    graph.add_edge(n2, exit, CETrue);
+
+    let actx = StringAst::default();
+    let cfg = ControlFlowGraph::new(graph, c1, cctx, actx);
+    let ast = cfg.structure_whole().0;
+    println!("{:#?}", ast);
+
+    // every byte is covered, so the largest case becomes the `default`
+    use self::AstNodeC::*;
+    assert_eq!(
+        Seq(vec![
+            Switch(
+                "b".to_owned(),
+                vec![(ValueSet::range(0, 1), BasicBlock("n1".to_owned()))],
+                Box::new(BasicBlock("n2".to_owned())),
+            ),
+            BasicBlock("return".to_owned()),
+        ]),
+        ast
+    );
+}
+
+#[test]
+fn value_set_ops() {
+    let max = u64::max_value();
+    let a = ValueSet::range(2, 5).union(&ValueSet::single(9));
+    assert_eq!(&[(2, 5), (9, 9)], a.ranges());
+    assert_eq!(5, a.len());
+    assert!(a.contains(4) && !a.contains(6));
+
+    let not_a = a.complement();
+    assert_eq!(&[(0, 1), (6, 8), (10, max)], not_a.ranges());
+    assert!(a.is_disjoint(&not_a));
+    assert!(a.union(&not_a).is_full());
+    assert_eq!(a, not_a.complement());
+
+    // adjacent ranges are merged
+    let b: ValueSet = vec![6, 3, 4, 5].into_iter().collect();
+    assert_eq!(&[(3, 6)], b.ranges());
+    assert_eq!(&[(3, 5)], a.intersection(&b).ranges());
+    assert_eq!(&[(2, 6), (9, 9)], a.union(&b).ranges());
+
+    assert!(ValueSet::range(5, 4).is_empty());
+    assert!(ValueSet::empty().complement().is_full());
+    assert!(ValueSet::full().complement().is_empty());
+    assert_eq!(1u128 << 64, ValueSet::full().len());
+    assert_eq!(Some(max), ValueSet::single(max).min());
+}
+
 #[test]
 fn ast_ifelse_cascade() {
     let cstore = condition::Storage::new();
@@ -646,6 +865,27 @@ fn ast_infinite_loop() {
     );
 }
 
+#[test]
+fn export_constant_conditions() {
+    use backend::lang_c::c_cfg::CCFG;
+
+    let cstore = condition::Storage::new();
+    let cctx = cstore.cctx();
+    let ccfg = CCFG::new("main");
+
+    // An empty conjunction is true and an empty disjunction is false.
+    use self::AstNodeC::*;
+    let ast = Seq(vec![
+        Loop(LoopType::PreChecked(cctx.mk_true()), Box::new(Break)),
+        Loop(LoopType::PostChecked(cctx.mk_false()), Box::new(Break)),
+    ]);
+    let code = export::to_c_ast(&ccfg, ast).unwrap().print();
+    println!("{}", code);
+    assert!(code.contains("while (1)"));
+    assert!(code.contains("while (0)"));
+    assert!(!code.contains("while (0) {"));
+}
+
 #[test]
 fn ast_complex_while_and() {
     /*
@@ -50,6 +50,7 @@ impl Ty {
 /// Defines the basic valid C data types.
 pub enum BTy {
     Int,
+    Short,
     Float,
     Char,
     Double,
@@ -58,6 +59,8 @@ pub enum BTy {
     Ptr(Box<BTy>),
     /// Array of type `BTy` and length.
     Array(Box<BTy>, u64),
+    /// Struct of the given name, see `CAST::declare_struct`.
+    Struct(String),
 }
 
 impl fmt::Display for Ty {
@@ -82,12 +85,14 @@ impl fmt::Display for BTy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let result = match *self {
             BTy::Int => "int".to_owned(),
+            BTy::Short => "short".to_owned(),
             BTy::Float => "float".to_owned(),
             BTy::Char => "char".to_owned(),
             BTy::Double => "double".to_owned(),
             BTy::Void => "void".to_owned(),
             BTy::Ptr(ref bty) => format!("*{}", bty),
             BTy::Array(ref bty, count) => format!("{}[{}]", bty, count - 1),
+            BTy::Struct(ref name) => format!("struct {}", name),
         };
         write!(f, "{}", result)
     }
@@ -107,6 +112,11 @@ pub enum CASTNode {
     Goto(String),
     Label(String),
     Break,
+    Switch,
+    /// `case` labels matching every value in the given inclusive ranges. Longer ranges are
+    /// printed as `case lo ... hi:`, which is a GNU extension to C.
+    Case(Vec<(u64, u64)>),
+    Default,
     ExpressionNode(Expr),
     Var(String),
     // Constant
@@ -140,6 +150,19 @@ pub enum Expr {
     Lt,
     LtEq,
     Eq,
+    // Signed variants of the operators above. Operands are printed with a cast to the signed
+    // integer type of the given width in bits.
+    SDiv(u16),
+    SMod(u16),
+    Sar(u16),
+    SGt(u16),
+    SLt(u16),
+    /// Member of the struct the operand points to, `op->name`.
+    Member(String),
+    /// Member of the struct operand, `op.name`.
+    Field(String),
+    /// Element of the array operand, `op[index]`.
+    Index,
 }
 
 #[derive(Clone, Debug)]
@@ -155,6 +178,9 @@ enum CASTEdge {
 
 const LHS: CASTEdge = CASTEdge::OpOrd(0);
 const RHS: CASTEdge = CASTEdge::OpOrd(1);
+/// Number of values up to which a range of `case` values is listed one label per value,
+/// longer ranges use the GNU `case lo ... hi:` syntax.
+const MAX_LISTED_CASES: u64 = 8;
 
 #[derive(Clone, Debug)]
 pub struct CAST {
@@ -167,6 +193,8 @@ pub struct CAST {
     /// Debug information for given NodeIndex of statement,
     /// assignment, etc, but not of nested expressions.
     comments: HashMap<NodeIndex, String>,
+    /// Struct definitions, printed before the function.
+    structs: Vec<(String, Vec<(Ty, String)>)>,
 }
 
 impl default::Default for CAST {
@@ -176,6 +204,7 @@ impl default::Default for CAST {
             eidx: 0,
             fn_head: NodeIndex::end(),
             comments: HashMap::new(),
+            structs: Vec::new(),
         };
         ast.fn_head = ast
             .ast
@@ -195,6 +224,16 @@ fn format_with_indent(string: &str, depth: usize) -> String {
         + string
 }
 
+// Signed integer type of `width` bits, used to cast the operands of signed operators.
+fn signed_ty(width: u16) -> &'static str {
+    match width {
+        0...8 => "int8_t",
+        9...16 => "int16_t",
+        17...32 => "int32_t",
+        _ => "int64_t",
+    }
+}
+
 //////////////////////////////////////////////////////////////////////////////
 //// Implementation to manipulate the C AST effectively.
 //////////////////////////////////////////////////////////////////////////////
@@ -207,6 +246,7 @@ impl CAST {
             eidx: 0,
             fn_head: NodeIndex::end(),
             comments: HashMap::new(),
+            structs: Vec::new(),
         };
         ast.fn_head = ast
             .ast
@@ -378,6 +418,50 @@ impl CAST {
         if_h
     }
 
+    /// Cases never fall through, so every case body should end with a `break`
+    /// if that is the intended behavior.
+    pub fn new_switch(
+        &mut self,
+        selector: NodeIndex,
+        cases: Vec<(Vec<(u64, u64)>, Vec<NodeIndex>)>,
+        default: Option<Vec<NodeIndex>>,
+    ) -> NodeIndex {
+        let idx = if let Some(e1) = self.ast.find_edge(self.fn_head, selector) {
+            let idx = self.get_statement_ord(e1);
+            self.ast.remove_edge(e1);
+            idx
+        } else {
+            self.next_edge_idx()
+        };
+        let switch_h = self.ast.add_node(CASTNode::Switch);
+        self.ast
+            .add_edge(self.fn_head, switch_h, CASTEdge::StatementOrd(idx));
+        self.ast.add_edge(switch_h, selector, CASTEdge::OpOrd(0));
+
+        let node = self.ast.add_node(CASTNode::Block);
+        self.ast.add_edge(switch_h, node, CASTEdge::OpOrd(1));
+        let labels = cases
+            .into_iter()
+            .map(|(ranges, body)| (CASTNode::Case(ranges), body))
+            .chain(default.map(|body| (CASTNode::Default, body)));
+        for (i, (label, body)) in labels.enumerate() {
+            let label_n = self.ast.add_node(label);
+            self.ast
+                .add_edge(node, label_n, CASTEdge::BlockOrd(i as u64));
+            let body_n = self.ast.add_node(CASTNode::Block);
+            self.ast.add_edge(label_n, body_n, CASTEdge::OpOrd(0));
+            for (j, n) in body.into_iter().enumerate() {
+                let e = self
+                    .ast
+                    .find_edge(self.fn_head, n)
+                    .expect("This cannot be `None`");
+                self.ast.remove_edge(e);
+                self.ast.add_edge(body_n, n, CASTEdge::BlockOrd(j as u64));
+            }
+        }
+        switch_h
+    }
+
     pub fn call_func(&mut self, func_name: &str, args: Vec<Option<NodeIndex>>) -> NodeIndex {
         let args_str = args
             .into_iter()
@@ -460,6 +544,10 @@ impl CAST {
         var_decls
     }
 
+    pub fn declare_struct(&mut self, name: &str, fields: &[(Ty, String)]) {
+        self.structs.push((name.to_owned(), fields.to_vec()));
+    }
+
     pub fn function_args(&mut self, args: &[(Ty, String)]) -> Vec<NodeIndex> {
         let mut arg_nodes = Vec::new();
         for (i, &(ref t, ref named)) in args.iter().enumerate() {
@@ -550,6 +638,44 @@ impl CAST {
                     condition
                 )
             }
+            CASTNode::Switch => {
+                // Get the arguments -> selector, block of case labels.
+                let args = self.get_args_ordered(node);
+                let selector = self.emit_c(&args[0], 0, true);
+                let cases = self.emit_c(&args[1], indent, false);
+                format!(
+                    "{} ({}) {{\n{}\n{}",
+                    format_with_indent("switch", indent),
+                    selector,
+                    cases,
+                    format_with_indent("}", indent)
+                )
+            }
+            CASTNode::Case(ref ranges) => {
+                // Case ranges are a GNU extension, only used for ranges too long to list.
+                let labels = ranges
+                    .iter()
+                    .flat_map(|&(lo, hi)| {
+                        if hi - lo < MAX_LISTED_CASES {
+                            (lo..=hi)
+                                .map(|v| format_with_indent(&format!("case 0x{:x}:", v), indent))
+                                .collect::<Vec<_>>()
+                        } else {
+                            let range = format!("case 0x{:x} ... 0x{:x}:", lo, hi);
+                            vec![format_with_indent(&range, indent)]
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let args = self.get_args_ordered(node);
+                let body = self.emit_c(&args[0], indent + 1, false);
+                format!("{}\n{}", labels, body)
+            }
+            CASTNode::Default => {
+                let args = self.get_args_ordered(node);
+                let body = self.emit_c(&args[0], indent + 1, false);
+                format!("{}\n{}", format_with_indent("default:", indent), body)
+            }
             CASTNode::Goto(ref label) => format_with_indent(&format!("goto {}", label), indent),
             CASTNode::Label(ref label) => format!("{}:", label),
             CASTNode::Break => format_with_indent("break", indent),
@@ -648,6 +774,54 @@ impl CAST {
                         format_with_indent(&op_str[0], indent),
                         &op_str[1]
                     ),
+                    Expr::SDiv(width) => format!(
+                        "{}(({}){} / ({}){})",
+                        format_with_indent("", indent),
+                        signed_ty(width),
+                        &op_str[0],
+                        signed_ty(width),
+                        &op_str[1]
+                    ),
+                    Expr::SMod(width) => format!(
+                        "{}(({}){} % ({}){})",
+                        format_with_indent("", indent),
+                        signed_ty(width),
+                        &op_str[0],
+                        signed_ty(width),
+                        &op_str[1]
+                    ),
+                    Expr::Sar(width) => format!(
+                        "{}(({}){} >> {})",
+                        format_with_indent("", indent),
+                        signed_ty(width),
+                        &op_str[0],
+                        &op_str[1]
+                    ),
+                    Expr::SGt(width) => format!(
+                        "{}(({}){} > ({}){})",
+                        format_with_indent("", indent),
+                        signed_ty(width),
+                        &op_str[0],
+                        signed_ty(width),
+                        &op_str[1]
+                    ),
+                    Expr::SLt(width) => format!(
+                        "{}(({}){} < ({}){})",
+                        format_with_indent("", indent),
+                        signed_ty(width),
+                        &op_str[0],
+                        signed_ty(width),
+                        &op_str[1]
+                    ),
+                    Expr::Member(ref name) => {
+                        format!("{}->{}", format_with_indent(&op_str[0], indent), name)
+                    }
+                    Expr::Field(ref name) => {
+                        format!("{}.{}", format_with_indent(&op_str[0], indent), name)
+                    }
+                    Expr::Index => {
+                        format!("{}[{}]", format_with_indent(&op_str[0], indent), &op_str[1])
+                    }
                 }
             }
             CASTNode::Var(ref ident) => ident.clone(),
@@ -701,6 +875,9 @@ impl CAST {
             | &CASTNode::While
             | &CASTNode::DoWhile
             | &CASTNode::If
+            | &CASTNode::Switch
+            | &CASTNode::Case(_)
+            | &CASTNode::Default
             | &CASTNode::Label(_)
             | &CASTNode::Var(_)
             | &CASTNode::Constant(_, _)
@@ -715,6 +892,20 @@ impl CAST {
         // Take them in order
         // Traverse the subtree and print out accordingly.
         let mut result = String::new();
+        for &(ref name, ref fields) in &self.structs {
+            result.push_str(&format!("struct {} {{\n", name));
+            for &(ref ty, ref field) in fields {
+                let member = match ty.base_type {
+                    BTy::Array(ref elem, len) => {
+                        let elem = Ty::new((**elem).clone(), ty.signed, ty.long);
+                        format!("{} {}[{}];\n", elem, field, len)
+                    }
+                    _ => format!("{} {};\n", ty, field),
+                };
+                result.push_str(&format_with_indent(&member, 1));
+            }
+            result.push_str("};\n\n");
+        }
         if let CASTNode::FunctionHeader(ref named) = self.ast[self.fn_head] {
             let args = self.get_args_ordered(&self.fn_head);
             let mut args_string = String::new();
@@ -793,6 +984,24 @@ mod test {
         println!("{}", c_ast.print());
     }
 
+    #[test]
+    fn c_ast_signed_test() {
+        let mut c_ast = CAST::new("main");
+        let vars = c_ast.declare_vars(
+            Ty::new(BTy::Int, false, 1),
+            &["i".to_owned(), "j".to_owned()],
+            false,
+        );
+        let div = c_ast.expr(Expr::SDiv(64), &vars, true);
+        let _ = c_ast.expr(Expr::Assign, &[vars[0], div], false);
+        let lt = c_ast.expr(Expr::SLt(32), &vars, true);
+        let _ = c_ast.expr(Expr::Assign, &[vars[1], lt], false);
+        let output = c_ast.print();
+        println!("{}", output);
+        assert!(output.contains("i = ((int64_t)i / (int64_t)j)"));
+        assert!(output.contains("j = ((int32_t)i < (int32_t)j)"));
+    }
+
     #[test]
     fn c_ast_call_test() {
         let mut c_ast = CAST::new("main");
@@ -859,4 +1068,59 @@ mod test {
         let _ = c_ast.ret(None);
         println!("{}", c_ast.print());
     }
+
+    #[test]
+    fn c_ast_switch_test() {
+        let mut c_ast = CAST::new("main");
+        let args = c_ast.function_args(&[(Ty::new(BTy::Int, false, 0), "x".to_owned())]);
+        let _ = c_ast.declare_vars(Ty::new(BTy::Int, false, 0), &["i".to_owned()], false);
+        let one = c_ast.call_func("one", Vec::new());
+        let break1 = c_ast.insert_break();
+        let two = c_ast.call_func("two", Vec::new());
+        let break2 = c_ast.insert_break();
+        let other = c_ast.call_func("other", Vec::new());
+        c_ast.new_switch(
+            args[0],
+            vec![
+                (vec![(1, 1), (4, 4)], vec![one, break1]),
+                (vec![(0x10, 0x1f), (0x20, 0x21)], vec![two, break2]),
+            ],
+            Some(vec![other]),
+        );
+        let _ = c_ast.ret(None);
+        let printed = c_ast.print();
+        println!("{}", printed);
+        assert!(printed.contains("switch (x) {"));
+        assert!(printed.contains("case 0x1:\n    case 0x4:\n        one();\n        break;"));
+        assert!(printed.contains("case 0x10 ... 0x1f:\n    case 0x20:\n    case 0x21:\n"));
+        assert!(printed.contains("default:\n        other();"));
+    }
+
+    #[test]
+    fn c_ast_struct_test() {
+        let mut c_ast = CAST::new("main");
+        c_ast.declare_struct(
+            "s0",
+            &[
+                (Ty::new(BTy::Int, true, 0), "field_0x0".to_owned()),
+                (Ty::new(BTy::Int, true, 1), "field_0x8".to_owned()),
+            ],
+        );
+        let args = c_ast.function_args(&[
+            (
+                Ty::new_ptr(BTy::Struct("s0".to_owned()), true, 0),
+                "p".to_owned(),
+            ),
+            (Ty::new_ptr(BTy::Int, true, 0), "a".to_owned()),
+        ]);
+        let vars = c_ast.declare_vars(Ty::new(BTy::Int, true, 0), &["i".to_owned()], false);
+        let field = c_ast.expr(Expr::Member("field_0x8".to_owned()), &[args[0]], true);
+        let elem = c_ast.expr(Expr::Index, &[args[1], vars[0]], true);
+        c_ast.expr(Expr::Assign, &[elem, field], false);
+        let _ = c_ast.ret(None);
+        let printed = c_ast.print();
+        println!("{}", printed);
+        assert!(printed.starts_with("struct s0 {\n    int field_0x0;\n    long int field_0x8;\n};"));
+        assert!(printed.contains("a[i] = p->field_0x8;"));
+    }
 }
@@ -1,5 +1,6 @@
-use super::{ActionEdge, ActionNode, CCFGEdge, CCFGNode, CCFGRef, ValueEdge, CCFG};
+use super::{ActionEdge, ActionNode, CCFGEdge, CCFGNode, CCFGRef, ValueEdge, ValueNode, CCFG};
 use backend::ctrl_flow_struct as flstr;
+use backend::ctrl_flow_struct::ast::ValueSet;
 use backend::ctrl_flow_struct::ast_context::{AstContext, AstContextMut};
 use backend::lang_c::c_ast::{self, CAST};
 
@@ -207,6 +208,55 @@ impl AstContextMut for CCFG {
         self.expr(&[var, val], c_ast::Expr::Eq)
     }
 
+    fn cond_value_set(&self, &cond: &Self::Condition) -> Option<(Self::Variable, ValueSet)> {
+        use self::c_ast::Expr;
+        let expr = match self.g.node_weight(cond) {
+            Some(CCFGNode::Value(ValueNode::Expression(expr))) => expr.clone(),
+            _ => return None,
+        };
+        match (expr, self.operands_from_expr(cond).as_slice()) {
+            (Expr::Not, &[opn]) => self.cond_value_set(&opn).map(|(var, vs)| {
+                let range = self.var_value_set(&var);
+                (var, vs.complement().intersection(&range))
+            }),
+            (Expr::And, &[lhs, rhs]) => {
+                let (var, lvs, rvs) = cond_value_sets(self, lhs, rhs)?;
+                Some((var, lvs.intersection(&rvs)))
+            }
+            (Expr::Or, &[lhs, rhs]) => {
+                let (var, lvs, rvs) = cond_value_sets(self, lhs, rhs)?;
+                Some((var, lvs.union(&rvs)))
+            }
+            (op, &[lhs, rhs]) => {
+                // normalize to `var <op> val`
+                let (var, val, op) = match (const_value(self, lhs), const_value(self, rhs)) {
+                    (None, Some(val)) => (lhs, val, op),
+                    (Some(val), None) => (rhs, val, flip_cmp(op)?),
+                    _ => return None,
+                };
+                let max = max_value(self, var);
+                let vs = match op {
+                    Expr::Eq => ValueSet::single(val),
+                    Expr::Lt => val
+                        .checked_sub(1)
+                        .map_or(ValueSet::empty(), |v| ValueSet::range(0, v)),
+                    Expr::LtEq => ValueSet::range(0, val),
+                    Expr::Gt => val
+                        .checked_add(1)
+                        .map_or(ValueSet::empty(), |v| ValueSet::range(v, max)),
+                    Expr::GtEq => ValueSet::range(val, max),
+                    _ => return None,
+                };
+                Some((var, vs.intersection(&ValueSet::range(0, max))))
+            }
+            _ => None,
+        }
+    }
+
+    fn var_value_set(&self, &var: &Self::Variable) -> ValueSet {
+        ValueSet::range(0, max_value(self, var))
+    }
+
     fn mk_cond_from_bool_var(&mut self, &var: &Self::BoolVariable) -> Self::Condition {
         var
     }
@@ -226,3 +276,50 @@ impl AstContextMut for CCFG {
         vec![self.assign(var, cond, unk)]
     }
 }
+
+/// Returns the value sets of two conditions that test the same variable.
+fn cond_value_sets(
+    ccfg: &CCFG,
+    lhs: CCFGRef,
+    rhs: CCFGRef,
+) -> Option<(CCFGRef, ValueSet, ValueSet)> {
+    let (lvar, lvs) = ccfg.cond_value_set(&lhs)?;
+    let (rvar, rvs) = ccfg.cond_value_set(&rhs)?;
+    if lvar == rvar {
+        Some((lvar, lvs, rvs))
+    } else {
+        None
+    }
+}
+
+/// Returns the largest value `node` can hold, as far as its width is known. Value sets are
+/// clamped to it, so that no `case` is out of the range of the selector.
+fn max_value(ccfg: &CCFG, node: CCFGRef) -> u64 {
+    match ccfg.width_of(node) {
+        Some(w) if w > 0 && w < 64 => (1 << w) - 1,
+        _ => u64::max_value(),
+    }
+}
+
+/// Returns the value of `node` if it is a numeric constant.
+fn const_value(ccfg: &CCFG, node: CCFGRef) -> Option<u64> {
+    let s = ccfg.constant_of(node)?;
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Returns `op'` such that `a op b` is equivalent to `b op' a`.
+fn flip_cmp(op: c_ast::Expr) -> Option<c_ast::Expr> {
+    use self::c_ast::Expr::*;
+    match op {
+        Eq => Some(Eq),
+        Lt => Some(Gt),
+        LtEq => Some(GtEq),
+        Gt => Some(Lt),
+        GtEq => Some(LtEq),
+        _ => None,
+    }
+}
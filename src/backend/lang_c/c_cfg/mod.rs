@@ -10,6 +10,7 @@ use petgraph::dot::Dot;
 use petgraph::graph::{EdgeIndex, EdgeReference, Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -99,6 +100,10 @@ pub struct CCFG {
     label_map: HashMap<CCFGRef, String>,
     /// Debug information retrieved from SSA
     debug_info: HashMap<CCFGRef, String>,
+    /// Recovered structs, by name
+    structs: Vec<(String, Vec<(Ty, String)>)>,
+    /// Width in bits of the values of nodes, where known
+    widths: HashMap<CCFGRef, u16>,
 }
 
 impl CCFGNode {
@@ -134,6 +139,8 @@ impl CCFG {
             exprs: Vec::new(),
             label_map: HashMap::new(),
             debug_info: HashMap::new(),
+            structs: Vec::new(),
+            widths: HashMap::new(),
         }
     }
 
@@ -205,6 +212,43 @@ impl CCFG {
         node
     }
 
+    /// Add ValueNode of expression which accesses the memory `addr` points to,
+    /// e.g. a struct member. It is used in place of `deref(addr)`.
+    pub fn access(&mut self, addr: CCFGRef, operands: &[CCFGRef], op: c_ast::Expr) -> CCFGRef {
+        let node = self.expr(operands, op);
+        let _ = self
+            .g
+            .add_edge(node, addr, CCFGEdge::Value(ValueEdge::DeRef));
+        node
+    }
+
+    /// Add definition of struct with the given fields
+    pub fn declare_struct(&mut self, name: &str, fields: Vec<(Ty, String)>) {
+        self.structs.push((name.to_string(), fields));
+    }
+
+    /// Records that the values of `node` are `width` bits wide. A node holding values of
+    /// several widths is as wide as the widest of them.
+    pub fn set_width(&mut self, node: CCFGRef, width: u16) {
+        let w = self.widths.entry(node).or_insert(width);
+        *w = cmp::max(*w, width);
+    }
+
+    pub fn width_of(&self, node: CCFGRef) -> Option<u16> {
+        self.widths.get(&node).cloned()
+    }
+
+    /// Sets the type of the variable or constant `node`.
+    pub fn set_type(&mut self, node: CCFGRef, ty: Ty) {
+        match self.g.node_weight_mut(node) {
+            Some(&mut CCFGNode::Value(ValueNode::Variable(ref mut ty_opt, _)))
+            | Some(&mut CCFGNode::Value(ValueNode::Constant(ref mut ty_opt, _))) => {
+                *ty_opt = Some(ty)
+            }
+            _ => {}
+        }
+    }
+
     /// Add ActionNode of assignment
     pub fn assign(&mut self, dst: CCFGRef, src: CCFGRef, prev_action: CCFGRef) -> CCFGRef {
         let node = self.g.add_node(CCFGNode::Action(ActionNode::Assignment));
@@ -228,6 +272,12 @@ impl CCFG {
         node
     }
 
+    /// Add ActionNode of basic block without preceding action, which is only
+    /// reached by jumps to it, e.g. a block splitting a control flow edge.
+    pub fn detached_block(&mut self) -> CCFGRef {
+        self.g.add_node(CCFGNode::Action(ActionNode::BasicBlock))
+    }
+
     /// Add ActionNode of function call
     pub fn call_func(
         &mut self,
@@ -832,6 +882,9 @@ impl<'a> CASTConverter<'a> {
             .cloned()
             .expect("This can not be None");
         self.node_map.insert(self.cfg.unknown, unknown_node);
+        for &(ref name, ref fields) in self.cfg.structs.iter() {
+            self.ast.declare_struct(name, fields);
+        }
         for &(is_implicit, con) in self.cfg.consts.iter() {
             if let Some(&CCFGNode::Value(ValueNode::Constant(ref ty_opt, ref value_name))) =
                 self.cfg.g.node_weight(con)
@@ -1278,4 +1331,66 @@ mod test {
         let output = cfg.to_c_ast().print();
         println!("{}", output);
     }
+
+    // struct s0 {
+    //     int field_0x0;
+    //     int field_0x4;
+    // };
+    //
+    // fn main () {
+    //     *struct s0 p;
+    //     unsigned int x;
+    //     p->field_0x4 = x
+    // }
+    #[test]
+    fn c_cfg_struct_test() {
+        let mut cfg = CCFG::new("main");
+        cfg.declare_struct(
+            "s0",
+            vec![
+                (Ty::new(BTy::Int, true, 0), "field_0x0".to_string()),
+                (Ty::new(BTy::Int, true, 0), "field_0x4".to_string()),
+            ],
+        );
+        let p = cfg.var(
+            "p",
+            Some(Ty::new_ptr(BTy::Struct("s0".to_string()), true, 0)),
+        );
+        let x = cfg.var("x", None);
+        let four = cfg.constant("0x4", None);
+        let entry = cfg.entry;
+        let addr = cfg.expr(&[p, four], c_ast::Expr::Add);
+        let field = cfg.access(addr, &[p], c_ast::Expr::Member("field_0x4".to_string()));
+        assert_eq!(cfg.derefed_node(addr), Some(field));
+        let _ = cfg.assign(field, x, entry);
+        CCFGVerifier::verify(&cfg).expect("CCFG verification failed");
+        let output = cfg.to_c_ast().print();
+        println!("{}", output);
+        assert!(output.starts_with("struct s0 {"));
+        assert!(output.contains("p->field_0x4 = x;"));
+    }
+
+    #[test]
+    fn c_cfg_value_set_width_test() {
+        use backend::ctrl_flow_struct::ast::ValueSet;
+        use backend::ctrl_flow_struct::ast_context::AstContextMut;
+
+        let mut cfg = CCFG::new("main");
+        let x = cfg.var("x", None);
+        cfg.set_width(x, 8);
+        let three = cfg.constant("3", None);
+        let eq = cfg.expr(&[x, three], c_ast::Expr::Eq);
+        let ne = cfg.expr(&[eq], c_ast::Expr::Not);
+        let gt = cfg.expr(&[three, x], c_ast::Expr::Lt);
+        // Values of `x` are clamped to 8 bits.
+        let expected = ValueSet::range(0, 2).union(&ValueSet::range(4, 0xff));
+        assert_eq!(cfg.cond_value_set(&ne), Some((x, expected)));
+        assert_eq!(cfg.cond_value_set(&gt), Some((x, ValueSet::range(4, 0xff))));
+        let y = cfg.var("y", None);
+        let gt = cfg.expr(&[y, three], c_ast::Expr::Gt);
+        assert_eq!(
+            cfg.cond_value_set(&gt),
+            Some((y, ValueSet::range(4, u64::max_value())))
+        );
+    }
 }
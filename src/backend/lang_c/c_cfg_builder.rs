@@ -7,9 +7,14 @@
 use super::c_ast;
 use super::c_ast::Ty;
 use super::c_cfg::{CCFGRef, CCFG};
+use super::fold;
+use super::out_of_ssa::{EdgeCopy, OutOfSSA, Source};
+use analysis::struct_recovery::{Aggregates, Layout};
+use analysis::tie::structs::RType;
 use frontend::radeco_containers::RadecoFunction;
 use middle::ir::{MAddress, MOpcode};
-use middle::ssa::cfg_traits::CFG;
+use middle::ssa::cfg_traits::{CASE_EDGE, CFG};
+use middle::ssa::graph_traits::Graph;
 use middle::ssa::ssa_traits::{SSAWalk, SSA};
 use middle::ssa::ssastorage::{NodeData, SSAStorage};
 use middle::ssa::utils;
@@ -31,6 +36,7 @@ pub fn recover_c_cfg(
     builder.datamap = data_graph;
     builder.cfg_from_ssa();
     builder.insert_jumps();
+    builder.record_widths();
     builder.cfg
 }
 
@@ -53,6 +59,8 @@ struct CCFGBuilder<'a> {
     fname_map: &'a HashMap<u64, String>,
     action_map: HashMap<SSARef, CCFGRef>,
     datamap: CCFGDataMap<'a>,
+    // Blocks executing the copies of a control flow edge `(pred, succ)`
+    split_blocks: HashMap<(SSARef, SSARef), CCFGRef>,
 }
 
 impl<'a> CCFGBuilder<'a> {
@@ -66,6 +74,22 @@ impl<'a> CCFGBuilder<'a> {
             fname_map: fname_map,
             action_map: HashMap::new(),
             datamap: CCFGDataMap::new(rfn),
+            split_blocks: HashMap::new(),
+        }
+    }
+
+    // Records the width of the values of the CCFG nodes the SSA values are mapped to.
+    fn record_widths(&mut self) {
+        for (&node, &cfg_node) in &self.datamap.var_map {
+            let width = self
+                .ssa
+                .node_data(node)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width());
+            match width {
+                Some(w) if w > 0 && cfg_node != self.cfg.unknown => self.cfg.set_width(cfg_node, w),
+                _ => {}
+            }
         }
     }
 
@@ -201,28 +225,6 @@ impl<'a> CCFGBuilder<'a> {
                 }
                 ret
             }
-            MOpcode::OpLoad => {
-                let ops = self.ssa.operands_of(node);
-                let dst = self
-                    .datamap
-                    .var_map
-                    .get(&node)
-                    .cloned()
-                    .unwrap_or(self.cfg.unknown);
-                let src = self
-                    .datamap
-                    .var_map
-                    .get(&ops[1])
-                    .map(|&x| self.cfg.derefed_node(x).unwrap_or(x))
-                    .unwrap_or(self.cfg.unknown);
-                let ret = self.assign(dst, src);
-                if is_debug() {
-                    let addr = self.addr_str(node);
-                    self.cfg
-                        .debug_info_at(ret, format!("*({:?}) = {:?} @ {}", dst, src, addr));
-                }
-                ret
-            }
             _ => unreachable!(),
         }
     }
@@ -231,7 +233,6 @@ impl<'a> CCFGBuilder<'a> {
         let op = self.ssa.opcode(node).unwrap_or(MOpcode::OpInvalid);
         match op {
             MOpcode::OpCall | MOpcode::OpStore => true,
-            MOpcode::OpLoad if self.datamap.is_used_by_call_store(node) => true,
             _ => false,
         }
     }
@@ -272,9 +273,37 @@ impl<'a> CCFGBuilder<'a> {
         }
     }
 
+    // Node and label to jump to along the edge from `block` to `succ`. If the edge carries
+    // copies, it is split by a block which executes them before jumping to `succ`.
+    fn jump_target(&mut self, block: SSARef, succ: SSARef) -> (CCFGRef, String) {
+        let succ_node = self
+            .action_map
+            .get(&succ)
+            .cloned()
+            .expect("This should not be None");
+        let label = self.gen_label(succ);
+        if self.datamap.out_of_ssa.copies(block, succ).is_empty() {
+            return (succ_node, label);
+        }
+        let split_label = format!("{}_from_{}", label, self.gen_label(block));
+        if let Some(&split) = self.split_blocks.get(&(block, succ)) {
+            return (split, split_label);
+        }
+        let split = self.cfg.detached_block();
+        let mut last = split;
+        for copy in self.datamap.out_of_ssa.copies(block, succ).to_vec() {
+            let (dst, src) = self.datamap.copy_operands(&copy, &mut self.cfg);
+            last = self.cfg.assign(dst, src, last);
+        }
+        self.cfg.add_goto(succ_node, &label, last);
+        self.split_blocks.insert((block, succ), split);
+        (split, split_label)
+    }
+
     fn handle_if(
         &mut self,
         _prev: SSARef,
+        block: SSARef,
         selector: SSARef,
         true_node: SSARef,
         false_node: SSARef,
@@ -287,26 +316,16 @@ impl<'a> CCFGBuilder<'a> {
             .expect("This should not be `None`");
         // Add goto statement as `if then` node
         let goto_then = {
-            let dst_node = self
-                .action_map
-                .get(&true_node)
-                .cloned()
-                .expect("This should not be None");
+            let (dst_node, label) = self.jump_target(block, true_node);
             // Edge from `unknown` will be removed later.
             let unknown = self.cfg.unknown;
-            let label = self.gen_label(true_node);
             self.cfg.add_goto(dst_node, &label, unknown)
         };
         // Add goto statement as `if else` node
         let goto_else = {
-            let dst_node = self
-                .action_map
-                .get(&false_node)
-                .cloned()
-                .expect("This should not be None");
+            let (dst_node, label) = self.jump_target(block, false_node);
             // Edge from `unknown` will be removed later.
             let unknown = self.cfg.unknown;
-            let label = self.gen_label(false_node);
             self.cfg.add_goto(dst_node, &label, unknown)
         };
         // Add condition node to if statement
@@ -326,6 +345,53 @@ impl<'a> CCFGBuilder<'a> {
         }
     }
 
+    // A switch is emitted as a chain of `if (sel == v0 | sel == v1 ...) goto case;`
+    fn handle_switch(
+        &mut self,
+        cur_block: SSARef,
+        block: SSARef,
+        selector: SSARef,
+        cases: Vec<(SSARef, Vec<u64>)>,
+    ) {
+        radeco_trace!("CCFGBuilder::handle_switch");
+        let mut prev = self
+            .action_map
+            .get(&cur_block)
+            .and_then(|&n| self.cfg.preds_of(n).first().cloned())
+            .expect("This should not be `None`");
+        let sel = self
+            .datamap
+            .var_map
+            .get(&selector)
+            .cloned()
+            .unwrap_or(self.cfg.unknown);
+        for (case_node, values) in cases {
+            let goto_case = {
+                let (dst_node, label) = self.jump_target(block, case_node);
+                // Edge from `unknown` will be removed later.
+                let unknown = self.cfg.unknown;
+                self.cfg.add_goto(dst_node, &label, unknown)
+            };
+            let mut cond = None;
+            for v in values {
+                let c = self.cfg.constant(&format!("0x{:x}", v), None);
+                let eq = self.cfg.expr(&[sel, c], c_ast::Expr::Eq);
+                cond = Some(match cond {
+                    Some(lhs) => self.cfg.expr(&[lhs, eq], c_ast::Expr::Or),
+                    None => eq,
+                });
+            }
+            let cond = cond.unwrap_or(self.cfg.unknown);
+            let if_node = self.cfg.insert_conditional(cond, goto_case, None, prev);
+            if is_debug() {
+                let addr = self.addr_str(cur_block);
+                self.cfg
+                    .debug_info_at(goto_case, format!("CASE JMP {:?} @ {}", if_node, addr));
+            }
+            prev = if_node;
+        }
+    }
+
     fn handle_return(&mut self, block: SSARef) {
         let prev = self
             .action_map
@@ -337,7 +403,31 @@ impl<'a> CCFGBuilder<'a> {
     }
 
     fn insert_jump(&mut self, cur_block: SSARef, prev_block: SSARef) {
-        if let Some(succ) = self.ssa.unconditional_block(prev_block) {
+        let mut cases = self
+            .ssa
+            .outgoing_edges(prev_block)
+            .into_iter()
+            .filter(|&(_, i)| i >= CASE_EDGE)
+            .collect::<Vec<_>>();
+        if !cases.is_empty() {
+            cases.sort_by_key(|&(_, i)| i);
+            let cases = cases
+                .into_iter()
+                .map(|(e, _)| {
+                    let values = self.ssa.case_values(e).unwrap_or_default();
+                    let target = self.ssa.edge_info(e).expect("Less-endpoints edge").target;
+                    (target, values)
+                })
+                .collect();
+            if let Some(selector) = self.ssa.selector_in(prev_block) {
+                self.handle_switch(cur_block, prev_block, selector, cases);
+            } else {
+                radeco_warn!(
+                    "block with case successors has no selector {:?}",
+                    prev_block
+                );
+            }
+        } else if let Some(succ) = self.ssa.unconditional_block(prev_block) {
             if let Some(_) = self.ssa.selector_in(prev_block) {
                 // TODO
                 radeco_trace!("CCFGBuilder::insert_jump INDIRET JMP");
@@ -350,6 +440,7 @@ impl<'a> CCFGBuilder<'a> {
             if let Some(selector) = self.ssa.selector_in(prev_block) {
                 self.handle_if(
                     cur_block,
+                    prev_block,
                     selector,
                     blk_cond_info.true_side,
                     blk_cond_info.false_side,
@@ -388,16 +479,46 @@ impl<'a> CCFGBuilder<'a> {
         }
     }
 
+    // Emits the copies of the edge from `block` to its successor, if it has only one. Copies
+    // of other edges are emitted by `jump_target`.
+    fn edge_copies(&mut self, block: SSARef) {
+        let succs = self.ssa.succs_of(block);
+        if succs.len() != 1 {
+            return;
+        }
+        for copy in self.datamap.out_of_ssa.copies(block, succs[0]).to_vec() {
+            let (dst, src) = self.datamap.copy_operands(&copy, &mut self.cfg);
+            self.assign(dst, src);
+        }
+    }
+
     fn cfg_from_ssa(&mut self) {
+        let mut last_block = None;
         for node in self.ssa.inorder_walk() {
+            let def = self.datamap.defs.get(&node).cloned();
             if self.is_recover_action(node) {
                 let n = self.recover_action(node);
                 self.action_map.insert(node, n);
+            } else if let Some(src) = def {
+                let dst = self.datamap.var_map[&node];
+                let n = self.assign(dst, src);
+                if is_debug() {
+                    let addr = self.addr_str(node);
+                    self.cfg
+                        .debug_info_at(n, format!("{:?} = {:?} @ {}", dst, src, addr));
+                }
             } else if self.ssa.is_action(node) {
+                if let Some(block) = last_block {
+                    self.edge_copies(block);
+                }
                 let n = self.basic_block();
                 self.action_map.insert(node, n);
+                last_block = Some(node);
             };
         }
+        if let Some(block) = last_block {
+            self.edge_copies(block);
+        }
     }
 }
 
@@ -412,10 +533,27 @@ struct CCFGDataMap<'a> {
     pub const_nodes: HashSet<SSARef>,
     seen: HashSet<SSARef>,
     regvar_version: u32,
+    // Structs and arrays accessed by the function
+    aggregates: Aggregates,
+    // a map from the base pointer of structs to the name of the struct
+    struct_names: HashMap<SSARef, String>,
+    // Variables of the values and copies replacing phis
+    out_of_ssa: OutOfSSA,
+    // a map from the variables of out_of_ssa to CCFG's variables
+    variables: HashMap<usize, CCFGRef>,
+    // a map from node of SSAStorage to the value assigned to its variable
+    defs: HashMap<SSARef, CCFGRef>,
 }
 
 impl<'a> CCFGDataMap<'a> {
     fn new(rfn: &'a RadecoFunction) -> CCFGDataMap<'a> {
+        let aggregates = Aggregates::recover(rfn.ssa());
+        let out_of_ssa = {
+            let needs_var = |node| Self::needs_var(rfn, &aggregates, node);
+            // Single-use values are inlined instead, so that nested expressions are printed.
+            let folded = fold::folded_values(rfn.ssa(), &needs_var);
+            OutOfSSA::new(rfn.ssa(), |node| needs_var(node) && !folded.contains(&node))
+        };
         CCFGDataMap {
             ssa: rfn.ssa(),
             rfn: rfn,
@@ -424,6 +562,11 @@ impl<'a> CCFGDataMap<'a> {
             const_nodes: HashSet::new(),
             seen: HashSet::new(),
             regvar_version: 0,
+            aggregates: aggregates,
+            struct_names: HashMap::new(),
+            out_of_ssa: out_of_ssa,
+            variables: HashMap::new(),
+            defs: HashMap::new(),
         }
     }
 
@@ -435,9 +578,10 @@ impl<'a> CCFGDataMap<'a> {
         let mut s = Self::new(rfn);
         s.prepare_consts(cfg, strings);
         s.prepare_regs(cfg);
+        s.prepare_structs(cfg);
         for node in s.ssa.inorder_walk() {
             if s.ssa.is_phi(node) {
-                s.handle_phi(node);
+                s.handle_phi(node, cfg);
             } else if s.ssa.is_expr(node) {
                 s.update_values(node, cfg);
             }
@@ -449,7 +593,8 @@ impl<'a> CCFGDataMap<'a> {
         let name = format!("v{}", self.regvar_version);
         self.regvar_version += 1;
         if self.var_map.get(&node).cloned().is_none() {
-            let n = cfg.constant(&name, None);
+            let type_info = self.inferred_type(node);
+            let n = cfg.constant(&name, type_info);
             self.var_map.insert(node, n);
             Some(n)
         } else {
@@ -483,16 +628,166 @@ impl<'a> CCFGDataMap<'a> {
         cfg.deref(n)
     }
 
-    fn handle_phi(&mut self, node: SSARef) {
+    // Accesses the memory `node` points to, as a member or an element if it points into a
+    // recovered struct or array.
+    fn access(&mut self, node: SSARef, cfg: &mut CCFG) -> CCFGRef {
+        let access = match self.aggregates.access(node) {
+            Some(&access) => access,
+            None => return self.deref(node, cfg),
+        };
+        radeco_trace!("Access {:?}", access);
+        let addr = self.var_map.get(&node).cloned().unwrap_or(cfg.unknown);
+        let base = match self.var_map.get(&access.base).cloned() {
+            Some(base) => {
+                self.set_base_type(access.base, base, cfg);
+                base
+            }
+            None => cfg.unknown,
+        };
+        let stride = self.aggregates.layout(access.base).and_then(|l| l.stride);
+        let stride = match stride {
+            Some(stride) => stride,
+            None => {
+                let field = Self::field_name(access.offset);
+                return cfg.access(addr, &[base], c_ast::Expr::Member(field));
+            }
+        };
+        // Constant offsets of whole elements are added to the index.
+        let mut index = access
+            .index
+            .map(|(i, _)| self.var_map.get(&i).cloned().unwrap_or(cfg.unknown));
+        let elem_offset = access.offset / stride;
+        if elem_offset != 0 || index.is_none() {
+            let c = cfg.constant(&format!("0x{:x}", elem_offset), None);
+            index = Some(match index {
+                Some(i) => cfg.expr(&[i, c], c_ast::Expr::Add),
+                None => c,
+            });
+        }
+        let index = index.expect("Index of an array access exists");
+        if self.struct_names.contains_key(&access.base) {
+            let elem = cfg.expr(&[base, index], c_ast::Expr::Index);
+            let field = Self::field_name(access.offset % stride);
+            cfg.access(addr, &[elem], c_ast::Expr::Field(field))
+        } else {
+            cfg.access(addr, &[base, index], c_ast::Expr::Index)
+        }
+    }
+
+    // Types the variable of a base pointer as a pointer to its struct or array elements.
+    fn set_base_type(&self, base: SSARef, cfg_node: CCFGRef, cfg: &mut CCFG) {
+        let ty = match self.struct_names.get(&base) {
+            Some(name) => Some(Ty::new_ptr(c_ast::BTy::Struct(name.clone()), true, 0)),
+            None => self
+                .aggregates
+                .layout(base)
+                .and_then(|layout| layout.fields.get(&0))
+                .and_then(|&width| Self::parts_of_width(width))
+                .map(|(base, signed, long)| Ty::new_ptr(base, signed, long)),
+        };
+        if let Some(ty) = ty {
+            cfg.set_type(cfg_node, ty);
+        }
+    }
+
+    fn field_name(offset: u64) -> String {
+        format!("field_0x{:x}", offset)
+    }
+
+    fn handle_phi(&mut self, node: SSARef, cfg: &mut CCFG) {
         debug_assert!(self.ssa.is_phi(node));
         radeco_trace!("CCFGBuilder::handle_phi {:?}", node);
-        let ops = self.ssa.operands_of(node);
-        // Take first available/mappable node of CCFG's node from phi node
-        if let Some(&head) = ops.into_iter().filter_map(|n| self.var_map.get(&n)).next() {
-            self.var_map.insert(node, head);
+        // Operands are copied into the variable of the phi on the incoming edges.
+        if let Some(var) = self.out_of_ssa.var_of(node) {
+            let cfg_node = self.variable(var, cfg);
+            self.var_map.insert(node, cfg_node);
+        }
+    }
+
+    // Whether the value of `node` is assigned to a variable rather than inlined into its uses.
+    fn needs_var(rfn: &RadecoFunction, aggregates: &Aggregates, node: SSARef) -> bool {
+        let ssa = rfn.ssa();
+        let width = ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .unwrap_or(0);
+        // Values of the final register state are not emitted.
+        let exit = ssa.exit_node();
+        let is_used = ssa.is_selector(node)
+            || ssa
+                .uses_of(node)
+                .into_iter()
+                .any(|u| ssa.opcode(u).is_some() || (ssa.is_phi(u) && ssa.block_for(u) != exit));
+        if width == 0 || !is_used || rfn.local_at(node, true).is_some() {
+            return false;
+        }
+        if ssa.is_phi(node) {
+            return ssa.block_for(node) != exit;
+        }
+        if ssa.is_comment(node) {
+            // The return value of a call is assigned by the call.
+            let call = ssa.operands_of(node).first().cloned();
+            return match (call, ret_value_string(rfn)) {
+                (Some(call), Some(ret_reg)) => {
+                    utils::call_rets(call, ssa)
+                        .into_iter()
+                        .any(|(idx, (n, _))| {
+                            n == node && ssa.regfile.get_name(idx) == Some(ret_reg.as_str())
+                        })
+                }
+                _ => false,
+            };
+        }
+        match ssa.opcode(node) {
+            Some(MOpcode::OpLoad) => rfn.local_at(ssa.operands_of(node)[1], true).is_none(),
+            Some(ref op) if Self::op_to_expr(op, Self::operand_width(ssa, node)).is_some() => {
+                // Addresses of struct members and array elements are rebuilt from their base
+                // and index where they are accessed.
+                let is_address = |u: SSARef| match ssa.opcode(u) {
+                    Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => ssa.operands_of(u)[1] == node,
+                    _ => false,
+                };
+                aggregates.access(node).is_none() || !ssa.uses_of(node).into_iter().all(is_address)
+            }
+            _ => false,
+        }
+    }
+
+    fn variable(&mut self, var: usize, cfg: &mut CCFG) -> CCFGRef {
+        if let Some(&cfg_node) = self.variables.get(&var) {
+            return cfg_node;
+        }
+        let name = format!("v{}", self.regvar_version);
+        self.regvar_version += 1;
+        let type_info = self.inferred_type(self.out_of_ssa.representative(var));
+        let cfg_node = cfg.var(&name, type_info);
+        self.variables.insert(var, cfg_node);
+        cfg_node
+    }
+
+    // Assigns the value of `node` to its variable, which its uses refer to instead.
+    fn materialize(&mut self, node: SSARef, cfg: &mut CCFG) {
+        if let Some(var) = self.out_of_ssa.var_of(node) {
+            if let Some(&value) = self.var_map.get(&node) {
+                self.defs.insert(node, value);
+            }
+            let cfg_node = self.variable(var, cfg);
+            self.var_map.insert(node, cfg_node);
         }
     }
 
+    // Destination and source of `copy` in CCFG
+    fn copy_operands(&mut self, copy: &EdgeCopy, cfg: &mut CCFG) -> (CCFGRef, CCFGRef) {
+        let dst = self.variable(copy.dst, cfg);
+        let src = match copy.src {
+            Source::Value(node) => self.var_map.get(&node).cloned().unwrap_or(cfg.unknown),
+            Source::Var(var) => self.variable(var, cfg),
+            Source::Unknown => cfg.unknown,
+        };
+        (dst, src)
+    }
+
     fn type_from_str(type_str: &str) -> Option<Ty> {
         // TODO More types
         match type_str {
@@ -501,10 +796,50 @@ impl<'a> CCFGDataMap<'a> {
         }
     }
 
-    fn op_to_expr(op: &MOpcode) -> Option<c_ast::Expr> {
+    // Type inferred for `node` by `analysis::tie`, if any.
+    fn inferred_type(&self, node: SSARef) -> Option<Ty> {
+        self.rfn.inferred_type(node).and_then(Self::type_from_rtype)
+    }
+
+    fn type_from_rtype(rtype: &RType) -> Option<Ty> {
+        Self::parts_of_rtype(rtype).map(|(base, signed, long)| Ty::new(base, signed, long))
+    }
+
+    // Base type, signedness and `long` qualifiers of `rtype` on LP64.
+    fn parts_of_rtype(rtype: &RType) -> Option<(c_ast::BTy, bool, u8)> {
+        match *rtype {
+            RType::Int8 | RType::Num8 => Some((c_ast::BTy::Char, true, 0)),
+            RType::UInt8 => Some((c_ast::BTy::Char, false, 0)),
+            RType::Int16 | RType::Num16 => Some((c_ast::BTy::Short, true, 0)),
+            RType::UInt16 => Some((c_ast::BTy::Short, false, 0)),
+            RType::Int32 | RType::Num32 => Some((c_ast::BTy::Int, true, 0)),
+            RType::UInt32 => Some((c_ast::BTy::Int, false, 0)),
+            RType::Int64 | RType::Num64 => Some((c_ast::BTy::Int, true, 1)),
+            RType::UInt64 => Some((c_ast::BTy::Int, false, 1)),
+            RType::Ptr(ref inner) => {
+                let (base, signed, long) =
+                    Self::parts_of_rtype(inner).unwrap_or((c_ast::BTy::Void, false, 0));
+                Some((c_ast::BTy::Ptr(Box::new(base)), signed, long))
+            }
+            _ => None,
+        }
+    }
+
+    // Width in bits of the first operand of `node`, which its signed operator is performed at.
+    fn operand_width(ssa: &SSAStorage, node: SSARef) -> u16 {
+        ssa.operands_of(node)
+            .first()
+            .and_then(|&op| ssa.node_data(op).ok())
+            .and_then(|nd| nd.vt.width().get_width())
+            .unwrap_or(64)
+    }
+
+    // `width` is the width of the operands, see `operand_width`.
+    fn op_to_expr(op: &MOpcode, width: u16) -> Option<c_ast::Expr> {
         match op {
             MOpcode::OpAdd => Some(c_ast::Expr::Add),
             MOpcode::OpAnd => Some(c_ast::Expr::And),
+            MOpcode::OpAsr => Some(c_ast::Expr::Sar(width)),
             MOpcode::OpDiv => Some(c_ast::Expr::Div),
             MOpcode::OpEq => Some(c_ast::Expr::Eq),
             MOpcode::OpGt => Some(c_ast::Expr::Gt),
@@ -518,6 +853,10 @@ impl<'a> CCFGDataMap<'a> {
             MOpcode::OpOr => Some(c_ast::Expr::Or),
             MOpcode::OpRol => unimplemented!(),
             MOpcode::OpRor => unimplemented!(),
+            MOpcode::OpSDiv => Some(c_ast::Expr::SDiv(width)),
+            MOpcode::OpSGt => Some(c_ast::Expr::SGt(width)),
+            MOpcode::OpSLt => Some(c_ast::Expr::SLt(width)),
+            MOpcode::OpSMod => Some(c_ast::Expr::SMod(width)),
             MOpcode::OpSub => Some(c_ast::Expr::Sub),
             MOpcode::OpXor => Some(c_ast::Expr::Xor),
             // TODO Add `Narrow` info
@@ -533,7 +872,8 @@ impl<'a> CCFGDataMap<'a> {
     fn def_of(&self, node: SSARef) -> (Option<c_ast::Expr>, Vec<SSARef>) {
         let op = self.ssa.opcode(node).unwrap_or(MOpcode::OpInvalid);
         let operands = self.ssa.operands_of(node);
-        match (op.clone(), Self::op_to_expr(&op)) {
+        let width = Self::operand_width(self.ssa, node);
+        match (op.clone(), Self::op_to_expr(&op, width)) {
             (MOpcode::OpStore, None) => (None, operands),
             (MOpcode::OpLoad, None) => (None, operands),
             (MOpcode::OpCall, None) => (None, operands),
@@ -542,19 +882,6 @@ impl<'a> CCFGDataMap<'a> {
         }
     }
 
-    fn is_used_by_call_store(&self, node: SSARef) -> bool {
-        let opcodes = &[MOpcode::OpCall, MOpcode::OpStore];
-        self.ssa
-            .uses_of(node)
-            .iter()
-            .filter(|&n| {
-                let op = self.ssa.opcode(*n).unwrap_or(MOpcode::OpInvalid);
-                opcodes.contains(&op)
-            })
-            .next()
-            .is_some()
-    }
-
     fn update_values(&mut self, ret_node: SSARef, cfg: &mut CCFG) {
         debug_assert!(self.ssa.is_expr(ret_node));
         radeco_trace!("CCFGBuilder::update_values {:?}", ret_node);
@@ -564,8 +891,8 @@ impl<'a> CCFGDataMap<'a> {
         self.seen.insert(ret_node);
         // Checking whether `ret_node` is a local variable.
         if let Some(bindings) = self.rfn.local_at(ret_node, true) {
-            // TODO add type
-            let type_info = Self::type_from_str(&bindings[0].type_str);
+            let type_info =
+                Self::type_from_str(&bindings[0].type_str).or_else(|| self.inferred_type(ret_node));
             let cfg_node = cfg.var(bindings[0].name(), type_info);
             self.var_map.insert(ret_node, cfg_node);
             return;
@@ -585,7 +912,7 @@ impl<'a> CCFGDataMap<'a> {
                     debug_assert!(ops.len() == 3);
                     // Variables do not need Deref
                     if self.rfn.local_at(ops[1], true).is_none() {
-                        self.deref(ops[1], cfg);
+                        self.access(ops[1], cfg);
                     }
                 }
                 MOpcode::OpLoad => {
@@ -593,10 +920,8 @@ impl<'a> CCFGDataMap<'a> {
                     if self.rfn.local_at(ops[1], true).is_some() {
                         let cfg_node = *self.var_map.get(&ops[1]).expect("This can not be `None`");
                         self.var_map.insert(ret_node, cfg_node);
-                    } else if self.is_used_by_call_store(ret_node) {
-                        self.add_regvar(ret_node, cfg);
                     } else {
-                        let derefed = self.deref(ops[1], cfg);
+                        let derefed = self.access(ops[1], cfg);
                         self.var_map.insert(ret_node, derefed);
                     }
                 }
@@ -609,6 +934,7 @@ impl<'a> CCFGDataMap<'a> {
                 _ => unreachable!(),
             }
         }
+        self.materialize(ret_node, cfg);
     }
 
     fn update_data_graph_by_call(&mut self, call_node: SSARef, cfg: &mut CCFG) {
@@ -622,8 +948,13 @@ impl<'a> CCFGDataMap<'a> {
         for (idx, (node, _)) in reg_map.into_iter() {
             let name = self.ssa.regfile.get_name(idx).unwrap_or("mem").to_string();
             if name == ret_reg_name {
-                // TODO add type
-                let cfg_node = cfg.var("tmp", None);
+                let cfg_node = match self.out_of_ssa.var_of(node) {
+                    Some(var) => self.variable(var, cfg),
+                    None => {
+                        let type_info = self.inferred_type(node);
+                        cfg.var("tmp", type_info)
+                    }
+                };
                 self.var_map.insert(node, cfg_node);
             } else {
                 self.add_regvar(node, cfg);
@@ -648,6 +979,78 @@ impl<'a> CCFGDataMap<'a> {
         }
     }
 
+    // Declares a struct for every base pointer of a recovered struct or array of structs.
+    fn prepare_structs(&mut self, cfg: &mut CCFG) {
+        let mut bases = self
+            .aggregates
+            .layouts()
+            .filter(|&(_, layout)| layout.is_struct())
+            .map(|(&base, _)| base)
+            .collect::<Vec<_>>();
+        // Name structs in order of their base, not of the HashMap.
+        bases.sort();
+        for base in bases {
+            let name = format!("s{}", self.struct_names.len());
+            let fields = self
+                .aggregates
+                .layout(base)
+                .map_or(Vec::new(), Self::struct_members);
+            cfg.declare_struct(&name, fields);
+            self.struct_names.insert(base, name);
+        }
+    }
+
+    // Members of a struct laid out as `layout`. Gaps between the fields and up to the stride
+    // are padded, so that the struct is as large as an element of the array.
+    fn struct_members(layout: &Layout) -> Vec<(Ty, String)> {
+        let mut members = Vec::new();
+        let mut end = 0;
+        for (&offset, &width) in &layout.fields {
+            if offset > end {
+                members.push(Self::padding(end, offset - end));
+            }
+            let size = (u64::from(width) + 7) / 8;
+            // C aligns members to their size and the size of a struct to its members. Members
+            // that would be moved or make the struct larger are declared as bytes.
+            let aligned = offset % size == 0 && layout.stride.map_or(true, |s| s % size == 0);
+            let ty = match Self::type_of_width(width) {
+                Some(ty) if aligned => ty,
+                _ => Ty::new_array(c_ast::BTy::Char, false, 0, size),
+            };
+            members.push((ty, Self::field_name(offset)));
+            end = offset + size;
+        }
+        if let Some(stride) = layout.stride {
+            if stride > end {
+                members.push(Self::padding(end, stride - end));
+            }
+        }
+        members
+    }
+
+    fn padding(offset: u64, size: u64) -> (Ty, String) {
+        (
+            Ty::new_array(c_ast::BTy::Char, false, 0, size),
+            format!("pad_0x{:x}", offset),
+        )
+    }
+
+    fn type_of_width(width: u16) -> Option<Ty> {
+        Self::parts_of_width(width).map(|(base, signed, long)| Ty::new(base, signed, long))
+    }
+
+    // Base type, signedness and `long` qualifiers of the unsigned type of exactly `width` bits
+    // on LP64.
+    fn parts_of_width(width: u16) -> Option<(c_ast::BTy, bool, u8)> {
+        match width {
+            8 => Some((c_ast::BTy::Char, false, 0)),
+            16 => Some((c_ast::BTy::Short, false, 0)),
+            32 => Some((c_ast::BTy::Int, false, 0)),
+            64 => Some((c_ast::BTy::Int, false, 1)),
+            _ => None,
+        }
+    }
+
     fn prepare_regs(&mut self, cfg: &mut CCFG) {
         for walk_node in self.ssa.inorder_walk() {
             let reg_state = self.ssa.registers_in(walk_node);
@@ -941,8 +1344,9 @@ impl CCFGDataMapVerifier {
 #[cfg(test)]
 mod test {
     use backend::lang_c::c_cfg;
+    use backend::lang_c::c_cfg::ctrl_flow_struct;
     use backend::lang_c::c_cfg_builder::{
-        CCFGBuilder, CCFGBuilderVerifier, CCFGDataMap, CCFGDataMapVerifier,
+        recover_c_cfg, CCFGBuilder, CCFGBuilderVerifier, CCFGDataMap, CCFGDataMapVerifier,
     };
     use frontend::radeco_containers::RadecoFunction;
     use frontend::radeco_source::SourceErr;
@@ -983,6 +1387,151 @@ mod test {
         rfn
     }
 
+    fn load_il(il: &str) -> RadecoFunction {
+        let regfile = Arc::new(SubRegisterFile::new(
+            &register_profile().expect("Unable to load register profile"),
+        ));
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ir_reader::parse_il(il, regfile);
+        rfn
+    }
+
+    // Loads the bases of an array of structs and of an array of shorts. Reads two fields of
+    // the structs, 0x18 bytes large, and stores the second into the shorts.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ARRAYS_TXT: &str = "\
+define-fun sym.arrays(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*) = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64(*) = $rdx;
+        %4: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x10):
+        [@0x000100.0001] %5: $Unknown64(*) = Load(%4, %1);
+        [@0x000100.0002] %6: $Unknown64 = %2 * #x18;
+        [@0x000100.0003] %7: $Unknown64(*) = %5 + %6;
+        [@0x000100.0004] %8: $Unknown32 = Load(%4, %7);
+        [@0x000100.0005] %9: $Unknown64(*) = %5 + #x20;
+        [@0x000100.0006] %10: $Unknown16 = Load(%4, %9);
+        [@0x000100.0007] %11: $Unknown64(*) = Load(%4, %3);
+        [@0x000100.0008] %12: $Unknown64 = %2 * #x2;
+        [@0x000100.0009] %13: $Unknown64(*) = %11 + %12;
+        [@0x000100.000a] %14: $Unknown0 = Store(%4, %13, %10);
+        [@0x000100.000b] %15: $Unknown64(*) = %11 + #x2;
+        [@0x000100.000c] %16: $Unknown0 = Store(%14, %15, %10);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %8;
+        $mem = %16;
+}
+";
+
+    #[test]
+    fn c_cfg_aggregates_test() {
+        let rfn = load_il(ARRAYS_TXT);
+        let ccfg = recover_c_cfg(&rfn, &HashMap::new(), &HashMap::new());
+        let output = ctrl_flow_struct::structure_and_convert(ccfg)
+            .expect("Structuring failed")
+            .print();
+        println!("{}", output);
+        // Padded up to the stride, with the 16-bit field declared as a short.
+        assert!(output.starts_with(
+            "struct s0 {\n    unsigned int field_0x0;\n    unsigned char pad_0x4[4];\n    \
+             unsigned short field_0x8;\n    unsigned char pad_0xa[14];\n};"
+        ));
+        // The loaded bases are typed after what they point to.
+        assert!(output.contains("*struct s0 v"));
+        assert!(output.contains("unsigned *short v"));
+        assert!(output.contains("[0x1].field_0x8;"));
+        assert!(output.contains("[rsi] = "));
+    }
+
+    // Reads an int from an array whose elements are 8 bytes large.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STRIDED_TXT: &str = "\
+define-fun sym.strided(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*) = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x4):
+        [@0x000100.0001] %4: $Unknown64(*) = Load(%3, %1);
+        [@0x000100.0002] %5: $Unknown64 = %2 * #x8;
+        [@0x000100.0003] %6: $Unknown64(*) = %4 + %5;
+        [@0x000100.0004] %7: $Unknown32 = Load(%3, %6);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn c_cfg_strided_array_test() {
+        let rfn = load_il(STRIDED_TXT);
+        let ccfg = recover_c_cfg(&rfn, &HashMap::new(), &HashMap::new());
+        let output = ctrl_flow_struct::structure_and_convert(ccfg)
+            .expect("Structuring failed")
+            .print();
+        println!("{}", output);
+        // Indexing an `unsigned int *` would scale by 4, so the elements are padded structs.
+        assert!(output.starts_with(
+            "struct s0 {\n    unsigned int field_0x0;\n    unsigned char pad_0x4[4];\n};"
+        ));
+        assert!(output.contains("*struct s0 v"));
+        assert!(output.contains("[rsi].field_0x0;"));
+    }
+
+    // Sums rdi with itself until it exceeds 0x1000, adding one more on the first iteration
+    // only, and stores the sum.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOOP_TXT: &str = "\
+define-fun sym.first(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x1):
+        JMP 0x000101.0000
+    bb_0x000101.0000(sz 0x8):
+        %3: $Unknown64 = Phi(#x1 FROM 0x000100.0000, #x0 FROM 0x000101.0000);
+        %4: $Unknown64 = Phi(%1 FROM 0x000100.0000, %5 FROM 0x000101.0000);
+        [@0x000101.0001] %5: $Unknown64 = %4 + %3;
+        [@0x000101.0002] %6: $Unknown1 = %5 > #x1000;
+        JMP IF %6 0x000102.0000 ELSE 0x000101.0000
+    bb_0x000102.0000(sz 0x1):
+        [@0x000102.0001] %7: $Unknown0 = Store(%2, #x2000, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %7;
+}
+";
+
+    #[test]
+    fn c_cfg_loop_test() {
+        let rfn = load_il(LOOP_TXT);
+        let ccfg = recover_c_cfg(&rfn, &HashMap::new(), &HashMap::new());
+        let output = ctrl_flow_struct::structure_and_convert(ccfg)
+            .expect("Structuring failed")
+            .print();
+        println!("{}", output);
+        // Each edge into the loop sets the flag to the constant flowing from it.
+        assert!(output.contains(" = 0x1;"));
+        assert!(output.contains(" = 0x0;"));
+        assert!(output.contains(" = rdi;"));
+        assert!(!output.contains("= unknown"));
+        // The sum is carried over the back edge in a single variable.
+        let add = output
+            .lines()
+            .find(|l| l.contains(" + "))
+            .expect("No addition");
+        let mut sides = add.splitn(2, " = ");
+        let (dst, src) = (sides.next().unwrap().trim(), sides.next().unwrap());
+        assert!(src.contains(dst));
+    }
+
     const FILES: [&'static str; 2] = ["./test_files/bin1_main_ssa", "./test_files/loopy_main_ssa"];
 
     #[test]
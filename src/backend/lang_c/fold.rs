@@ -0,0 +1,233 @@
+//! Folding of expressions into their uses for the C backend.
+//!
+//! A value which would get its own C variable is folded, i.e. inlined into the
+//! expression using it, if
+//!
+//! * it is computed by an operation without side effects,
+//! * it is used exactly once, by an operation or as the selector of its block,
+//! * it is evaluated in the block it is defined in, and
+//! * no operation with side effects lies between its definition and the place
+//!   it is evaluated at, so that it neither reads memory after a store or a
+//!   call changed it, nor a variable after it was reassigned.
+//!
+//! Values which are folded are evaluated where their user is, so chains of
+//! single-use values become nested C expressions.
+
+use middle::ssa::cfg_traits::CFG;
+use middle::ssa::ssa_traits::{SSAWalk, SSA};
+use middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::{HashMap, HashSet};
+
+/// Returns the values to be inlined into their single use, out of the ones
+/// which `needs_var` would otherwise assign to a variable.
+pub fn folded_values<F>(ssa: &SSAStorage, needs_var: F) -> HashSet<NodeIndex>
+where
+    F: Fn(NodeIndex) -> bool,
+{
+    let mut folding = Folding::new(ssa, needs_var);
+    let nodes = ssa.inorder_walk().collect::<Vec<_>>();
+    nodes
+        .into_iter()
+        .filter(|&node| folding.is_folded(node))
+        .collect()
+}
+
+struct Folding<'a, F> {
+    ssa: &'a SSAStorage,
+    needs_var: F,
+    // Block and position in it of every operation
+    sites: HashMap<NodeIndex, (NodeIndex, usize)>,
+    // Positions of the operations with side effects by block
+    sideeffects: HashMap<NodeIndex, Vec<usize>>,
+    // Number of operations by block, i.e. the position of its end
+    ends: HashMap<NodeIndex, usize>,
+    folded: HashMap<NodeIndex, bool>,
+}
+
+impl<'a, F> Folding<'a, F>
+where
+    F: Fn(NodeIndex) -> bool,
+{
+    fn new(ssa: &'a SSAStorage, needs_var: F) -> Folding<'a, F> {
+        let mut sites = HashMap::new();
+        let mut sideeffects = HashMap::new();
+        let mut ends = HashMap::new();
+        let mut block = None;
+        for node in ssa.inorder_walk() {
+            if ssa.is_action(node) {
+                block = Some(node);
+                ends.insert(node, 0);
+                continue;
+            }
+            let (cur, op) = match (block, ssa.opcode(node)) {
+                (Some(cur), Some(op)) => (cur, op),
+                _ => continue,
+            };
+            let end = ends.entry(cur).or_insert(0);
+            if op.has_sideeffects() {
+                sideeffects.entry(cur).or_insert_with(Vec::new).push(*end);
+            }
+            sites.insert(node, (cur, *end));
+            *end += 1;
+        }
+        Folding {
+            ssa: ssa,
+            needs_var: needs_var,
+            sites: sites,
+            sideeffects: sideeffects,
+            ends: ends,
+            folded: HashMap::new(),
+        }
+    }
+
+    // Uses which are emitted, i.e. excluding the final register state.
+    fn uses(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let exit = self.ssa.exit_node();
+        self.ssa
+            .uses_of(node)
+            .into_iter()
+            .filter(|&u| {
+                self.ssa.opcode(u).is_some()
+                    || (self.ssa.is_phi(u) && self.ssa.block_for(u) != exit)
+            })
+            .collect()
+    }
+
+    fn is_folded(&mut self, node: NodeIndex) -> bool {
+        if let Some(&folded) = self.folded.get(&node) {
+            return folded;
+        }
+        let folded = self.can_fold(node);
+        self.folded.insert(node, folded);
+        folded
+    }
+
+    fn can_fold(&mut self, node: NodeIndex) -> bool {
+        let pure = self
+            .ssa
+            .opcode(node)
+            .map_or(false, |op| !op.has_sideeffects());
+        if !pure || !(self.needs_var)(node) {
+            return false;
+        }
+        let is_selector = self.ssa.is_selector(node);
+        if self.uses(node).len() + is_selector as usize != 1 {
+            return false;
+        }
+        let (block, pos) = match self.sites.get(&node) {
+            Some(&site) => site,
+            None => return false,
+        };
+        match self.evaluated_at(node, block) {
+            Some(last) => self.sideeffects.get(&block).map_or(true, |positions| {
+                positions.iter().all(|&p| p <= pos || p >= last)
+            }),
+            None => false,
+        }
+    }
+
+    // The last position in `block` at which `node` is evaluated when it is
+    // inlined into its uses, or `None` if it is evaluated out of `block`.
+    fn evaluated_at(&mut self, node: NodeIndex, block: NodeIndex) -> Option<usize> {
+        let mut last = if self.ssa.is_selector(node) {
+            if self.ssa.selector_for(node) != Some(block) {
+                return None;
+            }
+            Some(self.ends[&block])
+        } else {
+            None
+        };
+        for user in self.uses(node) {
+            // Operands of phis are copied at the end of the predecessors.
+            if self.ssa.is_phi(user) {
+                return None;
+            }
+            let (user_block, user_pos) = match self.sites.get(&user) {
+                Some(&site) => site,
+                None => return None,
+            };
+            if user_block != block {
+                return None;
+            }
+            let emitted = self
+                .ssa
+                .opcode(user)
+                .map_or(false, |op| op.has_sideeffects());
+            let pos = if emitted || ((self.needs_var)(user) && !self.is_folded(user)) {
+                user_pos
+            } else {
+                self.evaluated_at(user, block)?
+            };
+            last = last.max(Some(pos));
+        }
+        last
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir::MAddress;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.fold(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x7):
+        [@0x000100.0001] %4: $Unknown64 = %1 + %2;
+        [@0x000100.0002] %5: $Unknown64 = %4 << #x2;
+        [@0x000100.0003] %6: $Unknown64 = Load(%3, %1);
+        [@0x000100.0004] %7: $Unknown0 = Store(%3, %2, #x0);
+        [@0x000100.0005] %8: $Unknown64 = %6 + %5;
+        [@0x000100.0006] %9: $Unknown0 = Store(%7, %5, %8);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %9;
+}
+";
+
+    fn def_at(ssa: &SSAStorage, offset: u64) -> NodeIndex {
+        let addr = MAddress::new(0x100, offset);
+        ssa.inorder_walk()
+            .find(|&n| ssa.address(n) == Some(addr))
+            .unwrap()
+    }
+
+    #[test]
+    fn fold_single_uses() {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(SSA_TXT, regfile);
+        let needs_var = |n: NodeIndex| ssa.opcode(n).map_or(false, |op| !op.has_sideeffects());
+        let folded = folded_values(&ssa, needs_var);
+
+        let (sum, shifted, loaded, sum2) = (
+            def_at(&ssa, 1),
+            def_at(&ssa, 2),
+            def_at(&ssa, 3),
+            def_at(&ssa, 5),
+        );
+        assert!(folded.contains(&sum));
+        // Used twice
+        assert!(!folded.contains(&shifted));
+        // Memory is stored to before the loaded value is used.
+        assert!(!folded.contains(&loaded));
+        assert!(folded.contains(&sum2));
+        assert_eq!(folded.len(), 2);
+    }
+}
@@ -8,6 +8,8 @@
 pub mod c_ast;
 pub mod c_cfg;
 pub mod c_cfg_builder;
+pub mod fold;
+pub mod out_of_ssa;
 
 #[cfg(test)]
 mod test;
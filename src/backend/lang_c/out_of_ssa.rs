@@ -0,0 +1,743 @@
+//! Translation out of SSA for the C backend.
+//!
+//! Every value of a function that needs a C variable is assigned to one, and
+//! the phis are replaced by copies on the control flow edges into their block:
+//!
+//! 1. Phi operands are matched to the predecessors they flow from. These are
+//!    recorded when the operands are added, otherwise the operand defined
+//!    last on the way to a predecessor is taken, if there is a single one. A
+//!    phi gets an unknown value from a predecessor without an operand.
+//! 2. Liveness of the values is computed per block. A phi is defined at the
+//!    entry of its block and uses its operands at the end of the
+//!    predecessors.
+//! 3. A phi is coalesced with its operands whenever no value of the one
+//!    variable interferes with a value of the other, so that the copy between
+//!    them vanishes.
+//! 4. The remaining copies of an edge are a parallel copy, which is ordered so
+//!    that no variable is overwritten before it is read, using a temporary
+//!    variable to break cycles such as a swap.
+//!
+//! The copies of an edge leaving a block with several successors cannot be put
+//! into either block, so the edge has to be split by the user of `OutOfSSA`.
+//!
+//! Values without a variable are inlined into their uses, so the values they
+//! read are live up to these uses.
+
+use analysis::dom::domtree::DomTree;
+use middle::ir::MOpcode;
+use middle::ssa::cfg_traits::CFG;
+use middle::ssa::ssa_traits::{SSAWalk, SSA};
+use middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::{Graph, NodeIndex};
+
+use std::collections::{HashMap, HashSet};
+
+/// Value copied into a variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Value(NodeIndex),
+    /// Another variable, i.e. a temporary.
+    Var(usize),
+    /// No value is known to flow along the edge.
+    Unknown,
+}
+
+/// Copy of `src` into the variable `dst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdgeCopy {
+    pub dst: usize,
+    pub src: Source,
+}
+
+/// Variables of the values of a function and the copies replacing its phis.
+#[derive(Clone, Debug, Default)]
+pub struct OutOfSSA {
+    vars: HashMap<NodeIndex, usize>,
+    // Value every variable was made for; temporaries take the one of the
+    // variable they save a value for.
+    reprs: Vec<NodeIndex>,
+    // Copies in order of execution by control flow edge `(pred, succ)`.
+    copies: HashMap<(NodeIndex, NodeIndex), Vec<EdgeCopy>>,
+}
+
+impl OutOfSSA {
+    /// Translates `ssa` out of SSA, where `needs_var` tells the values that
+    /// have to be assigned to a variable. Values of phis that do not need one
+    /// are not copied.
+    pub fn new<F>(ssa: &SSAStorage, needs_var: F) -> OutOfSSA
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        let mut t = Translation::new(ssa, needs_var);
+        t.match_phi_operands();
+        t.liveness();
+        t.coalesce();
+        t.number_vars();
+        t.insert_copies();
+        t.out
+    }
+
+    /// Variable `node` is assigned to, if any.
+    pub fn var_of(&self, node: NodeIndex) -> Option<usize> {
+        self.vars.get(&node).cloned()
+    }
+
+    /// Number of variables, including temporaries.
+    pub fn var_count(&self) -> usize {
+        self.reprs.len()
+    }
+
+    /// Value which represents the variable `var`, e.g. for its type.
+    pub fn representative(&self, var: usize) -> NodeIndex {
+        self.reprs[var]
+    }
+
+    /// Copies to execute in order when control flows from `pred` to `succ`.
+    pub fn copies(&self, pred: NodeIndex, succ: NodeIndex) -> &[EdgeCopy] {
+        self.copies
+            .get(&(pred, succ))
+            .map_or(&[][..], |copies| &copies[..])
+    }
+}
+
+struct Translation<'a> {
+    ssa: &'a SSAStorage,
+    // Values which are assigned to a variable
+    values: HashSet<NodeIndex>,
+    blocks: Vec<NodeIndex>,
+    // Values of every block in order of definition
+    nodes: HashMap<NodeIndex, Vec<NodeIndex>>,
+    // Block and position of the definition of every value of a block
+    sites: HashMap<NodeIndex, (NodeIndex, usize)>,
+    // Operand of a phi flowing from a reachable predecessor, by `(phi, pred)`,
+    // `None` if it is not known
+    operands: HashMap<(NodeIndex, NodeIndex), Option<NodeIndex>>,
+    live_in: HashMap<NodeIndex, HashSet<NodeIndex>>,
+    live_out: HashMap<NodeIndex, HashSet<NodeIndex>>,
+    interference: HashSet<(NodeIndex, NodeIndex)>,
+    // Union-find of the coalesced values
+    parent: HashMap<NodeIndex, NodeIndex>,
+    members: HashMap<NodeIndex, Vec<NodeIndex>>,
+    out: OutOfSSA,
+}
+
+impl<'a> Translation<'a> {
+    fn new<F>(ssa: &'a SSAStorage, needs_var: F) -> Translation<'a>
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        let mut values = HashSet::new();
+        let mut blocks = Vec::new();
+        let mut nodes = HashMap::new();
+        let mut sites = HashMap::new();
+        for (i, node) in ssa.inorder_walk().enumerate() {
+            if ssa.is_action(node) {
+                blocks.push(node);
+                nodes.insert(node, Vec::new());
+                continue;
+            }
+            let block = match blocks.last() {
+                Some(&block) => block,
+                None => continue,
+            };
+            let mut defs = vec![node];
+            // Values returned by a call are defined by it.
+            if ssa.opcode(node) == Some(MOpcode::OpCall) {
+                defs.extend(ssa.uses_of(node).into_iter().filter(|&u| ssa.is_comment(u)));
+            }
+            for def in defs {
+                sites.insert(def, (block, i));
+                if let Some(ns) = nodes.get_mut(&block) {
+                    ns.push(def);
+                }
+                if needs_var(def) {
+                    values.insert(def);
+                }
+            }
+        }
+        Translation {
+            ssa: ssa,
+            values: values,
+            blocks: blocks,
+            nodes: nodes,
+            sites: sites,
+            operands: HashMap::new(),
+            live_in: HashMap::new(),
+            live_out: HashMap::new(),
+            interference: HashSet::new(),
+            parent: HashMap::new(),
+            members: HashMap::new(),
+            out: OutOfSSA::default(),
+        }
+    }
+
+    fn phis(&self, block: NodeIndex) -> Vec<NodeIndex> {
+        self.nodes[&block]
+            .iter()
+            .filter(|&&n| self.ssa.is_phi(n) && self.values.contains(&n))
+            .cloned()
+            .collect()
+    }
+
+    fn preds(&self, block: NodeIndex) -> Vec<NodeIndex> {
+        let mut preds = self.ssa.preds_of(block);
+        preds.sort();
+        preds.dedup();
+        preds
+    }
+
+    fn succs(&self, block: NodeIndex) -> Vec<NodeIndex> {
+        let mut succs = self.ssa.succs_of(block);
+        succs.sort();
+        succs.dedup();
+        succs
+    }
+
+    // The value a chain of `OpMov` forwards.
+    fn resolve(&self, mut node: NodeIndex) -> NodeIndex {
+        while self.ssa.opcode(node) == Some(MOpcode::OpMov) {
+            node = self.ssa.operands_of(node)[0];
+        }
+        node
+    }
+
+    // Values with a variable read when `node` is evaluated.
+    fn reads(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut reads = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            if !seen.insert(n) {
+                continue;
+            }
+            if self.values.contains(&n) {
+                reads.push(n);
+                continue;
+            }
+            // Memory is not a value.
+            let width = self
+                .ssa
+                .node_data(n)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width())
+                .unwrap_or(0);
+            if self.ssa.opcode(n).is_some() && width != 0 {
+                stack.extend(self.ssa.operands_of(n));
+            }
+        }
+        reads
+    }
+
+    fn is_emitted(&self, node: NodeIndex) -> bool {
+        match self.ssa.opcode(node) {
+            Some(MOpcode::OpStore) | Some(MOpcode::OpCall) => true,
+            Some(_) => self.values.contains(&node),
+            None => false,
+        }
+    }
+
+    fn match_phi_operands(&mut self) {
+        let doms = dominators(self.ssa);
+        let mut operands = HashMap::new();
+        for &block in &self.blocks {
+            for phi in self.phis(block) {
+                for pred in self.preds(block) {
+                    // Unreachable predecessors are never emitted.
+                    let pred_doms = match doms.get(&pred) {
+                        Some(pred_doms) => pred_doms,
+                        None => continue,
+                    };
+                    let operand = self
+                        .ssa
+                        .phi_source(phi, pred)
+                        .or_else(|| self.last_def(phi, pred_doms, &doms));
+                    if operand.is_none() {
+                        radeco_warn!("No operand of {:?} from {:?}", phi, pred);
+                    }
+                    operands.insert((phi, pred), operand);
+                }
+            }
+        }
+        self.operands = operands;
+    }
+
+    // The operand of `phi` defined last on the way to the predecessor with the
+    // dominators `pred_doms`, which flows from it if the operands are
+    // definitions of a single variable. `None` if no operand or several ones
+    // are defined last, as for constants.
+    fn last_def(
+        &self,
+        phi: NodeIndex,
+        pred_doms: &HashSet<NodeIndex>,
+        doms: &HashMap<NodeIndex, HashSet<NodeIndex>>,
+    ) -> Option<NodeIndex> {
+        let mut defs = self
+            .ssa
+            .operands_of(phi)
+            .into_iter()
+            .filter_map(|op| match self.sites.get(&op) {
+                // Constants and arguments
+                None => Some((0, 0, op)),
+                Some(&(b, pos)) if pred_doms.contains(&b) => Some((doms[&b].len(), pos, op)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        defs.sort();
+        match (defs.pop(), defs.last()) {
+            (Some((depth, pos, _)), Some(&(d, p, _))) if (d, p) == (depth, pos) => None,
+            (last, _) => last.map(|(_, _, op)| op),
+        }
+    }
+
+    // Values read by the copies on the edge from `pred` to `succ`.
+    fn edge_reads(&self, pred: NodeIndex, succ: NodeIndex) -> Vec<NodeIndex> {
+        self.phis(succ)
+            .into_iter()
+            .filter_map(|phi| self.operands.get(&(phi, pred)).and_then(|&op| op))
+            .flat_map(|op| self.reads(op))
+            .collect()
+    }
+
+    // Returns the values live at the entry of `block`, given the ones live at
+    // its end, and records the interferences of the values defined in it.
+    fn transfer(&mut self, block: NodeIndex, record: bool) -> HashSet<NodeIndex> {
+        let mut live = self.live_out.get(&block).cloned().unwrap_or_default();
+        if let Some(selector) = self.ssa.selector_in(block) {
+            live.extend(self.reads(selector));
+        }
+        let nodes = self.nodes[&block].clone();
+        for &node in nodes.iter().rev() {
+            if self.ssa.is_phi(node) {
+                continue;
+            }
+            if self.values.contains(&node) {
+                live.remove(&node);
+                if record {
+                    for &v in &live {
+                        self.interfere(node, v);
+                    }
+                }
+            }
+            if self.is_emitted(node) {
+                for op in self.ssa.operands_of(node) {
+                    live.extend(self.reads(op));
+                }
+            }
+        }
+        // Phis are defined at once at the entry of the block.
+        let phis = self.phis(block);
+        for phi in &phis {
+            live.remove(phi);
+        }
+        if record {
+            for &phi in &phis {
+                for &v in live.iter().chain(&phis) {
+                    if v != phi {
+                        self.interfere(phi, v);
+                    }
+                }
+            }
+        }
+        live
+    }
+
+    fn liveness(&mut self) {
+        let blocks = self.blocks.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in blocks.iter().rev() {
+                let mut out = HashSet::new();
+                for succ in self.succs(block) {
+                    if let Some(live) = self.live_in.get(&succ) {
+                        out.extend(live.iter().cloned());
+                    }
+                    out.extend(self.edge_reads(block, succ));
+                }
+                self.live_out.insert(block, out);
+                let live = self.transfer(block, false);
+                if self.live_in.get(&block) != Some(&live) {
+                    self.live_in.insert(block, live);
+                    changed = true;
+                }
+            }
+        }
+        for &block in &blocks {
+            self.transfer(block, true);
+        }
+    }
+
+    fn interfere(&mut self, a: NodeIndex, b: NodeIndex) {
+        self.interference.insert((a.min(b), a.max(b)));
+    }
+
+    fn find(&self, mut node: NodeIndex) -> NodeIndex {
+        while let Some(&p) = self.parent.get(&node) {
+            node = p;
+        }
+        node
+    }
+
+    // Puts `a` and `b` into the same variable unless their values interfere.
+    fn try_union(&mut self, a: NodeIndex, b: NodeIndex) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return;
+        }
+        let (ma, mb) = (
+            self.members.get(&ra).cloned().unwrap_or_else(|| vec![ra]),
+            self.members.get(&rb).cloned().unwrap_or_else(|| vec![rb]),
+        );
+        let interferes = ma.iter().any(|&x| {
+            mb.iter()
+                .any(|&y| self.interference.contains(&(x.min(y), x.max(y))))
+        });
+        if interferes {
+            return;
+        }
+        let (root, child, mut members, other) = if ma.len() >= mb.len() {
+            (ra, rb, ma, mb)
+        } else {
+            (rb, ra, mb, ma)
+        };
+        members.extend(other);
+        self.parent.insert(child, root);
+        self.members.remove(&child);
+        self.members.insert(root, members);
+    }
+
+    fn coalesce(&mut self) {
+        for block in self.blocks.clone() {
+            for phi in self.phis(block) {
+                for pred in self.preds(block) {
+                    let op = match self.operands.get(&(phi, pred)) {
+                        Some(&Some(op)) => self.resolve(op),
+                        _ => continue,
+                    };
+                    if self.values.contains(&op) {
+                        self.try_union(phi, op);
+                    }
+                }
+            }
+        }
+    }
+
+    fn number_vars(&mut self) {
+        let mut ids = HashMap::new();
+        for &block in &self.blocks {
+            for &node in &self.nodes[&block] {
+                if !self.values.contains(&node) {
+                    continue;
+                }
+                let root = self.find(node);
+                let next = self.out.reprs.len();
+                let id = *ids.entry(root).or_insert(next);
+                if id == next {
+                    self.out.reprs.push(node);
+                }
+                self.out.vars.insert(node, id);
+            }
+        }
+    }
+
+    fn insert_copies(&mut self) {
+        for block in self.blocks.clone() {
+            let phis = self.phis(block);
+            if phis.is_empty() {
+                continue;
+            }
+            for pred in self.preds(block) {
+                let mut parallel = Vec::new();
+                for &phi in &phis {
+                    let dst = self.out.vars[&phi];
+                    let copy = match self.operands.get(&(phi, pred)) {
+                        Some(&Some(op)) => {
+                            if self.out.var_of(self.resolve(op)) == Some(dst) {
+                                continue;
+                            }
+                            let reads = self
+                                .reads(op)
+                                .into_iter()
+                                .filter_map(|v| self.out.var_of(v))
+                                .collect::<Vec<_>>();
+                            let copy = EdgeCopy {
+                                dst: dst,
+                                src: Source::Value(op),
+                            };
+                            (copy, reads)
+                        }
+                        // The variable must not keep the value of another edge.
+                        Some(&None) => {
+                            let copy = EdgeCopy {
+                                dst: dst,
+                                src: Source::Unknown,
+                            };
+                            (copy, Vec::new())
+                        }
+                        None => continue,
+                    };
+                    parallel.push(copy);
+                }
+                if !parallel.is_empty() {
+                    let copies = self.sequentialize(parallel);
+                    self.out.copies.insert((pred, block), copies);
+                }
+            }
+        }
+    }
+
+    // Orders the parallel copies `pending`, along with the variables each of
+    // them reads, so that no variable is written before it is read.
+    fn sequentialize(&mut self, mut pending: Vec<(EdgeCopy, Vec<usize>)>) -> Vec<EdgeCopy> {
+        let mut copies = Vec::new();
+        while !pending.is_empty() {
+            let ready = (0..pending.len()).find(|&i| {
+                let dst = pending[i].0.dst;
+                pending
+                    .iter()
+                    .enumerate()
+                    .all(|(j, &(_, ref reads))| i == j || !reads.contains(&dst))
+            });
+            if let Some(i) = ready {
+                copies.push(pending.remove(i).0);
+                continue;
+            }
+            // Every copy overwrites a variable another one still reads, so the
+            // value of one of them is saved into a temporary first.
+            let (dst, src) = (pending[0].0.dst, pending[0].0.src);
+            let tmp = self.out.reprs.len();
+            let repr = self.out.reprs[dst];
+            self.out.reprs.push(repr);
+            copies.push(EdgeCopy { dst: tmp, src: src });
+            pending[0] = (
+                EdgeCopy {
+                    dst: dst,
+                    src: Source::Var(tmp),
+                },
+                vec![tmp],
+            );
+        }
+        copies
+    }
+}
+
+// Dominators of every block reachable from the entry.
+fn dominators(ssa: &SSAStorage) -> HashMap<NodeIndex, HashSet<NodeIndex>> {
+    let mut doms = HashMap::new();
+    let entry = match ssa.entry_node() {
+        Some(entry) => entry,
+        None => return doms,
+    };
+    // `DomTree` cannot handle unreachable blocks, so only the reachable ones are copied.
+    let mut g = Graph::<NodeIndex, u8>::new();
+    let mut index = HashMap::new();
+    let mut worklist = vec![entry];
+    index.insert(entry, g.add_node(entry));
+    while let Some(block) = worklist.pop() {
+        for succ in ssa.succs_of(block) {
+            if !index.contains_key(&succ) {
+                index.insert(succ, g.add_node(succ));
+                worklist.push(succ);
+            }
+            g.add_edge(index[&block], index[&succ], 0);
+        }
+    }
+    let dom = DomTree::build_dom_tree(&g, index[&entry]);
+    for (&block, &i) in &index {
+        doms.insert(block, dom.doms(i).into_iter().map(|d| g[d]).collect());
+    }
+    doms
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // Swaps the values of rdi and rsi rdx times and stores one of them into
+    // the other.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.swap(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rdx;
+        %4: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x1):
+        JMP 0x000101.0000
+    bb_0x000101.0000(sz 0x8):
+        %5: $Unknown64 = Phi(%1, %6);
+        %6: $Unknown64 = Phi(%2, %5);
+        %7: $Unknown64 = Phi(%3, %8);
+        [@0x000101.0001] %8: $Unknown64 = %7 - #x1;
+        [@0x000101.0002] %9: $Unknown1 = %8 == #x0;
+        JMP IF %9 0x000102.0000 ELSE 0x000101.0000
+    bb_0x000102.0000(sz 0x1):
+        [@0x000102.0001] %10: $Unknown0 = Store(%4, %5, %6);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %10;
+}
+";
+
+    fn phi_of(ssa: &SSAStorage, reg: &str) -> NodeIndex {
+        ssa.inorder_walk()
+            .find(|&n| {
+                ssa.is_phi(n)
+                    && ssa
+                        .operands_of(n)
+                        .iter()
+                        .any(|&op| ssa.comment(op).map_or(false, |c| c == reg))
+            })
+            .unwrap()
+    }
+
+    // Sums rdi with itself until it exceeds 0x1000, adding one more on the
+    // first iteration only.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const FIRST_TXT: &str = "\
+define-fun sym.first(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x1):
+        JMP 0x000101.0000
+    bb_0x000101.0000(sz 0x8):
+        %3: $Unknown64 = Phi(#x1 FROM 0x000100.0000, #x0 FROM 0x000101.0000);
+        %4: $Unknown64 = Phi(%1 FROM 0x000100.0000, %5 FROM 0x000101.0000);
+        [@0x000101.0001] %5: $Unknown64 = %4 + %3;
+        [@0x000101.0002] %6: $Unknown1 = %5 > #x1000;
+        JMP IF %6 0x000102.0000 ELSE 0x000101.0000
+    bb_0x000102.0000(sz 0x1):
+        [@0x000102.0001] %7: $Unknown0 = Store(%2, #x2000, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %7;
+}
+";
+
+    fn translate(txt: &str) -> (SSAStorage, OutOfSSA) {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let ssa = ir_reader::parse_il(txt, regfile);
+        let out = {
+            let needs_var = |n: NodeIndex| {
+                (ssa.is_phi(n) || ssa.opcode(n).map_or(false, |op| !op.has_sideeffects()))
+                    && ssa
+                        .node_data(n)
+                        .ok()
+                        .and_then(|nd| nd.vt.width().get_width())
+                        .map_or(false, |w| w != 0)
+            };
+            OutOfSSA::new(&ssa, needs_var)
+        };
+        (ssa, out)
+    }
+
+    // Sources of the copies into the variable of the phi of constants, from the
+    // entry and from the back edge.
+    fn first_sources(ssa: &SSAStorage, out: &OutOfSSA) -> (Source, Source) {
+        let first = ssa
+            .inorder_walk()
+            .find(|&n| ssa.is_phi(n) && ssa.operands_of(n).iter().all(|&op| ssa.is_constant(op)))
+            .unwrap();
+        let var = out.var_of(first).unwrap();
+        let loop_block = ssa.block_for(first).unwrap();
+        let entry = ssa
+            .preds_of(loop_block)
+            .into_iter()
+            .find(|&p| p != loop_block)
+            .unwrap();
+        let source = |pred: NodeIndex| {
+            out.copies(pred, loop_block)
+                .iter()
+                .find(|copy| copy.dst == var)
+                .map(|copy| copy.src)
+                .unwrap()
+        };
+        (source(entry), source(loop_block))
+    }
+
+    #[test]
+    fn operands_by_predecessor() {
+        let (ssa, out) = translate(FIRST_TXT);
+        let constant = |src: Source| match src {
+            Source::Value(n) => ssa.constant_value(n),
+            _ => None,
+        };
+        let (from_entry, from_loop) = first_sources(&ssa, &out);
+        assert_eq!(constant(from_entry), Some(1));
+        assert_eq!(constant(from_loop), Some(0));
+    }
+
+    #[test]
+    fn unknown_operand() {
+        // Without the predecessors, either constant may flow from either edge.
+        let txt = FIRST_TXT
+            .replace(" FROM 0x000100.0000", "")
+            .replace(" FROM 0x000101.0000", "");
+        let (ssa, out) = translate(&txt);
+        assert_eq!(
+            first_sources(&ssa, &out),
+            (Source::Unknown, Source::Unknown)
+        );
+    }
+
+    #[test]
+    fn swap_in_loop() {
+        let (ssa, out) = translate(SSA_TXT);
+
+        let (a, b, i) = (
+            phi_of(&ssa, "rdi"),
+            phi_of(&ssa, "rsi"),
+            phi_of(&ssa, "rdx"),
+        );
+        let dec = ssa
+            .uses_of(i)
+            .into_iter()
+            .find(|&n| !ssa.is_phi(n))
+            .unwrap();
+        assert_ne!(out.var_of(a), out.var_of(b));
+        // The counter is not live after it is decremented.
+        assert_eq!(out.var_of(i), out.var_of(dec));
+
+        let loop_block = ssa.block_for(a).unwrap();
+        let entry = ssa
+            .preds_of(loop_block)
+            .into_iter()
+            .find(|&p| p != loop_block)
+            .unwrap();
+        assert_eq!(out.copies(entry, loop_block).len(), 3);
+
+        // Run the copies of the back edge on a = 1 and b = 2.
+        let mut vals = vec![0u64; out.var_count()];
+        let (va, vb, vi) = (
+            out.var_of(a).unwrap(),
+            out.var_of(b).unwrap(),
+            out.var_of(i).unwrap(),
+        );
+        vals[va] = 1;
+        vals[vb] = 2;
+        vals[vi] = 3;
+        let copies = out.copies(loop_block, loop_block);
+        assert_eq!(copies.len(), 3);
+        for copy in copies {
+            vals[copy.dst] = match copy.src {
+                Source::Value(n) => vals[out.var_of(n).unwrap()],
+                Source::Var(v) => vals[v],
+            };
+        }
+        assert_eq!((vals[va], vals[vb], vals[vi]), (2, 1, 3));
+    }
+}
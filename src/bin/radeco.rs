@@ -0,0 +1,509 @@
+//! Command-line interface to radeco-lib.
+//!
+//! Loads a binary (or a `FileSource` snapshot of one), runs a pipeline of
+//! passes over it and emits IL, DOT or C for the selected functions.
+
+extern crate base64;
+extern crate docopt;
+extern crate radeco_lib;
+#[macro_use]
+extern crate serde_derive;
+
+use docopt::Docopt;
+use radeco_lib::backend::lang_c::c_cfg::ctrl_flow_struct;
+use radeco_lib::backend::lang_c::c_cfg_builder;
+use radeco_lib::frontend::radeco_containers::{
+    FunctionType, LoadError, ProjectLoader, RadecoFunction, RadecoModule, RadecoProject,
+};
+use radeco_lib::frontend::radeco_source::{FileSource, Source};
+use radeco_lib::middle::{dot, ir_writer};
+use radeco_lib::utils::pass_manager::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+
+static USAGE: &'static str = "
+radeco. Decompile the functions of a binary.
+
+Usage:
+  radeco [options] <bin> [<function>...]
+  radeco (-h | --help)
+
+Options:
+  -h --help              Show this screen.
+  -p --passes=<passes>   Comma-separated list of passes to run on every module
+                         [default: forward_stores,dead_stores,sccp,cse,dce,tie].
+  -e --emit=<kind>       Emit `il`, `dot` (SSA graph), `ccfg` (C CFG as DOT)
+                         or `c` for every selected function [default: c].
+  -o --output=<dir>      Write one file per function into <dir> instead of
+                         printing to stdout.
+  --snapshot             <bin> is the base path of a snapshot written by
+                         `radeco_source::snapshot` instead of a binary.
+  --verify               Verify the SSA after every pass.
+  --timings              Print the time spent in every pass to stderr.
+
+Functions are selected by name or by address, either in hex prefixed with 0x
+or in decimal. All functions are emitted if none is given.
+
+Passes: sccp, dce, cse, inst_combine, copy_propagation, sort_operands,
+fix_calls, infer_regusage, memory_ssa, forward_stores, dead_stores, tie.
+
+Exit status:
+  0  Success.
+  1  Invalid arguments.
+  2  The binary could not be loaded.
+  3  A selected function does not exist.
+  4  A pass failed.
+  5  Some function could not be emitted.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_bin: String,
+    arg_function: Vec<String>,
+    flag_passes: String,
+    flag_emit: String,
+    flag_output: Option<String>,
+    flag_snapshot: bool,
+    flag_verify: bool,
+    flag_timings: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Emit {
+    Il,
+    Dot,
+    Ccfg,
+    C,
+}
+
+impl Emit {
+    fn extension(&self) -> &'static str {
+        match *self {
+            Emit::Il => "il",
+            Emit::Dot | Emit::Ccfg => "dot",
+            Emit::C => "c",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Selector {
+    Name(String),
+    Address(u64),
+}
+
+impl Selector {
+    fn parse(s: &str) -> Selector {
+        let addr = if s.starts_with("0x") {
+            u64::from_str_radix(&s[2..], 16).ok()
+        } else {
+            s.parse::<u64>().ok()
+        };
+        match addr {
+            Some(addr) => Selector::Address(addr),
+            None => Selector::Name(s.to_owned()),
+        }
+    }
+
+    fn matches(&self, rfn: &RadecoFunction) -> bool {
+        match *self {
+            Selector::Name(ref name) => rfn.name == *name,
+            Selector::Address(addr) => rfn.offset == addr,
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Selector::Name(ref name) => write!(f, "{}", name),
+            Selector::Address(addr) => write!(f, "{:#x}", addr),
+        }
+    }
+}
+
+/// Reasons for radeco to stop early. Each maps to a distinct exit status.
+#[derive(Debug)]
+enum Error {
+    Usage(String),
+    Load(LoadError),
+    NoSuchFunction(String),
+    Pass(PassError),
+    Emit(usize),
+}
+
+impl Error {
+    fn status(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => 1,
+            Error::Load(_) => 2,
+            Error::NoSuchFunction(_) => 3,
+            Error::Pass(_) => 4,
+            Error::Emit(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref s) => write!(f, "{}", s),
+            Error::Load(ref e) => write!(f, "{}", e),
+            Error::NoSuchFunction(ref s) => write!(f, "No function named or at {}", s),
+            Error::Pass(ref e) => write!(f, "{}", e),
+            Error::Emit(n) => write!(f, "Failed to emit {} function(s)", n),
+        }
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(e: LoadError) -> Error {
+        Error::Load(e)
+    }
+}
+
+impl From<PassError> for Error {
+    fn from(e: PassError) -> Error {
+        Error::Pass(e)
+    }
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    process::exit(match run(&args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("radeco: {}", e);
+            e.status()
+        }
+    });
+}
+
+fn run(args: &Args) -> Result<(), Error> {
+    let emit = parse_emit(&args.flag_emit)?;
+    let mut pm = pipeline(&args.flag_passes, args.flag_verify)?;
+    let selectors: Vec<Selector> = args
+        .arg_function
+        .iter()
+        .map(|s| Selector::parse(s))
+        .collect();
+
+    let mut project = load(args)?;
+    for e in project.load_errors() {
+        eprintln!("radeco: warning: {}", e);
+    }
+
+    let result = pm.run(&mut project);
+    if args.flag_timings {
+        for t in pm.timings() {
+            eprintln!("{:>20} {:>10.3?} {}", t.pass, t.elapsed, t.module);
+        }
+    }
+    result?;
+
+    let is_selected = |rfn: &RadecoFunction| {
+        rfn.ftype == FunctionType::Function
+            && (selectors.is_empty() || selectors.iter().any(|s| s.matches(rfn)))
+    };
+    if let Some(s) = selectors.iter().find(|s| {
+        !project.iter().any(|zm| {
+            zm.module
+                .functions
+                .values()
+                .any(|rfn| rfn.ftype == FunctionType::Function && s.matches(rfn))
+        })
+    }) {
+        return Err(Error::NoSuchFunction(s.to_string()));
+    }
+
+    if let Some(ref dir) = args.flag_output {
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(Error::Usage(format!("Unable to create {}: {}", dir, e)));
+        }
+    }
+
+    let mut failed = 0;
+    for zm in project.iter() {
+        let rmod = zm.module;
+        let fname_map = function_names(rmod);
+        let strings = strings(rmod);
+        for rfn in rmod.functions.values().filter(|rfn| is_selected(rfn)) {
+            let out = match emit_function(emit, rfn, &fname_map, &strings) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("radeco: {} @ {:#x}: {}", rfn.name, rfn.offset, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            match args.flag_output {
+                Some(ref dir) => {
+                    let mut path = PathBuf::from(dir);
+                    path.push(format!(
+                        "{}_{:x}.{}",
+                        sanitize(&rfn.name),
+                        rfn.offset,
+                        emit.extension()
+                    ));
+                    if let Err(e) = fs::write(&path, out) {
+                        eprintln!("radeco: Unable to write {}: {}", path.display(), e);
+                        failed += 1;
+                    }
+                }
+                None => println!("{}", out),
+            }
+        }
+    }
+
+    if failed > 0 {
+        Err(Error::Emit(failed))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_emit(kind: &str) -> Result<Emit, Error> {
+    match kind {
+        "il" => Ok(Emit::Il),
+        "dot" => Ok(Emit::Dot),
+        "ccfg" => Ok(Emit::Ccfg),
+        "c" => Ok(Emit::C),
+        _ => Err(Error::Usage(format!("Unknown output kind: {}", kind))),
+    }
+}
+
+/// Builds the pass pipeline from a comma-separated list of pass names. Passes
+/// that provide analyses are also registered to run on demand.
+fn pipeline(passes: &str, verify: bool) -> Result<PassManager, Error> {
+    let mut pm = PassManager::new()
+        .module_analysis(BuildCallGraph)
+        .module_analysis(FixCalls::default())
+        .module_analysis(InferRegUsage)
+        .module_analysis(BuildMemorySSA)
+        .function_analysis(SortOperands);
+    for name in passes.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        pm = match name {
+            "sccp" => pm.function_pass(Sccp),
+            "dce" => pm.function_pass(Dce),
+            "cse" => pm.function_pass(Cse),
+            "inst_combine" => pm.function_pass(InstCombine),
+            "copy_propagation" => pm.function_pass(CopyPropagation),
+            "sort_operands" => pm.function_pass(SortOperands),
+            "fix_calls" => pm.module_pass(FixCalls::default()),
+            "infer_regusage" => pm.module_pass(InferRegUsage),
+            "memory_ssa" => pm.module_pass(BuildMemorySSA),
+            "forward_stores" => pm.module_pass(ForwardStores::default()),
+            "dead_stores" => pm.module_pass(DeadStores::default()),
+            "tie" => pm.module_pass(InferTypes),
+            _ => return Err(Error::Usage(format!("Unknown pass: {}", name))),
+        };
+    }
+    if verify {
+        pm = pm.verify();
+    }
+    Ok(pm)
+}
+
+fn load(args: &Args) -> Result<RadecoProject, LoadError> {
+    let mut loader = ProjectLoader::new().path(&args.arg_bin);
+    if args.flag_snapshot {
+        let src: Rc<Source> = Rc::new(FileSource::open(&args.arg_bin));
+        loader = loader.source(src);
+    }
+    loader.try_load()
+}
+
+fn emit_function(
+    emit: Emit,
+    rfn: &RadecoFunction,
+    fname_map: &HashMap<u64, String>,
+    strings: &HashMap<u64, String>,
+) -> Result<String, String> {
+    match emit {
+        Emit::Il => {
+            let mut out = String::new();
+            ir_writer::emit_il(&mut out, Some(rfn.name.to_string()), rfn.ssa())
+                .map_err(|e| e.to_string())?;
+            Ok(out)
+        }
+        Emit::Dot => Ok(dot::emit_dot(rfn.ssa())),
+        Emit::Ccfg => Ok(c_cfg_builder::recover_c_cfg(rfn, fname_map, strings).dot_str()),
+        Emit::C => {
+            let ccfg = c_cfg_builder::recover_c_cfg(rfn, fname_map, strings);
+            ctrl_flow_struct::structure_and_convert(ccfg)
+                .map(|cast| cast.print())
+                .map_err(|e| e.to_owned())
+        }
+    }
+}
+
+fn function_names(rmod: &RadecoModule) -> HashMap<u64, String> {
+    rmod.functions
+        .iter()
+        .map(|(&off, rfn)| (off, rfn.name.to_string()))
+        .collect()
+}
+
+// Strings from r2 are base64 encoded.
+fn strings(rmod: &RadecoModule) -> HashMap<u64, String> {
+    rmod.strings()
+        .iter()
+        .filter_map(|s| {
+            let bytes = base64::decode(s.string.as_ref()?).ok()?;
+            Some((s.vaddr?, String::from_utf8_lossy(&bytes).into_owned()))
+        })
+        .collect()
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use radeco_lib::frontend::radeco_containers::ModuleLoader;
+
+    fn args(argv: &[&str]) -> Args {
+        Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv.iter()).deserialize())
+            .expect("Invalid arguments")
+    }
+
+    #[test]
+    fn selector_parse() {
+        match Selector::parse("0x40059d") {
+            Selector::Address(0x40059d) => {}
+            s => panic!("Unexpected selector: {:?}", s),
+        }
+        match Selector::parse("4195741") {
+            Selector::Address(0x40059d) => {}
+            s => panic!("Unexpected selector: {:?}", s),
+        }
+        match Selector::parse("main") {
+            Selector::Name(ref name) if name == "main" => {}
+            s => panic!("Unexpected selector: {:?}", s),
+        }
+        // Not a valid number, so taken as a name.
+        match Selector::parse("0xmain") {
+            Selector::Name(ref name) if name == "0xmain" => {}
+            s => panic!("Unexpected selector: {:?}", s),
+        }
+    }
+
+    #[test]
+    fn selector_matches() {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = "main".into();
+        rfn.offset = 0x40059d;
+        assert!(Selector::parse("main").matches(&rfn));
+        assert!(Selector::parse("0x40059d").matches(&rfn));
+        assert!(Selector::parse("4195741").matches(&rfn));
+        assert!(!Selector::parse("foo").matches(&rfn));
+        assert!(!Selector::parse("0x400000").matches(&rfn));
+    }
+
+    #[test]
+    fn selector_display() {
+        assert_eq!(Selector::parse("4195741").to_string(), "0x40059d");
+        assert_eq!(Selector::parse("main").to_string(), "main");
+    }
+
+    #[test]
+    fn emit_kinds() {
+        for &(kind, ext) in &[("il", "il"), ("dot", "dot"), ("ccfg", "dot"), ("c", "c")] {
+            assert_eq!(parse_emit(kind).unwrap().extension(), ext);
+        }
+        for kind in &["", "C", "asm"] {
+            match parse_emit(kind) {
+                Err(ref e @ Error::Usage(_)) => assert_eq!(e.status(), 1),
+                r => panic!("Unexpected result for {:?}: {:?}", kind, r),
+            }
+        }
+    }
+
+    #[test]
+    fn pipeline_passes() {
+        let all = "sccp,dce,cse,inst_combine,copy_propagation,sort_operands,\
+                   fix_calls,infer_regusage,memory_ssa,forward_stores,dead_stores,tie";
+        assert!(pipeline(all, false).is_ok());
+        assert!(pipeline(all, true).is_ok());
+        // Whitespace and empty entries are ignored.
+        assert!(pipeline(" sccp , dce,,", false).is_ok());
+        assert!(pipeline("", false).is_ok());
+    }
+
+    #[test]
+    fn pipeline_unknown_pass() {
+        match pipeline("sccp,foo,dce", false) {
+            Err(ref e @ Error::Usage(_)) => {
+                assert_eq!(e.status(), 1);
+                assert_eq!(e.to_string(), "Unknown pass: foo");
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Unknown pass accepted"),
+        }
+    }
+
+    #[test]
+    fn default_pipeline() {
+        let args = args(&["radeco", "bin"]);
+        assert_eq!(args.arg_bin, "bin");
+        assert!(args.arg_function.is_empty());
+        assert_eq!(
+            args.flag_passes,
+            "forward_stores,dead_stores,sccp,cse,dce,tie"
+        );
+        match parse_emit(&args.flag_emit) {
+            Ok(Emit::C) => {}
+            r => panic!("Unexpected default output kind: {:?}", r),
+        }
+        assert!(pipeline(&args.flag_passes, args.flag_verify).is_ok());
+    }
+
+    #[test]
+    fn function_arguments() {
+        let args = args(&[
+            "radeco", "-p", "sccp", "-e", "il", "bin", "main", "0x400000",
+        ]);
+        assert_eq!(args.flag_passes, "sccp");
+        assert_eq!(args.flag_emit, "il");
+        let selectors: Vec<String> = args
+            .arg_function
+            .iter()
+            .map(|s| Selector::parse(s).to_string())
+            .collect();
+        assert_eq!(selectors, vec!["main", "0x400000"]);
+    }
+
+    #[test]
+    fn default_pipeline_bin1() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let mut rmod = ModuleLoader::default()
+            .build_ssa()
+            .build_callgraph()
+            .load(Rc::new(FileSource::open(path.to_str().unwrap())));
+        let regfile = rmod.functions[&0x40059d].ssa().regfile.clone();
+
+        let args = args(&["radeco", "bin1"]);
+        let mut pm = pipeline(&args.flag_passes, args.flag_verify).unwrap();
+        if let Err(e) = pm.run_module(&mut rmod, &regfile) {
+            panic!("Default pipeline failed: {}", e);
+        }
+        // The call graph is rebuilt after the passes that modify the SSA.
+        let passes: Vec<&str> = pm.timings().iter().map(|t| t.pass).collect();
+        let tie = passes.iter().position(|&p| p == "tie").unwrap();
+        assert_eq!(passes[tie - 1], "callgraph");
+    }
+}
@@ -122,7 +122,9 @@ impl<B: RBindings> RadecoFunction<B> {
         {
             let regfile = SubRegisterFile::new(reg_profile);
             let mut constructor = SSAConstruct::new(&mut rfn.ssa, &regfile);
-            constructor.run(insts.as_slice());
+            if let Err(e) = constructor.run(insts.as_slice()) {
+                radeco_err!("{}", e);
+            }
         }
         rfn
     }
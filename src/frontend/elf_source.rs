@@ -0,0 +1,881 @@
+//! `Source` that reads module metadata directly from an ELF file.
+//!
+//! Unlike `WrappedR2Api` and `FileSource`, `ElfSource` needs neither a running radare2 nor
+//! pre-dumped json files. It provides sections, symbols, imports, exports, relocations,
+//! needed libraries, the entrypoint and strings on its own. Everything that requires
+//! disassembly (instructions, register profile, locals, calling conventions, ...) is delegated
+//! to another `Source` attached with `ElfSource::disassembler`.
+//!
+//! ```rust,no_run
+//! # extern crate radeco_lib;
+//! # use std::rc::Rc;
+//! # use radeco_lib::frontend::elf_source::ElfSource;
+//! # use radeco_lib::frontend::radeco_containers::ModuleLoader;
+//! # fn main() {
+//! let elf = ElfSource::open("/bin/ls").expect("Not an ELF file");
+//! // Only metadata is loaded as no disassembler is attached.
+//! let rmod = ModuleLoader::default().try_load(Rc::new(elf)).unwrap();
+//! for s in rmod.sections().iter() {
+//!     println!("{:?}", s.name);
+//! }
+//! # }
+//! ```
+
+use base64;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+use r2api::structs::{
+    FunctionInfo, LCCInfo, LEntryInfo, LExportInfo, LFlagInfo, LImportInfo, LOpInfo, LRegInfo,
+    LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo, LSymbolType, LVarInfo,
+};
+
+use frontend::radeco_source::{Source, SourceErr};
+
+const ELFMAG: &[u8] = b"\x7fELF";
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_DYNSYM: u32 = 11;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+const PT_LOAD: u32 = 1;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+const SHN_UNDEF: u16 = 0;
+
+/// Size of a PLT stub if the section does not say otherwise (x86 and x86_64).
+const PLT_ENTRY_SIZE: u64 = 16;
+/// Shortest run of printable characters that is reported as a string.
+const MIN_STRING_LEN: usize = 4;
+
+#[derive(Clone, Debug)]
+struct SectionHeader {
+    name: String,
+    stype: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+    ptype: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Symbol {
+    index: u64,
+    name: String,
+    value: u64,
+    size: u64,
+    bind: u8,
+    stype: u8,
+    shndx: u16,
+}
+
+#[derive(Clone, Debug)]
+struct Relocation {
+    offset: u64,
+    sym: u64,
+}
+
+/// Bounds checked reads of ELF structures with the byte order and word size of the file.
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: u64, len: u64) -> Result<&'a [u8], SourceErr> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.data.len() as u64 => {
+                Ok(&self.data[offset as usize..end as usize])
+            }
+            _ => Err(SourceErr::SrcErr("Truncated ELF file")),
+        }
+    }
+
+    fn uint(&self, offset: u64, len: u64) -> Result<u64, SourceErr> {
+        let bytes = self.bytes(offset, len)?;
+        let fold = |acc: u64, b: &u8| (acc << 8) | u64::from(*b);
+        if self.is_le {
+            Ok(bytes.iter().rev().fold(0, fold))
+        } else {
+            Ok(bytes.iter().fold(0, fold))
+        }
+    }
+
+    fn read_u8(&self, offset: u64) -> Result<u8, SourceErr> {
+        Ok(self.uint(offset, 1)? as u8)
+    }
+
+    fn read_u16(&self, offset: u64) -> Result<u16, SourceErr> {
+        Ok(self.uint(offset, 2)? as u16)
+    }
+
+    fn read_u32(&self, offset: u64) -> Result<u32, SourceErr> {
+        Ok(self.uint(offset, 4)? as u32)
+    }
+
+    /// Size of a target word, 4 bytes for ELF32 and 8 bytes for ELF64.
+    fn word_size(&self) -> u64 {
+        if self.is_64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn word(&self, offset: u64) -> Result<u64, SourceErr> {
+        self.uint(offset, self.word_size())
+    }
+
+    /// Offset of the `index`th entry of a table at `base`. The entry has to start inside the
+    /// file, so adding the offset of one of its fields to the result cannot overflow.
+    fn entry(&self, base: u64, index: u64, entsize: u64) -> Result<u64, SourceErr> {
+        match index.checked_mul(entsize).and_then(|o| o.checked_add(base)) {
+            Some(off) if off < self.data.len() as u64 => Ok(off),
+            _ => Err(SourceErr::SrcErr("ELF table entry out of bounds")),
+        }
+    }
+
+    /// NUL terminated string at `offset` in a string table starting at `base`.
+    fn cstr(&self, base: u64, offset: u64) -> Result<String, SourceErr> {
+        let start = match base.checked_add(offset) {
+            Some(start) if start < self.data.len() as u64 => start,
+            _ => return Err(SourceErr::SrcErr("String offset out of bounds")),
+        };
+        let tail = &self.data[start as usize..];
+        let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+        Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+    }
+}
+
+/// `Source` backed by an ELF file on disk. See the module documentation.
+pub struct ElfSource {
+    data: Vec<u8>,
+    is_64: bool,
+    is_le: bool,
+    entry: u64,
+    sections: Vec<SectionHeader>,
+    segments: Vec<Segment>,
+    disassembler: Option<Rc<Source>>,
+}
+
+impl ElfSource {
+    /// Read and parse the ELF file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ElfSource, SourceErr> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        ElfSource::from_bytes(data)
+    }
+
+    /// Parse an in-memory ELF image.
+    pub fn from_bytes(data: Vec<u8>) -> Result<ElfSource, SourceErr> {
+        if data.len() < 16 || &data[..4] != ELFMAG {
+            return Err(SourceErr::SrcErr("Not an ELF file"));
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(SourceErr::SrcErr("Unknown ELF class")),
+        };
+        let is_le = match data[5] {
+            1 => true,
+            2 => false,
+            _ => return Err(SourceErr::SrcErr("Unknown ELF data encoding")),
+        };
+
+        let (entry, sections, segments) = {
+            let r = Reader {
+                data: &data,
+                is_64: is_64,
+                is_le: is_le,
+            };
+            let w = r.word_size();
+            // e_entry, e_phoff and e_shoff follow each other, the remaining fields are 16 bit
+            // and start after e_flags.
+            let entry = r.word(24)?;
+            let phoff = r.word(24 + w)?;
+            let shoff = r.word(24 + 2 * w)?;
+            let half = 24 + 3 * w + 4 + 2;
+            let phentsize = u64::from(r.read_u16(half)?);
+            let phnum = u64::from(r.read_u16(half + 2)?);
+            let shentsize = u64::from(r.read_u16(half + 4)?);
+            let shnum = u64::from(r.read_u16(half + 6)?);
+            let shstrndx = r.read_u16(half + 8)? as usize;
+
+            let mut segments = Vec::new();
+            for i in 0..phnum {
+                let off = r.entry(phoff, i, phentsize)?;
+                // p_flags sits right after p_type in ELF64 and at the end in ELF32.
+                let fields = if is_64 { off + 8 } else { off + 4 };
+                segments.push(Segment {
+                    ptype: r.read_u32(off)?,
+                    offset: r.word(fields)?,
+                    vaddr: r.word(fields + w)?,
+                    filesz: r.word(fields + 3 * w)?,
+                });
+            }
+
+            let mut sections = Vec::new();
+            let mut name_offsets = Vec::new();
+            if shoff != 0 {
+                for i in 0..shnum {
+                    let off = r.entry(shoff, i, shentsize)?;
+                    name_offsets.push(u64::from(r.read_u32(off)?));
+                    sections.push(SectionHeader {
+                        name: String::new(),
+                        stype: r.read_u32(off + 4)?,
+                        flags: r.word(off + 8)?,
+                        addr: r.word(off + 8 + w)?,
+                        offset: r.word(off + 8 + 2 * w)?,
+                        size: r.word(off + 8 + 3 * w)?,
+                        link: r.read_u32(off + 8 + 4 * w)?,
+                        entsize: r.word(off + 16 + 5 * w)?,
+                    });
+                }
+            }
+            if let Some(shstr) = sections.get(shstrndx).map(|s| s.offset) {
+                for (section, name_off) in sections.iter_mut().zip(name_offsets) {
+                    section.name = r.cstr(shstr, name_off)?;
+                }
+            }
+            (entry, sections, segments)
+        };
+
+        Ok(ElfSource {
+            data: data,
+            is_64: is_64,
+            is_le: is_le,
+            entry: entry,
+            sections: sections,
+            segments: segments,
+            disassembler: None,
+        })
+    }
+
+    /// Delegate everything that needs disassembly to `disassembler`.
+    pub fn disassembler(mut self, disassembler: Rc<Source>) -> ElfSource {
+        self.disassembler = Some(disassembler);
+        self
+    }
+
+    fn backend(&self) -> Result<&Rc<Source>, SourceErr> {
+        self.disassembler
+            .as_ref()
+            .ok_or(SourceErr::SrcErr("No disassembler attached to ElfSource"))
+    }
+
+    fn reader(&self) -> Reader {
+        Reader {
+            data: &self.data,
+            is_64: self.is_64,
+            is_le: self.is_le,
+        }
+    }
+
+    fn section_named(&self, name: &str) -> Option<&SectionHeader> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Translate a virtual address to an offset in the file.
+    fn paddr_of(&self, vaddr: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|s| s.ptype == PT_LOAD && vaddr >= s.vaddr && vaddr - s.vaddr < s.filesz)
+            .and_then(|s| s.offset.checked_add(vaddr - s.vaddr))
+    }
+
+    /// Address the first loadable segment expects to be mapped at.
+    fn base_address(&self) -> u64 {
+        self.segments
+            .iter()
+            .filter(|s| s.ptype == PT_LOAD)
+            .map(|s| s.vaddr.wrapping_sub(s.offset))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn symbols_in(&self, table: &SectionHeader) -> Result<Vec<Symbol>, SourceErr> {
+        let r = self.reader();
+        let strtab = self
+            .sections
+            .get(table.link as usize)
+            .ok_or(SourceErr::SrcErr("Symbol table without string table"))?;
+        let entsize = match table.entsize {
+            0 if self.is_64 => 24,
+            0 => 16,
+            n => n,
+        };
+        let mut symbols = Vec::new();
+        for index in 0..table.size / entsize {
+            let off = r.entry(table.offset, index, entsize)?;
+            let (info, shndx, value, size) = if self.is_64 {
+                (
+                    r.read_u8(off + 4)?,
+                    r.read_u16(off + 6)?,
+                    r.word(off + 8)?,
+                    r.word(off + 16)?,
+                )
+            } else {
+                (
+                    r.read_u8(off + 12)?,
+                    r.read_u16(off + 14)?,
+                    r.word(off + 4)?,
+                    r.word(off + 8)?,
+                )
+            };
+            symbols.push(Symbol {
+                index: index,
+                name: r.cstr(strtab.offset, u64::from(r.read_u32(off)?))?,
+                value: value,
+                size: size,
+                bind: info >> 4,
+                stype: info & 0xf,
+                shndx: shndx,
+            });
+        }
+        Ok(symbols)
+    }
+
+    fn relocations_in(&self, table: &SectionHeader) -> Result<Vec<Relocation>, SourceErr> {
+        let r = self.reader();
+        let w = r.word_size();
+        let entsize = match table.entsize {
+            0 if table.stype == SHT_RELA => 3 * w,
+            0 => 2 * w,
+            n => n,
+        };
+        let mut relocs = Vec::new();
+        for i in 0..table.size / entsize {
+            let off = r.entry(table.offset, i, entsize)?;
+            let info = r.word(off + w)?;
+            relocs.push(Relocation {
+                offset: r.word(off)?,
+                sym: if self.is_64 { info >> 32 } else { info >> 8 },
+            });
+        }
+        Ok(relocs)
+    }
+
+    /// Symbols of `.dynsym`, or nothing for a static binary.
+    fn dynamic_symbols(&self) -> Result<Vec<Symbol>, SourceErr> {
+        match self.sections.iter().find(|s| s.stype == SHT_DYNSYM) {
+            Some(table) => self.symbols_in(table),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Symbols of `.symtab` if the binary is not stripped, `.dynsym` otherwise.
+    fn all_symbols(&self) -> Result<Vec<Symbol>, SourceErr> {
+        match self.sections.iter().find(|s| s.stype == SHT_SYMTAB) {
+            Some(table) => self.symbols_in(table),
+            None => self.dynamic_symbols(),
+        }
+    }
+
+    /// Map from the index of a dynamic symbol to the PLT stub that jumps to it.
+    ///
+    /// The stubs are assumed to be laid out in the order of the `.rel(a).plt` entries, which is
+    /// what the x86 and x86_64 toolchains do.
+    fn plt_stubs(&self) -> Result<HashMap<u64, u64>, SourceErr> {
+        let mut stubs = HashMap::new();
+        let relplt = match self
+            .sections
+            .iter()
+            .find(|s| s.name == ".rela.plt" || s.name == ".rel.plt")
+        {
+            Some(s) => s,
+            None => return Ok(stubs),
+        };
+        let stub_size = |s: &SectionHeader| {
+            if s.entsize != 0 {
+                s.entsize
+            } else {
+                PLT_ENTRY_SIZE
+            }
+        };
+        // With IBT the stubs live in `.plt.sec`, otherwise the first `.plt` entry is the
+        // resolver trampoline.
+        let (mut stub, size) = if let Some(plt) = self.section_named(".plt.sec") {
+            (plt.addr, stub_size(plt))
+        } else if let Some(plt) = self.section_named(".plt") {
+            (plt.addr + stub_size(plt), stub_size(plt))
+        } else {
+            return Ok(stubs);
+        };
+        for rel in self.relocations_in(relplt)? {
+            stubs.insert(rel.sym, stub);
+            stub += size;
+        }
+        Ok(stubs)
+    }
+
+    fn scan_strings(
+        &self,
+        section: &SectionHeader,
+        strings: &mut Vec<LStringInfo>,
+    ) -> Result<(), SourceErr> {
+        let bytes = self.reader().bytes(section.offset, section.size)?;
+        let mut start = None;
+        for (i, &b) in bytes.iter().enumerate() {
+            if is_printable(b) {
+                start = start.or(Some(i));
+                continue;
+            }
+            match start.take() {
+                Some(s) if b == 0 && i - s >= MIN_STRING_LEN => {
+                    let text = escape(&bytes[s..i]);
+                    let ordinal = strings.len() as u64;
+                    strings.push(LStringInfo {
+                        length: Some((i - s) as u64),
+                        ordinal: Some(ordinal),
+                        paddr: Some(section.offset + s as u64),
+                        section: Some(section.name.clone()),
+                        size: Some((i - s) as u64 + 1),
+                        string: Some(base64::encode(&text)),
+                        vaddr: Some(section.addr + s as u64),
+                        ..Default::default()
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_printable(b: u8) -> bool {
+    (b >= 0x20 && b < 0x7f) || b == b'\t' || b == b'\n' || b == b'\r'
+}
+
+/// Escape control characters the way radare2 does before encoding strings.
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'\t' => s.push_str("\\t"),
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            _ => s.push(b as char),
+        }
+    }
+    s
+}
+
+fn bind_name(bind: u8) -> &'static str {
+    match bind {
+        STB_GLOBAL => "GLOBAL",
+        STB_WEAK => "WEAK",
+        _ => "LOCAL",
+    }
+}
+
+// Only function symbols are interpreted further by the loader.
+fn symbol_type(stype: u8) -> Option<LSymbolType> {
+    if stype == STT_FUNC {
+        Some(LSymbolType::Func)
+    } else {
+        None
+    }
+}
+
+/// Keep the characters radare2 keeps in flag names.
+fn flag_name(s: &str) -> String {
+    s.chars()
+        .filter_map(|c| match c {
+            ' ' => Some('_'),
+            c if c.is_ascii_alphanumeric() || c == '.' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Source for ElfSource {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        if let Some(ref disassembler) = self.disassembler {
+            return disassembler.functions();
+        }
+        Ok(self
+            .all_symbols()?
+            .into_iter()
+            .filter(|s| s.stype == STT_FUNC && s.shndx != SHN_UNDEF && s.value != 0)
+            .map(|s| FunctionInfo {
+                name: Some(s.name),
+                offset: Some(s.value),
+                size: Some(s.size),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.backend()?.instructions_at(address)
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        self.backend()?.register_profile()
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        let mut flags = vec![LFlagInfo {
+            offset: self.entry,
+            name: "entry0".to_owned(),
+            size: 1,
+        }];
+        for s in self.sections.iter().filter(|s| s.addr != 0) {
+            flags.push(LFlagInfo {
+                offset: s.addr,
+                name: format!("section.{}", s.name),
+                size: s.size,
+            });
+        }
+        for s in self.symbols()? {
+            if let (Some(name), Some(vaddr)) = (s.name, s.vaddr) {
+                flags.push(LFlagInfo {
+                    offset: vaddr,
+                    name: format!("sym.{}", name),
+                    size: s.size.unwrap_or(0),
+                });
+            }
+        }
+        for i in self.imports()? {
+            if let (Some(name), Some(plt)) = (i.name, i.plt) {
+                flags.push(LFlagInfo {
+                    offset: plt,
+                    name: format!("sym.imp.{}", name),
+                    size: PLT_ENTRY_SIZE,
+                });
+            }
+        }
+        let r = self.reader();
+        for s in self.strings(true)? {
+            if let (Some(vaddr), Some(paddr)) = (s.vaddr, s.paddr) {
+                flags.push(LFlagInfo {
+                    offset: vaddr,
+                    name: format!("str.{}", flag_name(&r.cstr(paddr, 0)?)),
+                    size: s.size.unwrap_or(0),
+                });
+            }
+        }
+        Ok(flags)
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        Ok(self
+            .sections
+            .iter()
+            .map(|s| {
+                let perm = |flag, c| if s.flags & flag != 0 { c } else { '-' };
+                LSectionInfo {
+                    flags: Some(format!(
+                        "--{}{}{}",
+                        perm(SHF_ALLOC, 'r'),
+                        perm(SHF_WRITE, 'w'),
+                        perm(SHF_EXECINSTR, 'x')
+                    )),
+                    name: Some(s.name.clone()),
+                    paddr: Some(s.offset),
+                    size: Some(s.size),
+                    vaddr: Some(s.addr),
+                    vsize: Some(s.size),
+                }
+            })
+            .collect())
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        Ok(self
+            .all_symbols()?
+            .into_iter()
+            .filter(|s| {
+                s.shndx != SHN_UNDEF
+                    && !s.name.is_empty()
+                    && s.stype != STT_SECTION
+                    && s.stype != STT_FILE
+            })
+            .map(|s| LSymbolInfo {
+                name: Some(s.name),
+                vaddr: Some(s.value),
+                size: Some(s.size),
+                stype: symbol_type(s.stype),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        let stubs = self.plt_stubs()?;
+        Ok(self
+            .dynamic_symbols()?
+            .into_iter()
+            .filter(|s| s.shndx == SHN_UNDEF && !s.name.is_empty())
+            .map(|s| LImportInfo {
+                bind: Some(bind_name(s.bind).to_owned()),
+                name: Some(s.name),
+                ordinal: Some(s.index),
+                plt: stubs.get(&s.index).cloned(),
+                itype: symbol_type(s.stype),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        Ok(self
+            .dynamic_symbols()?
+            .into_iter()
+            .filter(|s| {
+                s.shndx != SHN_UNDEF
+                    && !s.name.is_empty()
+                    && (s.bind == STB_GLOBAL || s.bind == STB_WEAK)
+                    && (s.stype == STT_FUNC || s.stype == STT_OBJECT)
+            })
+            .map(|s| LExportInfo {
+                name: Some(s.name),
+                vaddr: Some(s.value),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        let mut relocs = Vec::new();
+        // Only relocations applied by the dynamic loader, in the order they appear.
+        for table in self
+            .sections
+            .iter()
+            .filter(|s| (s.stype == SHT_RELA || s.stype == SHT_REL) && s.flags & SHF_ALLOC != 0)
+        {
+            let symbols = match self.sections.get(table.link as usize) {
+                Some(symtab) if symtab.stype == SHT_DYNSYM || symtab.stype == SHT_SYMTAB => {
+                    self.symbols_in(symtab)?
+                }
+                _ => Vec::new(),
+            };
+            for rel in self.relocations_in(table)? {
+                let name = symbols
+                    .get(rel.sym as usize)
+                    .filter(|s| rel.sym != 0 && !s.name.is_empty())
+                    .map(|s| s.name.clone());
+                relocs.push(LRelocInfo {
+                    name: name,
+                    paddr: self.paddr_of(rel.offset),
+                    vaddr: Some(rel.offset),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(relocs)
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        let dynamic = match self.sections.iter().find(|s| s.stype == SHT_DYNAMIC) {
+            Some(s) => s,
+            None => return Ok(Vec::new()),
+        };
+        let dynstr = self
+            .sections
+            .get(dynamic.link as usize)
+            .ok_or(SourceErr::SrcErr("Dynamic section without string table"))?;
+        let r = self.reader();
+        let w = r.word_size();
+        let mut libs = Vec::new();
+        for i in 0..dynamic.size / (2 * w) {
+            let off = r.entry(dynamic.offset, i, 2 * w)?;
+            match r.word(off)? {
+                DT_NULL => break,
+                DT_NEEDED => libs.push(r.cstr(dynstr.offset, r.word(off + w)?)?),
+                _ => {}
+            }
+        }
+        Ok(libs)
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        Ok(vec![LEntryInfo {
+            vaddr: Some(self.entry),
+            paddr: self.paddr_of(self.entry),
+            baddr: Some(self.base_address()),
+            ..Default::default()
+        }])
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.backend()?.disassemble_function(name)
+    }
+
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.backend()?.disassemble_n_bytes(n, at)
+    }
+
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.backend()?.disassemble_n_insts(n, at)
+    }
+
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        self.backend()?.locals_of(start_addr)
+    }
+
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        self.backend()?.cc_info_of(start_addr)
+    }
+
+    /// Strings in the data sections if `data_only`, in every section with contents otherwise.
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        let mut strings = Vec::new();
+        for s in self.sections.iter().filter(|s| {
+            s.stype != SHT_NOBITS && s.size != 0 && (!data_only || s.name.contains("data"))
+        }) {
+            self.scan_strings(s, &mut strings)?;
+        }
+        Ok(strings)
+    }
+
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        self.backend()?.raw(cmd)
+    }
+
+    fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+        let paddr = self
+            .paddr_of(at)
+            .ok_or(SourceErr::SrcErr("Address is not backed by the file"))?;
+        Ok(self.reader().bytes(paddr, n)?.to_vec())
+    }
+
+    fn send(&self, s: String) -> Result<(), SourceErr> {
+        match self.disassembler {
+            Some(ref disassembler) => disassembler.send(s),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use frontend::radeco_containers::ModuleLoader;
+    use frontend::radeco_source::FileSource;
+    use std::path::PathBuf;
+
+    // `test_files/bin_file` holds what radare2 reports for `test_files/file`.
+    fn open_file() -> (ElfSource, FileSource) {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/file");
+        let elf = ElfSource::open(&path).expect("Unable to parse ELF");
+        path.pop();
+        path.push("bin_file/bin_file");
+        (elf, FileSource::open(path.to_str().unwrap()))
+    }
+
+    #[test]
+    fn elf_source_metadata_test() {
+        let (elf, r2) = open_file();
+
+        let sections = elf.sections().unwrap();
+        let r2_sections = r2.sections().unwrap();
+        assert_eq!(sections.len(), 30);
+        for (s, r2_s) in sections.iter().zip(r2_sections.iter()) {
+            assert_eq!(s.name, r2_s.name);
+            assert_eq!(s.vaddr, r2_s.vaddr);
+            assert_eq!(s.paddr, r2_s.paddr);
+            assert_eq!(s.size, r2_s.size);
+            assert_eq!(s.flags, r2_s.flags);
+        }
+
+        let strings = elf.strings(true).unwrap();
+        let r2_strings = r2.strings(true).unwrap();
+        assert_eq!(strings.len(), r2_strings.len());
+        for (s, r2_s) in strings.iter().zip(r2_strings.iter()) {
+            assert_eq!(s.vaddr, r2_s.vaddr);
+            assert_eq!(s.string, r2_s.string);
+        }
+
+        let ep = elf.entrypoint().unwrap();
+        assert_eq!(ep[0].vaddr, Some(0x2640));
+        assert_eq!(ep[0].paddr, Some(0x2640));
+        assert_eq!(
+            elf.libraries().unwrap(),
+            vec!["libmagic.so.1".to_owned(), "libc.so.6".to_owned()]
+        );
+    }
+
+    #[test]
+    fn elf_source_corrupt_header_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/file");
+        let mut data = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+
+        // e_phoff at the very end of the address space.
+        let mut corrupt = data.clone();
+        for b in &mut corrupt[32..40] {
+            *b = 0xff;
+        }
+        assert!(ElfSource::from_bytes(corrupt).is_err());
+
+        // A huge e_shentsize pushes all but the first section header past the end of the file.
+        let mut corrupt = data.clone();
+        corrupt[58] = 0xff;
+        corrupt[59] = 0xff;
+        assert!(ElfSource::from_bytes(corrupt).is_err());
+
+        // Cut off right after the ELF header.
+        assert!(ElfSource::from_bytes(data[..64].to_vec()).is_err());
+    }
+
+    #[test]
+    fn elf_source_imports_test() {
+        let (elf, _) = open_file();
+        let imports = elf.imports().unwrap();
+        let getenv = imports
+            .iter()
+            .find(|i| i.name.as_ref().map(|n| n == "getenv").unwrap_or(false))
+            .expect("getenv is not imported");
+        // First entry of `.rela.plt`, right after the resolver stub at 0x1600.
+        assert_eq!(getenv.plt, Some(0x1610));
+        assert_eq!(imports.iter().filter(|i| i.plt.is_some()).count(), 39);
+        assert!(elf.instructions_at(0x2640).is_err());
+    }
+
+    #[test]
+    fn elf_source_module_loader_test() {
+        let (elf, _) = open_file();
+        let rmod = ModuleLoader::default()
+            .try_load(Rc::new(elf))
+            .expect("Unable to load module");
+        assert!(rmod.imports.contains_key(&0x1610));
+        assert_eq!(rmod.sections().len(), 30);
+        assert_eq!(rmod.strings().len(), 91);
+    }
+}
@@ -1,26 +1,39 @@
 //! Defines structs and methods to deal with imports and dynamic linking
 
-use frontend::radeco_containers::RadecoFunction;
+use frontend::radeco_containers::{FunctionType, RadecoFunction};
 use std::borrow::Cow;
 use std::cell::RefCell;
 
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ImportInfo {
     pub plt: u64,
     pub name: Cow<'static, str>,
     pub rfn: Arc<RefCell<RadecoFunction>>,
+    /// Function defining this import. `None` until the library exporting it is loaded.
+    pub target: Option<ImportTarget>,
+}
+
+/// Location of an imported function in the `RadecoProject`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportTarget {
+    /// Index of the module defining the function
+    pub module: u16,
+    /// Offset of the function in that module
+    pub offset: u64,
 }
 
 impl ImportInfo {
     pub fn new_stub(plt: u64, name: Cow<'static, str>) -> ImportInfo {
         let mut rfn = RadecoFunction::default();
         rfn.name = name.clone();
+        rfn.ftype = FunctionType::Import(u16::max_value());
         ImportInfo {
             plt: plt,
             name: name,
             rfn: Arc::new(RefCell::new(rfn)),
+            target: None,
         }
     }
 }
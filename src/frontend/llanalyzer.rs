@@ -61,6 +61,33 @@ pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGrap
     cg
 }
 
+/// Matches the edges of the call graph of `rmod` to the call sites in the SSA of its functions
+/// again, after the SSA has been modified. Call sites are matched by address, and their
+/// contexts are reset to the ones the loader starts from. Edges whose call site is gone are
+/// removed.
+pub fn refresh_call_graph(rmod: &mut RadecoModule) {
+    let mut stale = Vec::new();
+    for rfn in rmod.functions.values() {
+        let csites = analyze_callsite_initial(rfn);
+        let mut edges = rmod
+            .callgraph
+            .neighbors_directed(rfn.cgid(), Direction::Outgoing)
+            .detach();
+        while let Some(call_edge) = edges.next_edge(&rmod.callgraph) {
+            let csite = rmod.callgraph[call_edge].csite;
+            match csites.get(&csite) {
+                Some(cctx) => rmod.callgraph[call_edge] = cctx.clone(),
+                None => stale.push(call_edge),
+            }
+        }
+    }
+    // Removing an edge moves the last one into its place, so the last ones go first.
+    stale.sort();
+    for call_edge in stale.into_iter().rev() {
+        rmod.callgraph.remove_edge(call_edge);
+    }
+}
+
 /// Iterates through nodes in SSA for rfn and initializes the inital CallContextInfo
 fn analyze_callsite_initial(rfn: &RadecoFunction) -> HashMap<u64, CallContextInfo> {
     let mut cctxs = HashMap::new();
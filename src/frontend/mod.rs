@@ -11,6 +11,7 @@ pub mod source;
 /*********************/
 
 // New replacements
+pub mod elf_source;
 pub mod radeco_containers;
 pub mod radeco_source;
 
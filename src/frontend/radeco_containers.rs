@@ -26,17 +26,25 @@
 //! # }
 //! ```
 //!
+//! `load` panics if the binary cannot be loaded at all. Use `try_load` to get a `LoadError`
+//! instead. Problems that only affect parts of a binary, such as malformed metadata for a
+//! function or a function whose SSA could not be constructed, do not fail the loading. These
+//! are recorded as `LoadError`s in the loaded `RadecoModule`, see `RadecoModule::load_errors`.
+//!
 //! All default options are defined under `radeco_containers::loader_defaults`.
 //!
 //! For more examples of loading, check the `examples/` directory of this project.
 
-use frontend::imports::ImportInfo;
+use analysis::jump_tables;
+use analysis::tie::structs::RType;
+use frontend::imports::{ImportInfo, ImportTarget};
 use frontend::llanalyzer;
-use frontend::radeco_source::Source;
-use frontend::ssaconstructor::SSAConstruct;
+use frontend::radeco_source::{Source, SourceErr};
+use frontend::ssaconstructor::{LiftDiagnostic, SSAConstruct};
 
 use middle::regfile::{RegisterUsage, SubRegisterFile};
 use middle::ssa::cfg_traits::CFG;
+use middle::ssa::memoryssa::{MemoryGraph, MemorySSA};
 use middle::ssa::ssa_traits::{NodeType, SSA};
 
 use middle::ssa::ssastorage::SSAStorage;
@@ -46,18 +54,22 @@ use petgraph::graph::{Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use r2api::api_trait::R2Api;
 use r2api::structs::{
-    LCCInfo, LEntryInfo, LExportInfo, LOpInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo,
-    LSymbolType, LVarInfo,
+    LCCInfo, LEntryInfo, LExportInfo, LOpInfo, LRegInfo, LRelocInfo, LSectionInfo, LStringInfo,
+    LSymbolInfo, LSymbolType, LVarInfo, LVarRef,
 };
 
 use r2pipe::r2::R2;
 use rayon::prelude::*;
+use serde_json;
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::{btree_map, HashSet};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::slice;
 use std::sync::Arc;
@@ -67,7 +79,7 @@ use std::sync::Arc;
 /// Defines sane defaults for the loading process.
 pub mod loader_defaults {
     use super::FLResult;
-    use super::{RadecoFunction, RadecoModule};
+    use super::{LoadError, RadecoFunction, RadecoModule};
     use frontend::radeco_source::Source;
     use r2api::structs::LSymbolType;
     use std::borrow::Cow;
@@ -89,10 +101,20 @@ pub mod loader_defaults {
                 }
             })
             .fold(FLResult::default(), |mut acc, s| {
+                let (name, offset) = match (s.name.as_ref(), s.vaddr) {
+                    (Some(name), Some(offset)) => (name, offset),
+                    _ => {
+                        acc.errors.push(LoadError::MalformedMetadata(Cow::from(format!(
+                            "Function symbol without name or address: {:?}",
+                            s
+                        ))));
+                        return acc;
+                    }
+                };
                 let mut rfn = RadecoFunction::default();
-                rfn.name = Cow::from(s.name.as_ref().unwrap().to_owned());
-                rfn.offset = s.vaddr.unwrap();
-                rfn.size = s.size.unwrap();
+                rfn.name = Cow::from(name.to_owned());
+                rfn.offset = offset;
+                rfn.size = s.size.unwrap_or(0);
 
                 acc.functions.insert(rfn.offset, rfn);
                 acc.new += 1;
@@ -106,33 +128,102 @@ pub mod loader_defaults {
         fl: &FLResult,
         _rmod: &RadecoModule,
     ) -> FLResult {
+        let mut new_fl = FLResult::default();
         // Load function information fom `Source`
         if let Some(ref src) = source {
-            let mut new_fl = FLResult::default();
-            if let Ok(ref functions) = src.functions() {
-                for function in functions {
-                    let mut rfn = RadecoFunction::default();
-                    rfn.offset = function.offset.unwrap();
-                    rfn.size = function.size.unwrap();
-                    rfn.name = Cow::from(function.name.as_ref().unwrap().to_owned());
-                    rfn.callconv_name = function.calltype.as_ref().unwrap().to_owned();
-                    new_fl.functions.insert(rfn.offset, rfn);
-                    new_fl.new = new_fl.new + 1;
+            match src.functions() {
+                Ok(ref functions) => {
+                    for function in functions {
+                        let (name, offset) = match (function.name.as_ref(), function.offset) {
+                            (Some(name), Some(offset)) => (name, offset),
+                            _ => {
+                                new_fl.errors.push(LoadError::MalformedMetadata(Cow::from(
+                                    format!("Function without name or offset: {:?}", function),
+                                )));
+                                continue;
+                            }
+                        };
+                        let mut rfn = RadecoFunction::default();
+                        rfn.offset = offset;
+                        rfn.size = function.size.unwrap_or(0);
+                        rfn.name = Cow::from(name.to_owned());
+                        rfn.callconv_name = function.calltype.clone().unwrap_or_default();
+                        new_fl.functions.insert(rfn.offset, rfn);
+                        new_fl.new = new_fl.new + 1;
+                    }
                 }
+                Err(e) => new_fl.errors.push(LoadError::Source(e)),
             }
-            new_fl
         } else {
-            fl.clone()
+            // Nothing to analyze, pass on what has been identified so far. Errors
+            // were already reported by the strategy that produced them.
+            new_fl.functions = fl.functions.clone();
+            new_fl.new = fl.new;
+        }
+        new_fl
+    }
+}
+
+/// Errors encountered while loading a `RadecoProject`
+#[derive(Debug)]
+pub enum LoadError {
+    /// `Source` failed to provide some information
+    Source(SourceErr),
+    /// Information provided by `Source` is missing required fields
+    MalformedMetadata(Cow<'static, str>),
+    /// A library needed by a module could not be found in the library path
+    MissingLibrary(Cow<'static, str>),
+    /// SSA construction failed for a function. The function is dropped from its module.
+    Lift {
+        offset: u64,
+        name: Cow<'static, str>,
+        reason: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Source(ref e) => write!(f, "Source error: {}", e),
+            LoadError::MalformedMetadata(ref s) => write!(f, "Malformed metadata: {}", s),
+            LoadError::MissingLibrary(ref s) => write!(f, "Library not found: {}", s),
+            LoadError::Lift {
+                offset,
+                ref name,
+                ref reason,
+            } => write!(f, "Unable to lift {} @ {:#x}: {}", name, offset, reason),
         }
     }
 }
 
+impl From<SourceErr> for LoadError {
+    fn from(e: SourceErr) -> LoadError {
+        LoadError::Source(e)
+    }
+}
+
 /// Top level container used to hold all analysis
 pub struct RadecoProject {
     /// Map of loaded modules
     modules: Vec<RadecoModule>,
     /// Register/Arch information for loaded project
     reginfo: Arc<SubRegisterFile>,
+    /// Calls between the loaded modules, through their resolved imports
+    import_calls: Vec<ImportCall>,
+}
+
+/// A call from a function of one module to a function defined in another module, made
+/// through an import stub of the calling module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImportCall {
+    /// Index of the calling module
+    pub module: u16,
+    /// Offset of the calling function
+    pub caller: u64,
+    /// Address of the callsite
+    pub csite: u64,
+    /// Function called
+    pub callee: ImportTarget,
 }
 
 // Graph where every node is an Address (function start address) and edges are labeled
@@ -161,7 +252,7 @@ impl CGInfo for CallGraph {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 /// Container to store information about a single loaded binary or library.
 pub struct RadecoModule {
     /// Human-readable name for the  module
@@ -171,6 +262,7 @@ pub struct RadecoModule {
     strings: Vec<LStringInfo>,
     sections: Arc<Vec<LSectionInfo>>,
     // Map from PLT entry address to `ImportInfo` for an import
+    #[serde(with = "::utils::sorted_map")]
     pub imports: HashMap<u64, ImportInfo>,
     exports: Vec<LExportInfo>,
     relocs: Vec<LRelocInfo>,
@@ -182,7 +274,11 @@ pub struct RadecoModule {
     /// Map of functions loaded
     pub functions: BTreeMap<u64, RadecoFunction>,
     /// Source used to load this module
+    #[serde(skip)]
     pub source: Option<Rc<Source>>,
+    /// Non-fatal errors encountered while loading this module
+    #[serde(skip)]
+    load_errors: Vec<LoadError>,
 }
 
 impl fmt::Debug for RadecoModule {
@@ -191,7 +287,7 @@ impl fmt::Debug for RadecoModule {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FunctionType {
     /// Function defined in the current binary
     Function,
@@ -200,7 +296,13 @@ pub enum FunctionType {
     Import(u16),
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+impl Default for FunctionType {
+    fn default() -> FunctionType {
+        FunctionType::Function
+    }
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum BindingType {
     // Arguments - ith argument
     RegisterArgument(usize),
@@ -244,7 +346,7 @@ impl BindingType {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VarBinding {
     pub btype: BindingType,
     name: Cow<'static, str>,
@@ -274,16 +376,29 @@ impl VarBinding {
         }
     }
 
-    fn local(local: LVarInfo) -> VarBinding {
-        let r = local.reference.unwrap();
-        let btype = BindingType::RegisterLocal(r.base.unwrap(), r.offset.unwrap());
-        VarBinding::new(
+    fn local(local: LVarInfo) -> Result<VarBinding, LoadError> {
+        let malformed = || {
+            LoadError::MalformedMetadata(Cow::from(format!(
+                "Local variable without type or location: {:?}",
+                local
+            )))
+        };
+        let (base, offset) = match local.reference {
+            Some(ref r) => match (r.base.clone(), r.offset) {
+                (Some(base), Some(offset)) => (base, offset),
+                _ => return Err(malformed()),
+            },
+            None => return Err(malformed()),
+        };
+        let vtype = local.vtype.clone().ok_or_else(&malformed)?;
+        let btype = BindingType::RegisterLocal(base, offset);
+        Ok(VarBinding::new(
             btype,
-            local.vtype.unwrap(),
-            local.name,
+            vtype,
+            local.name.clone(),
             NodeIndex::end(),
             None,
-        )
+        ))
     }
 
     fn is_matched_reg_local(binding: &VarBinding, reg_name: String, offset: i64) -> bool {
@@ -322,12 +437,12 @@ impl VarBinding {
 
 pub type VarBindings = Vec<VarBinding>;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 /// Container to store information about identified function.
 /// Used as a basic unit in intra-functional analysis.
 pub struct RadecoFunction {
-    // Represents the type of function
-    // ftype: FunctionType,
+    /// Represents the type of function
+    pub ftype: FunctionType,
     /// Raw instruction information for the current function
     pub instructions: Vec<LOpInfo>,
     /// Is current function known to be recursive
@@ -349,6 +464,7 @@ pub struct RadecoFunction {
     bindings: VarBindings,
     // XXX Should it be separated from RadecoFunction?
     // NodeIndex is SSAStorage's NodeIndex
+    #[serde(with = "::utils::sorted_map")]
     binding_map: HashMap<NodeIndex, VarBindings>,
     /// Calling convention of this function
     pub callconv: Option<LCCInfo>,
@@ -357,6 +473,14 @@ pub struct RadecoFunction {
     /// Name of the calling convention of this function (e.g. amd64, ms, arm64, etc.)
     // see https://github.com/radare/radare2/tree/9e08da0fa6b6c36edf04db72d22e065ccc90d381/libr/anal/d
     pub callconv_name: String,
+    /// Instructions that could not be lifted while constructing the SSA
+    pub lift_diagnostics: Vec<LiftDiagnostic>,
+    /// Memory SSA of the function, if built. Dropped whenever the SSA is modified.
+    #[serde(skip)]
+    memory_ssa: Option<MemoryGraph<NodeIndex, NodeIndex>>,
+    /// Types inferred for the values of the SSA. Dropped whenever the SSA is modified.
+    #[serde(skip)]
+    types: HashMap<NodeIndex, RType>,
 }
 
 #[derive(Default)]
@@ -367,6 +491,7 @@ pub struct ProjectLoader<'a> {
     load_library_path: Option<Cow<'static, str>>,
     filter_modules: Option<fn(&RadecoModule) -> bool>,
     source: Option<Rc<Source>>,
+    library_source: Option<fn(&Path) -> Result<Rc<Source>, LoadError>>,
     mloader: Option<ModuleLoader<'a>>,
 }
 
@@ -378,16 +503,16 @@ impl<'a> ProjectLoader<'a> {
             load_library_path: None,
             filter_modules: None,
             source: None,
+            library_source: None,
             mloader: None,
         }
     }
     // TODO:
     //  - Associate identified bins/libs with their ModuleLoaders
-    //  - Implement loading of libraries
     //  - Parallelize module loading as they should have different sources
-    //  - Use filter option
     //  - Setup arch information in `RadecoProject`
-    /// Enable loading of libraries
+    /// Enable loading of libraries. The imports of every module are linked to the functions
+    /// of the libraries exporting them, see `RadecoProject::import_calls`.
     pub fn load_libs(mut self) -> ProjectLoader<'a> {
         self.load_libs = true;
         self
@@ -414,38 +539,57 @@ impl<'a> ProjectLoader<'a> {
 
     /// Set path to look for libraries. The `ProjectLoader` looks for
     /// matching filenames recursively within this directory.
+    /// Defaults to the directory of the loaded binary.
     /// Only used if `load_libs` is true.
     pub fn load_library_path(mut self, path: &'static str) -> ProjectLoader<'a> {
         self.load_library_path = Some(Cow::from(path));
         self
     }
 
-    /// Filter loading of `RadecoModules` based on `f`
+    /// Set the function used to open a `Source` for a library found in the library path.
+    /// Defaults to opening the library in r2.
+    /// Only used if `load_libs` is true.
+    pub fn library_source(
+        mut self,
+        f: fn(&Path) -> Result<Rc<Source>, LoadError>,
+    ) -> ProjectLoader<'a> {
+        self.library_source = Some(f);
+        self
+    }
+
+    /// Filter loading of library `RadecoModules` based on `f`
     pub fn filter_modules(mut self, f: fn(&RadecoModule) -> bool) -> ProjectLoader<'a> {
         self.filter_modules = Some(f);
         self
     }
 
     /// Kick everything off based on the config/defaults
-    pub fn load(mut self) -> RadecoProject {
+    ///
+    /// Panics if the project cannot be loaded, see `try_load`.
+    pub fn load(self) -> RadecoProject {
+        match self.try_load() {
+            Ok(rp) => rp,
+            Err(e) => panic!("Unable to load project: {}", e),
+        }
+    }
+
+    /// Kick everything off based on the config/defaults
+    ///
+    /// Returns an error only if nothing could be loaded. Other errors are recorded in the
+    /// loaded modules.
+    pub fn try_load(mut self) -> Result<RadecoProject, LoadError> {
         if self.source.is_none() {
-            // Load r2 source.
-            let mut r2 = R2::new(Some(&self.path)).expect("Unable to open r2");
-            let _ = r2.raw("e bin.minstr=1".to_string());
-            //New r2 process is launched thus it needs to analyze
-            r2.analyze_all();
-            let r2w = Rc::new(RefCell::new(r2));
-            self.source = Some(Rc::new(r2w));
+            self.source = Some(open_r2_source(Path::new(&*self.path))?);
         };
 
-        let source = self.source.as_ref().unwrap();
+        let source = Rc::clone(self.source.as_ref().unwrap());
 
         // TODO: Load more arch specific information from the source
 
         if self.mloader.is_none() {
             self.mloader = Some(
                 ModuleLoader::default()
-                    .source(Rc::clone(source))
+                    .source(Rc::clone(&source))
                     .build_ssa()
                     .build_callgraph()
                     .load_datarefs()
@@ -460,25 +604,164 @@ impl<'a> ProjectLoader<'a> {
 
         {
             let mod_loader = self.mloader.as_mut().unwrap();
-            // TODO: Set name correctly
-            mod_map.push(mod_loader.load(Rc::clone(source)));
+            if self.load_libs {
+                // Imports need stubs to be linked to the libraries.
+                mod_loader.stub_imports = true;
+            }
+            let mut rmod = mod_loader.try_load(Rc::clone(&source))?;
+            rmod.name = self.path.clone();
+            mod_map.push(rmod);
         }
 
         // Clear out irrelevant fields in self and move it into project loader
         // XXX: Do when needed!
         // self.mod_loader = None;
-        let regfile = SubRegisterFile::new(
-            &source
-                .register_profile()
-                .expect("Unable to load register profile"),
-        );
+        let regfile = SubRegisterFile::new(&source.register_profile()?);
 
-        RadecoProject {
+        let import_calls = if self.load_libs {
+            self.load_libraries(&mut mod_map);
+            link_imports(&mut mod_map);
+            link_call_graphs(&mod_map)
+        } else {
+            Vec::new()
+        };
+
+        Ok(RadecoProject {
             modules: mod_map,
             // XXX
             reginfo: Arc::new(regfile),
+            import_calls: import_calls,
+        })
+    }
+}
+
+impl<'a> ProjectLoader<'a> {
+    // Loads the libraries needed by the modules in `modules`, as well as the libraries needed
+    // by those libraries, and appends them to `modules`.
+    fn load_libraries(&mut self, modules: &mut Vec<RadecoModule>) {
+        let lib_path = match self.load_library_path {
+            Some(ref path) => PathBuf::from(&**path),
+            None => Path::new(&*self.path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+        };
+        let open_source = self.library_source.unwrap_or(open_r2_source);
+        let mod_loader = self.mloader.as_mut().unwrap();
+        // The `Source`s set for the `ModuleLoader` are the ones of the main binary. Libraries
+        // are opened as plain `Source`s, so their functions are loaded sequentially.
+        let main_source = mod_loader.source.take();
+        let main_sync_source = mod_loader.sync_source.take();
+
+        let mut seen = HashSet::new();
+        let mut i = 0;
+        while i < modules.len() {
+            for lib in modules[i].libs.clone() {
+                if !seen.insert(lib.clone()) {
+                    continue;
+                }
+                let res = find_library(&lib_path, &lib)
+                    .ok_or_else(|| LoadError::MissingLibrary(Cow::from(lib.clone())))
+                    .and_then(|path| open_source(&path))
+                    .and_then(|src| mod_loader.try_load(src));
+                match res {
+                    Ok(mut lmod) => {
+                        lmod.name = Cow::from(lib);
+                        if self.filter_modules.map_or(true, |f| f(&lmod)) {
+                            modules.push(lmod);
+                        }
+                    }
+                    Err(e) => {
+                        radeco_warn!(e);
+                        modules[i].load_errors.push(e);
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        mod_loader.source = main_source;
+        mod_loader.sync_source = main_sync_source;
+    }
+}
+
+// Opens `path` in r2 and analyzes it.
+fn open_r2_source(path: &Path) -> Result<Rc<Source>, LoadError> {
+    let path = path
+        .to_str()
+        .ok_or(LoadError::Source(SourceErr::SrcErr("Invalid path")))?;
+    let mut r2 = R2::new(Some(path))
+        .map_err(|_| LoadError::Source(SourceErr::SrcErr("Unable to open r2")))?;
+    let _ = r2.raw("e bin.minstr=1".to_string());
+    //New r2 process is launched thus it needs to analyze
+    r2.analyze_all();
+    let r2w = Rc::new(RefCell::new(r2));
+    Ok(Rc::new(r2w))
+}
+
+// Looks for a file called `name` in `dir` and its subdirectories.
+fn find_library(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut subdirs = Vec::new();
+    for entry in fs::read_dir(dir).ok()?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        // Do not follow symlinks to directories, to not get stuck in cycles.
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            subdirs.push(path);
+        } else if path.file_name().map_or(false, |f| f == name) {
+            return Some(path);
+        }
+    }
+    subdirs.into_iter().filter_map(|d| find_library(&d, name)).next()
+}
+
+// Links the import stubs of every module to the functions exported by the other modules.
+fn link_imports(modules: &mut [RadecoModule]) {
+    let mut exported = HashMap::new();
+    for (i, rmod) in modules.iter().enumerate() {
+        for export in &rmod.exports {
+            if let (Some(name), Some(vaddr)) = (export.name.as_ref(), export.vaddr) {
+                exported.entry(name.clone()).or_insert(ImportTarget {
+                    module: i as u16,
+                    offset: vaddr,
+                });
+            }
+        }
+    }
+
+    for (i, rmod) in modules.iter_mut().enumerate() {
+        for ifn in rmod.imports.values_mut() {
+            if let Some(&target) = exported.get(&*ifn.name) {
+                if target.module as usize == i {
+                    continue;
+                }
+                ifn.target = Some(target);
+                ifn.rfn.borrow_mut().ftype = FunctionType::Import(target.module);
+            }
+        }
+    }
+}
+
+// Follows the calls to the resolved imports of every module to the functions defining them.
+fn link_call_graphs(modules: &[RadecoModule]) -> Vec<ImportCall> {
+    let mut calls = Vec::new();
+    for (i, rmod) in modules.iter().enumerate() {
+        let cg = &rmod.callgraph;
+        for node in cg.node_indices() {
+            let target = match rmod.imports.get(&cg[node]).and_then(|ifn| ifn.target) {
+                Some(target) => target,
+                None => continue,
+            };
+            for edge in cg.edges_directed(node, Direction::Incoming) {
+                calls.push(ImportCall {
+                    module: i as u16,
+                    caller: cg[edge.source()],
+                    csite: edge.weight().csite,
+                    callee: target,
+                });
+            }
         }
     }
+    calls
 }
 
 // Iterators over RadecoProject to yeils RadecoModules
@@ -575,6 +858,7 @@ impl<'f> Iterator for FunctionIterMut<'f> {
 /// Module-level loader used to construct a `RadecoModule`
 pub struct ModuleLoader<'a> {
     source: Option<Rc<Source>>,
+    sync_source: Option<Arc<Source + Send + Sync>>,
     floader: Option<FunctionLoader<'a>>,
     filter: Option<fn(&RadecoFunction) -> bool>,
     build_callgraph: bool,
@@ -584,6 +868,8 @@ pub struct ModuleLoader<'a> {
     parallel: bool,
     assume_cc: bool,
     stub_imports: bool,
+    resolve_jump_tables: bool,
+    build_memory_ssa: bool,
 }
 
 impl<'a> ModuleLoader<'a> {
@@ -600,6 +886,17 @@ impl<'a> ModuleLoader<'a> {
         self
     }
 
+    /// Setup a thread-safe `Source` (e.g. a `SyncSource`) for `ModuleLoader`. With `parallel`,
+    /// per-function information is then queried concurrently.
+    pub fn sync_source<S>(mut self, src: S) -> ModuleLoader<'a>
+    where
+        S: Source + Send + Sync + Clone + 'static,
+    {
+        self.source = Some(Rc::new(src.clone()));
+        self.sync_source = Some(Arc::new(src));
+        self
+    }
+
     /// Builds callgraph. Needs support from `Source`
     pub fn build_callgraph(mut self) -> ModuleLoader<'a> {
         self.build_callgraph = true;
@@ -640,6 +937,20 @@ impl<'a> ModuleLoader<'a> {
         self
     }
 
+    /// Resolves indirect jumps through jump tables into switches once the SSA is built.
+    /// Needs support from `Source` to read the tables.
+    pub fn resolve_jump_tables(mut self) -> ModuleLoader<'a> {
+        self.resolve_jump_tables = true;
+        self
+    }
+
+    /// Builds the Memory SSA of loaded functions, see `RadecoFunction::memory_ssa`.
+    /// Needs `build_ssa`. Uses datarefs, locals and the callgraph if they are loaded.
+    pub fn build_memory_ssa(mut self) -> ModuleLoader<'a> {
+        self.build_memory_ssa = true;
+        self
+    }
+
     /// Create blank, stub entries for imported functions.
     /// Required for load-libs, auto set when load_libs is true for the project loader.
     pub fn stub_imports(mut self) -> ModuleLoader<'a> {
@@ -733,7 +1044,20 @@ impl<'a> ModuleLoader<'a> {
     }
 
     /// Kick everything off and load module information based on config and defaults
+    ///
+    /// Panics if the module cannot be loaded, see `try_load`.
     pub fn load(&mut self, src: Rc<Source>) -> RadecoModule {
+        match self.try_load(src) {
+            Ok(rmod) => rmod,
+            Err(e) => panic!("Unable to load module: {}", e),
+        }
+    }
+
+    /// Kick everything off and load module information based on config and defaults
+    ///
+    /// Returns an error only if nothing could be loaded. Other errors are recorded in
+    /// `RadecoModule::load_errors`.
+    pub fn try_load(&mut self, src: Rc<Source>) -> Result<RadecoModule, LoadError> {
         let source = if self.source.is_some() {
             self.source.as_ref().unwrap()
         } else {
@@ -752,67 +1076,84 @@ impl<'a> ModuleLoader<'a> {
         // Fill in module level information from the `Source`
         match source.symbols() {
             Ok(sym_info) => rmod.symbols = sym_info,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.sections() {
             Ok(section_info) => rmod.sections = Arc::new(section_info),
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.imports() {
             // TODO: Set the node in callgraph, either now or later.
             Ok(import_info) => {
-                rmod.imports = import_info
-                    .iter()
-                    .filter_map(|ii| {
-                        if let Some(plt) = ii.plt {
-                            if let Some(LSymbolType::Func) = ii.itype {
-                                Some((
-                                    plt,
-                                    ImportInfo::new_stub(
-                                        plt,
-                                        Cow::from(ii.name.as_ref().unwrap().clone()),
-                                    ),
-                                ))
-                            } else {
-                                None
-                            }
+                for ii in &import_info {
+                    if let (Some(plt), Some(&LSymbolType::Func)) = (ii.plt, ii.itype.as_ref()) {
+                        if let Some(ref name) = ii.name {
+                            rmod.imports
+                                .insert(plt, ImportInfo::new_stub(plt, Cow::from(name.clone())));
                         } else {
-                            None
+                            rmod.load_errors.push(LoadError::MalformedMetadata(Cow::from(
+                                format!("Import without name @ {:#x}", plt),
+                            )));
                         }
-                    })
-                    .collect();
+                    }
+                }
+            }
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
             }
-            Err(e) => radeco_warn!(e),
         }
 
         match source.exports() {
             Ok(exports) => rmod.exports = exports,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.relocs() {
             Ok(relocs) => rmod.relocs = relocs,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.libraries() {
             Ok(libs) => rmod.libs = libs,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.entrypoint() {
             Ok(ep) => rmod.entrypoint = ep,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         match source.strings(true) {
             Ok(strings) => rmod.strings = strings,
-            Err(e) => radeco_warn!(e),
+            Err(e) => {
+                radeco_warn!(e);
+                rmod.load_errors.push(LoadError::Source(e));
+            }
         }
 
         let mut flresult = floader.load(&rmod);
+        rmod.load_errors.append(&mut flresult.errors);
         flresult.functions = if self.filter.is_some() {
             let filter_fn = self.filter.as_ref().unwrap();
             flresult
@@ -826,15 +1167,25 @@ impl<'a> ModuleLoader<'a> {
 
         rmod.functions = flresult.functions;
 
-        // Load instructions into functions
-        for rfn in rmod.functions.values_mut() {
-            rfn.instructions = source.disassemble_function(&rfn.name).unwrap_or(Vec::new());
+        // Load instructions and calling conventions into functions
+        match self.sync_source {
+            Some(ref sync_source) if self.parallel => {
+                rmod.functions.par_iter_mut().for_each(|(&rfn_addr, rfn)| {
+                    rfn.instructions = sync_source
+                        .disassemble_function(&rfn.name)
+                        .unwrap_or(Vec::new());
+                    rfn.callconv = sync_source.cc_info_of(rfn_addr).ok();
+                });
+            }
+            _ => {
+                for (&rfn_addr, rfn) in &mut rmod.functions {
+                    rfn.instructions = source.disassemble_function(&rfn.name).unwrap_or(Vec::new());
+                    rfn.callconv = source.cc_info_of(rfn_addr).ok();
+                }
+            }
         }
 
-        // Load calling conventions for all functions and imports
-        for (&rfn_addr, rfn) in &mut rmod.functions {
-            rfn.callconv = source.cc_info_of(rfn_addr).ok();
-        }
+        // Load calling conventions for imports
         for (&imp_addr, imp_info) in &mut rmod.imports {
             let imp_rfn = &mut *imp_info.rfn.borrow_mut();
             imp_rfn.callconv = source.cc_info_of(imp_addr).ok();
@@ -843,32 +1194,48 @@ impl<'a> ModuleLoader<'a> {
             }
         }
 
-        // Optionally construct the SSA.
-        let reg_p = source
-            .register_profile()
-            .expect("Unable to load register profile");
+        // Optionally construct the SSA. The register profile is only needed to lift and to bind
+        // arguments, so sources that only provide metadata can be loaded without these.
+        let reg_p = if self.build_ssa || self.stub_imports || self.assume_cc {
+            source.register_profile()?
+        } else {
+            LRegInfo::default()
+        };
         let sub_reg_f = SubRegisterFile::new(&reg_p);
         if self.build_ssa {
-            if self.parallel {
-                let ascc = self.assume_cc;
-                rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, ascc, true);
-                });
+            let ascc = self.assume_cc;
+            let lift_errors: Vec<LoadError> = if self.parallel {
+                rmod.functions
+                    .par_iter_mut()
+                    .filter_map(|(_, rfn)| construct_ssa(rfn, &reg_p, ascc).err())
+                    .collect()
             } else {
+                rmod.functions
+                    .values_mut()
+                    .filter_map(|rfn| construct_ssa(rfn, &reg_p, ascc).err())
+                    .collect()
+            };
+            // Drop the functions whose SSA could not be constructed, so that the analyses
+            // that follow do not see a half-built SSA.
+            for err in lift_errors {
+                if let LoadError::Lift { offset, .. } = err {
+                    rmod.functions.remove(&offset);
+                }
+                rmod.load_errors.push(err);
+            }
+
+            if self.resolve_jump_tables {
                 for rfn in rmod.functions.values_mut() {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, self.assume_cc, true);
+                    jump_tables::resolve_jump_tables(rfn, &reg_p, source, ascc);
                 }
             }
         }
 
         if self.stub_imports {
             for ifn in rmod.imports.values_mut() {
-                SSAConstruct::<SSAStorage>::construct(
-                    &mut ifn.rfn.borrow_mut(),
-                    &reg_p,
-                    self.assume_cc,
-                    true,
-                );
+                if let Err(e) = construct_ssa(&mut ifn.rfn.borrow_mut(), &reg_p, self.assume_cc) {
+                    rmod.load_errors.push(e);
+                }
             }
         }
 
@@ -878,6 +1245,7 @@ impl<'a> ModuleLoader<'a> {
                 Ok(info) => info,
                 Err(e) => {
                     radeco_warn!(e);
+                    rmod.load_errors.push(LoadError::Source(e));
                     Vec::new()
                 }
             };
@@ -899,9 +1267,10 @@ impl<'a> ModuleLoader<'a> {
                 }
             }
 
+            // Functions without an offset have already been reported by the function loader.
             if self.load_datarefs {
                 for info in &aux_info {
-                    if let Some(mut rfn) = rmod.functions.get_mut(&info.offset.unwrap()) {
+                    if let Some(mut rfn) = info.offset.and_then(|o| rmod.functions.get_mut(&o)) {
                         rfn.datarefs = info.datarefs.clone().unwrap_or_default();
                     }
                 }
@@ -909,15 +1278,26 @@ impl<'a> ModuleLoader<'a> {
 
             if self.load_locals {
                 for info in &aux_info {
-                    if let Some(mut rfn) = rmod.functions.get_mut(&info.offset.unwrap()) {
+                    let rfn = match info.offset {
+                        Some(offset) => rmod.functions.get_mut(&offset),
+                        None => None,
+                    };
+                    if let Some(mut rfn) = rfn {
                         let locals_res = self.source.as_ref().map(|s| s.locals_of(rfn.offset));
                         let mut locals = match locals_res {
-                            Some(Ok(_locals)) => _locals
-                                .into_iter()
-                                .map(|l| VarBinding::local(l))
-                                .collect::<Vec<_>>(),
+                            Some(Ok(_locals)) => {
+                                let mut locals = Vec::new();
+                                for l in _locals {
+                                    match VarBinding::local(l) {
+                                        Ok(vb) => locals.push(vb),
+                                        Err(e) => rmod.load_errors.push(e),
+                                    }
+                                }
+                                locals
+                            }
                             Some(Err(e)) => {
                                 radeco_warn!("{:?}", e);
+                                rmod.load_errors.push(LoadError::Source(e));
                                 Vec::new()
                             }
                             None => {
@@ -947,10 +1327,19 @@ impl<'a> ModuleLoader<'a> {
             rfn.mark_locals();
             rfn.mark_args();
         }
+
+        if self.build_ssa && self.build_memory_ssa {
+            let callgraph = &rmod.callgraph;
+            for rfn in rmod.functions.values_mut() {
+                if rfn.ftype == FunctionType::Function {
+                    rfn.build_memory_ssa(callgraph);
+                }
+            }
+        }
         // Set source
         rmod.source = Some(Rc::clone(&source));
 
-        rmod
+        Ok(rmod)
     }
 
     /// Setup a function loader for the module
@@ -966,6 +1355,22 @@ impl<'a> ModuleLoader<'a> {
     }
 }
 
+// Constructs the SSA for `rfn`. A function that cannot be lifted yields a `LoadError::Lift`
+// instead of bringing down the loading of the whole module.
+fn construct_ssa(
+    rfn: &mut RadecoFunction,
+    reg_p: &LRegInfo,
+    assume_cc: bool,
+) -> Result<(), LoadError> {
+    SSAConstruct::<SSAStorage>::construct(rfn, reg_p, assume_cc, true).map_err(|reason| {
+        LoadError::Lift {
+            offset: rfn.offset,
+            name: rfn.name.clone(),
+            reason: reason,
+        }
+    })
+}
+
 #[derive(Default)]
 /// Breaks down `RadecoModule` into functions
 /// Performs low-level function identification.
@@ -1004,13 +1409,15 @@ where
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Default)]
 /// Results from `FunctionLoader`
 pub struct FLResult {
     /// Map from identified function offset to the RadecoFunction instance
     functions: BTreeMap<u64, RadecoFunction>,
     /// Number of functions identified
     new: u32,
+    /// Errors for functions that could not be identified
+    errors: Vec<LoadError>,
 }
 
 impl<'a> FunctionLoader<'a> {
@@ -1029,6 +1436,7 @@ impl<'a> FunctionLoader<'a> {
                     let fl = f.strategy(self.source.as_ref(), &acc, rmod);
                     acc.new += fl.new;
                     acc.functions.extend(fl.functions.into_iter());
+                    acc.errors.extend(fl.errors.into_iter());
                 }
                 acc
             })
@@ -1047,6 +1455,7 @@ impl RadecoProject {
         RadecoProject {
             modules: Vec::new(),
             reginfo: Arc::new(SubRegisterFile::default()),
+            import_calls: Vec::new(),
         }
     }
 
@@ -1082,6 +1491,38 @@ impl RadecoProject {
             iter: self.modules.iter_mut(),
         }
     }
+
+    /// Returns the module and the function that the import at `plt` in the `idx`th module
+    /// resolves to. Returns `None` if the library defining it was not loaded.
+    pub fn resolve_import(
+        &self,
+        idx: usize,
+        plt: u64,
+    ) -> Option<(&RadecoModule, &RadecoFunction)> {
+        let target = self.modules.get(idx)?.imports.get(&plt)?.target?;
+        let rmod = self.modules.get(target.module as usize)?;
+        rmod.function(target.offset).map(|rfn| (rmod, rfn))
+    }
+
+    /// Returns the calls from one module into another. These are only linked if the project
+    /// was loaded along with its libraries.
+    pub fn import_calls(&self) -> &[ImportCall] {
+        &self.import_calls
+    }
+
+    /// Returns the calls from other modules to the function at `offset` in the `idx`th module.
+    pub fn import_callers(&self, idx: usize, offset: u64) -> Vec<ImportCall> {
+        self.import_calls
+            .iter()
+            .filter(|c| c.callee.module as usize == idx && c.callee.offset == offset)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the non-fatal errors encountered while loading the modules of this project.
+    pub fn load_errors<'a>(&'a self) -> Box<Iterator<Item = &'a LoadError> + 'a> {
+        box self.modules.iter().flat_map(|rmod| rmod.load_errors.iter())
+    }
 }
 
 impl RadecoModule {
@@ -1124,6 +1565,24 @@ impl RadecoModule {
         &self.strings
     }
 
+    /// Returns the non-fatal errors encountered while loading this module.
+    pub fn load_errors(&self) -> &[LoadError] {
+        &self.load_errors
+    }
+
+    /// Writes the module as JSON to `out`. This includes the SSA, bindings and register usage of
+    /// every function, so that an analyzed module can be restored without loading it again.
+    ///
+    /// The `source` and the load errors of the module are not written.
+    pub fn save<W: Write>(&self, out: W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(out, self)
+    }
+
+    /// Reads a module previously written by `save`.
+    pub fn restore<R: Read>(input: R) -> Result<RadecoModule, serde_json::Error> {
+        serde_json::from_reader(input)
+    }
+
     pub fn callees_of(&self, rfn: &RadecoFunction) -> Vec<(u64, NodeIndex)> {
         // TODO More efficient implementation
         let csite_nodes = rfn
@@ -1149,10 +1608,79 @@ impl RadecoFunction {
         &self.ssa
     }
 
+    /// Returns the SSA for modification. This drops the Memory SSA of the function, which
+    /// has to be built again if needed.
     pub fn ssa_mut(&mut self) -> &mut SSAStorage {
+        self.memory_ssa = None;
+        self.types.clear();
         &mut self.ssa
     }
 
+    /// Returns the Memory SSA of this function, if it was built and the SSA was not modified
+    /// since.
+    pub fn memory_ssa(&self) -> Option<&MemoryGraph<NodeIndex, NodeIndex>> {
+        self.memory_ssa.as_ref()
+    }
+
+    /// Builds the Memory SSA of this function. Memory is partitioned using the datarefs, the
+    /// locals and the call sites of the function in `callgraph`.
+    pub fn build_memory_ssa(&mut self, callgraph: &CallGraph) {
+        let locals = self
+            .bindings
+            .iter()
+            .filter_map(|vb| match vb.btype {
+                BindingType::RegisterLocal(ref base, offset) => Some(LVarInfo {
+                    name: Some(vb.name.to_string()),
+                    vtype: Some(vb.type_str.clone()),
+                    reference: Some(LVarRef {
+                        base: Some(base.clone()),
+                        offset: Some(offset),
+                    }),
+                    ..Default::default()
+                }),
+                _ => None,
+            })
+            .collect();
+        let callrefs = self
+            .call_sites(callgraph)
+            .into_iter()
+            .map(|c| c.csite_node)
+            .collect();
+        let graph = {
+            let mut mssa = MemorySSA::new(&self.ssa);
+            mssa.gather_variables(&self.datarefs, &locals, &callrefs);
+            mssa.run();
+            mssa.into_graph()
+        };
+        self.memory_ssa = Some(graph);
+    }
+
+    /// Returns the type inferred for `node`, if the types were inferred and the SSA was not
+    /// modified since.
+    pub fn inferred_type(&self, node: NodeIndex) -> Option<&RType> {
+        self.types.get(&node)
+    }
+
+    /// Sets the types inferred for the values of the SSA, see `analysis::tie`. Values inferred
+    /// to be pointers are marked as references and numbers as scalars.
+    pub fn set_types(&mut self, types: HashMap<NodeIndex, RType>) {
+        for (&node, ty) in &types {
+            if let Some(vi) = self.ssa.g.node_weight_mut(node).and_then(|n| n.valueinfo_mut()) {
+                if ty.is_ptr() {
+                    vi.mark_as_reference();
+                } else if ty.is_num() {
+                    vi.mark_as_scalar();
+                }
+            }
+        }
+        self.types = types;
+    }
+
+    /// Returns the instructions that could not be lifted and were replaced by opaque nodes.
+    pub fn lift_diagnostics(&self) -> &[LiftDiagnostic] {
+        &self.lift_diagnostics
+    }
+
     /// Returns the id in the call graph for this function.
     pub fn cgid(&self) -> NodeIndex {
         self.cgid
@@ -1281,7 +1809,7 @@ impl RadecoFunction {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CallContextInfo {
     /// NodeIndex mapping from a node in the caller's context to a node in callee's context
     pub map: Vec<(NodeIndex, NodeIndex)>,
@@ -1294,6 +1822,9 @@ pub struct CallContextInfo {
 #[cfg(test)]
 mod test {
     use super::*;
+    use frontend::radeco_source::FileSource;
+    use middle::{dce, ir_writer};
+    use serde_json;
 
     #[test]
     fn test_fn_loader() {
@@ -1302,4 +1833,138 @@ mod test {
         // let mut fl = FunctionLoader::default();
         // fl.strategy(&ld);
     }
+    #[test]
+    fn test_use_source_without_source() {
+        let rmod = RadecoModule::new("main".to_owned());
+        let mut fl = FLResult::default();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        fl.functions.insert(rfn.offset, rfn);
+        fl.new = 1;
+        let res = loader_defaults::strat_use_source(None, &fl, &rmod);
+        assert_eq!(res.new, 1);
+        assert!(res.functions.contains_key(&0x1000));
+        assert!(res.errors.is_empty());
+    }
+
+    #[test]
+    fn test_find_library() {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("test_files");
+        let path = find_library(&dir, "bin1_libraries.json").expect("Library not found");
+        assert!(path.ends_with("bin1_filesource/bin1_libraries.json"));
+        assert!(find_library(&dir, "libdoesnotexist.so").is_none());
+    }
+
+    #[test]
+    fn test_link_imports() {
+        let mut main = RadecoModule::new("main".to_owned());
+        main.imports
+            .insert(0x400470, ImportInfo::new_stub(0x400470, Cow::from("puts")));
+        main.imports
+            .insert(0x400480, ImportInfo::new_stub(0x400480, Cow::from("unknown")));
+        let mut libc = RadecoModule::new("libc.so.6".to_owned());
+        libc.exports = vec![serde_json::from_str(r#"{"name": "puts", "vaddr": 4096}"#).unwrap()];
+        let mut rfn = RadecoFunction::new();
+        rfn.offset = 0x1000;
+        libc.functions.insert(rfn.offset, rfn);
+
+        let mut rp = RadecoProject::new();
+        rp.modules = vec![main, libc];
+        link_imports(&mut rp.modules);
+
+        let puts = &rp.modules[0].imports[&0x400470];
+        assert_eq!(
+            puts.target,
+            Some(ImportTarget {
+                module: 1,
+                offset: 0x1000,
+            })
+        );
+        assert_eq!(puts.rfn.borrow().ftype, FunctionType::Import(1));
+        assert!(rp.modules[0].imports[&0x400480].target.is_none());
+        let (rmod, rfn) = rp.resolve_import(0, 0x400470).expect("Unresolved import");
+        assert_eq!(rmod.name(), "libc.so.6");
+        assert_eq!(rfn.offset, 0x1000);
+    }
+
+    #[test]
+    fn test_link_call_graphs() {
+        let mut main = RadecoModule::new("main".to_owned());
+        main.imports
+            .insert(0x400470, ImportInfo::new_stub(0x400470, Cow::from("puts")));
+        main.imports
+            .insert(0x400480, ImportInfo::new_stub(0x400480, Cow::from("unknown")));
+        let caller = main.callgraph.add_node(0x400500);
+        let local = main.callgraph.add_node(0x400600);
+        let puts = main.callgraph.add_node(0x400470);
+        let unknown = main.callgraph.add_node(0x400480);
+        for &(target, csite) in &[(puts, 0x400510), (local, 0x400520), (unknown, 0x400530)] {
+            let mut cctx = CallContextInfo::default();
+            cctx.csite = csite;
+            main.callgraph.add_edge(caller, target, cctx);
+        }
+        let mut libc = RadecoModule::new("libc.so.6".to_owned());
+        libc.exports = vec![serde_json::from_str(r#"{"name": "puts", "vaddr": 4096}"#).unwrap()];
+        let mut rfn = RadecoFunction::new();
+        rfn.offset = 0x1000;
+        libc.functions.insert(rfn.offset, rfn);
+
+        let mut rp = RadecoProject::new();
+        rp.modules = vec![main, libc];
+        link_imports(&mut rp.modules);
+        rp.import_calls = link_call_graphs(&rp.modules);
+
+        // Neither the local call nor the one to the unresolved import cross modules.
+        let expected = ImportCall {
+            module: 0,
+            caller: 0x400500,
+            csite: 0x400510,
+            callee: ImportTarget {
+                module: 1,
+                offset: 0x1000,
+            },
+        };
+        assert_eq!(rp.import_calls(), &[expected]);
+        assert_eq!(rp.import_callers(1, 0x1000), vec![expected]);
+        assert!(rp.import_callers(0, 0x400600).is_empty());
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let mut rmod = ModuleLoader::default()
+            .build_ssa()
+            .build_callgraph()
+            .load(Rc::new(FileSource::open(path.to_str().unwrap())));
+        // Leave holes in the node indices
+        for rfn in rmod.functions.values_mut() {
+            dce::collect(rfn.ssa_mut());
+        }
+
+        let mut saved = Vec::new();
+        rmod.save(&mut saved).expect("Unable to save module");
+        let restored = RadecoModule::restore(&saved[..]).expect("Unable to restore module");
+
+        assert_eq!(restored.name(), rmod.name());
+        assert_eq!(restored.functions.len(), rmod.functions.len());
+        assert_eq!(restored.imports.len(), rmod.imports.len());
+        assert_eq!(restored.callgraph.edge_count(), rmod.callgraph.edge_count());
+        for (offset, rfn) in &rmod.functions {
+            let restored_fn = restored.function(*offset).expect("Function not restored");
+            let (mut il, mut restored_il) = (String::new(), String::new());
+            ir_writer::emit_il(&mut il, None, rfn.ssa()).unwrap();
+            ir_writer::emit_il(&mut restored_il, None, restored_fn.ssa()).unwrap();
+            assert_eq!(restored_il, il);
+            assert_eq!(restored_fn.bindings().len(), rfn.bindings().len());
+            assert!(restored_fn.regusage.is_compatible_with(&rfn.regusage));
+            assert!(rfn.regusage.is_compatible_with(&restored_fn.regusage));
+        }
+
+        // Saving the restored module again gives the same output
+        let mut resaved = Vec::new();
+        restored.save(&mut resaved).expect("Unable to save module");
+        assert_eq!(resaved, saved);
+    }
 }
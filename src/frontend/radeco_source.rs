@@ -3,12 +3,15 @@
 use serde_json;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs::{self, File};
+use std::hash::Hash;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use r2api::api_trait::R2Api;
 use r2api::structs::{
@@ -84,6 +87,10 @@ pub trait Source {
     fn raw(&self, _cmd: String) -> Result<String, SourceErr> {
         unimplemented!()
     }
+    /// Read `n` bytes of the loaded image starting at virtual address `at`.
+    fn read_bytes(&self, _n: u64, _at: u64) -> Result<Vec<u8>, SourceErr> {
+        Err(SourceErr::SrcErr("`Source::read_bytes` is not implemented"))
+    }
 
     fn send(&self, _: String) -> Result<(), SourceErr> {
         Ok(())
@@ -147,18 +154,96 @@ pub trait Source {
 // However, this should not be a problem as `Rc` prevents concurrent accesses from multiple
 // threads.
 //
-// TODO: For threading, this should be replaced by `RwLock<T>` or `Mutex<T>`
-// But it is still up for discussion if this should be done at the r2pipe level rather than here.
-// RwLock allows for multiple concurrent readers and one writer, however this will not improve
-// the performance when compared to Mutex as every function takes `self` mutably (as it has to
-// communicate with r2 which amounts to writing out to process pipe). Therefore, it'd make
-// sense to have some sort of cached information so that concurrent reads can occur. This should
-// be invalidated whenever some information is exported back to radare or some analysis is run on
-// r2.
+// For threading, wrap the `RefCell` in a `SyncSource` instead. Every query then takes a lock on
+// r2, and the results are cached so that concurrent reads of the same information do not have to
+// wait on the pipe. The cache is invalidated whenever something is sent to r2.
 pub type WrappedR2Api<R> = Rc<RefCell<R>>;
 
+// Forwarding implementation so that shared sources (such as `WrappedR2Api`) are sources too.
+impl<S: Source + ?Sized> Source for Rc<S> {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        (**self).functions()
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        (**self).instructions_at(address)
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        (**self).register_profile()
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        (**self).flags()
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        (**self).sections()
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        (**self).symbols()
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        (**self).imports()
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        (**self).exports()
+    }
+
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        (**self).relocs()
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        (**self).libraries()
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        (**self).entrypoint()
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        (**self).disassemble_function(name)
+    }
+
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        (**self).disassemble_n_bytes(n, at)
+    }
+
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        (**self).disassemble_n_insts(n, at)
+    }
+
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        (**self).locals_of(start_addr)
+    }
+
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        (**self).cc_info_of(start_addr)
+    }
+
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        (**self).strings(data_only)
+    }
+
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        (**self).raw(cmd)
+    }
+
+    fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+        (**self).read_bytes(n, at)
+    }
+
+    fn send(&self, s: String) -> Result<(), SourceErr> {
+        (**self).send(s)
+    }
+}
+
 // Implementation of `Source` trait for R2.
-impl<R: R2Api> Source for WrappedR2Api<R> {
+impl<R: R2Api> Source for RefCell<R> {
     fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
         Ok(self.try_borrow_mut()?.fn_list()?)
     }
@@ -241,6 +326,18 @@ impl<R: R2Api> Source for WrappedR2Api<R> {
         Ok(self.try_borrow_mut()?.raw(cmd))
     }
 
+    fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+        let hex = self.try_borrow_mut()?.raw(format!("p8 {} @ {}", n, at));
+        let hex = hex.trim();
+        if hex.len() != 2 * n as usize {
+            return Err(SourceErr::SrcErr("Short read"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(SourceErr::from))
+            .collect()
+    }
+
     fn send(&self, s: String) -> Result<(), SourceErr> {
         let _ = self.try_borrow_mut()?.raw(s);
         Ok(())
@@ -252,8 +349,8 @@ impl<R: R2Api> Source for WrappedR2Api<R> {
 /// The files in the directory must be of the form "<base_name>_<suffix>.json". This means that the
 /// files must have specific names.
 ///
-/// To construct a FileSource painlessly (and save it for future use), use the `from::From` trait
-/// implemented on `R2`.
+/// To construct a FileSource painlessly (and save it for future use), use `snapshot` or the
+/// `From` trait implemented for `WrappedR2Api`. `RecordingSource` saves only what is queried.
 pub struct FileSource {
     /// Directory where files should be loaded from.
     pub dir: String,
@@ -272,12 +369,12 @@ impl FileSource {
         Ok(json_str)
     }
 
-    fn write_file(&mut self, suffix: &str, data: &str) {
+    fn write_file(&self, suffix: &str, data: &str) -> Result<(), SourceErr> {
         let mut path = PathBuf::from(&self.dir);
         path.push(&format!("{}_{}.json", self.base_name, suffix));
-        let mut f = File::create(path).expect("Failed to open file");
-        f.write_all(data.to_string().as_bytes())
-            .expect("Failed to read file");
+        let mut f = File::create(path)?;
+        f.write_all(data.as_bytes())?;
+        Ok(())
     }
 }
 
@@ -402,143 +499,401 @@ impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
     fn from(r2: WrappedR2Api<R>) -> FileSource {
         let bin_info = r2.borrow_mut().bin_info().expect("Failed to load bin_info");
         let fname = bin_info.core.unwrap().file.unwrap();
-        let fname = Path::new(&fname).file_stem().unwrap();
+        let fname = Path::new(&fname)
+            .file_stem()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
         let mut dir = PathBuf::from(".");
         dir.push(&fname);
-        fs::create_dir_all(&dir).expect("Failed to create directory");
-        let mut fsource = FileSource {
-            dir: dir.to_str().unwrap().to_owned(),
-            base_name: fname.to_str().unwrap().to_owned(),
-        };
+        snapshot(r2, dir.to_str().unwrap(), &fname).expect("Failed to save r2 information")
+    }
+}
 
-        {
-            let fns = r2.functions().expect("Failed to load function info");
-            {
-                let json_str = serde_json::to_string(&fns).expect("Failed to encode to json");
-                fsource.write_file(suffix::FUNCTION_INFO, &json_str);
-            }
+macro_rules! record {
+    ($rec:expr, $suffix:expr, $result:expr) => {{
+        let result = $result?;
+        $rec.out
+            .write_file($suffix, &serde_json::to_string(&result)?)?;
+        Ok(result)
+    }};
+}
 
-            for f in fns {
-                {
-                    let result = r2
-                        .instructions_at(f.offset.unwrap())
-                        .expect("Failed to load instructions");
-                    let json_str =
-                        serde_json::to_string(&result).expect("Failed to encode to json");
-                    let suffix = format!("{}_{:#X}", suffix::INSTRUCTIONS, f.offset.unwrap());
-                    fsource.write_file(&suffix, &json_str)
-                }
+/// Wraps a `Source` and saves the result of every successful query as a json file that
+/// `FileSource` can read back.
+///
+/// Queries that `FileSource` cannot answer (`raw`, `disassemble_n_*`) are passed through without
+/// being recorded.
+pub struct RecordingSource<S: Source> {
+    source: S,
+    out: FileSource,
+}
 
-                {
-                    let name = f.name.clone().unwrap();
-                    let result = r2
-                        .disassemble_function(&name)
-                        .map(|ops| LFunctionInfo {
-                            addr: f.offset,
-                            name: f.name.clone(),
-                            ops: Some(ops),
-                            size: f.size,
-                        })
-                        .expect(&format!("Failed to load instructions @ {}", name));
-                    let json_str =
-                        serde_json::to_string(&result).expect("Failed to encode to json");
-                    let suffix = format!("{}_{}", suffix::FUNCTION, name);
-                    fsource.write_file(&suffix, &json_str)
-                }
+impl<S: Source> RecordingSource<S> {
+    /// Record the queries made to `source` into `dir`, creating it if needed. Files are
+    /// prefixed with `base_name`.
+    pub fn new(source: S, dir: &str, base_name: &str) -> Result<RecordingSource<S>, SourceErr> {
+        fs::create_dir_all(dir)?;
+        Ok(RecordingSource {
+            source: source,
+            out: FileSource {
+                dir: dir.to_owned(),
+                base_name: base_name.to_owned(),
+            },
+        })
+    }
 
-                {
-                    let result = r2
-                        .locals_of(f.offset.unwrap())
-                        .expect("Failed to load locals");
-                    let json_str =
-                        serde_json::to_string(&result).expect("Failed to encode to json");
-                    let suffix = format!("{}_{}", suffix::LOCAL, f.offset.unwrap());
-                    fsource.write_file(&suffix, &json_str)
-                }
+    /// `FileSource` reading what has been recorded so far.
+    pub fn file_source(&self) -> FileSource {
+        self.out.clone()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: Source> Source for RecordingSource<S> {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        record!(self, suffix::FUNCTION_INFO, self.source.functions())
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        let suffix = format!("{}_{:#X}", suffix::INSTRUCTIONS, address);
+        record!(self, &suffix, self.source.instructions_at(address))
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        record!(self, suffix::REGISTER, self.source.register_profile())
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        record!(self, suffix::FLAG, self.source.flags())
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        record!(self, suffix::SECTION, self.source.sections())
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        record!(self, suffix::SYMBOL, self.source.symbols())
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        record!(self, suffix::IMPORT, self.source.imports())
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        record!(self, suffix::EXPORT, self.source.exports())
+    }
 
-                {
-                    let result = r2
-                        .cc_info_of(f.offset.unwrap())
-                        .expect("Failed to load cc info");
-                    let json_str =
-                        serde_json::to_string(&result).expect("Failed to encode to json");
-                    let suffix = format!("{}_{}", suffix::CCINFO, f.offset.unwrap());
-                    fsource.write_file(&suffix, &json_str)
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        record!(self, suffix::RELOC, self.source.relocs())
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        record!(self, suffix::LIBRARY, self.source.libraries())
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        record!(self, suffix::ENTRY, self.source.entrypoint())
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        let suffix = format!("{}_{}", suffix::FUNCTION, name);
+        // The bounds of the function are the ones of its instructions.
+        let result: Result<LFunctionInfo, SourceErr> =
+            self.source.disassemble_function(name).map(|ops| {
+                let start = ops.iter().filter_map(|op| op.offset).min();
+                let end = ops
+                    .iter()
+                    .filter_map(|op| Some(op.offset? + op.size?))
+                    .max();
+                LFunctionInfo {
+                    addr: start,
+                    name: Some(name.to_owned()),
+                    ops: Some(ops),
+                    size: match (start, end) {
+                        (Some(start), Some(end)) => Some(end - start),
+                        _ => None,
+                    },
                 }
-            }
+            });
+        let function_info: Result<LFunctionInfo, SourceErr> = record!(self, &suffix, result);
+        function_info.map(|f| f.ops.unwrap_or_default())
+    }
 
-            {
-                let reg = r2.register_profile().expect("Failed to load register info");
-                let json_str = serde_json::to_string(&reg).expect("Failed to encode to json");
-                fsource.write_file(suffix::REGISTER, &json_str);
-            }
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.source.disassemble_n_bytes(n, at)
+    }
 
-            {
-                let flags = r2.flags().expect("Failed to load flag info");
-                let json_str = serde_json::to_string(&flags).expect("Failed to encode to json");
-                fsource.write_file(suffix::FLAG, &json_str);
-            }
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.source.disassemble_n_insts(n, at)
+    }
 
-            {
-                let sections = r2.sections().expect("Failed to load section map");
-                let json_str = serde_json::to_string(&sections).expect("Failed to encode to json");
-                fsource.write_file(suffix::SECTION, &json_str);
-            }
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        let suffix = format!("{}_{}", suffix::LOCAL, start_addr);
+        record!(self, &suffix, self.source.locals_of(start_addr))
+    }
 
-            {
-                let strings = r2
-                    .borrow_mut()
-                    .strings(true)
-                    .expect("Unable to load String info from r2");
-                let json_str = serde_json::to_string(&strings).expect("Failed to encode to json");
-                fsource.write_file(suffix::STRING, &json_str);
-            }
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        let suffix = format!("{}_{}", suffix::CCINFO, start_addr);
+        record!(self, &suffix, self.source.cc_info_of(start_addr))
+    }
 
-            {
-                let imports = r2
-                    .borrow_mut()
-                    .imports()
-                    .expect("Unable to load import info from r2");
-                let json_str = serde_json::to_string(&imports).expect("Failed to encode to json");
-                fsource.write_file(suffix::IMPORT, &json_str);
-            }
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        // `FileSource` only keeps one set of strings, the one the loader asks for.
+        if data_only {
+            record!(self, suffix::STRING, self.source.strings(data_only))
+        } else {
+            self.source.strings(data_only)
+        }
+    }
 
-            // FIXME: Error occurs during `r2.borrow_mut().exports()`
-            // {
-            //     let exports = r2.borrow_mut().exports().expect("Unable to load export info from r2");
-            //     let json_str = serde_json::to_string(&exports).expect("Failed to encode to json");
-            //     fsource.write_file(suffix::EXPORT, &json_str);
-            // }
-
-            {
-                let relocs = r2
-                    .borrow_mut()
-                    .relocs()
-                    .expect("Unable to load reloc info from r2");
-                let json_str = serde_json::to_string(&relocs).expect("Failed to encode to json");
-                fsource.write_file(suffix::RELOC, &json_str);
-            }
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        self.source.raw(cmd)
+    }
 
-            {
-                let libraries = r2
-                    .borrow_mut()
-                    .libraries()
-                    .expect("Unable to load library info from r2");
-                let json_str = serde_json::to_string(&libraries).expect("Failed to encode to json");
-                fsource.write_file(suffix::LIBRARY, &json_str);
-            }
+    fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+        self.source.read_bytes(n, at)
+    }
 
-            {
-                let entry = r2
-                    .borrow_mut()
-                    .entrypoint()
-                    .expect("Unable to load entry info from r2");
-                let json_str = serde_json::to_string(&entry).expect("Failed to encode to json");
-                fsource.write_file(suffix::ENTRY, &json_str);
-            }
+    fn send(&self, s: String) -> Result<(), SourceErr> {
+        self.source.send(s)
+    }
+}
+
+/// Save everything a `ModuleLoader` may ask `source` for into `dir`, and return a `FileSource`
+/// that reads it back. Used to (re)generate the fixtures in `test_files`.
+///
+/// Functions, the register profile, flags and sections are required. Failures to provide the
+/// remaining information are ignored, the corresponding files are then simply missing.
+pub fn snapshot<S: Source>(source: S, dir: &str, base_name: &str) -> Result<FileSource, SourceErr> {
+    let rec = RecordingSource::new(source, dir, base_name)?;
+
+    let functions = rec.functions()?;
+    rec.register_profile()?;
+    rec.flags()?;
+    rec.sections()?;
+
+    let _ = rec.symbols();
+    let _ = rec.imports();
+    let _ = rec.exports();
+    let _ = rec.relocs();
+    let _ = rec.libraries();
+    let _ = rec.entrypoint();
+    let _ = rec.strings(true);
+
+    for f in &functions {
+        if let Some(offset) = f.offset {
+            let _ = rec.instructions_at(offset);
+            let _ = rec.locals_of(offset);
+            let _ = rec.cc_info_of(offset);
+        }
+        if let Some(ref name) = f.name {
+            let _ = rec.disassemble_function(name);
+        }
+    }
+
+    Ok(rec.file_source())
+}
+
+/// Answers to the queries made to a `SyncSource`, keyed by their arguments.
+#[derive(Default)]
+struct SourceCache {
+    functions: Option<Vec<FunctionInfo>>,
+    instructions: HashMap<u64, Vec<LOpInfo>>,
+    register_profile: Option<LRegInfo>,
+    flags: Option<Vec<LFlagInfo>>,
+    sections: Option<Vec<LSectionInfo>>,
+    symbols: Option<Vec<LSymbolInfo>>,
+    imports: Option<Vec<LImportInfo>>,
+    exports: Option<Vec<LExportInfo>>,
+    relocs: Option<Vec<LRelocInfo>>,
+    libraries: Option<Vec<String>>,
+    entrypoint: Option<Vec<LEntryInfo>>,
+    disassembly: HashMap<String, Vec<LOpInfo>>,
+    locals: HashMap<u64, Vec<LVarInfo>>,
+    cc_info: HashMap<u64, LCCInfo>,
+    strings: HashMap<bool, Vec<LStringInfo>>,
+}
+
+/// Thread-safe `Source` that can be shared with the workers of `ModuleLoader::parallel`.
+///
+/// Queries to the wrapped source are serialized by a lock, and their results are cached so that
+/// repeated queries do not have to go through it again. The cache has a lock of its own, so
+/// cached information can be looked up while another query waits on the wrapped source. Since
+/// `send` and `raw` may change the state of the wrapped source (e.g. run an analysis in r2), they
+/// invalidate the cache. Both answers and invalidations are written to the cache with the source
+/// lock held, hence no answer from before a `send` is cached after it.
+///
+/// To share r2, wrap the `RefCell` of a `WrappedR2Api`: `SyncSource::new(RefCell::new(r2))`.
+pub struct SyncSource<S: Source + Send> {
+    source: Arc<Mutex<S>>,
+    cache: Arc<Mutex<SourceCache>>,
+}
+
+impl<S: Source + Send> Clone for SyncSource<S> {
+    fn clone(&self) -> SyncSource<S> {
+        SyncSource {
+            source: Arc::clone(&self.source),
+            cache: Arc::clone(&self.cache),
+        }
+    }
+}
+
+impl<S: Source + Send> SyncSource<S> {
+    pub fn new(source: S) -> SyncSource<S> {
+        SyncSource {
+            source: Arc::new(Mutex::new(source)),
+            cache: Arc::new(Mutex::new(SourceCache::default())),
+        }
+    }
+
+    /// Forget all cached information.
+    pub fn invalidate(&self) -> Result<(), SourceErr> {
+        *self.cache()? = SourceCache::default();
+        Ok(())
+    }
+
+    fn source(&self) -> Result<MutexGuard<S>, SourceErr> {
+        self.source
+            .lock()
+            .map_err(|_| SourceErr::SrcErr("Source lock is poisoned"))
+    }
+
+    fn cache(&self) -> Result<MutexGuard<SourceCache>, SourceErr> {
+        self.cache
+            .lock()
+            .map_err(|_| SourceErr::SrcErr("Source cache lock is poisoned"))
+    }
+
+    fn cached<T, G, Q>(&self, get: G, query: Q) -> Result<T, SourceErr>
+    where
+        T: Clone,
+        G: Fn(&mut SourceCache) -> &mut Option<T>,
+        Q: FnOnce(&S) -> Result<T, SourceErr>,
+    {
+        if let Some(ref v) = *get(&mut *self.cache()?) {
+            return Ok(v.clone());
+        }
+        let source = self.source()?;
+        let v = query(&*source)?;
+        *get(&mut *self.cache()?) = Some(v.clone());
+        Ok(v)
+    }
+
+    fn cached_at<K, T, G, Q>(&self, key: K, get: G, query: Q) -> Result<T, SourceErr>
+    where
+        K: Eq + Hash,
+        T: Clone,
+        G: Fn(&mut SourceCache) -> &mut HashMap<K, T>,
+        Q: FnOnce(&S) -> Result<T, SourceErr>,
+    {
+        if let Some(v) = get(&mut *self.cache()?).get(&key) {
+            return Ok(v.clone());
         }
+        let source = self.source()?;
+        let v = query(&*source)?;
+        get(&mut *self.cache()?).insert(key, v.clone());
+        Ok(v)
+    }
+}
+
+impl<S: Source + Send> Source for SyncSource<S> {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.cached(|c| &mut c.functions, |s| s.functions())
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.cached_at(
+            address,
+            |c| &mut c.instructions,
+            |s| s.instructions_at(address),
+        )
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        self.cached(|c| &mut c.register_profile, |s| s.register_profile())
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        self.cached(|c| &mut c.flags, |s| s.flags())
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        self.cached(|c| &mut c.sections, |s| s.sections())
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        self.cached(|c| &mut c.symbols, |s| s.symbols())
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        self.cached(|c| &mut c.imports, |s| s.imports())
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        self.cached(|c| &mut c.exports, |s| s.exports())
+    }
+
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        self.cached(|c| &mut c.relocs, |s| s.relocs())
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        self.cached(|c| &mut c.libraries, |s| s.libraries())
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        self.cached(|c| &mut c.entrypoint, |s| s.entrypoint())
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.cached_at(
+            name.to_owned(),
+            |c| &mut c.disassembly,
+            |s| s.disassemble_function(name),
+        )
+    }
+
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.source()?.disassemble_n_bytes(n, at)
+    }
+
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.source()?.disassemble_n_insts(n, at)
+    }
+
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        self.cached_at(start_addr, |c| &mut c.locals, |s| s.locals_of(start_addr))
+    }
 
-        fsource
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        self.cached_at(start_addr, |c| &mut c.cc_info, |s| s.cc_info_of(start_addr))
+    }
+
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        self.cached_at(data_only, |c| &mut c.strings, |s| s.strings(data_only))
+    }
+
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        let source = self.source()?;
+        let res = source.raw(cmd);
+        self.invalidate()?;
+        res
+    }
+
+    fn read_bytes(&self, n: u64, at: u64) -> Result<Vec<u8>, SourceErr> {
+        self.source()?.read_bytes(n, at)
+    }
+
+    fn send(&self, s: String) -> Result<(), SourceErr> {
+        let source = self.source()?;
+        let res = source.send(s);
+        self.invalidate()?;
+        res
     }
 }
 
@@ -548,7 +903,10 @@ mod test {
     use frontend::radeco_containers::*;
     use frontend::radeco_source::*;
     use r2pipe::r2::R2;
+    use std::env;
+    use std::process;
     use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
     #[test]
     fn file_source_test() {
@@ -557,4 +915,168 @@ mod test {
         let source = FileSource::open(path.to_str().unwrap());
         ProjectLoader::new().source(Rc::new(source)).load();
     }
+
+    #[test]
+    fn file_source_try_load_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        let rp = ProjectLoader::new()
+            .source(Rc::new(source))
+            .try_load()
+            .expect("Unable to load project");
+        // bin1 has no symbol information, which is not fatal.
+        assert!(rp.load_errors().any(|e| match *e {
+            LoadError::Source(_) => true,
+            _ => false,
+        }));
+        assert!(rp.nth_module(0).unwrap().function(0x40059d).is_some());
+    }
+
+    #[test]
+    fn snapshot_round_trip_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        // Tests run concurrently, possibly in several processes.
+        let mut dir = env::temp_dir();
+        dir.push(format!("radeco_snapshot_round_trip_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let snap = snapshot(source.clone(), dir.to_str().unwrap(), "bin1")
+            .expect("Unable to snapshot bin1");
+
+        let orig = ProjectLoader::new().source(Rc::new(source)).load();
+        let snap_files = snap.clone();
+        let copy = ProjectLoader::new().source(Rc::new(snap)).load();
+        let orig_mod = orig.nth_module(0).unwrap();
+        let copy_mod = copy.nth_module(0).unwrap();
+        assert_eq!(orig_mod.functions.len(), copy_mod.functions.len());
+        for (off, rfn) in &orig_mod.functions {
+            let copy_fn = copy_mod
+                .function(*off)
+                .expect("Function missing from snapshot");
+            assert_eq!(rfn.instructions().len(), copy_fn.instructions().len());
+            let recorded = snap_files
+                .read_file(&format!("{}_{}", suffix::FUNCTION, rfn.name))
+                .expect("Disassembly not recorded");
+            let info: LFunctionInfo = serde_json::from_str(&recorded).unwrap();
+            assert_eq!(info.addr, Some(*off));
+            let end = *off + info.size.expect("No size recorded");
+            assert!(info.ops.unwrap().iter().all(|op| {
+                let start = op.offset.unwrap();
+                start >= *off && start + op.size.unwrap() <= end
+            }));
+        }
+        assert_eq!(orig_mod.strings().len(), copy_mod.strings().len());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    struct CountingSource {
+        queries: AtomicUsize,
+    }
+
+    impl Source for CountingSource {
+        fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+            self.queries.fetch_add(1, AtomicOrdering::SeqCst);
+            Ok(Vec::new())
+        }
+        fn instructions_at(&self, _: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            Err(SourceErr::SrcErr("No register profile"))
+        }
+        fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn sync_source_cache_test() {
+        let source = SyncSource::new(CountingSource {
+            queries: AtomicUsize::new(0),
+        });
+        let queries = || {
+            source
+                .source()
+                .unwrap()
+                .queries
+                .load(AtomicOrdering::SeqCst)
+        };
+        source.functions().unwrap();
+        source.functions().unwrap();
+        assert_eq!(queries(), 1);
+        source.send("aaa".to_owned()).unwrap();
+        source.functions().unwrap();
+        assert_eq!(queries(), 2);
+    }
+
+    #[test]
+    fn sync_source_parallel_load_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = SyncSource::new(FileSource::open(path.to_str().unwrap()));
+        let rmod = ModuleLoader::default()
+            .sync_source(source.clone())
+            .build_ssa()
+            .parallel()
+            .try_load(Rc::new(source))
+            .expect("Unable to load module");
+        let main = rmod.function(0x40059d).expect("main is not loaded");
+        assert!(!main.instructions().is_empty());
+        assert!(main.callconv.is_some());
+    }
+
+    #[test]
+    fn sync_source_parallel_library_test() {
+        // `bin_file` has no recorded disassembly, unlike `bin1`.
+        fn open_bin_file(_: &Path) -> Result<Rc<Source>, LoadError> {
+            let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push("test_files/bin_file/bin_file");
+            Ok(Rc::new(FileSource::open(path.to_str().unwrap())))
+        }
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = SyncSource::new(FileSource::open(path.to_str().unwrap()));
+        // Tests run concurrently, possibly in several processes.
+        let mut dir = env::temp_dir();
+        dir.push(format!("radeco_parallel_library_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("libc.so.6")).unwrap();
+
+        let rp = ProjectLoader::new()
+            .path(dir.join("bin1").to_str().unwrap())
+            .source(Rc::new(source.clone()))
+            .module_loader(ModuleLoader::default().sync_source(source).parallel())
+            .load_libs()
+            .library_source(open_bin_file)
+            .try_load()
+            .expect("Unable to load project");
+        let _ = fs::remove_dir_all(&dir);
+
+        let bin1_main = rp.nth_module(0).unwrap().function(0x40059d).unwrap();
+        assert!(!bin1_main.instructions().is_empty());
+        let libc = rp.nth_module(1).expect("Library is not loaded");
+        assert_eq!(libc.name(), "libc.so.6");
+        // Both binaries have a `main`, the library's must not be disassembled from `bin1`.
+        let lib_main = libc.function(0x18d0).expect("main is not loaded");
+        assert!(lib_main.instructions().is_empty());
+    }
+
+    #[test]
+    fn file_source_missing_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/does_not_exist/bin");
+        let source = FileSource::open(path.to_str().unwrap());
+        match ProjectLoader::new().source(Rc::new(source)).try_load() {
+            Err(LoadError::Source(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Loaded a project without a source"),
+        }
+    }
 }
@@ -2,14 +2,21 @@
 //! straight from raw esil
 
 // NB:
-// There are some limitations to the current ESIL parser and these may/must be
-// improved in the
-// further commits.
-// 1. We only parse ESIL that is "well formed", that is, it cannot have any
-// statements after an if.
-// For example: "zf,?{,0x80,rip,=,}" is a valid esil statement as it does not
-// have any
-// instructions after "}" in the same instruction.
+// ESIL that has a single `?{ }` block at the end of the instruction, such as
+// "zf,?{,0x80,rip,=,}", is lifted by letting the false edge of the conditional
+// fall through to the next instruction.
+// Anything else, i.e. statements after a `}`, nested conditionals, `}{` (else),
+// `GOTO` and `BREAK`, is lifted by splitting the instruction into sub-instruction
+// blocks. These blocks start at `MAddress`es that share the address of the
+// instruction and differ in their `offset`. `GOTO` targets are ESIL word indices
+// and must be constants.
+// The signed operators `~/`, `~%` and `>>>>` are not known to the ESIL parser and
+// are lifted the same way, with the words between them handed to the parser.
+//
+// Instructions that cannot be lifted (unknown tokens, missing operands, ...) do not
+// abort the construction. They are replaced by an opaque `OpCustom` node that clobbers
+// the registers and memory the instruction writes, and a `LiftDiagnostic` is recorded
+// for them.
 
 use esil::lexer::{Token, Tokenizer};
 
@@ -27,6 +34,7 @@ use r2api::structs::{LOpInfo, LRegInfo};
 
 // use regex::Regex;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::{cmp, fmt, u64};
 
@@ -57,9 +65,40 @@ where
     // Used to keep track of the offset within an instruction.
     instruction_offset: u64,
     needs_new_block: bool,
+    // Set if the current instruction could not be lifted, with the reason why.
+    lift_error: Option<String>,
     mem_id: u64,
     assume_cc: bool,
     replace_pc: bool,
+    diagnostics: Vec<LiftDiagnostic>,
+    // Known targets of indirect jumps, by address of the jump instruction. See `set_switches`.
+    switches: HashMap<u64, Vec<(u64, Vec<u64>)>>,
+}
+
+/// Records an instruction that could not be lifted and was replaced by an opaque node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiftDiagnostic {
+    /// Address of the instruction
+    pub address: u64,
+    /// Disassembly of the instruction, if known
+    pub disassembly: Option<String>,
+    /// ESIL of the instruction
+    pub esil: String,
+    /// Why the instruction could not be lifted
+    pub reason: String,
+}
+
+impl fmt::Display for LiftDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#x}: {} ({}): {}",
+            self.address,
+            self.disassembly.as_ref().map_or("unknown", |s| s.as_str()),
+            self.esil,
+            self.reason
+        )
+    }
 }
 
 impl<'a, T> SSAConstruct<'a, T>
@@ -82,9 +121,12 @@ where
             nesting: Vec::new(),
             instruction_offset: 0,
             needs_new_block: true,
+            lift_error: None,
             mem_id: 0,
             assume_cc: false,
             replace_pc: true,
+            diagnostics: Vec::new(),
+            switches: HashMap::new(),
         };
 
         // Add all the registers to the variable list.
@@ -97,14 +139,49 @@ where
     }
 
     // Helper wrapper.
-    pub fn construct(rfn: &mut RadecoFunction, ri: &LRegInfo, assume_cc: bool, replace_pc: bool) {
+    pub fn construct(
+        rfn: &mut RadecoFunction,
+        ri: &LRegInfo,
+        assume_cc: bool,
+        replace_pc: bool,
+    ) -> Result<(), String> {
+        SSAConstruct::construct_with_switches(rfn, ri, assume_cc, replace_pc, HashMap::new())
+    }
+
+    // Helper wrapper. Like `construct`, but lifts the indirect jumps in `switches` as switches.
+    pub fn construct_with_switches(
+        rfn: &mut RadecoFunction,
+        ri: &LRegInfo,
+        assume_cc: bool,
+        replace_pc: bool,
+        switches: HashMap<u64, Vec<(u64, Vec<u64>)>>,
+    ) -> Result<(), String> {
         let instructions = rfn.instructions().to_vec();
         let regfile = Arc::new(SubRegisterFile::new(ri));
         rfn.ssa_mut().regfile = regfile.clone();
-        let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-        constr.assume_cc = assume_cc;
-        constr.replace_pc = replace_pc;
-        constr.run(instructions.as_slice());
+        let (result, diagnostics) = {
+            let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constr.assume_cc = assume_cc;
+            constr.replace_pc = replace_pc;
+            constr.set_switches(switches);
+            let result = constr.run(instructions.as_slice());
+            (result, constr.diagnostics)
+        };
+        rfn.lift_diagnostics = diagnostics;
+        result
+    }
+
+    /// Sets the known targets of indirect jumps. `switches` maps the address of a jump
+    /// instruction to its targets, each along with the values of the selector that lead to it.
+    /// Such a jump is lifted as a switch on the jump target, with one case edge per target,
+    /// instead of as an unexplored indirect jump.
+    pub fn set_switches(&mut self, switches: HashMap<u64, Vec<(u64, Vec<u64>)>>) {
+        self.switches = switches;
+    }
+
+    /// Returns the instructions that could not be lifted so far.
+    pub fn diagnostics(&self) -> &[LiftDiagnostic] {
+        &self.diagnostics
     }
 
     fn set_mem_id(&mut self, id: u64) {
@@ -128,16 +205,18 @@ where
         var: &Option<Token>,
         address: &mut MAddress,
         length: Option<u64>,
-    ) -> Option<T::ValueRef> {
-        if var.is_none() {
-            return None;
-        }
-        let ret = match *var.as_ref().expect("This cannot be `None`") {
+    ) -> Result<Option<T::ValueRef>, String> {
+        let var = if let Some(ref var) = *var {
+            var
+        } else {
+            return Ok(None);
+        };
+        let ret = match *var {
             // Since ESIL has no concept of intermediates, the identifier spotted by parser
             // has to be a register.
             Token::ERegister(ref name) | Token::EIdentifier(ref name) => {
                 if self.replace_pc
-                    && name == self.regfile.alias_info.get("PC").unwrap()
+                    && Some(name) == self.regfile.alias_info.get("PC")
                     && length.is_some()
                 {
                     // PC is a constant value at given address
@@ -152,7 +231,7 @@ where
             Token::EEntry(ref id, _) => *self
                 .intermediates
                 .get(*id)
-                .expect("This cannot return `None`"),
+                .ok_or_else(|| format!("Unknown intermediate {}", id))?,
             Token::EConstant(value) => {
                 // Add or retrieve a constant with the value from the table.
                 self.phiplacer.add_const(address, value, None)
@@ -162,12 +241,11 @@ where
                 let value = address.address;
                 self.phiplacer.add_const(address, value, None)
             }
-            _ => panic!(
-                "SSAConstruct Error: Found something other than a Var as an operand to an \
-                 instruction!"
-            ),
+            ref token => {
+                return Err(format!("Unexpected operand {:?}", token));
+            }
         };
-        Some(ret)
+        Ok(Some(ret))
     }
 
     fn process_out(&mut self, result: Option<T::ValueRef>, _: MAddress) -> Option<Token> {
@@ -195,14 +273,14 @@ where
         address: &mut MAddress,
         operands: &[Option<Token>; 2],
         op_length: u64,
-    ) -> Option<T::ValueRef> {
+    ) -> Result<Option<T::ValueRef>, String> {
         // This is where the real transformation from ESIL to radeco IL happens. This
         // method choose the opcodes to translate from ESIL to radecoIL and also
         // handles assignments
         // and jumps as these are cases that need to be handled a bit differently from
         // the rest of the opcodes.
-        let mut lhs = self.process_in(&operands[0], address, Some(op_length));
-        let mut rhs = self.process_in(&operands[1], address, Some(op_length));
+        let mut lhs = self.process_in(&operands[0], address, Some(op_length))?;
+        let mut rhs = self.process_in(&operands[1], address, Some(op_length))?;
 
         self.phiplacer
             .narrow_const_operand(address, &mut lhs, &mut rhs);
@@ -236,7 +314,8 @@ where
                 // If the register being written into is "PC" then we emit a jump (jmp) instead
                 // of an assignment.
                 if let Some(Token::EIdentifier(ref name)) = operands[0] {
-                    if name == self.regfile.alias_info.get("PC").expect("") {
+                    let rhs = rhs.ok_or_else(|| missing_operand(token))?;
+                    if Some(name) == self.regfile.alias_info.get("PC") {
                         // There is a possibility that the jump target is not a constant and we
                         // don't have enough information right now to resolve this target. In this
                         // case, we add a new block and label it unresolved. This maybe resolved as
@@ -251,54 +330,40 @@ where
                                 Some(UNCOND_EDGE),
                             );
                             self.needs_new_block = true;
+                        } else if let Some(cases) = self.switches.get(&address.address) {
+                            // Indirect CF transfer with known targets
+                            self.phiplacer.add_switch(&rhs, address, cases);
+                            self.needs_new_block = true;
                         } else {
                             // Indirect CF transfer
-                            if let Some(ref jump_idx) = rhs {
-                                self.phiplacer
-                                    .add_indirect_cf(jump_idx, address, UNCOND_EDGE);
-                                // Next instruction should begin in a new block
-                                self.needs_new_block = true;
-                            } else {
-                                radeco_warn!(
-                                    "Found a indirect jump without a corresponding target \
-                                     expression"
-                                );
-                            }
+                            self.phiplacer
+                                .add_indirect_cf(&rhs, address, UNCOND_EDGE);
+                            // Next instruction should begin in a new block
+                            self.needs_new_block = true;
                         }
                     } else {
                         // We are writing into a register.
-                        self.phiplacer.write_register(
-                            address,
-                            name,
-                            rhs.expect("rhs for EEq cannot be `None`"),
-                        );
+                        self.phiplacer.write_register(address, name, rhs);
                     }
                 } else {
                     // This means that we're performing a memory write. So we need to emit an
                     // OpStore operation.
                     radeco_trace!("Memory Write");
+                    let addr = lhs.ok_or_else(|| missing_operand(token))?;
+                    let value = rhs.ok_or_else(|| missing_operand(token))?;
                     let op_node = self
                         .phiplacer
                         .add_op(&MOpcode::OpStore, address, *MEM_VALUEINFO);
-                    self.phiplacer.op_use(
-                        &op_node,
-                        0,
-                        lhs.as_ref()
-                            .expect("`addr` of `MemoryWrite` cannot be `None`"),
-                    );
-                    self.phiplacer.op_use(
-                        &op_node,
-                        1,
-                        rhs.as_ref()
-                            .expect("`value` of `MemoryWrite` cannot be `None`"),
-                    );
+                    self.phiplacer.op_use(&op_node, 0, &addr);
+                    self.phiplacer.op_use(&op_node, 1, &value);
                 }
-                return None;
+                return Ok(None);
             }
             // Returns None.
             Token::EIf => {
                 // Create a new block for true.
                 // The next instruction must be a part of the true block, unless we see an "{"
+                let cond = lhs.ok_or_else(|| missing_operand(token))?;
                 let op_node = self.phiplacer.add_op(
                     &MOpcode::OpITE,
                     address,
@@ -318,10 +383,9 @@ where
                     scalar!(0),
                     format!("T: {}", true_address),
                 );
-                self.phiplacer
-                    .op_use(&op_node, 0, lhs.as_ref().expect("lhs cannot be `None`"));
+                self.phiplacer.op_use(&op_node, 0, &cond);
                 self.phiplacer.op_use(&op_node, 1, &true_comment);
-                return None;
+                return Ok(None);
             }
             Token::ELsl => (
                 MOpcode::OpLsl,
@@ -377,6 +441,8 @@ where
             ),
             Token::EPoke(_) => {
                 // TODO: rhs has to be cast to size 'n' if it's size is not already n.
+                let addr = lhs.ok_or_else(|| missing_operand(token))?;
+                let value = rhs.ok_or_else(|| missing_operand(token))?;
                 let mem_id = self.mem_id();
                 let mem = self.phiplacer.read_variable(address, mem_id);
                 let op_node = self
@@ -384,16 +450,15 @@ where
                     .add_op(&MOpcode::OpStore, address, scalar!(0));
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
-                self.phiplacer
-                    .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
-                self.phiplacer
-                    .op_use(&op_node, 2, rhs.as_ref().expect("rhs cannot be `None`"));
+                self.phiplacer.op_use(&op_node, 1, &addr);
+                self.phiplacer.op_use(&op_node, 2, &value);
 
                 self.phiplacer
                     .write_variable(*address, self.mem_id, op_node);
-                return None;
+                return Ok(None);
             }
             Token::EPeek(n) => {
+                let addr = lhs.ok_or_else(|| missing_operand(token))?;
                 let mem = self.phiplacer.read_variable(address, self.mem_id);
                 let op_node = self.phiplacer.add_op(
                     &MOpcode::OpLoad,
@@ -402,66 +467,107 @@ where
                 );
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
-                self.phiplacer
-                    .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
-                return Some(op_node);
+                self.phiplacer.op_use(&op_node, 1, &addr);
+                return Ok(Some(op_node));
             }
-            Token::EPop => unreachable!(),
-            Token::EGoto => unimplemented!(),
-            Token::EBreak => unimplemented!(),
             Token::EEndIf | Token::ENop => {
-                return None;
+                return Ok(None);
             }
-            // Anything else is considered invalid. Log this as a warning and move on.
-            // We may not
-            // want to panic here as we can still achieve a reasonable decompilation
-            // missing just
+            // `GOTO` and `BREAK` are handled by `lift_structured` and never reach here.
+            // Anything else is considered invalid. The caller replaces the instruction with an
+            // opaque node, as we can still achieve a reasonable decompilation missing just
             // one or two instructions.
             _ => {
-                unimplemented!();
+                return Err(format!("Unsupported token {:?}", token));
             }
         };
 
+        let lhs = lhs.ok_or_else(|| missing_operand(token))?;
+        Ok(Some(self.add_arith_op(&op, vt, lhs, rhs, address, false)))
+    }
+
+    // Adds the operation `op` on `lhs` and, for binary operations, `rhs`. The narrower of the
+    // two operands is widened first, by sign extension if `signed` is set.
+    fn add_arith_op(
+        &mut self,
+        op: &MOpcode,
+        vt: ValueInfo,
+        lhs: T::ValueRef,
+        rhs: Option<T::ValueRef>,
+        address: &mut MAddress,
+        signed: bool,
+    ) -> T::ValueRef {
+        let lhs_size = self.phiplacer.operand_width(&lhs);
+        let rhs_size = rhs.map_or(0, |i| self.phiplacer.operand_width(&i));
+        let extend = |size| {
+            if signed {
+                MOpcode::OpSignExt(size)
+            } else {
+                MOpcode::OpZeroExt(size)
+            }
+        };
         // Insert `widen` cast of the two are not of same size and rhs is_some.
-        if rhs.is_some() {
+        if let Some(rhs) = rhs {
             let (lhs, rhs) = match lhs_size.cmp(&rhs_size) {
                 cmp::Ordering::Greater => {
                     let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(lhs_size));
-                    let casted_rhs =
-                        self.phiplacer
-                            .add_op(&MOpcode::OpZeroExt(lhs_size), address, vt);
-                    self.phiplacer
-                        .op_use(&casted_rhs, 0, rhs.as_ref().expect(""));
+                    let casted_rhs = self.phiplacer.add_op(&extend(lhs_size), address, vt);
+                    self.phiplacer.op_use(&casted_rhs, 0, &rhs);
                     self.phiplacer.propagate_reginfo(&casted_rhs);
-                    (lhs.expect("lhs cannot be `None`"), casted_rhs)
+                    (lhs, casted_rhs)
                 }
                 cmp::Ordering::Less => {
                     let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(rhs_size));
-                    let casted_lhs =
-                        self.phiplacer
-                            .add_op(&MOpcode::OpZeroExt(rhs_size), address, vt);
-                    self.phiplacer.op_use(
-                        &casted_lhs,
-                        0,
-                        lhs.as_ref().expect("lhs cannot be `None`"),
-                    );
+                    let casted_lhs = self.phiplacer.add_op(&extend(rhs_size), address, vt);
+                    self.phiplacer.op_use(&casted_lhs, 0, &lhs);
                     self.phiplacer.propagate_reginfo(&casted_lhs);
-                    (casted_lhs, rhs.expect(""))
+                    (casted_lhs, rhs)
                 }
-                cmp::Ordering::Equal => (lhs.expect(""), rhs.expect("")),
+                cmp::Ordering::Equal => (lhs, rhs),
             };
-            let op_node_ = self.phiplacer.add_op(&op, address, vt);
+            let op_node_ = self.phiplacer.add_op(op, address, vt);
             self.phiplacer.op_use(&op_node_, 0, &lhs);
             self.phiplacer.op_use(&op_node_, 1, &rhs);
-            Some(op_node_)
+            op_node_
         } else {
             // There is only one operand, that is lhs. No need for cast.
-            let op_node_ = self.phiplacer.add_op(&op, address, vt);
-            self.phiplacer.op_use(&op_node_, 0, lhs.as_ref().expect(""));
-            Some(op_node_)
+            let op_node_ = self.phiplacer.add_op(op, address, vt);
+            self.phiplacer.op_use(&op_node_, 0, &lhs);
+            op_node_
         }
     }
 
+    // Lifts one of the signed ESIL operators `~/`, `~%` and `>>>>`, which are not known to
+    // the ESIL parser. `operands` are fetched like the ones of their unsigned counterparts.
+    fn process_signed_op(
+        &mut self,
+        word: &str,
+        address: &mut MAddress,
+        operands: &[Option<Token>; 2],
+        op_length: u64,
+    ) -> Result<Option<T::ValueRef>, String> {
+        let op = match word {
+            "~/" => MOpcode::OpSDiv,
+            "~%" => MOpcode::OpSMod,
+            ">>>>" => MOpcode::OpAsr,
+            _ => return Err(format!("Unsupported operator {}", word)),
+        };
+        let mut lhs = self.process_in(&operands[0], address, Some(op_length))?;
+        let mut rhs = self.process_in(&operands[1], address, Some(op_length))?;
+        self.phiplacer
+            .narrow_const_operand(address, &mut lhs, &mut rhs);
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => return Err(format!("Missing operand for {}", word)),
+        };
+        let result_size = cmp::max(
+            self.phiplacer.operand_width(&lhs),
+            self.phiplacer.operand_width(&rhs),
+        );
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::from(result_size));
+        Ok(Some(self.add_arith_op(&op, vt, lhs, Some(rhs), address, true)))
+    }
+
     fn init_blocks(&mut self) {
         // Create a start block with all registers as variables defined in this block.
         // Seal this block as the start block cannot have any more successors.
@@ -509,7 +615,14 @@ where
     // ESIL is received, it merely takes this vector of ESIL strings and transforms
     // it into its SSA
     // form.
-    pub fn run(&mut self, op_info: &[LOpInfo]) {
+    //
+    // Instructions that cannot be lifted are replaced by opaque nodes, see `diagnostics`. An
+    // error is returned only if the function as a whole cannot be lifted, e.g. because the
+    // register profile lacks a register the lifter relies on.
+    pub fn run(&mut self, op_info: &[LOpInfo]) -> Result<(), String> {
+        if self.regfile.alias_info.get("PC").is_none() {
+            return Err("The register profile has no PC alias".to_owned());
+        }
         let mut p = Parser::init(
             Some(
                 self.regfile
@@ -522,6 +635,9 @@ where
         );
 
         let mut current_address = MAddress::new(0, 0);
+        // Set if the previous instruction was split into sub-instruction blocks. In that case,
+        // the block that falls through to the next instruction is the last of these blocks.
+        let mut last_was_structured = false;
         self.init_blocks();
         for op in op_info {
             if op.esil.is_none() {
@@ -547,7 +663,10 @@ where
                 self.phiplacer.add_block(next_address, None, None);
             }
 
-            current_address.offset = 0;
+            if !last_was_structured {
+                current_address.offset = 0;
+            }
+            last_was_structured = false;
             self.phiplacer.maybe_add_edge(current_address, next_address);
             current_address = next_address;
 
@@ -573,7 +692,7 @@ where
             // NOTE: This is a hack.
             {
                 // also handle unknown ESIL this way
-                let overrides = &["GOTO", "TRAP", "$", "TODO", "REPEAT"];
+                let overrides = &["TRAP", "$", "TODO", "REPEAT"];
                 let opt_call_ty = if esil_str.split(",").any(|x| overrides.contains(&x)) {
                     Some(Cow::Owned(format!("ESIL: {}", esil_str)))
                 } else if let Some(ref ty) = op.optype {
@@ -588,6 +707,14 @@ where
 
                 if let Some(call_ty) = opt_call_ty {
                     let is_real_call = &*call_ty == "call" || &*call_ty == "ucall";
+                    if !is_real_call {
+                        self.diagnostics.push(LiftDiagnostic {
+                            address: offset,
+                            disassembly: op.opcode.clone(),
+                            esil: esil_str.clone(),
+                            reason: "Unsupported ESIL".to_owned(),
+                        });
+                    }
 
                     let unknown_str = "unknown".to_owned();
 
@@ -635,7 +762,7 @@ where
                                 .regfile
                                 .whole_registers
                                 .get(i)
-                                .expect("Unable to find register with index");
+                                .ok_or_else(|| format!("No width for register {}", reg))?;
                             let comment_node = self.phiplacer.add_comment(
                                 current_address,
                                 *width,
@@ -671,12 +798,14 @@ where
                             .whole_names
                             .iter()
                             .position(|r| r == reg)
-                            .expect("Invalid register");
+                            .ok_or_else(|| {
+                                format!("Return register {} is not a whole register", reg)
+                            })?;
                         let width = self
                             .regfile
                             .whole_registers
                             .get(idx)
-                            .expect("Unable to find register with index");
+                            .ok_or_else(|| format!("No width for register {}", reg))?;
                         let comment_node = self.phiplacer.add_comment(
                             current_address,
                             *width,
@@ -722,20 +851,19 @@ where
              }
              */
 
-            while let Some(ref token) = p.parse::<_, Tokenizer>(esil_str) {
-                radeco_trace!("ssa_construct_token|{}|{:?}", current_address, token);
-                let (lhs, rhs) = p.fetch_operands(token);
-                // Determine what to do with the operands and get the result.
-                let result = self.process_op(
-                    token,
-                    &mut current_address,
-                    &[lhs, rhs],
-                    op.size.unwrap_or(0),
-                );
-                if let Some(result_) = self.process_out(result, current_address) {
-                    p.push(result_);
-                }
-                current_address.offset += 1;
+            let words = esil_str.split(',').collect::<Vec<_>>();
+            if needs_structured_lift(&words) {
+                self.lift_structured(&mut p, &words, &mut current_address, op.size.unwrap_or(0));
+                last_was_structured = true;
+            } else {
+                self.lift_esil(&mut p, esil_str, &mut current_address, op.size.unwrap_or(0));
+            }
+            if let Some(reason) = self.lift_error.take() {
+                // Tokens lifted before the failure may have written registers or memory
+                // already. The opaque node stands for the whole instruction, so it has to see
+                // the state from before it.
+                self.phiplacer.restore_state_before(offset);
+                self.lift_opaque(op, esil_str, reason, &mut current_address);
             }
         }
         // BUG: The last block may not have the biggest address, which means current_address
@@ -743,6 +871,380 @@ where
         // self.phiplacer.add_edge(current_address, MAddress::new(u64::MAX, 0), UNCOND_EDGE);
         self.phiplacer.gather_exits();
         self.phiplacer.finish(op_info);
+        Ok(())
+    }
+
+    // Lifts straight-line ESIL.
+    fn lift_esil(
+        &mut self,
+        p: &mut Parser,
+        esil: &str,
+        current_address: &mut MAddress,
+        op_length: u64,
+    ) {
+        while let Some(ref token) = p.parse::<_, Tokenizer>(esil) {
+            radeco_trace!("ssa_construct_token|{}|{:?}", current_address, token);
+            let (lhs, rhs) = p.fetch_operands(token);
+            // Determine what to do with the operands and get the result.
+            let result = match self.process_op(token, current_address, &[lhs, rhs], op_length) {
+                Ok(result) => result,
+                Err(reason) => {
+                    // Skip the rest of this piece of ESIL, the instruction is replaced by an
+                    // opaque node once it has been processed.
+                    while p.parse::<_, Tokenizer>(esil).is_some() {}
+                    if self.lift_error.is_none() {
+                        self.lift_error = Some(reason);
+                    }
+                    return;
+                }
+            };
+            if let Some(result_) = self.process_out(result, *current_address) {
+                p.push(result_);
+            }
+            current_address.offset += 1;
+        }
+    }
+
+    // Replaces an instruction that could not be lifted by an opaque node. The node reads and
+    // clobbers every register the instruction writes to, as well as memory if the instruction
+    // stores to it.
+    fn lift_opaque(
+        &mut self,
+        op: &LOpInfo,
+        esil: &str,
+        reason: String,
+        current_address: &mut MAddress,
+    ) {
+        let offset = op.offset.unwrap_or(0);
+        radeco_warn!("Unable to lift {:?} at {:#x}: {}", esil, offset, reason);
+        self.diagnostics.push(LiftDiagnostic {
+            address: offset,
+            disassembly: op.opcode.clone(),
+            esil: esil.to_owned(),
+            reason: reason,
+        });
+
+        let description = self.phiplacer.add_comment(
+            *current_address,
+            scalar!(0),
+            op.opcode.clone().unwrap_or_else(|| "unknown".to_owned()),
+        );
+        let opcode = MOpcode::OpCustom(format!("ESIL: {}", esil));
+        let opaque = self
+            .phiplacer
+            .add_op(&opcode, current_address, scalar!(0));
+        self.phiplacer.op_use(&opaque, 0, &description);
+
+        let (registers, writes_memory) = written_locations(esil);
+        let regfile = self.regfile;
+        let pc = regfile.alias_info.get("PC");
+        let mut i = 1;
+        for reg in registers {
+            // Writes to PC are jumps, which we cannot follow without a target.
+            if Some(&reg) == pc {
+                continue;
+            }
+            let width = match regfile.get_subregister(&reg) {
+                Some(subreg) => subreg.width as u16,
+                None => continue,
+            };
+            let old = self.phiplacer.read_register(current_address, &reg);
+            self.phiplacer.op_use(&opaque, i, &old);
+            i += 1;
+            let clobber = self.phiplacer.add_comment(
+                *current_address,
+                scalar!(width),
+                format!("{}@{}", reg, current_address),
+            );
+            self.phiplacer
+                .write_register(current_address, &reg, clobber);
+            self.phiplacer.op_use(&clobber, 0, &opaque);
+        }
+
+        if writes_memory {
+            let mem_id = self.mem_id();
+            let mem = self.phiplacer.read_variable(current_address, mem_id);
+            self.phiplacer.op_use(&opaque, i, &mem);
+            let clobber = self.phiplacer.add_comment(
+                *current_address,
+                *MEM_VALUEINFO,
+                format!("mem@{}", current_address),
+            );
+            self.phiplacer
+                .write_variable(*current_address, mem_id, clobber);
+            self.phiplacer.op_use(&clobber, 0, &opaque);
+        }
+    }
+
+    // Lifts an instruction whose ESIL contains control flow (conditionals followed by more
+    // statements, `}{`, `GOTO` or `BREAK`) by splitting it into sub-instruction blocks.
+    //
+    // The straight-line parts between control flow words are handed over to the parser as is.
+    // On return, `current_address` is an address in the last of the sub-instruction blocks.
+    fn lift_structured(
+        &mut self,
+        p: &mut Parser,
+        words: &[&str],
+        current_address: &mut MAddress,
+        op_length: u64,
+    ) {
+        let goto_targets = words
+            .windows(2)
+            .filter(|w| w[1] == "GOTO")
+            .filter_map(|w| parse_esil_constant(w[0]))
+            .map(|t| t as usize)
+            .collect::<HashSet<_>>();
+
+        // Start of the sub-instruction block for every `GOTO` target seen so far.
+        let mut target_blocks: HashMap<usize, MAddress> = HashMap::new();
+        // Sources of `GOTO`s to targets that are not lifted yet.
+        let mut forward_jumps: HashMap<usize, Vec<MAddress>> = HashMap::new();
+        // Sources of jumps to the end of the instruction.
+        let mut exits: Vec<MAddress> = Vec::new();
+        let mut conditionals: Vec<Conditional<T::ValueRef>> = Vec::new();
+        // Words that have not been handed to the parser yet.
+        let mut pending: Vec<&str> = Vec::new();
+        // Set if the current block has ended in a jump. Words following it are dead until the
+        // next block starts.
+        let mut terminated = false;
+
+        self.needs_new_block = false;
+        for (i, &word) in words.iter().enumerate() {
+            if goto_targets.contains(&i) {
+                self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                let at = self.start_sub_block(current_address, !terminated);
+                for src in forward_jumps.remove(&i).unwrap_or_default() {
+                    self.phiplacer.add_edge(src, at, UNCOND_EDGE);
+                }
+                target_blocks.insert(i, at);
+                terminated = false;
+            }
+
+            match word {
+                "?{" => {
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    if terminated {
+                        // The whole conditional is dead, but still needs to be tracked to
+                        // match the braces.
+                        conditionals.push(Conditional::dead());
+                        continue;
+                    }
+                    let (cond, _) = p.fetch_operands(&Token::EIf);
+                    let cond = match self.process_in(&cond, current_address, Some(op_length)) {
+                        Ok(Some(cond)) => cond,
+                        Ok(None) | Err(_) => {
+                            if self.lift_error.is_none() {
+                                self.lift_error = Some(missing_operand(&Token::EIf));
+                            }
+                            self.phiplacer.add_undefined(*current_address, scalar!(1))
+                        }
+                    };
+                    let ite = self.phiplacer.add_op(
+                        &MOpcode::OpITE,
+                        current_address,
+                        ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
+                    );
+                    let cond_address = *current_address;
+                    current_address.offset += 1;
+                    let true_address = *current_address;
+                    self.phiplacer
+                        .add_block(true_address, Some(cond_address), Some(TRUE_EDGE));
+                    let true_comment = self.phiplacer.add_comment(
+                        cond_address,
+                        scalar!(0),
+                        format!("T: {}", true_address),
+                    );
+                    self.phiplacer.op_use(&ite, 0, &cond);
+                    self.phiplacer.op_use(&ite, 1, &true_comment);
+                    current_address.offset += 1;
+                    conditionals.push(Conditional::new(ite, cond_address));
+                }
+                "}{" => {
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    let dead = conditionals.last().map_or(true, |c| c.ite.is_none());
+                    if !terminated && !dead {
+                        if let Some(c) = conditionals.last_mut() {
+                            c.exits.push(*current_address);
+                        }
+                    }
+                    if dead {
+                        continue;
+                    }
+                    let else_address = self.start_sub_block(current_address, false);
+                    if let Some(c) = conditionals.last_mut() {
+                        self.add_false_edge(c, else_address);
+                    }
+                    terminated = false;
+                }
+                "}" => {
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    if let Some(c) = conditionals.pop() {
+                        terminated = self.close_conditional(c, current_address, terminated);
+                    } else {
+                        radeco_warn!("Unbalanced `}}` at {}", current_address);
+                    }
+                }
+                "BREAK" => {
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    if !terminated {
+                        exits.push(*current_address);
+                        terminated = true;
+                    }
+                }
+                "GOTO" => {
+                    // The target is the constant right before `GOTO`. It has been kept out of
+                    // `pending`, see below.
+                    let target = i
+                        .checked_sub(1)
+                        .and_then(|j| parse_esil_constant(words[j]))
+                        .map(|t| t as usize);
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    if terminated {
+                        continue;
+                    }
+                    match target {
+                        Some(t) if t <= i => {
+                            if let Some(&at) = target_blocks.get(&t) {
+                                self.phiplacer.add_edge(*current_address, at, UNCOND_EDGE);
+                            }
+                        }
+                        Some(t) if t < words.len() => {
+                            forward_jumps.entry(t).or_insert_with(Vec::new).push(*current_address);
+                        }
+                        _ => {
+                            radeco_warn!(
+                                "Unsupported GOTO target at {}; treating it as BREAK",
+                                current_address
+                            );
+                            exits.push(*current_address);
+                        }
+                    }
+                    terminated = true;
+                }
+                "~/" | "~%" | ">>>>" => {
+                    self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+                    if terminated {
+                        continue;
+                    }
+                    let (lhs, rhs) = p.fetch_operands(&Token::EDiv);
+                    match self.process_signed_op(word, current_address, &[lhs, rhs], op_length) {
+                        Ok(result) => {
+                            if let Some(result_) = self.process_out(result, *current_address) {
+                                p.push(result_);
+                            }
+                            current_address.offset += 1;
+                        }
+                        Err(reason) => {
+                            if self.lift_error.is_none() {
+                                self.lift_error = Some(reason);
+                            }
+                        }
+                    }
+                }
+                "" => {}
+                _ => {
+                    let is_goto_target = words.get(i + 1) == Some(&"GOTO")
+                        && parse_esil_constant(word).is_some();
+                    if !terminated && !is_goto_target {
+                        pending.push(word);
+                    }
+                }
+            }
+        }
+        self.flush_esil(p, &mut pending, current_address, op_length, &mut terminated);
+
+        // Close conditionals that were left open.
+        while let Some(c) = conditionals.pop() {
+            terminated = self.close_conditional(c, current_address, terminated);
+        }
+
+        // Jumps past the last word of the instruction continue at its end.
+        for (_, srcs) in forward_jumps.drain() {
+            exits.extend(srcs);
+        }
+        if !exits.is_empty() {
+            let at = self.start_sub_block(current_address, !terminated);
+            for src in exits {
+                self.phiplacer.add_edge(src, at, UNCOND_EDGE);
+            }
+            terminated = false;
+        }
+
+        // If the instruction ended in a jump, the next instruction has to start a new block.
+        self.needs_new_block = terminated;
+    }
+
+    // Hands the words collected so far over to the parser. Sets `terminated` if they ended in
+    // a jump.
+    fn flush_esil(
+        &mut self,
+        p: &mut Parser,
+        pending: &mut Vec<&str>,
+        current_address: &mut MAddress,
+        op_length: u64,
+        terminated: &mut bool,
+    ) {
+        if pending.is_empty() {
+            return;
+        }
+        let esil = pending.join(",");
+        pending.clear();
+        self.lift_esil(p, &esil, current_address, op_length);
+        if self.needs_new_block {
+            self.needs_new_block = false;
+            *terminated = true;
+        }
+    }
+
+    // Starts a new block at `current_address`, with an unconditional edge from the current
+    // block if `fallthrough` is set. Returns the address of the new block.
+    fn start_sub_block(&mut self, current_address: &mut MAddress, fallthrough: bool) -> MAddress {
+        let at = *current_address;
+        if !self.phiplacer.blocks.contains_key(&at) {
+            if fallthrough {
+                self.phiplacer.add_block(at, Some(at), Some(UNCOND_EDGE));
+            } else {
+                self.phiplacer.add_block(at, None, None);
+            }
+        }
+        current_address.offset += 1;
+        at
+    }
+
+    fn add_false_edge(&mut self, c: &mut Conditional<T::ValueRef>, target: MAddress) {
+        if let Some(ite) = c.ite {
+            let false_comment =
+                self.phiplacer
+                    .add_comment(c.address, scalar!(0), format!("F: {}", target));
+            self.phiplacer.add_edge(c.address, target, FALSE_EDGE);
+            self.phiplacer.op_use(&ite, 2, &false_comment);
+            c.has_false_edge = true;
+        }
+    }
+
+    // Joins the branches of a conditional at a new block. Returns whether the new block is
+    // unreachable.
+    fn close_conditional(
+        &mut self,
+        mut c: Conditional<T::ValueRef>,
+        current_address: &mut MAddress,
+        terminated: bool,
+    ) -> bool {
+        if c.ite.is_none() {
+            // Dead conditional.
+            return terminated;
+        }
+        if !terminated {
+            c.exits.push(*current_address);
+        }
+        let join_address = self.start_sub_block(current_address, false);
+        for src in &c.exits {
+            self.phiplacer.add_edge(*src, join_address, UNCOND_EDGE);
+        }
+        if !c.has_false_edge {
+            self.add_false_edge(&mut c, join_address);
+        }
+        false
     }
 
     #[allow(dead_code)]
@@ -756,6 +1258,7 @@ where
     ) -> T::ValueRef {
         let base_node = if let Some(ref reg) = *base {
             self.process_in(&Some(Token::ERegister(reg.clone())), addr, None)
+                .unwrap_or(None)
         } else {
             None
         };
@@ -765,6 +1268,8 @@ where
             //    <index> '*' <scale>
             let reg_node = self
                 .process_in(&Some(Token::ERegister(reg.clone())), addr, None)
+                .ok()
+                .and_then(|n| n)
                 .expect("Invalid op");
             // TODO: s/64/default op size/
             let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(64));
@@ -901,14 +1406,102 @@ where
      */
 } // end impl SSAConstruct
 
+// Returns `true` if the ESIL words of an instruction have control flow other than a single
+// trailing `?{ }` block, or signed operators the ESIL parser does not know.
+fn needs_structured_lift(words: &[&str]) -> bool {
+    let mut depth = 0;
+    let mut closed = false;
+    for &word in words {
+        match word {
+            "GOTO" | "BREAK" | "}{" => return true,
+            "~/" | "~%" | ">>>>" => return true,
+            "?{" if closed || depth > 0 => return true,
+            "?{" => depth += 1,
+            "}" => {
+                depth -= 1;
+                closed = true;
+            }
+            "" => {}
+            _ if closed => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn missing_operand(token: &Token) -> String {
+    format!("Missing operand for {:?}", token)
+}
+
+// Returns the names of the registers that are assigned to by the ESIL, and whether it
+// writes to memory.
+fn written_locations(esil: &str) -> (Vec<String>, bool) {
+    let words = esil.split(',').collect::<Vec<_>>();
+    let mut registers = Vec::new();
+    let mut writes_memory = false;
+    for (i, word) in words.iter().enumerate() {
+        if word.contains("=[") {
+            writes_memory = true;
+        } else if word.ends_with('=') && !["==", "<=", ">="].contains(word) && i > 0 {
+            let reg = words[i - 1].to_owned();
+            if !registers.contains(&reg) {
+                registers.push(reg);
+            }
+        }
+    }
+    (registers, writes_memory)
+}
+
+fn parse_esil_constant(word: &str) -> Option<u64> {
+    if word.starts_with("0x") {
+        u64::from_str_radix(&word[2..], 16).ok()
+    } else {
+        word.parse::<u64>().ok()
+    }
+}
+
+// A conditional (`?{`) of an instruction being lifted by `SSAConstruct::lift_structured`.
+struct Conditional<V> {
+    // The ITE node that marks the selector, `None` if the conditional is dead.
+    ite: Option<V>,
+    // Address of the ITE node.
+    address: MAddress,
+    // Addresses of the blocks that continue after the matching `}`.
+    exits: Vec<MAddress>,
+    has_false_edge: bool,
+}
+
+impl<V> Conditional<V> {
+    fn new(ite: V, address: MAddress) -> Conditional<V> {
+        Conditional {
+            ite: Some(ite),
+            address: address,
+            exits: Vec::new(),
+            has_false_edge: false,
+        }
+    }
+
+    fn dead() -> Conditional<V> {
+        Conditional {
+            ite: None,
+            address: MAddress::invalid_address(),
+            exits: Vec::new(),
+            has_false_edge: false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use analysis::sccp;
     use middle::ir_writer;
+    use middle::ssa::cfg_traits::CFG;
+    use middle::ssa::ssa_traits::{SSAWalk, SSA};
     use middle::ssa::ssastorage::SSAStorage;
     use middle::{dce, dot};
-    use r2api::structs::{LFunctionInfo, LRegInfo};
+    use petgraph::graph::NodeIndex;
+    use r2api::structs::{LFunctionInfo, LOpInfo, LRegInfo};
     use serde_json;
     use std::fs::File;
     use std::io::prelude::*;
@@ -929,6 +1522,66 @@ mod test {
         *instructions = serde_json::from_str(&*s).unwrap();
     }
 
+    // Lifts one instruction at 0x100, 0x101, ... for every ESIL string, followed by a `ret`.
+    fn lift(esils: &[&str]) -> (SSAStorage, Vec<LiftDiagnostic>) {
+        let mut register_profile = File::open(REGISTER_PROFILE).unwrap();
+        let mut s = String::new();
+        register_profile.read_to_string(&mut s).unwrap();
+        let reg_profile: LRegInfo = serde_json::from_str(&*s).unwrap();
+        let mut ops = esils
+            .iter()
+            .enumerate()
+            .map(|(i, esil)| LOpInfo {
+                offset: Some(0x100 + i as u64),
+                size: Some(1),
+                esil: Some(esil.to_string()),
+                opcode: Some(esil.to_string()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        ops.push(LOpInfo {
+            offset: Some(0x100 + esils.len() as u64),
+            size: Some(1),
+            esil: Some("rsp,[8],rip,=,8,rsp,+=".to_owned()),
+            optype: Some("ret".to_owned()),
+            ..Default::default()
+        });
+        let mut ssa = SSAStorage::new();
+        let diagnostics = {
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            ssa.regfile = regfile.clone();
+            let mut constructor = SSAConstruct::new(&mut ssa, &*regfile);
+            constructor.run(&ops).unwrap();
+            constructor.diagnostics().to_vec()
+        };
+        (ssa, diagnostics)
+    }
+
+    fn ops_of(ssa: &SSAStorage, opcode: MOpcode) -> Vec<NodeIndex> {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn ssa_signed_ops_test() {
+        let (ssa, diagnostics) = lift(&[
+            "rbx,rax,~/,rcx,=",
+            "rbx,rax,~%,rdx,=",
+            "4,rax,>>>>,rsi,=",
+            "ebx,rax,~/,rdi,=",
+        ]);
+        assert!(diagnostics.is_empty());
+        assert_eq!(ops_of(&ssa, MOpcode::OpSDiv).len(), 2);
+        assert_eq!(ops_of(&ssa, MOpcode::OpSMod).len(), 1);
+        let asr = ops_of(&ssa, MOpcode::OpAsr);
+        assert_eq!(asr.len(), 1);
+        let rax = ssa.operands_of(asr[0])[0];
+        assert_eq!(ssa.comment(rax), Some("rax".to_owned()));
+        // The narrower divisor is sign extended.
+        assert_eq!(ops_of(&ssa, MOpcode::OpSignExt(64)).len(), 1);
+    }
+
     #[test]
     fn ssa_simple_test_1() {
         let mut reg_profile = Default::default();
@@ -942,7 +1595,7 @@ mod test {
         {
             let regfile = SubRegisterFile::new(&reg_profile);
             let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor.run(instructions.ops.unwrap().as_slice()).unwrap();
         }
         {
             dce::collect(&mut ssa);
@@ -965,7 +1618,7 @@ mod test {
         {
             let regfile = SubRegisterFile::new(&reg_profile);
             let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor.run(instructions.ops.unwrap().as_slice()).unwrap();
         }
         {
             dce::collect(&mut ssa);
@@ -997,7 +1650,7 @@ mod test {
             let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
             ssa.regfile = regfile.clone();
             let mut constructor = SSAConstruct::new(&mut ssa, &*regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor.run(instructions.ops.unwrap().as_slice()).unwrap();
         }
         {
             dce::collect(&mut ssa);
@@ -1019,6 +1672,183 @@ mod test {
         ir_writer::emit_il(&mut il, Some("main".to_owned()), &ssa).unwrap();
         println!("{}", il);
     }
+
+    #[test]
+    fn ssa_unliftable_test() {
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/unliftable_instructions.json",
+        );
+        let mut ssa = SSAStorage::new();
+        let diagnostics = {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
+            constructor.run(instructions.ops.unwrap().as_slice()).unwrap();
+            constructor.diagnostics().to_vec()
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].address, 0x105);
+        assert_eq!(diagnostics[0].esil, "rcx,=");
+        assert_eq!(diagnostics[0].disassembly, Some("bogus rcx".to_owned()));
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some("main".to_owned()), &ssa).unwrap();
+        assert!(il.contains("ESIL: rcx,="));
+    }
+
+    #[test]
+    fn ssa_partially_lifted_test() {
+        // `1,rax,=` is lifted before `rcx,=` fails.
+        let (ssa, diagnostics) = lift(&["1,rax,=,rcx,="]);
+        assert_eq!(diagnostics.len(), 1);
+        let opaque = ops_of(&ssa, MOpcode::OpCustom("ESIL: 1,rax,=,rcx,=".to_owned()));
+        assert_eq!(opaque.len(), 1);
+        let operands = ssa.operands_of(opaque[0]);
+        // The opaque node reads rax from before the instruction, not the partial write.
+        assert!(operands.iter().any(|&n| ssa.comment(n) == Some("rax".to_owned())));
+        assert!(operands.iter().all(|&n| ssa.constant(n).is_none()));
+        // The partial write does not reach the end of the function either.
+        let exit = ssa.exit_node().unwrap();
+        let final_state = ssa.registers_in(exit).unwrap();
+        assert!(ssa
+            .operands_of(final_state)
+            .iter()
+            .all(|&n| ssa.constant(n) != Some(1)));
+    }
+
+    #[test]
+    fn ssa_no_pc_test() {
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        let mut regfile = SubRegisterFile::new(&reg_profile);
+        regfile.alias_info.remove("PC");
+        let mut ssa = SSAStorage::new();
+        let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
+        assert!(constructor
+            .run(instructions.ops.unwrap().as_slice())
+            .is_err());
+    }
+
+    #[test]
+    fn ssa_goto_break_test() {
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/rep_stos_instructions.json",
+        );
+        let mut ssa = SSAStorage::new();
+        {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
+            constructor.run(instructions.ops.unwrap().as_slice()).unwrap();
+        }
+        // `0,GOTO` jumps back to the start of `rep stosb`, so it is reached from the previous
+        // instruction and from the end of the loop body.
+        let loop_head = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(0x105, 0)))
+            .expect("No block for the loop head");
+        assert_eq!(ssa.preds_of(loop_head).len(), 2);
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some("main".to_owned()), &ssa).unwrap();
+        println!("{}", il);
+    }
+
+    // Sorted operands of `phi`: `#c` for constants, the comment of comments, `?` otherwise.
+    fn phi_operands(ssa: &SSAStorage, phi: NodeIndex) -> Vec<String> {
+        let mut operands = ssa
+            .operands_of(phi)
+            .into_iter()
+            .map(|n| match ssa.constant(n) {
+                Some(c) => format!("#{}", c),
+                None => ssa.comment(n).unwrap_or_else(|| "?".to_owned()),
+            })
+            .collect::<Vec<_>>();
+        operands.sort();
+        operands
+    }
+
+    fn has_phi(ssa: &SSAStorage, operands: &[&str]) -> bool {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.is_phi(n))
+            .any(|phi| phi_operands(ssa, phi) == operands)
+    }
+
+    #[test]
+    fn ssa_break_test() {
+        let (ssa, diagnostics) = lift(&["zf,?{,BREAK,},1,rax,+="]);
+        assert!(diagnostics.is_empty());
+        assert_eq!(ops_of(&ssa, MOpcode::OpITE).len(), 1);
+        // The addition is skipped when breaking out of the instruction.
+        let add = ops_of(&ssa, MOpcode::OpAdd);
+        assert_eq!(add.len(), 1);
+        let add_block = ssa.block_for(add[0]).unwrap();
+        assert_eq!(ssa.preds_of(add_block).len(), 1);
+        let end = ssa
+            .uses_of(add[0])
+            .into_iter()
+            .find(|&u| ssa.is_phi(u))
+            .expect("No phi merging rax at the end of the instruction");
+        assert_eq!(ssa.operands_of(end).len(), 2);
+        assert_eq!(phi_operands(&ssa, end), vec!["?", "rax"]);
+    }
+
+    #[test]
+    fn ssa_else_test() {
+        let (ssa, diagnostics) = lift(&["zf,?{,1,rax,=,}{,2,rax,=,}"]);
+        assert!(diagnostics.is_empty());
+        let ite = ops_of(&ssa, MOpcode::OpITE);
+        assert_eq!(ite.len(), 1);
+        let cond_block = ssa.block_for(ite[0]).unwrap();
+        assert!(ssa.conditional_blocks(cond_block).is_some());
+        // Either branch assigns rax, the entry value does not reach the end.
+        assert!(has_phi(&ssa, &["#1", "#2"]));
+        assert!(!ssa
+            .inorder_walk()
+            .filter(|&n| ssa.is_phi(n))
+            .any(|phi| phi_operands(&ssa, phi).contains(&"rax".to_owned())));
+        // Each constant is recorded along with the branch it flows from.
+        let phi = ssa
+            .inorder_walk()
+            .find(|&n| ssa.is_phi(n) && phi_operands(&ssa, n) == &["#1", "#2"][..])
+            .unwrap();
+        let block = ssa.block_for(phi).unwrap();
+        let mut sources = ssa
+            .preds_of(block)
+            .into_iter()
+            .map(|pred| ssa.phi_source(phi, pred).and_then(|n| ssa.constant(n)))
+            .collect::<Vec<_>>();
+        sources.sort();
+        assert_eq!(sources, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn ssa_inner_conditional_test() {
+        let (ssa, diagnostics) = lift(&["zf,?{,1,rax,=,},rax,rbx,+="]);
+        assert!(diagnostics.is_empty());
+        assert_eq!(ops_of(&ssa, MOpcode::OpITE).len(), 1);
+        // The words after `}` run on both paths and see either value of rax.
+        let add = ops_of(&ssa, MOpcode::OpAdd);
+        assert_eq!(add.len(), 1);
+        let add_block = ssa.block_for(add[0]).unwrap();
+        assert_eq!(ssa.preds_of(add_block).len(), 2);
+        let rax = ssa
+            .operands_of(add[0])
+            .into_iter()
+            .find(|&n| ssa.is_phi(n))
+            .expect("rax is not merged before the addition");
+        assert_eq!(phi_operands(&ssa, rax), vec!["#1", "rax"]);
+    }
 }
 
 lazy_static! {
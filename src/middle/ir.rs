@@ -15,7 +15,7 @@ use std::fmt;
 pub type Address = u64;
 
 /// Used to describe the width of an operand/operation in a SSA Node.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum WidthSpec {
     /// Takes whatever width the other operation takes
     Adaptive,
@@ -48,7 +48,9 @@ impl From<u16> for WidthSpec {
     }
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Clone, Default, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 // New address struct
 pub struct MAddress {
     pub address: u64,
@@ -100,10 +102,12 @@ pub enum MArity {
     Zero,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MOpcode {
     OpAdd,
     OpAnd,
+    // Arithmetic Shift Right
+    OpAsr,
     OpCJmp,
     OpCall,
     OpConst(u64),
@@ -131,6 +135,14 @@ pub enum MOpcode {
     OpRol,
     // Rotate Shift Right
     OpRor,
+    // Signed Division
+    OpSDiv,
+    // Signed Greater Than
+    OpSGt,
+    // Signed Less Than
+    OpSLt,
+    // Signed Modulo
+    OpSMod,
     // Sign Extend to width
     OpSignExt(u16),
     OpStore,
@@ -195,6 +207,7 @@ impl MOpcode {
         match *self {
             MOpcode::OpAdd => (Cow::from("OpAdd"), MArity::Binary),
             MOpcode::OpAnd => (Cow::from("OpAnd"), MArity::Binary),
+            MOpcode::OpAsr => (Cow::from("OpAsr"), MArity::Binary),
             MOpcode::OpCJmp => (Cow::from("OpJmpIf"), MArity::Binary),
             MOpcode::OpCall => (Cow::from("OpCall"), MArity::Unary),
             MOpcode::OpConst(c) => (Cow::from(format!("OpCost({})", c)), MArity::Zero),
@@ -219,6 +232,10 @@ impl MOpcode {
             MOpcode::OpOr => (Cow::from("OpOr"), MArity::Binary),
             MOpcode::OpRol => (Cow::from("OpRol"), MArity::Binary),
             MOpcode::OpRor => (Cow::from("OpRor"), MArity::Binary),
+            MOpcode::OpSDiv => (Cow::from("OpSDiv"), MArity::Binary),
+            MOpcode::OpSGt => (Cow::from("OpSGt"), MArity::Binary),
+            MOpcode::OpSLt => (Cow::from("OpSLt"), MArity::Binary),
+            MOpcode::OpSMod => (Cow::from("OpSMod"), MArity::Binary),
             MOpcode::OpSignExt(_) => (Cow::from("OpSignExt"), MArity::Unary),
             MOpcode::OpStore => (Cow::from("OpStore"), MArity::Ternary),
             MOpcode::OpSub => (Cow::from("OpSub"), MArity::Binary),
@@ -231,71 +248,196 @@ impl MOpcode {
         match *self {
             MOpcode::OpAdd => 0,
             MOpcode::OpAnd => 1,
-            MOpcode::OpCJmp => 2,
-            MOpcode::OpCall => 3,
-            MOpcode::OpConst(_) => 4,
-            MOpcode::OpCustom(_) => 5,
-            MOpcode::OpDiv => 6,
-            MOpcode::OpEq => 7,
-            MOpcode::OpGt => 8,
-            MOpcode::OpITE => 9,
-            MOpcode::OpIf => 10,
-            MOpcode::OpInvalid => 11,
-            MOpcode::OpJmp => 12,
-            MOpcode::OpLoad => 13,
-            MOpcode::OpLsl => 14,
-            MOpcode::OpLsr => 15,
-            MOpcode::OpLt => 16,
-            MOpcode::OpMod => 17,
-            MOpcode::OpMov => 18,
-            MOpcode::OpMul => 19,
-            MOpcode::OpNarrow(_) => 20,
-            MOpcode::OpNop => 21,
-            MOpcode::OpNot => 22,
-            MOpcode::OpOr => 23,
-            MOpcode::OpRol => 24,
-            MOpcode::OpRor => 25,
-            MOpcode::OpSignExt(_) => 26,
-            MOpcode::OpStore => 27,
-            MOpcode::OpSub => 28,
-            MOpcode::OpXor => 29,
-            MOpcode::OpZeroExt(_) => 30,
+            MOpcode::OpAsr => 2,
+            MOpcode::OpCJmp => 3,
+            MOpcode::OpCall => 4,
+            MOpcode::OpConst(_) => 5,
+            MOpcode::OpCustom(_) => 6,
+            MOpcode::OpDiv => 7,
+            MOpcode::OpEq => 8,
+            MOpcode::OpGt => 9,
+            MOpcode::OpITE => 10,
+            MOpcode::OpIf => 11,
+            MOpcode::OpInvalid => 12,
+            MOpcode::OpJmp => 13,
+            MOpcode::OpLoad => 14,
+            MOpcode::OpLsl => 15,
+            MOpcode::OpLsr => 16,
+            MOpcode::OpLt => 17,
+            MOpcode::OpMod => 18,
+            MOpcode::OpMov => 19,
+            MOpcode::OpMul => 20,
+            MOpcode::OpNarrow(_) => 21,
+            MOpcode::OpNop => 22,
+            MOpcode::OpNot => 23,
+            MOpcode::OpOr => 24,
+            MOpcode::OpRol => 25,
+            MOpcode::OpRor => 26,
+            MOpcode::OpSDiv => 27,
+            MOpcode::OpSGt => 28,
+            MOpcode::OpSLt => 29,
+            MOpcode::OpSMod => 30,
+            MOpcode::OpSignExt(_) => 31,
+            MOpcode::OpStore => 32,
+            MOpcode::OpSub => 33,
+            MOpcode::OpXor => 34,
+            MOpcode::OpZeroExt(_) => 35,
         }
     }
 
+    /// Evaluates a binary operation on 64-bit operands.
+    ///
+    /// Returns `None` if the operation cannot be evaluated or if its result is undefined (for
+    /// example, division by zero). See `eval_binop_width` for width aware evaluation.
     pub fn eval_binop(&self, lhs: u64, rhs: u64) -> Option<u64> {
+        self.eval_binop_width(lhs, rhs, 64, 64)
+            .and_then(EvalResult::value)
+    }
+
+    /// Evaluates a unary operation on a 64-bit operand.
+    pub fn eval_unop(&self, val: u64) -> Option<u64> {
+        let result_width = match *self {
+            MOpcode::OpNarrow(w) | MOpcode::OpSignExt(w) | MOpcode::OpZeroExt(w) => w,
+            _ => 64,
+        };
+        self.eval_unop_width(val, 64, result_width)
+            .and_then(EvalResult::value)
+    }
+
+    /// Evaluates a binary operation whose operands are `operand_width` bits wide and whose
+    /// result is `result_width` bits wide.
+    ///
+    /// Operands are truncated to `operand_width` before evaluation and signed operations
+    /// sign extend them from that width. The result is truncated to `result_width`. A width
+    /// of `0` or above `64` is treated as 64 bits.
+    ///
+    /// Returns `None` if the opcode cannot be evaluated and `Some(EvalResult::Undefined)` if
+    /// the result is undefined, as is the case for division by zero.
+    pub fn eval_binop_width(
+        &self,
+        lhs: u64,
+        rhs: u64,
+        operand_width: u16,
+        result_width: u16,
+    ) -> Option<EvalResult> {
         use self::MOpcode::*;
-        use std::num::Wrapping;
-
-        let lhs = Wrapping(lhs);
-        let rhs = Wrapping(rhs);
-        Some(match self {
-            OpAdd => (lhs + rhs).0,
-            OpSub => (lhs - rhs).0,
-            OpMul => (lhs * rhs).0,
-            OpDiv => (lhs / rhs).0,
-            OpMod => (lhs % rhs).0,
-            OpAnd => (lhs & rhs).0,
-            OpOr => (lhs | rhs).0,
-            OpXor => (lhs ^ rhs).0,
+
+        let ow = effective_width(operand_width);
+        let lhs = truncate(lhs, ow);
+        let rhs = truncate(rhs, ow);
+        let slhs = sign_extend(lhs, ow) as i64;
+        let srhs = sign_extend(rhs, ow) as i64;
+
+        let val = match *self {
+            OpAdd => lhs.wrapping_add(rhs),
+            OpSub => lhs.wrapping_sub(rhs),
+            OpMul => lhs.wrapping_mul(rhs),
+            OpDiv | OpMod | OpSDiv | OpSMod if rhs == 0 => return Some(EvalResult::Undefined),
+            OpDiv => lhs / rhs,
+            OpMod => lhs % rhs,
+            OpSDiv => slhs.wrapping_div(srhs) as u64,
+            OpSMod => slhs.wrapping_rem(srhs) as u64,
+            OpAnd => lhs & rhs,
+            OpOr => lhs | rhs,
+            OpXor => lhs ^ rhs,
             OpEq => (lhs == rhs) as u64,
             OpGt => (lhs > rhs) as u64,
             OpLt => (lhs < rhs) as u64,
-            OpLsl => (lhs << (rhs.0 as usize)).0,
-            OpLsr => (lhs >> (rhs.0 as usize)).0,
-            OpRol => lhs.0.rotate_left(rhs.0 as u32),
-            OpRor => lhs.0.rotate_right(rhs.0 as u32),
+            OpSGt => (slhs > srhs) as u64,
+            OpSLt => (slhs < srhs) as u64,
+            OpLsl if rhs >= ow as u64 => 0,
+            OpLsl => lhs << rhs,
+            OpLsr if rhs >= ow as u64 => 0,
+            OpLsr => lhs >> rhs,
+            OpAsr if rhs >= ow as u64 => (slhs >> 63) as u64,
+            OpAsr => (slhs >> rhs) as u64,
+            OpRol | OpRor => {
+                let amount = (rhs % ow as u64) as u32;
+                let rotated = if amount == 0 {
+                    lhs
+                } else if *self == OpRol {
+                    (lhs << amount) | (lhs >> (ow as u32 - amount))
+                } else {
+                    (lhs >> amount) | (lhs << (ow as u32 - amount))
+                };
+                truncate(rotated, ow)
+            }
             _ => return None,
-        })
+        };
+
+        Some(EvalResult::Value(truncate(
+            val,
+            effective_width(result_width),
+        )))
     }
 
-    pub fn eval_unop(&self, val: u64) -> Option<u64> {
+    /// Evaluates a unary operation whose operand is `operand_width` bits wide and whose result
+    /// is `result_width` bits wide. Widths are handled as in `eval_binop_width`.
+    pub fn eval_unop_width(
+        &self,
+        val: u64,
+        operand_width: u16,
+        result_width: u16,
+    ) -> Option<EvalResult> {
         use self::MOpcode::*;
 
-        Some(match self {
+        let ow = effective_width(operand_width);
+        let val = truncate(val, ow);
+        let res = match *self {
             OpNot => !val,
+            OpMov | OpNarrow(_) | OpZeroExt(_) => val,
+            OpSignExt(_) => sign_extend(val, ow),
             _ => return None,
-        })
+        };
+
+        Some(EvalResult::Value(truncate(
+            res,
+            effective_width(result_width),
+        )))
+    }
+}
+
+/// Result of evaluating an operation on constant operands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvalResult {
+    Value(u64),
+    /// The operation has no defined result for these operands, e.g. division by zero.
+    Undefined,
+}
+
+impl EvalResult {
+    pub fn value(self) -> Option<u64> {
+        match self {
+            EvalResult::Value(v) => Some(v),
+            EvalResult::Undefined => None,
+        }
+    }
+}
+
+fn effective_width(w: u16) -> u16 {
+    if w == 0 || w > 64 {
+        64
+    } else {
+        w
+    }
+}
+
+/// Truncates `val` to its lower `width` bits.
+pub fn truncate(val: u64, width: u16) -> u64 {
+    if width >= 64 {
+        val
+    } else {
+        val & ((1 << width) - 1)
+    }
+}
+
+/// Sign extends the lower `width` bits of `val` to 64 bits.
+pub fn sign_extend(val: u64, width: u16) -> u64 {
+    if width == 0 || width >= 64 {
+        val
+    } else {
+        let shift = 64 - width as u32;
+        ((val << shift) as i64 >> shift) as u64
     }
 }
 
@@ -304,3 +446,51 @@ impl fmt::Display for MOpcode {
         write!(f, "{}", self.to_string())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::EvalResult::*;
+    use super::MOpcode::*;
+
+    #[test]
+    fn eval_binop_wraps_at_width() {
+        assert_eq!(OpAdd.eval_binop_width(0xff, 1, 8, 8), Some(Value(0)));
+        assert_eq!(OpAdd.eval_binop_width(0xff, 1, 16, 16), Some(Value(0x100)));
+        assert_eq!(OpSub.eval_binop_width(0, 1, 32, 32), Some(Value(0xffff_ffff)));
+        assert_eq!(OpMul.eval_binop_width(0x80, 2, 8, 8), Some(Value(0)));
+        assert_eq!(OpLsl.eval_binop_width(1, 8, 8, 8), Some(Value(0)));
+        assert_eq!(OpRol.eval_binop_width(0x81, 1, 8, 8), Some(Value(0x03)));
+        assert_eq!(OpRor.eval_binop_width(0x81, 1, 8, 8), Some(Value(0xc0)));
+    }
+
+    #[test]
+    fn eval_binop_signed() {
+        assert_eq!(OpSLt.eval_binop_width(0xff, 0, 8, 1), Some(Value(1)));
+        assert_eq!(OpLt.eval_binop_width(0xff, 0, 8, 1), Some(Value(0)));
+        assert_eq!(OpSGt.eval_binop_width(0x7f, 0x80, 8, 1), Some(Value(1)));
+        assert_eq!(OpSDiv.eval_binop_width(0xfc, 2, 8, 8), Some(Value(0xfe)));
+        assert_eq!(OpSMod.eval_binop_width(0xfb, 2, 8, 8), Some(Value(0xff)));
+        assert_eq!(OpAsr.eval_binop_width(0x80, 7, 8, 8), Some(Value(0xff)));
+        assert_eq!(OpAsr.eval_binop_width(0x80, 9, 8, 8), Some(Value(0xff)));
+    }
+
+    #[test]
+    fn eval_binop_div_by_zero() {
+        assert_eq!(OpDiv.eval_binop_width(1, 0, 64, 64), Some(Undefined));
+        assert_eq!(OpMod.eval_binop_width(1, 0x100, 8, 8), Some(Undefined));
+        assert_eq!(OpSDiv.eval_binop_width(1, 0, 32, 32), Some(Undefined));
+        assert_eq!(OpDiv.eval_binop(1, 0), None);
+        assert_eq!(OpLoad.eval_binop_width(1, 1, 64, 64), None);
+    }
+
+    #[test]
+    fn eval_unop_resize() {
+        assert_eq!(OpNarrow(8).eval_unop_width(0x1234, 16, 8), Some(Value(0x34)));
+        assert_eq!(OpZeroExt(32).eval_unop_width(0x80, 8, 32), Some(Value(0x80)));
+        assert_eq!(
+            OpSignExt(32).eval_unop_width(0x80, 8, 32),
+            Some(Value(0xffff_ff80))
+        );
+        assert_eq!(OpNot.eval_unop_width(0, 8, 8), Some(Value(0xff)));
+    }
+}
@@ -4,7 +4,7 @@ use super::simple_ast as sast;
 use middle::ir;
 use middle::ir::MOpcode as IrOpcode;
 use middle::regfile::RegisterId;
-use middle::ssa::cfg_traits::{CFGMod, CFG};
+use middle::ssa::cfg_traits::{CFGMod, CASE_EDGE, CFG};
 use middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
 use middle::ssa::ssastorage::SSAStorage;
 
@@ -44,7 +44,7 @@ struct LowerSsa<'a> {
     blocks: HashMap<ir::MAddress, SSABlock>,
     values: HashMap<sast::ValueRef, SSAValue>,
     fw_ref_values: HashMap<sast::ValueRef, SSAValue>,
-    phi_operands: Vec<(SSAValue, Vec<sast::Operand>)>,
+    phi_operands: Vec<(SSAValue, Vec<sast::PhiOperand>)>,
 }
 
 impl<'a> LowerSsa<'a> {
@@ -95,8 +95,14 @@ impl<'a> LowerSsa<'a> {
 
         for (phi, sops) in mem::replace(&mut self.phi_operands, Vec::new()) {
             for sop in sops.into_iter().rev() {
-                let op = self.lower_operand(sop)?;
-                self.ssa.phi_use(phi, op);
+                let op = self.lower_operand(sop.value)?;
+                match sop.pred {
+                    Some(at) => {
+                        let pred = self.pred_at(at)?;
+                        self.ssa.phi_use_from(phi, op, pred);
+                    }
+                    None => self.ssa.phi_use(phi, op),
+                }
             }
         }
 
@@ -171,6 +177,19 @@ impl<'a> LowerSsa<'a> {
                 self.ssa
                     .insert_control_edge(bb, self.exit_node, UNCOND_EDGE);
             }
+            sast::Terminator::JmpSwitch(sel_sop, cases) => {
+                let sel_op = self.lower_operand(sel_sop)?;
+                self.ssa.set_selector(sel_op, bb);
+                for (k, (values, tgt)) in cases.into_iter().enumerate() {
+                    let tgt_bb = self.block_at(tgt)?;
+                    let edge = self
+                        .ssa
+                        .insert_control_edge(bb, tgt_bb, CASE_EDGE + k as u8);
+                    if let Some(edge) = edge {
+                        self.ssa.set_case_values(edge, values);
+                    }
+                }
+            }
             sast::Terminator::Unreachable => {
                 // nothing to do
             }
@@ -306,6 +325,16 @@ impl<'a> LowerSsa<'a> {
         })
     }
 
+    // Block at `at` that a phi operand flows from, which may be the entry node.
+    fn pred_at(&self, at: ir::MAddress) -> Result<SSABlock> {
+        if self.ssa.starting_address(self.entry_node) == Some(at) {
+            return Ok(self.entry_node);
+        }
+        self.blocks.get(&at).cloned().ok_or_else(|| {
+            LoweringError::InvalidAst(format!("no block at {} for a phi operand", at))
+        })
+    }
+
     fn index_of_reg(&self, sreg: &sast::PhysReg) -> Result<RegisterId> {
         if sreg.0 == "mem" {
             Ok(self.ssa.regfile.mem_id())
@@ -335,6 +364,11 @@ fn lower_infix_op(siop: sast::InfixOp) -> IrOpcode {
         sast::InfixOp::Lt => IrOpcode::OpLt,
         sast::InfixOp::Lsl => IrOpcode::OpLsl,
         sast::InfixOp::Lsr => IrOpcode::OpLsr,
+        sast::InfixOp::Asr => IrOpcode::OpAsr,
+        sast::InfixOp::SDiv => IrOpcode::OpSDiv,
+        sast::InfixOp::SMod => IrOpcode::OpSMod,
+        sast::InfixOp::SGt => IrOpcode::OpSGt,
+        sast::InfixOp::SLt => IrOpcode::OpSLt,
     }
 }
 
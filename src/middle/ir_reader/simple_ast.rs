@@ -35,16 +35,23 @@ pub enum Terminator {
     JmpUncond(ir::MAddress),
     JmpCond(Operand, ir::MAddress, ir::MAddress),
     JmpIndirect(Operand),
+    JmpSwitch(Operand, Vec<(Vec<u64>, ir::MAddress)>),
     Unreachable,
 }
 
 #[derive(Debug)]
 pub enum Operation {
-    Phi(NewValue, Vec<Operand>),
+    Phi(NewValue, Vec<PhiOperand>),
     Assign(Option<ir::MAddress>, NewValue, Expr),
     Call(Option<ir::MAddress>, Vec<CallRet>, Operand, Vec<CallArg>),
 }
 
+#[derive(Debug)]
+pub struct PhiOperand {
+    pub value: Operand,
+    pub pred: Option<ir::MAddress>,
+}
+
 #[derive(Debug)]
 pub struct CallRet {
     pub value: NewValue,
@@ -100,6 +107,11 @@ pub enum InfixOp {
     Lt,
     Lsl,
     Lsr,
+    Asr,
+    SDiv,
+    SMod,
+    SGt,
+    SLt,
 }
 
 #[derive(Debug)]
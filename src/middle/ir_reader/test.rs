@@ -151,6 +151,46 @@ fn roundtrip_basic_ssa() {
     roundtrip("sym.foo".to_owned(), SSA_TXT);
 }
 
+#[test]
+fn roundtrip_signed_ops() {
+    for op in &[">>>", "/s", "%s", ">s", "<s"] {
+        let ssa_txt = SSA_TXT.replace("#x1 + %14", &format!("#x1 {} %14", op));
+        roundtrip("sym.foo".to_owned(), &ssa_txt);
+    }
+}
+
+#[test]
+fn roundtrip_switch() {
+    let ssa_txt = SSA_TXT.replace(
+        "        RETURN\n    exit-node:",
+        "        JMP SWITCH %35 CASE #x0, #x2 TO 0x000620.0000 CASE #x1 TO 0x000630.0000
+    bb_0x000620.0000(sz 0x0):
+        RETURN
+    bb_0x000630.0000(sz 0x0):
+        RETURN
+    exit-node:",
+    );
+    roundtrip("sym.foo".to_owned(), &ssa_txt);
+}
+
+#[test]
+fn roundtrip_phi_sources() {
+    // The predecessors of the phi operands are only emitted if they were read back.
+    let ssa_txt = SSA_TXT.replace(
+        "        RETURN\n    exit-node:",
+        "        JMP 0x000620.0000
+    bb_0x000620.0000(sz 0x0):
+        %36: $Unknown64(*?) = Phi(%35 FROM 0x000610.0000, %37 FROM 0x000620.0000);
+        [@0x000620.0001] %37: $Unknown64(*?) = %36 + #x1;
+        [@0x000620.0002] %38: $Unknown1 = %37 == #x10;
+        JMP IF %38 0x000630.0000 ELSE 0x000620.0000
+    bb_0x000630.0000(sz 0x0):
+        RETURN
+    exit-node:",
+    );
+    roundtrip("sym.foo".to_owned(), &ssa_txt);
+}
+
 #[test]
 fn roundtrip_bin1_main() {
     roundtrip_file("main".to_owned(), "test_files/bin1_main_ssa");
@@ -7,8 +7,9 @@
 //! representation is inspired from (and probably similar) LLVM IR.
 
 use frontend::radeco_containers::RadecoFunction;
-use middle::ir::MOpcode;
-use middle::ssa::cfg_traits::CFG;
+use middle::ir::{MAddress, MOpcode};
+use middle::ssa::cfg_traits::{CASE_EDGE, CFG};
+use middle::ssa::graph_traits::Graph;
 use middle::ssa::ssa_traits::{SSAWalk, ValueInfo, SSA};
 use middle::ssa::ssastorage::{NodeData, SSAStorage};
 use middle::ssa::utils;
@@ -121,8 +122,16 @@ impl<'a, O: Write> IRWriter<'a, O> {
                     self.indent(2)?;
                     self.emit_new_value(node, vt)?;
                     write!(self.output, "Phi(")?;
-                    let operands = self.ssa.operands_of(node);
-                    self.emit_operand_list(&operands)?;
+                    let sources = self.phi_sources(node);
+                    if sources.is_empty() {
+                        let operands = self.ssa.operands_of(node);
+                        self.emit_operand_list(&operands)?;
+                    } else {
+                        emit_list!(self.output, sources, |(addr, operand)| {
+                            self.emit_operand(operand)?;
+                            write!(self.output, " FROM {}", addr)?;
+                        });
+                    }
                     writeln!(self.output, ");")?;
                 }
                 NodeData::BasicBlock(addr, sz) => {
@@ -215,6 +224,11 @@ impl<'a, O: Write> IRWriter<'a, O> {
             OpLt => self.emit_binop("<", operands),
             OpLsl => self.emit_binop("<<", operands),
             OpLsr => self.emit_binop(">>", operands),
+            OpAsr => self.emit_binop(">>>", operands),
+            OpSDiv => self.emit_binop("/s", operands),
+            OpSMod => self.emit_binop("%s", operands),
+            OpSGt => self.emit_binop(">s", operands),
+            OpSLt => self.emit_binop("<s", operands),
             OpNot => {
                 write!(self.output, "!")?;
                 self.emit_opt_operand(operands.get(0).cloned())?;
@@ -284,6 +298,28 @@ impl<'a, O: Write> IRWriter<'a, O> {
         }
     }
 
+    // Operands of `phi` along with the addresses of the predecessors they flow from, in order
+    // of address. Empty unless the operand of every predecessor was recorded.
+    fn phi_sources(&self, phi: NodeIndex) -> Vec<(MAddress, NodeIndex)> {
+        let mut preds = self
+            .ssa
+            .block_for(phi)
+            .map(|block| self.ssa.preds_of(block))
+            .unwrap_or_default();
+        preds.sort();
+        preds.dedup();
+        let sources = preds
+            .into_iter()
+            .map(|pred| {
+                let addr = self.ssa.starting_address(pred)?;
+                Some((addr, self.ssa.phi_source(phi, pred)?))
+            })
+            .collect::<Option<Vec<_>>>();
+        let mut sources = sources.unwrap_or_default();
+        sources.sort();
+        sources
+    }
+
     fn emit_operand_list(&mut self, operands: &[NodeIndex]) -> fmt::Result {
         emit_list!(self.output, operands, |&n| self.emit_operand(n)?);
         Ok(())
@@ -330,6 +366,41 @@ impl<'a, O: Write> IRWriter<'a, O> {
     }
 
     fn emit_jump(&mut self, blk: NodeIndex) -> fmt::Result {
+        let mut cases: Vec<_> = self
+            .ssa
+            .outgoing_edges(blk)
+            .into_iter()
+            .filter(|&(_, i)| i >= CASE_EDGE)
+            .collect();
+        if !cases.is_empty() {
+            // switch
+            if let Some(selector) = self.ssa.selector_in(blk) {
+                cases.sort_by_key(|&(_, i)| i);
+                write!(self.output, "JMP SWITCH ")?;
+                self.emit_operand(selector)?;
+                for (edge, _) in cases {
+                    write!(self.output, " CASE ")?;
+                    let values = self.ssa.case_values(edge).unwrap_or_default();
+                    emit_list!(self.output, values, |v| write!(self.output, "#x{:x}", v)?);
+                    write!(self.output, " TO ")?;
+                    let target = self
+                        .ssa
+                        .edge_info(edge)
+                        .expect("Less-endpoints edge")
+                        .target;
+                    self.emit_jump_tgt(target)?;
+                }
+            } else {
+                log_emit_err!(
+                    self,
+                    "block with case successors has no selector: {:?} ({:?})",
+                    blk,
+                    self.ssa.g[blk]
+                )?;
+            }
+            return writeln!(self.output, "");
+        }
+
         if let Some(successor_blk) = self.ssa.unconditional_block(blk) {
             if let Some(selector) = self.ssa.selector_in(blk) {
                 // indirect jump
@@ -14,7 +14,8 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::u64;
 
 use middle::ir::{self, MAddress, MOpcode};
-use middle::ssa::graph_traits::{ConditionInfo, EdgeInfo, Graph};
+use middle::ssa::cfg_traits::CASE_EDGE;
+use middle::ssa::graph_traits::{EdgeInfo, Graph};
 use middle::ssa::ssa_traits::{SSAExtra, SSAMod, ValueInfo};
 use r2api::structs::LOpInfo;
 
@@ -113,6 +114,23 @@ where
         radeco_trace!("Wrote: {:?} <- {:?}", variable, value);
     }
 
+    /// Forget the definitions made by the instruction at `address`, restoring the state of
+    /// every variable to the one before the instruction. Used to drop the partial effects of
+    /// an instruction that could only be lifted in part.
+    pub fn restore_state_before(&mut self, address: u64) {
+        let start = MAddress::new(address, 0);
+        let end = MAddress::new(address, u64::MAX);
+        for defs in &mut self.current_def {
+            let written = defs
+                .range((Included(&start), Included(&end)))
+                .map(|(&addr, _)| addr)
+                .collect::<Vec<_>>();
+            for addr in written {
+                defs.remove(&addr);
+            }
+        }
+    }
+
     // Returns the address that provides this definition and the corresponding
     // ValueRef.
     // This method is different from current_def_in_block as this will return a
@@ -282,50 +300,31 @@ where
             //   this is used to provide the def for this use.
 
             // Copy all the outgoing CF edges.
-            let invalid_edge = self
-                .ssa
-                .invalid_edge()
-                .expect("Invalid Edge is not defined");
-            let conditional_branches =
-                if let Some(branches) = self.ssa.conditional_edges(upper_block) {
-                    branches
-                } else {
-                    ConditionInfo::new(invalid_edge, invalid_edge)
-                };
-            let outgoing = [
-                conditional_branches.false_side,
-                conditional_branches.true_side,
-                self.ssa
-                    .unconditional_edge(upper_block)
-                    .unwrap_or(invalid_edge),
-            ];
-            for (i, edge) in outgoing.iter().enumerate() {
-                if *edge != self
+            for (edge, index) in self.ssa.outgoing_edges(upper_block) {
+                let target = self
                     .ssa
-                    .invalid_edge()
-                    .expect("Invalid Edge is not defined")
-                {
-                    let target = self
-                        .ssa
-                        .edge_info(*edge)
-                        .unwrap_or_else(|| {
-                            radeco_err!("Less-endpoints edge");
-                            EdgeInfo::new(
-                                self.ssa.invalid_action().unwrap(),
-                                self.ssa.invalid_action().unwrap(),
-                            )
-                        })
-                        .target;
-                    if lower_block != target {
-                        radeco_trace!(
-                            "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
-                            lower_block,
-                            i,
-                            target
-                        );
-                        self.ssa.insert_control_edge(lower_block, target, i as u8);
-                        self.ssa.remove_control_edge(*edge);
+                    .edge_info(edge)
+                    .unwrap_or_else(|| {
+                        radeco_err!("Less-endpoints edge");
+                        EdgeInfo::new(
+                            self.ssa.invalid_action().unwrap(),
+                            self.ssa.invalid_action().unwrap(),
+                        )
+                    })
+                    .target;
+                if lower_block != target {
+                    radeco_trace!(
+                        "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
+                        lower_block,
+                        index,
+                        target
+                    );
+                    let case_values = self.ssa.case_values(edge);
+                    let new_edge = self.ssa.insert_control_edge(lower_block, target, index);
+                    if let (Some(values), Some(new_edge)) = (case_values, new_edge) {
+                        self.ssa.set_case_values(new_edge, values);
                     }
+                    self.ssa.remove_control_edge(edge);
                 }
             }
 
@@ -436,6 +435,43 @@ where
         self.op_use(&op_node, 0, selector);
     }
 
+    // Function to add an indirect control flow transfer whose targets are known, such as a jump
+    // through a jump table. `cases` lists the targets along with the values of the selector that
+    // lead to them. The k-th target is reached through the case edge `CASE_EDGE + k`.
+    pub fn add_switch(
+        &mut self,
+        selector: &T::ValueRef,
+        current_addr: &mut MAddress,
+        cases: &[(u64, Vec<u64>)],
+    ) {
+        for (k, &(target, ref values)) in cases.iter().enumerate() {
+            let index = CASE_EDGE + k as u8;
+            self.add_block(MAddress::new(target, 0), Some(*current_addr), Some(index));
+            // Adding the block may have split the block we are in.
+            let source_block = self.block_of(*current_addr).unwrap_or_else(|| {
+                radeco_err!("Block not found @ {:?}", current_addr);
+                self.ssa.invalid_action().unwrap()
+            });
+            let edge = self
+                .ssa
+                .outgoing_edges(source_block)
+                .into_iter()
+                .find(|&(_, i)| i == index);
+            if let Some((edge, _)) = edge {
+                self.ssa.set_case_values(edge, values.clone());
+            }
+        }
+
+        // Add a dummy ITE to mark the selector.
+        let op_node = self.add_op(
+            &MOpcode::OpITE,
+            current_addr,
+            ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
+        );
+
+        self.op_use(&op_node, 0, selector);
+    }
+
     pub fn add_return(&mut self, current_addr: MAddress, edge_type: u8) {
         let source_block = self.block_of(current_addr).unwrap_or_else(|| {
             radeco_err!("Block not found @ {:?}", current_addr);
@@ -537,7 +573,7 @@ where
             radeco_trace!("phip_add_phi_operands|cur:{}|pred:{}", baddr, p_addr);
             let datasource = self.read_variable(&mut p_addr, variable);
             radeco_trace!("datasource: {:?}", datasource);
-            self.ssa.phi_use(phi, datasource);
+            self.ssa.phi_use_from(phi, datasource, pred);
             radeco_trace!("done with phi_use, phi: {:?}, ds: {:?}", phi, datasource);
             if self.ssa.registers(phi).is_empty() {
                 self.propagate_reginfo(&phi);
@@ -24,7 +24,7 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::From;
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegister {
     pub base: u64,
     pub shift: u64,
@@ -47,7 +47,7 @@ impl SubRegister {
 ///
 /// It can then translate accesses to partial registers to accesses of whole registers.
 /// Shifts and masks are added automatically.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegisterFile {
     /// `ValueType`s of whole registers ready to be added to a `PhiPlacer`.
     /// The index within `PhiPlacer` to the first register is needed
@@ -55,10 +55,13 @@ pub struct SubRegisterFile {
     pub whole_registers: Vec<ValueInfo>,
     /// Contains the respective names for the registers described in `whole_registers`
     pub whole_names: Vec<String>,
+    #[serde(with = "::utils::sorted_map")]
     pub named_registers: HashMap<String, SubRegister>,
     /// Contains the alias information for some registers.
+    #[serde(with = "::utils::sorted_map")]
     pub alias_info: HashMap<String, String>,
     /// Contains the type information for every registers.
+    #[serde(with = "::utils::sorted_map")]
     pub type_info: HashMap<String, String>,
 }
 
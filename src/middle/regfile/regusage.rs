@@ -1,5 +1,6 @@
 use super::RegisterId;
 use fixedbitset::FixedBitSet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// The set of registers (possibly including the memory "register") that a
 /// function reads and/or preserves.
@@ -79,3 +80,55 @@ impl RegisterUsage {
         self.preserves.set_range(.., false)
     }
 }
+
+/// Serialized form of a `FixedBitSet`: its length and the indices of the set bits.
+#[derive(Serialize, Deserialize)]
+struct BitSetRepr {
+    len: usize,
+    ones: Vec<usize>,
+}
+
+impl<'a> From<&'a FixedBitSet> for BitSetRepr {
+    fn from(set: &'a FixedBitSet) -> BitSetRepr {
+        BitSetRepr {
+            len: set.len(),
+            ones: set.ones().collect(),
+        }
+    }
+}
+
+impl From<BitSetRepr> for FixedBitSet {
+    fn from(repr: BitSetRepr) -> FixedBitSet {
+        let mut set = FixedBitSet::with_capacity(repr.len);
+        for i in repr.ones.into_iter().filter(|&i| i < repr.len) {
+            set.set(i, true);
+        }
+        set
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegisterUsageRepr {
+    ignores: BitSetRepr,
+    preserves: BitSetRepr,
+}
+
+impl Serialize for RegisterUsage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RegisterUsageRepr {
+            ignores: BitSetRepr::from(&self.ignores),
+            preserves: BitSetRepr::from(&self.preserves),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RegisterUsage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = RegisterUsageRepr::deserialize(deserializer)?;
+        Ok(RegisterUsage {
+            ignores: repr.ignores.into(),
+            preserves: repr.preserves.into(),
+        })
+    }
+}
@@ -43,6 +43,11 @@ use std::hash::Hash;
 use middle::ir::MAddress;
 use super::graph_traits::{Graph, ConditionInfo};
 
+/// Index of the first control edge out of a switch block. Edges `0`, `1` and `2` are the false,
+/// true and unconditional edges, the `k`-th distinct target of a switch is reached through the
+/// edge `CASE_EDGE + k`. The values of the selector that lead to a target are its case values.
+pub const CASE_EDGE: u8 = 3;
+
 /// Provides __accessors__ to the underlying storage
 pub trait CFG: Graph {
     type ActionRef: Eq + Hash + Clone + Copy + Debug;
@@ -102,6 +107,10 @@ pub trait CFG: Graph {
 
     /// Reference that represents an Invalid control flow edge.
     fn invalid_edge(&self) -> Option<Self::CFEdgeRef>;
+
+    /// Values of the selector for which the case edge `edge` is taken. `None` if `edge` is not a
+    /// case edge.
+    fn case_values(&self, edge: Self::CFEdgeRef) -> Option<Vec<u64>>;
 }
 
 /// Provides __mutators__ to the underlying storage
@@ -129,5 +138,8 @@ pub trait CFGMod: CFG {
     /// Remove a control edge from the graph
     fn remove_control_edge(&mut self, source: Self::CFEdgeRef);
 
+    /// Set the values of the selector for which the case edge `edge` is taken
+    fn set_case_values(&mut self, edge: Self::CFEdgeRef, values: Vec<u64>);
+
     fn set_block_size(&mut self, bb: Self::ActionRef, last: u64);
 }
@@ -15,9 +15,14 @@
 //! work, it may be improved to Hybrid Partitioning.
 //! For more details, please refer:
 //!     * http://www.airs.com/dnovillo/Papers/mem-ssa.pdf
+//!
+//! `MemorySSA` builds the Memory SSA of a function. Once built, it is turned
+//! into a `MemoryGraph` with `into_graph`, which no longer borrows the SSA and
+//! answers queries through `MemorySSAQuery`.
 
 
 use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use std::marker::PhantomData;
 use petgraph::EdgeDirection;
 use petgraph::stable_graph::StableDiGraph;
@@ -38,8 +43,6 @@ use middle::ir::MOpcode;
 // easy work to combine these two files together. 
 
 
-// TODO: Add Memory SSA into SSA_Extra.
-// TODO: Add a trait for MemorySSA as APIs for future work.
 // TODO: Split this file into two files, one for may_alias set, another for MemorySSA. 
 // Above tasks should be done after Value Set Analysis finished.
 
@@ -51,6 +54,9 @@ pub enum VariableType {
     Global(u64),
     /// Extra variable, or heap variable, type.
     Extra(NodeIndex),
+    /// Memory not known to belong to any of the other variables. Always
+    /// present, so that accesses to unknown addresses are ordered.
+    Unknown,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -67,17 +73,16 @@ pub enum MemOpcode {
     Undefined,
 }
 
-// TODO: Now the edge weight in MemorySSA graph is all zero, it may help
-// if the operands are in order.
-type VarId = usize;
-type ArgOrd = u16;
+/// Index of a variable in `MemorySSA::variables`. Edges of the Memory SSA
+/// graph are weighted by the variable whose definition they lead to.
+pub type VarId = usize;
 
 pub struct MemorySSA<'a, I, T>
     where I: Iterator<Item = T::ValueRef>,
           T: 'a + SSA + SSAMod + SSAWalk<I>
 {
     ssa: &'a T,
-    pub g: StableDiGraph<MemOpcode, ArgOrd>,
+    pub g: StableDiGraph<MemOpcode, VarId>,
     /// Transform a Memory SSA Node into its associated basic block.
     pub associated_blocks: HashMap<NodeIndex, T::ActionRef>,
     /// Transform a VDef/VUse node into its associated opcode node. 
@@ -98,6 +103,8 @@ pub struct MemorySSA<'a, I, T>
     pub phi_nodes: HashMap<NodeIndex, VarId>,
     // phi_nodes is used to map every phi node to its variable, which will be useful
     // in future work.
+    /// Initial Memory Station, the definition of every variable on entry.
+    entry: NodeIndex,
     sealed_blocks: HashSet<T::ActionRef>,
     current_def: Vec<HashMap<T::ActionRef, NodeIndex>>,
     incomplete_phis: Vec<HashMap<T::ActionRef, NodeIndex>>, 
@@ -121,6 +128,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
             global_nodes: HashSet::new(),
             sealed_blocks: HashSet::new(),
             phi_nodes: HashMap::new(),
+            entry: NodeIndex::end(),
             current_def: Vec::new(),
             incomplete_phis: Vec::new(),
             foo: PhantomData,
@@ -129,6 +137,15 @@ impl<'a, I, T> MemorySSA<'a, I, T>
 
     /// Run to generate MemorySSA.
     pub fn run(&mut self) {
+        let has_unknown = self.variables.iter().any(|v| match *v {
+            VariableType::Unknown => true,
+            _ => false,
+        });
+        if !has_unknown {
+            self.variables.push(VariableType::Unknown);
+            self.resize_defs();
+        }
+
         self.gather_may_aliases();
         radeco_trace!("MemorrySSA|May_alias Set: {:?}", self.may_aliases);
         // Gather may_alias sets.
@@ -176,8 +193,12 @@ impl<'a, I, T> MemorySSA<'a, I, T>
 
         radeco_trace!("MemorrySSA|Gather variables: {:?}", self.variables);
 
-        // Resize associated data structures
-        for _ in 0..self.variables.len() {
+        self.resize_defs();
+    }
+
+    // Resize associated data structures
+    fn resize_defs(&mut self) {
+        while self.current_def.len() < self.variables.len() {
             self.current_def.push(HashMap::new());
             self.incomplete_phis.push(HashMap::new());
         }
@@ -311,7 +332,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
 
         for i in 0..self.variables.len() {
             match self.variables[i] {
-                VariableType::Extra(_) => {
+                VariableType::Extra(_) | VariableType::Unknown => {
                     if involve_all {
                         may_alias.insert(i); 
                     }
@@ -418,13 +439,14 @@ impl<'a, I, T> MemorySSA<'a, I, T>
         }
     }
 
-    fn add_use(&mut self, expr: &NodeIndex, arg: &NodeIndex) {
-        if !self.g.find_edge(*expr, *arg).is_none() {
+    fn add_use(&mut self, expr: &NodeIndex, arg: &NodeIndex, var: VarId) {
+        let (edges, targets) = self.gather_adjacent(expr, EdgeDirection::Outgoing);
+        if edges.iter().zip(targets).any(|(e, t)| t == *arg && self.g[*e] == var) {
             return;
             // In MemorySSA, it's common that add an edge between two nodes 
             // which have been connected before.
         }
-        self.g.add_edge(*expr, *arg, 0);
+        self.g.add_edge(*expr, *arg, var);
     }
 
     fn get_uses(&self, mem_node: &NodeIndex) -> Vec<NodeIndex> {
@@ -452,29 +474,20 @@ impl<'a, I, T> MemorySSA<'a, I, T>
         (edges, adjacent)
     }
 
-    fn disconnect(&mut self, src: &NodeIndex, dst: &NodeIndex) {
-        let edge = self.g.find_edge(*src, *dst);
-        if let Some(ei) = edge {
-            self.g.remove_edge(ei);
-        }
-    }
-
     fn replace(&mut self, origin: &NodeIndex, replacement: &NodeIndex) {
         radeco_trace!("MemorrySSA|Replace {:?} with {:?}", origin, replacement);
-        let users = self.get_uses(origin);
-        let operands = self.get_operands(origin);
+        let (use_edges, users) = self.gather_adjacent(origin, EdgeDirection::Incoming);
+        let (operand_edges, _) = self.gather_adjacent(origin, EdgeDirection::Outgoing);
+        let vars: Vec<VarId> = use_edges.iter().map(|e| self.g[*e]).collect();
 
         // Remove edges associated with origin node.
-        for user in &users {
-            self.disconnect(user, origin);
-        }
-        for operand in &operands {
-            self.disconnect(origin, operand);
+        for edge in use_edges.into_iter().chain(operand_edges) {
+            self.g.remove_edge(edge);
         }
 
         // Connect user with replacement node.
-        for user in &users {
-            self.add_use(user, replacement);
+        for (user, var) in users.iter().zip(vars) {
+            self.add_use(user, replacement, var);
         }
 
         // Delete the association with ssa graph
@@ -548,7 +561,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
         );
         for pred in &preds {
             let target = self.read_variable(var, pred);
-            self.add_use(phi, &target);
+            self.add_use(phi, &target, var);
         }
         return self.try_remove_trivial_phi(phi);
     }
@@ -625,6 +638,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
         let entry_node = entry_node_err!(self.ssa);
         let mem_entry_node = self.g.add_node(MemOpcode::MemoryAccess);
         self.associated_blocks.insert(mem_entry_node, entry_node);
+        self.entry = mem_entry_node;
         for i in 0..self.variables.len() {
             self.write_variable(i, &entry_node, &mem_entry_node);
         }
@@ -652,7 +666,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
                                                     self.ssa.invalid_action().unwrap()
                                                 });
                             let arg = self.read_variable(i, &block);
-                            self.add_use(&vuse, &arg);
+                            self.add_use(&vuse, &arg, i);
                         }
                     }
 
@@ -672,7 +686,7 @@ impl<'a, I, T> MemorySSA<'a, I, T>
                                                     self.ssa.invalid_action().unwrap()
                                                 });
                             let arg = self.read_variable(i, &block);
-                            self.add_use(&vdef, &arg);
+                            self.add_use(&vdef, &arg, i);
                             self.write_variable(i, &block, &vdef);
                         }
                     }
@@ -690,3 +704,307 @@ impl<'a, I, T> MemorySSA<'a, I, T>
         radeco_trace!("MemorrySSA|Memory SSA Graph: {:?}", self.g);
     }
 }
+
+impl<'a, I, T> MemorySSA<'a, I, T>
+    where I: Iterator<Item = T::ValueRef>,
+          T: 'a + SSA + SSAMod + SSAWalk<I>
+{
+    /// Detach the generated Memory SSA from the SSA it was built for.
+    pub fn into_graph(self) -> MemoryGraph<T::ValueRef, T::ActionRef> {
+        let mut phis: HashMap<T::ActionRef, Vec<NodeIndex>> = HashMap::new();
+        for phi in self.phi_nodes.keys() {
+            if let Some(block) = self.associated_blocks.get(phi) {
+                phis.entry(*block).or_insert_with(Vec::new).push(*phi);
+            }
+        }
+        for block_phis in phis.values_mut() {
+            block_phis.sort();
+        }
+        let accesses = self.associated_nodes
+                           .iter()
+                           .map(|(mem, node)| (*node, *mem))
+                           .collect();
+        MemoryGraph {
+            g: self.g,
+            entry: self.entry,
+            values: self.associated_nodes,
+            accesses: accesses,
+            phis: phis,
+            phi_vars: self.phi_nodes,
+            variables: self.variables,
+        }
+    }
+}
+
+/// A node of the Memory SSA, as seen from the SSA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryNode<V> {
+    /// The `VUse` of an `OpLoad`.
+    Load(V),
+    /// The `VDef` of an `OpStore`.
+    Store(V),
+    /// A memory phi.
+    Phi(NodeIndex),
+    /// The memory on entry to the function.
+    Entry,
+}
+
+/// Queries on the Memory SSA of a function.
+pub trait MemorySSAQuery {
+    type ValueRef: Copy + Eq;
+    type ActionRef;
+
+    /// Definitions the `OpLoad` `load` may read from. These are stores,
+    /// memory phis or the entry memory, one for each variable `load` may
+    /// access.
+    fn clobbers(&self, load: Self::ValueRef) -> Vec<MemoryNode<Self::ValueRef>>;
+
+    /// Memory SSA nodes that directly use the definition `node`.
+    fn users(&self, node: MemoryNode<Self::ValueRef>) -> Vec<MemoryNode<Self::ValueRef>>;
+
+    /// Memory phis placed at the start of `block`.
+    fn memory_phis(&self, block: Self::ActionRef) -> Vec<NodeIndex>;
+
+    /// Incoming definitions of the memory phi `phi`.
+    fn phi_operands(&self, phi: NodeIndex) -> Vec<MemoryNode<Self::ValueRef>>;
+
+    /// The `OpStore` that `load` reads from, if it is the only definition
+    /// clobbering `load`.
+    fn clobbering_store(&self, load: Self::ValueRef) -> Option<Self::ValueRef> {
+        match self.clobbers(load)[..] {
+            [MemoryNode::Store(store)] => Some(store),
+            _ => None,
+        }
+    }
+
+    /// The `OpLoad`s that read from `store` directly, that is not through a
+    /// memory phi.
+    fn store_uses(&self, store: Self::ValueRef) -> Vec<Self::ValueRef> {
+        self.users(MemoryNode::Store(store))
+            .into_iter()
+            .filter_map(|u| match u {
+                MemoryNode::Load(load) => Some(load),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Memory SSA of a function, as produced by `MemorySSA::into_graph`.
+#[derive(Clone, Debug)]
+pub struct MemoryGraph<V: Eq + Hash, A: Eq + Hash> {
+    g: StableDiGraph<MemOpcode, VarId>,
+    entry: NodeIndex,
+    /// Memory SSA node to its `OpLoad`/`OpStore`.
+    values: HashMap<NodeIndex, V>,
+    /// `OpLoad`/`OpStore` to its Memory SSA node.
+    accesses: HashMap<V, NodeIndex>,
+    phis: HashMap<A, Vec<NodeIndex>>,
+    phi_vars: HashMap<NodeIndex, VarId>,
+    variables: Vec<VariableType>,
+}
+
+impl<V, A> MemoryGraph<V, A>
+    where V: Copy + Eq + Hash,
+          A: Copy + Eq + Hash
+{
+    /// The underlying graph. Edges point from a use to the definition it
+    /// reads and are weighted by the variable they carry.
+    pub fn graph(&self) -> &StableDiGraph<MemOpcode, VarId> {
+        &self.g
+    }
+
+    /// Variables the memory was partitioned into.
+    pub fn variables(&self) -> &[VariableType] {
+        &self.variables
+    }
+
+    /// Variable a memory phi merges the definitions of.
+    pub fn phi_variable(&self, phi: NodeIndex) -> Option<&VariableType> {
+        self.phi_vars.get(&phi).map(|v| &self.variables[*v])
+    }
+
+    fn index_of(&self, node: MemoryNode<V>) -> Option<NodeIndex> {
+        match node {
+            MemoryNode::Load(v) | MemoryNode::Store(v) => self.accesses.get(&v).cloned(),
+            MemoryNode::Phi(phi) => if self.g.contains_node(phi) { Some(phi) } else { None },
+            MemoryNode::Entry => Some(self.entry),
+        }
+    }
+
+    fn node_at(&self, idx: NodeIndex) -> MemoryNode<V> {
+        match (self.g[idx], self.values.get(&idx)) {
+            (MemOpcode::VUse, Some(v)) => MemoryNode::Load(*v),
+            (MemOpcode::VDef, Some(v)) => MemoryNode::Store(*v),
+            (MemOpcode::Phi, _) => MemoryNode::Phi(idx),
+            // Undefined stands for the memory of unreachable code.
+            _ => MemoryNode::Entry,
+        }
+    }
+
+    fn adjacent(&self, idx: NodeIndex, direction: EdgeDirection) -> Vec<MemoryNode<V>> {
+        let mut nodes = Vec::new();
+        for n in self.g.neighbors_directed(idx, direction) {
+            let node = self.node_at(n);
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+        }
+        nodes
+    }
+}
+
+impl<V, A> MemorySSAQuery for MemoryGraph<V, A>
+    where V: Copy + Eq + Hash,
+          A: Copy + Eq + Hash
+{
+    type ValueRef = V;
+    type ActionRef = A;
+
+    fn clobbers(&self, load: V) -> Vec<MemoryNode<V>> {
+        match self.index_of(MemoryNode::Load(load)) {
+            Some(idx) if self.g[idx] == MemOpcode::VUse => {
+                self.adjacent(idx, EdgeDirection::Outgoing)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn users(&self, node: MemoryNode<V>) -> Vec<MemoryNode<V>> {
+        self.index_of(node)
+            .map(|idx| self.adjacent(idx, EdgeDirection::Incoming))
+            .unwrap_or_default()
+    }
+
+    fn memory_phis(&self, block: A) -> Vec<NodeIndex> {
+        self.phis.get(&block).cloned().unwrap_or_default()
+    }
+
+    fn phi_operands(&self, phi: NodeIndex) -> Vec<MemoryNode<V>> {
+        match self.index_of(MemoryNode::Phi(phi)) {
+            Some(idx) if self.g[idx] == MemOpcode::Phi => {
+                self.adjacent(idx, EdgeDirection::Outgoing)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use middle::ir_reader;
+    use middle::regfile::SubRegisterFile;
+    use middle::ssa::ssastorage::SSAStorage;
+    use serde_json;
+    use std::fs;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SSA_TXT: &str = "\
+define-fun sym.diamond(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Unknown64(*?) = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x000100.0000(sz 0x4):
+        [@0x000100.0001] %4: $Unknown0 = Store(%3, %1, #x1);
+        [@0x000100.0002] %5: $Unknown64(*?) = Load(%4, %1);
+        [@0x000100.0003] %6: $Unknown1(*?) = Narrow1(%2);
+        JMP IF %6 0x000110.0000 ELSE 0x000120.0000
+    bb_0x000110.0000(sz 0x4):
+        [@0x000110.0001] %7: $Unknown0 = Store(%4, %1, #x2);
+        JMP 0x000130.0000
+    bb_0x000120.0000(sz 0x4):
+        [@0x000120.0001] %8: $Unknown0 = Store(%4, %1, #x3);
+        JMP 0x000130.0000
+    bb_0x000130.0000(sz 0x4):
+        %9: $Unknown0 = Phi(%7, %8);
+        [@0x000130.0001] %10: $Unknown64(*?) = Load(%9, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %10;
+        $mem = %9;
+}
+";
+
+    fn load_ssa() -> SSAStorage {
+        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        ir_reader::parse_il(SSA_TXT, regfile)
+    }
+
+    fn store_of(ssa: &SSAStorage, val: u64) -> NodeIndex {
+        ssa.inorder_walk()
+            .find(|&n| {
+                ssa.opcode(n) == Some(MOpcode::OpStore)
+                    && ssa.constant(ssa.operands_of(n)[2]) == Some(val)
+            })
+            .unwrap()
+    }
+
+    // The load reading from the memory of the `Store` or `Phi` `mem`.
+    fn load_from(ssa: &SSAStorage, mem: NodeIndex) -> NodeIndex {
+        ssa.inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad) && ssa.operands_of(n)[0] == mem)
+            .unwrap()
+    }
+
+    #[test]
+    fn memory_ssa_queries() {
+        let ssa = load_ssa();
+        let graph = {
+            let mut mssa = MemorySSA::new(&ssa);
+            mssa.run();
+            mssa.into_graph()
+        };
+
+        let store1 = store_of(&ssa, 1);
+        let store2 = store_of(&ssa, 2);
+        let store3 = store_of(&ssa, 3);
+        let load1 = load_from(&ssa, store1);
+        let join_load = ssa.inorder_walk()
+            .find(|&n| n != load1 && ssa.opcode(n) == Some(MOpcode::OpLoad))
+            .unwrap();
+
+        assert_eq!(graph.clobbering_store(load1), Some(store1));
+        assert_eq!(graph.store_uses(store1), vec![load1]);
+        assert!(graph.store_uses(store2).is_empty());
+
+        // Both branches store, so the load after the join reads from a phi.
+        assert_eq!(graph.clobbering_store(join_load), None);
+        let phi = match graph.clobbers(join_load)[..] {
+            [MemoryNode::Phi(phi)] => phi,
+            ref c => panic!("Unexpected clobbers: {:?}", c),
+        };
+        let mut operands = graph.phi_operands(phi);
+        operands.sort_by_key(|n| match *n {
+            MemoryNode::Store(s) => s,
+            _ => NodeIndex::end(),
+        });
+        let mut expected = vec![MemoryNode::Store(store2), MemoryNode::Store(store3)];
+        expected.sort_by_key(|n| match *n {
+            MemoryNode::Store(s) => s,
+            _ => NodeIndex::end(),
+        });
+        assert_eq!(operands, expected);
+        assert_eq!(graph.users(MemoryNode::Phi(phi)), vec![MemoryNode::Load(join_load)]);
+
+        let join_block = ssa.block_for(join_load).unwrap();
+        assert_eq!(graph.memory_phis(join_block), vec![phi]);
+        let first_block = ssa.block_for(load1).unwrap();
+        assert!(graph.memory_phis(first_block).is_empty());
+        match graph.phi_variable(phi) {
+            Some(&VariableType::Unknown) => {}
+            v => panic!("Unexpected variable: {:?}", v),
+        }
+
+        // The first store reads the memory from the entry.
+        let store1_users = graph.users(MemoryNode::Entry);
+        assert_eq!(store1_users, vec![MemoryNode::Store(store1)]);
+    }
+}
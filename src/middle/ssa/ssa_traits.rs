@@ -60,7 +60,7 @@ macro_rules! registers_in_err {
     };
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 /// Defines the high level `type` of value for a node. It is only used to differentiate between
 /// references and non-reference (scalar) types.
 pub enum ValueType {
@@ -74,7 +74,7 @@ pub enum ValueType {
     Invalid,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 /// Information for a `ValueRef`
 pub struct ValueInfo {
     /// `Type` of value
@@ -234,6 +234,10 @@ pub trait SSA: CFG {
     /// Get all phis in the BasicBlock with index 'i'.
     fn phis_in(&self, i: Self::ActionRef) -> Vec<Self::ValueRef>;
 
+    /// Get the operand of the phi `phi` that flows from the predecessor `pred`, if it was
+    /// recorded when the operand was added.
+    fn phi_source(&self, phi: Self::ValueRef, pred: Self::ActionRef) -> Option<Self::ValueRef>;
+
     /// Get a node that has all register values at the beginning of the specified basic block as args
     fn registers_in(&self, i: Self::ActionRef) -> Option<Self::ValueRef>;
 
@@ -282,7 +286,8 @@ pub trait SSAMod: SSA + CFGMod {
     /// Set the register information for a value
     fn set_register(&mut self, i: Self::ValueRef, regname: String);
 
-    /// Set the node as selector for the control edges away from the specified basic block
+    /// Set the node as selector for the control edges away from the specified basic block,
+    /// replacing its previous selector, if any
     fn set_selector(&mut self, node: Self::ValueRef, block: Self::ActionRef);
 
     /// Insert a new operation node.
@@ -311,6 +316,9 @@ pub trait SSAMod: SSA + CFGMod {
     /// Add a data source to a phi node.
     fn phi_use(&mut self, phi: Self::ValueRef, node: Self::ValueRef);
 
+    /// Add a data source to a phi node that flows from the predecessor `pred`.
+    fn phi_use_from(&mut self, phi: Self::ValueRef, node: Self::ValueRef, pred: Self::ActionRef);
+
     /// Remove a data source from a phi node.
     fn phi_unuse(&mut self, phi: Self::ValueRef, node: Self::ValueRef);
 
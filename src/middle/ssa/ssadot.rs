@@ -15,7 +15,7 @@ use middle::ir::MOpcode;
 use middle::dot::{DotAttrBlock, GraphDot};
 use super::ssastorage::{EdgeData, NodeData, SSAStorage};
 use super::ssa_traits::{SSA, SSAExtra};
-use middle::ssa::cfg_traits::CFG;
+use middle::ssa::cfg_traits::{CASE_EDGE, CFG};
 
 ///////////////////////////////////////////////////////////////////////////////
 //// Implementation of GraphDot to emit Dot for SSAStorage.
@@ -134,10 +134,10 @@ impl GraphDot for SSAStorage {
                 let source_cluster = edge.source().index();
                 let dst_cluster = edge.target().index();
                 let (color, label) = match i {
-                    0 => ("red", "F"),
-                    1 => ("green", "T"),
-                    2 => ("blue", "U"),
-                    _ => unreachable!(),
+                    0 => ("red", "F".to_owned()),
+                    1 => ("green", "T".to_owned()),
+                    2 => ("blue", "U".to_owned()),
+                    _ => ("purple", format!("C{}", i - CASE_EDGE)),
                 };
                 vec![("color".to_string(), color.to_string()),
                      ("xlabel".to_string(), label.to_string()),
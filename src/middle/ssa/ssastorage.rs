@@ -13,13 +13,15 @@ use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::stable_graph::StableDiGraph;
 use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::EdgeDirection;
-use std::cmp::{Ordering, PartialEq, PartialOrd};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::cmp::{Ordering, PartialEq, PartialOrd, Reverse};
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt::{self, Debug};
 use std::sync::Arc;
 use std::{default, u64};
 
-use super::cfg_traits::{CFGMod, CFG};
+use super::cfg_traits::{CFGMod, CASE_EDGE, CFG};
 use super::graph_traits::{ConditionInfo, EdgeInfo, Graph};
 use super::ssa_traits::NodeData as TNodeData;
 use super::ssa_traits::NodeType as TNodeType;
@@ -28,7 +30,7 @@ use utils::logger;
 
 /// Structure that represents data that maybe associated with an node in the
 /// SSA
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AdditionalData {
     comments: Option<String>,
     flag: Option<String>,
@@ -67,7 +69,7 @@ pub type AssociatedData = HashMap<NodeIndex, AdditionalData>;
 /// `RegisterState` is neither.
 /// Value nodes have a `ValueInfo` that can be extracted with
 /// `SSA::get_node_data`
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeData {
     /// Represents on operation.
     Op(MOpcode, ValueInfo),
@@ -136,7 +138,7 @@ impl fmt::Display for NodeData {
 }
 
 /// Edge type for the SSAStorage-internal petgraph.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum EdgeData {
     /// Edge from action to action. Represents control flow. The number is
     /// used to distinguish true branch, false branch, etc.
@@ -172,6 +174,10 @@ pub struct SSAStorage {
     pub replaced_map: HashMap<NodeIndex, NodeIndex>,
     pub regfile: Arc<SubRegisterFile>,
     pub constants: HashMap<u64, NodeIndex>,
+    /// Case values of the case edges, by source block and edge index.
+    pub case_values: HashMap<(NodeIndex, u8), Vec<u64>>,
+    /// Operand every phi takes from a predecessor, by phi and predecessor block.
+    pub phi_sources: HashMap<(NodeIndex, NodeIndex), NodeIndex>,
 }
 
 impl default::Default for SSAStorage {
@@ -184,6 +190,8 @@ impl default::Default for SSAStorage {
             replaced_map: HashMap::new(),
             regfile: Arc::default(),
             constants: HashMap::new(),
+            case_values: HashMap::new(),
+            phi_sources: HashMap::new(),
         }
     }
 }
@@ -198,7 +206,173 @@ impl SSAStorage {
             replaced_map: HashMap::new(),
             regfile: Arc::default(),
             constants: HashMap::new(),
+            case_values: HashMap::new(),
+            phi_sources: HashMap::new(),
+        }
+    }
+
+    // Detach the selector of `block`, removing it if nothing else uses it.
+    fn remove_selector(&mut self, block: NodeIndex) {
+        if let Some(selector) = self.selector_in(block) {
+            self.remove_edges_between(block, selector);
+            if self.uses_of(selector).is_empty() {
+                self.remove_value(selector);
+            }
+        }
+    }
+}
+
+/// Serialized form of `SSAStorage`.
+///
+/// Nodes are stored by index, with `None` for the indices that were removed, so that every
+/// `NodeIndex` held outside of the graph (bindings, call contexts, ...) stays valid after a round
+/// trip. Maps are sorted by key to keep the output stable.
+#[derive(Serialize, Deserialize)]
+struct SSAStorageRepr {
+    nodes: Vec<Option<NodeData>>,
+    edges: Vec<(NodeIndex, NodeIndex, EdgeData)>,
+    entry_node: NodeIndex,
+    exit_node: NodeIndex,
+    assoc_data: Vec<(NodeIndex, AdditionalData)>,
+    replaced_map: Vec<(NodeIndex, NodeIndex)>,
+    regfile: SubRegisterFile,
+    constants: Vec<(u64, NodeIndex)>,
+    #[serde(default)]
+    case_values: Vec<((NodeIndex, u8), Vec<u64>)>,
+    #[serde(default)]
+    phi_sources: Vec<((NodeIndex, NodeIndex), NodeIndex)>,
+}
+
+impl SSAStorage {
+    /// Returns the edges of the graph in an order that, when the edges are added to an empty
+    /// graph, rebuilds the same incoming and outgoing edge lists for every node.
+    ///
+    /// petgraph puts a new edge at the head of the lists of its endpoints, so every list is a
+    /// chain of edges from newest to oldest. Any order consistent with all the chains works.
+    fn edges_in_insertion_order(&self) -> Vec<EdgeIndex> {
+        let mut indegree: HashMap<EdgeIndex, usize> =
+            self.g.edge_references().map(|e| (e.id(), 0)).collect();
+        let mut successors: HashMap<EdgeIndex, Vec<EdgeIndex>> = HashMap::new();
+        for node in self.g.node_indices() {
+            for &dir in &[EdgeDirection::Outgoing, EdgeDirection::Incoming] {
+                let mut adjacent = Vec::new();
+                let mut walk = self.g.neighbors_directed(node, dir).detach();
+                while let Some((edge, _)) = walk.next(&self.g) {
+                    adjacent.push(edge);
+                }
+                for pair in adjacent.windows(2) {
+                    successors
+                        .entry(pair[1])
+                        .or_insert_with(Vec::new)
+                        .push(pair[0]);
+                    *indegree.get_mut(&pair[0]).expect("Unknown edge") += 1;
+                }
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<EdgeIndex>> = indegree
+            .iter()
+            .filter(|&(_, &d)| d == 0)
+            .map(|(&e, _)| Reverse(e))
+            .collect();
+        let mut order = Vec::with_capacity(indegree.len());
+        while let Some(Reverse(edge)) = ready.pop() {
+            order.push(edge);
+            for succ in successors.get(&edge).into_iter().flat_map(|s| s.iter()) {
+                let d = indegree.get_mut(succ).expect("Unknown edge");
+                *d -= 1;
+                if *d == 0 {
+                    ready.push(Reverse(*succ));
+                }
+            }
         }
+        order
+    }
+}
+
+impl Serialize for SSAStorage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node_bound = self.g.node_indices().map(|n| n.index() + 1).max();
+        let nodes = (0..node_bound.unwrap_or(0))
+            .map(|i| self.g.node_weight(NodeIndex::new(i)).cloned())
+            .collect();
+        let edges = self
+            .edges_in_insertion_order()
+            .into_iter()
+            .filter_map(|e| {
+                let (src, dst) = self.g.edge_endpoints(e)?;
+                Some((src, dst, *self.g.edge_weight(e)?))
+            })
+            .collect();
+        let mut assoc_data: Vec<_> = self
+            .assoc_data
+            .iter()
+            .map(|(&n, d)| (n, d.clone()))
+            .collect();
+        assoc_data.sort_by_key(|&(n, _)| n);
+        let mut replaced_map: Vec<_> = self.replaced_map.iter().map(|(&k, &v)| (k, v)).collect();
+        replaced_map.sort();
+        let mut constants: Vec<_> = self.constants.iter().map(|(&k, &v)| (k, v)).collect();
+        constants.sort();
+        let mut case_values: Vec<_> = self
+            .case_values
+            .iter()
+            .map(|(&k, v)| (k, v.clone()))
+            .collect();
+        case_values.sort();
+        let mut phi_sources: Vec<_> = self.phi_sources.iter().map(|(&k, &v)| (k, v)).collect();
+        phi_sources.sort();
+
+        SSAStorageRepr {
+            nodes: nodes,
+            edges: edges,
+            entry_node: self.entry_node,
+            exit_node: self.exit_node,
+            assoc_data: assoc_data,
+            replaced_map: replaced_map,
+            regfile: (*self.regfile).clone(),
+            constants: constants,
+            case_values: case_values,
+            phi_sources: phi_sources,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SSAStorage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = SSAStorageRepr::deserialize(deserializer)?;
+        let present: Vec<bool> = repr.nodes.iter().map(Option::is_some).collect();
+        let mut g = StableDiGraph::with_capacity(repr.nodes.len(), repr.edges.len());
+        for node in repr.nodes {
+            g.add_node(node.unwrap_or(NodeData::Removed));
+        }
+        for (src, dst, edge) in repr.edges {
+            let is_present = |n: NodeIndex| present.get(n.index()).cloned().unwrap_or(false);
+            if !is_present(src) || !is_present(dst) {
+                return Err(de::Error::custom(format!(
+                    "Edge {:?} -> {:?} refers to a missing node",
+                    src, dst
+                )));
+            }
+            g.add_edge(src, dst, edge);
+        }
+        // The placeholders were only added to keep the indices of the following nodes.
+        for (i, _) in present.iter().enumerate().filter(|&(_, &p)| !p) {
+            g.remove_node(NodeIndex::new(i));
+        }
+
+        Ok(SSAStorage {
+            g: g,
+            entry_node: repr.entry_node,
+            exit_node: repr.exit_node,
+            assoc_data: repr.assoc_data.into_iter().collect(),
+            replaced_map: repr.replaced_map.into_iter().collect(),
+            regfile: Arc::new(repr.regfile),
+            constants: repr.constants.into_iter().collect(),
+            case_values: repr.case_values.into_iter().collect(),
+            phi_sources: repr.phi_sources.into_iter().collect(),
+        })
     }
 }
 
@@ -247,6 +421,9 @@ impl Graph for SSAStorage {
 
     fn remove_node(&mut self, exi: Self::GraphNodeRef) {
         radeco_trace!(logger::Event::SSARemoveNode(&exi));
+        if let Some(&NodeData::Phi(_, _)) = self.g.node_weight(exi) {
+            self.phi_sources.retain(|&(phi, _), _| phi != exi);
+        }
         // Remove the current association.
         if let Some(val) = self.constant(exi) {
             let uses = self.uses_of(exi);
@@ -265,6 +442,15 @@ impl Graph for SSAStorage {
         // Before replace, we need to copy over the edges.
         assert!(self.constant(i).is_none());
 
+        // Phis that took `i` from a predecessor take `j` from it instead.
+        if self.uses_of(i).into_iter().any(|u| self.is_phi(u)) {
+            for source in self.phi_sources.values_mut() {
+                if *source == i {
+                    *source = j;
+                }
+            }
+        }
+
         let mut walk = self
             .g
             .neighbors_directed(i, EdgeDirection::Incoming)
@@ -625,6 +811,21 @@ impl CFG for SSAStorage {
     fn invalid_edge(&self) -> Option<Self::CFEdgeRef> {
         Some(EdgeIndex::end())
     }
+
+    fn case_values(&self, edge: Self::CFEdgeRef) -> Option<Vec<u64>> {
+        match self.g.edge_weight(edge) {
+            Some(&EdgeData::Control(i)) if i >= CASE_EDGE => {
+                let source = self.g.edge_endpoints(edge)?.0;
+                Some(
+                    self.case_values
+                        .get(&(source, i))
+                        .cloned()
+                        .unwrap_or_default(),
+                )
+            }
+            _ => None,
+        }
+    }
 }
 
 impl CFGMod for SSAStorage {
@@ -696,12 +897,21 @@ impl CFGMod for SSAStorage {
         }
 
         let preds = self.preds_of(exi);
+        self.case_values.retain(|&(b, _), _| b != exi);
+        self.phi_sources.retain(|&(_, pred), _| pred != exi);
         self.remove_value(exi);
 
         // block removal can make predecessors lose selectors
         for pred_e in preds {
-            if self.succs_of(pred_e).len() == 1 {
+            let outgoing = self.outgoing_edges(pred_e);
+            if outgoing.len() == 1 {
                 let pred = pred_e;
+                // A switch that is left with a single target jumps there unconditionally.
+                let (edge, index) = outgoing[0];
+                if index >= CASE_EDGE {
+                    self.case_values.remove(&(pred, index));
+                    *self.g.edge_weight_mut(edge).expect("No weight found!") = EdgeData::Control(2);
+                }
                 let mut walk = self
                     .g
                     .neighbors_directed(pred, EdgeDirection::Incoming)
@@ -724,6 +934,14 @@ impl CFGMod for SSAStorage {
         self.g.remove_edge(edge);
     }
 
+    fn set_case_values(&mut self, edge: Self::CFEdgeRef, values: Vec<u64>) {
+        if let Some((source, _)) = self.g.edge_endpoints(edge) {
+            if let Some(&EdgeData::Control(i)) = self.g.edge_weight(edge) {
+                self.case_values.insert((source, i), values);
+            }
+        }
+    }
+
     fn set_block_size(&mut self, bb: Self::ActionRef, size: u64) {
         if let Some(&mut NodeData::BasicBlock(_, ref mut x)) = self.g.node_weight_mut(bb) {
             *x = size;
@@ -843,6 +1061,10 @@ impl SSA for SSAStorage {
         phis
     }
 
+    fn phi_source(&self, phi: Self::ValueRef, pred: Self::ActionRef) -> Option<Self::ValueRef> {
+        self.phi_sources.get(&(phi, pred)).cloned()
+    }
+
     fn registers_in(&self, exi: Self::ActionRef) -> Option<Self::ValueRef> {
         if !self.is_action(exi) {
             radeco_err!("Error: {:?} should be action", exi);
@@ -1045,6 +1267,12 @@ impl SSAMod for SSAStorage {
     }
 
     fn set_selector(&mut self, node: Self::ValueRef, block: Self::ActionRef) {
+        // A block has at most one selector.
+        if let Some(old) = self.selector_in(block) {
+            if old != node {
+                self.remove_edges_between(block, old);
+            }
+        }
         self.insert_edge(block, node, EdgeData::Selector);
     }
 
@@ -1086,8 +1314,15 @@ impl SSAMod for SSAStorage {
         self.insert_edge(phi, node, EdgeData::Data(0));
     }
 
+    fn phi_use_from(&mut self, phi: Self::ValueRef, node: Self::ValueRef, pred: Self::ActionRef) {
+        self.phi_use(phi, node);
+        self.phi_sources.insert((phi, pred), node);
+    }
+
     fn phi_unuse(&mut self, phi: Self::ValueRef, node: Self::ValueRef) {
         self.remove_edges_between(phi, node);
+        self.phi_sources
+            .retain(|&(p, _), &mut n| p != phi || n != node);
     }
 
     fn op_use(&mut self, node: Self::ValueRef, index: u8, argument: Self::ValueRef) {
@@ -1123,13 +1358,25 @@ impl SSAMod for SSAStorage {
 
     fn remove_data_edge(&mut self, i: Self::CFEdgeRef) {
         let src_node = self.edge_info(i).expect("Less-endpoints edge").source;
-        if let Some(selector) = self.selector_in(src_node) {
-            self.remove_edges_between(src_node, selector);
-            if self.uses_of(selector).is_empty() {
-                self.remove_value(selector);
+
+        // A switch only loses its selector once a single target is left.
+        if let Some(&EdgeData::Control(j)) = self.g.edge_weight(i) {
+            if j >= CASE_EDGE {
+                self.case_values.remove(&(src_node, j));
+                self.g.remove_edge(i);
+                let outgoing = self.outgoing_edges(src_node);
+                if outgoing.len() == 1 {
+                    let (edge, index) = outgoing[0];
+                    self.case_values.remove(&(src_node, index));
+                    *self.g.edge_weight_mut(edge).expect("No weight found!") = EdgeData::Control(2);
+                    self.remove_selector(src_node);
+                }
+                return;
             }
         }
 
+        self.remove_selector(src_node);
+
         let invalid_edge = self.invalid_edge().expect("Invalid Edge is not defined");
         let conditional_branches = if let Some(branches) = self.conditional_edges(src_node) {
             branches
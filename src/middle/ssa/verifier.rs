@@ -14,7 +14,7 @@ use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::result;
 
-use super::cfg_traits::CFG;
+use super::cfg_traits::{CASE_EDGE, CFG};
 use super::error::SSAErr;
 use super::graph_traits::Graph;
 use super::ssa_traits::NodeType as TNodeType;
@@ -88,13 +88,6 @@ impl Verify for SSAStorage {
         radeco_trace!("ssa verify|Block {:?}", block);
         radeco_trace!("ssa verify|Edges {:?}", edges);
 
-        // Every BB can have a maximum of 2 Outgoing CFG Edges.
-        // TODO: Relax this assumption when we have support for switch.
-        check!(
-            edges.len() < 3,
-            SSAErr::WrongNumEdges(*block, 3, edges.len())
-        );
-
         let mut edgecases = [false; 256];
 
         for edge in edges.iter() {
@@ -157,7 +150,21 @@ impl Verify for SSAStorage {
                     // SSAErr::InvalidTarget(*block, *edge,
                     // target_block));
                 }
-                _ => panic!("Found something other than a control edge!"),
+                _ => {
+                    // Things to lookout for:
+                    //  * All the outgoing edges must be case edges.
+                    //  * There _must_ be a selector.
+                    check!(
+                        edges.iter().all(|e| e.1 >= CASE_EDGE),
+                        SSAErr::InvalidControl(*block, edge.0)
+                    );
+                    check!(
+                        self.selector_in(*block).is_some(),
+                        SSAErr::NoSelector(*block)
+                    );
+                    // No need to test the next edge.
+                    break;
+                }
             }
         }
 
@@ -255,7 +262,11 @@ impl Verify for SSAStorage {
                             check!(opw < w0, SSAErr::IncompatibleWidth(*exi, opw, w0));
                             check!(w == w0, SSAErr::IncompatibleWidth(*exi, w, w0));
                         }
-                        MOpcode::OpEq | MOpcode::OpGt | MOpcode::OpLt => {
+                        MOpcode::OpEq
+                        | MOpcode::OpGt
+                        | MOpcode::OpLt
+                        | MOpcode::OpSGt
+                        | MOpcode::OpSLt => {
                             check!(w == 1, SSAErr::IncompatibleWidth(*exi, 1, w));
                         }
                         // TODO: Width of OpStore and OpLoad now is not certain.
@@ -0,0 +1,1052 @@
+//! Runs pipelines of analyses and transformations over a `RadecoProject`.
+//!
+//! Every pass declares which analyses it needs before it can run, which it
+//! makes available and which of the already available ones are still valid
+//! once it has modified the IR. The `PassManager` keeps track of the valid
+//! analyses while running a pipeline, schedules registered providers for
+//! analyses that a pass requires but that are missing, and records how long
+//! every pass took.
+//!
+//! ```rust,ignore
+//! let mut pm = PassManager::new()
+//!     .module_analysis(BuildCallGraph)
+//!     .function_pass(Sccp)
+//!     .module_pass(FixCalls::default())
+//!     .function_pass(Cse)
+//!     .function_pass(Dce)
+//!     .verify();
+//! pm.run(&mut project)?;
+//! for t in pm.timings() {
+//!     println!("{}: {} {:?}", t.module, t.pass, t.elapsed);
+//! }
+//! ```
+
+use analysis::copy_propagation;
+use analysis::cse::cse::CSE;
+use analysis::cse::ssasort::Sorter;
+use analysis::dead_stores;
+use analysis::functions::infer_regusage;
+use analysis::inst_combine;
+use analysis::interproc::fixcall::CallFixer;
+use analysis::sccp;
+use analysis::tie;
+use frontend::llanalyzer;
+use frontend::radeco_containers::{FunctionType, RadecoFunction, RadecoModule, RadecoProject};
+use middle::dce;
+use middle::regfile::SubRegisterFile;
+use middle::ssa::verifier;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Information about a module that is computed by one pass and relied upon by
+/// later ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Analysis {
+    /// `RadecoModule::callgraph` matches the call sites in the SSA of every
+    /// function. Built by the loader, and again by `BuildCallGraph`.
+    CallGraph,
+    /// The bindings of every call site have been fixed up by `CallFixer`.
+    Bindings,
+    /// Operands of commutative operations are in canonical order.
+    SortedOperands,
+    /// `RadecoFunction::regusage` describes every function of the module.
+    RegisterUsage,
+    /// Every function has its Memory SSA built, see `RadecoFunction::memory_ssa`.
+    /// Modifying the SSA of a function drops its Memory SSA.
+    MemorySSA,
+    /// Every function has the types of its values inferred, see
+    /// `RadecoFunction::inferred_type`. Modifying the SSA of a function drops
+    /// its types.
+    Types,
+}
+
+/// The analyses that are still valid after a pass has run.
+#[derive(Clone, Copy, Debug)]
+pub enum Preserved {
+    /// The pass does not invalidate anything.
+    All,
+    /// Only the listed analyses remain valid.
+    Only(&'static [Analysis]),
+}
+
+impl Preserved {
+    pub fn preserves(&self, analysis: Analysis) -> bool {
+        match *self {
+            Preserved::All => true,
+            Preserved::Only(kept) => kept.contains(&analysis),
+        }
+    }
+}
+
+/// Common interface of all passes.
+pub trait Pass {
+    /// Name used in timings and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Analyses that have to be valid before this pass can run.
+    fn requires(&self) -> &'static [Analysis] {
+        &[]
+    }
+
+    /// Analyses that are valid after this pass has run.
+    fn provides(&self) -> &'static [Analysis] {
+        &[]
+    }
+
+    /// Analyses that were valid before this pass and still are afterwards.
+    /// Passes invalidate everything unless they say otherwise.
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[])
+    }
+}
+
+/// A pass that works on a whole module at once, typically because it needs
+/// to look at the callers or callees of a function.
+pub trait ModulePass: Pass {
+    fn run_module(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile);
+}
+
+/// A pass that works on one function at a time. It is run on every function
+/// of a module, imports excluded.
+pub trait FunctionPass: Pass {
+    fn run_function(&mut self, rfn: &mut RadecoFunction);
+}
+
+enum PassKind {
+    Module(Box<ModulePass>),
+    Function(Box<FunctionPass>),
+}
+
+impl PassKind {
+    fn name(&self) -> &'static str {
+        match *self {
+            PassKind::Module(ref p) => p.name(),
+            PassKind::Function(ref p) => p.name(),
+        }
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        match *self {
+            PassKind::Module(ref p) => p.requires(),
+            PassKind::Function(ref p) => p.requires(),
+        }
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        match *self {
+            PassKind::Module(ref p) => p.provides(),
+            PassKind::Function(ref p) => p.provides(),
+        }
+    }
+
+    fn preserves(&self) -> Preserved {
+        match *self {
+            PassKind::Module(ref p) => p.preserves(),
+            PassKind::Function(ref p) => p.preserves(),
+        }
+    }
+
+    fn run(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile) {
+        match *self {
+            PassKind::Module(ref mut p) => p.run_module(rmod, regfile),
+            PassKind::Function(ref mut p) => {
+                for rfn in rmod.functions.values_mut() {
+                    if rfn.ftype == FunctionType::Function {
+                        p.run_function(rfn);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Time spent running a single pass on a single module.
+#[derive(Clone, Debug)]
+pub struct PassTiming {
+    pub pass: &'static str,
+    pub module: String,
+    pub elapsed: Duration,
+}
+
+/// Errors that stop a pipeline.
+#[derive(Debug)]
+pub enum PassError {
+    /// `pass` requires `analysis`, which is not valid at that point of the
+    /// pipeline and no registered pass provides it.
+    MissingAnalysis {
+        pass: &'static str,
+        analysis: Analysis,
+    },
+    /// Providing `analysis` ends up requiring `analysis` again.
+    DependencyCycle(Analysis),
+    /// The SSA of `function` failed verification after `pass` ran.
+    Verify {
+        pass: &'static str,
+        function: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PassError::MissingAnalysis { pass, analysis } => {
+                write!(
+                    f,
+                    "{} requires {:?}, which is not available",
+                    pass, analysis
+                )
+            }
+            PassError::DependencyCycle(analysis) => {
+                write!(f, "Cyclic dependency while providing {:?}", analysis)
+            }
+            PassError::Verify {
+                pass,
+                ref function,
+                ref reason,
+            } => write!(
+                f,
+                "SSA of {} is invalid after {}: {}",
+                function, pass, reason
+            ),
+        }
+    }
+}
+
+/// Runs a pipeline of passes over every module of a project.
+///
+/// Passes added with `module_pass` and `function_pass` make up the pipeline
+/// and run in the order they were added. Passes added with
+/// `module_analysis` and `function_analysis` only run when a pass in the
+/// pipeline requires an analysis they provide and that is not valid.
+pub struct PassManager {
+    pipeline: Vec<PassKind>,
+    providers: Vec<PassKind>,
+    assumed: Vec<Analysis>,
+    verify: bool,
+    timings: Vec<PassTiming>,
+}
+
+impl Default for PassManager {
+    fn default() -> PassManager {
+        PassManager::new()
+    }
+}
+
+impl PassManager {
+    /// Creates an empty pipeline. Modules are assumed to come straight from
+    /// the loader, so only the call graph is considered valid initially.
+    pub fn new() -> PassManager {
+        PassManager {
+            pipeline: Vec::new(),
+            providers: Vec::new(),
+            assumed: vec![Analysis::CallGraph],
+            verify: false,
+            timings: Vec::new(),
+        }
+    }
+
+    /// Appends a module pass to the pipeline.
+    pub fn module_pass<P: ModulePass + 'static>(mut self, pass: P) -> PassManager {
+        self.pipeline.push(PassKind::Module(Box::new(pass)));
+        self
+    }
+
+    /// Appends a function pass to the pipeline.
+    pub fn function_pass<P: FunctionPass + 'static>(mut self, pass: P) -> PassManager {
+        self.pipeline.push(PassKind::Function(Box::new(pass)));
+        self
+    }
+
+    /// Registers a module pass that is run on demand to provide its analyses.
+    pub fn module_analysis<P: ModulePass + 'static>(mut self, pass: P) -> PassManager {
+        self.providers.push(PassKind::Module(Box::new(pass)));
+        self
+    }
+
+    /// Registers a function pass that is run on demand to provide its analyses.
+    pub fn function_analysis<P: FunctionPass + 'static>(mut self, pass: P) -> PassManager {
+        self.providers.push(PassKind::Function(Box::new(pass)));
+        self
+    }
+
+    /// Treats `analysis` as valid when starting on a module.
+    pub fn assume_valid(mut self, analysis: Analysis) -> PassManager {
+        if !self.assumed.contains(&analysis) {
+            self.assumed.push(analysis);
+        }
+        self
+    }
+
+    /// Verifies the SSA of every function after each pass.
+    pub fn verify(mut self) -> PassManager {
+        self.verify = true;
+        self
+    }
+
+    /// Time taken by every pass that ran so far, in the order they ran.
+    pub fn timings(&self) -> &[PassTiming] {
+        &self.timings
+    }
+
+    /// Runs the pipeline on every module of `project`.
+    pub fn run(&mut self, project: &mut RadecoProject) -> Result<(), PassError> {
+        let regfile = project.regfile().clone();
+        for zmod in project.iter_mut() {
+            self.run_module(zmod.module, &regfile)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the pipeline on a single module.
+    pub fn run_module(
+        &mut self,
+        rmod: &mut RadecoModule,
+        regfile: &SubRegisterFile,
+    ) -> Result<(), PassError> {
+        let mut valid: HashSet<Analysis> = self.assumed.iter().cloned().collect();
+        for i in 0..self.pipeline.len() {
+            let name = self.pipeline[i].name();
+            let requires = self.pipeline[i].requires();
+            for &analysis in requires {
+                self.provide(analysis, name, &mut valid, &mut Vec::new(), rmod, regfile)?;
+            }
+            // A provider may have invalidated what an earlier one provided.
+            if let Some(&analysis) = requires.iter().find(|&&a| !valid.contains(&a)) {
+                return Err(PassError::MissingAnalysis {
+                    pass: name,
+                    analysis: analysis,
+                });
+            }
+            execute(
+                &mut self.pipeline[i],
+                rmod,
+                regfile,
+                &mut valid,
+                self.verify,
+                &mut self.timings,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Makes `analysis` valid by running the first registered provider for it,
+    /// after recursively providing what that provider requires.
+    fn provide(
+        &mut self,
+        analysis: Analysis,
+        requester: &'static str,
+        valid: &mut HashSet<Analysis>,
+        pending: &mut Vec<Analysis>,
+        rmod: &mut RadecoModule,
+        regfile: &SubRegisterFile,
+    ) -> Result<(), PassError> {
+        if valid.contains(&analysis) {
+            return Ok(());
+        }
+        if pending.contains(&analysis) {
+            return Err(PassError::DependencyCycle(analysis));
+        }
+        let idx = match self
+            .providers
+            .iter()
+            .position(|p| p.provides().contains(&analysis))
+        {
+            Some(idx) => idx,
+            None => {
+                return Err(PassError::MissingAnalysis {
+                    pass: requester,
+                    analysis: analysis,
+                })
+            }
+        };
+
+        radeco_trace!(
+            "PassManager|Running {} to provide {:?} for {}",
+            self.providers[idx].name(),
+            analysis,
+            requester
+        );
+        pending.push(analysis);
+        let name = self.providers[idx].name();
+        let requires = self.providers[idx].requires();
+        for &req in requires {
+            self.provide(req, name, valid, pending, rmod, regfile)?;
+        }
+        pending.pop();
+
+        execute(
+            &mut self.providers[idx],
+            rmod,
+            regfile,
+            valid,
+            self.verify,
+            &mut self.timings,
+        )
+    }
+}
+
+fn execute(
+    pass: &mut PassKind,
+    rmod: &mut RadecoModule,
+    regfile: &SubRegisterFile,
+    valid: &mut HashSet<Analysis>,
+    verify: bool,
+    timings: &mut Vec<PassTiming>,
+) -> Result<(), PassError> {
+    let start = Instant::now();
+    pass.run(rmod, regfile);
+    let elapsed = start.elapsed();
+    radeco_trace!(
+        "PassManager|{} on {} took {:?}",
+        pass.name(),
+        rmod.name(),
+        elapsed
+    );
+    timings.push(PassTiming {
+        pass: pass.name(),
+        module: rmod.name().to_owned(),
+        elapsed: elapsed,
+    });
+
+    let preserved = pass.preserves();
+    valid.retain(|&a| preserved.preserves(a));
+    valid.extend(pass.provides().iter().cloned());
+
+    if verify {
+        for rfn in rmod.functions.values() {
+            if rfn.ftype != FunctionType::Function {
+                continue;
+            }
+            if let Err(e) = verifier::verify(rfn.ssa()) {
+                return Err(PassError::Verify {
+                    pass: pass.name(),
+                    function: rfn.name.to_string(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Matches the call graph to the call sites of every function again, see
+/// `llanalyzer::refresh_call_graph`. Fixed up call sites have to be fixed up
+/// again afterwards.
+#[derive(Debug, Default)]
+pub struct BuildCallGraph;
+
+impl Pass for BuildCallGraph {
+    fn name(&self) -> &'static str {
+        "callgraph"
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::CallGraph]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[
+            Analysis::SortedOperands,
+            Analysis::RegisterUsage,
+            Analysis::MemorySSA,
+            Analysis::Types,
+        ])
+    }
+}
+
+impl ModulePass for BuildCallGraph {
+    fn run_module(&mut self, rmod: &mut RadecoModule, _: &SubRegisterFile) {
+        llanalyzer::refresh_call_graph(rmod);
+    }
+}
+
+/// Sparse conditional constant propagation.
+#[derive(Debug, Default)]
+pub struct Sccp;
+
+impl Pass for Sccp {
+    fn name(&self) -> &'static str {
+        "sccp"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::RegisterUsage])
+    }
+}
+
+impl FunctionPass for Sccp {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        let ssa = {
+            let mut analyzer = sccp::Analyzer::new(rfn.ssa_mut());
+            analyzer.analyze();
+            analyzer.emit_ssa()
+        };
+        *rfn.ssa_mut() = ssa;
+    }
+}
+
+/// Dead code elimination.
+#[derive(Debug, Default)]
+pub struct Dce;
+
+impl Pass for Dce {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[
+            Analysis::CallGraph,
+            Analysis::SortedOperands,
+            Analysis::RegisterUsage,
+        ])
+    }
+}
+
+impl FunctionPass for Dce {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        dce::collect(rfn.ssa_mut());
+    }
+}
+
+/// Common subexpression elimination. Sorts operands as a side effect.
+#[derive(Debug, Default)]
+pub struct Cse;
+
+impl Pass for Cse {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::SortedOperands]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph, Analysis::RegisterUsage])
+    }
+}
+
+impl FunctionPass for Cse {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        CSE::new(rfn.ssa_mut()).run();
+    }
+}
+
+/// Peephole combination of instructions.
+#[derive(Debug, Default)]
+pub struct InstCombine;
+
+impl Pass for InstCombine {
+    fn name(&self) -> &'static str {
+        "inst_combine"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph, Analysis::RegisterUsage])
+    }
+}
+
+impl FunctionPass for InstCombine {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        inst_combine::run(rfn.ssa_mut());
+    }
+}
+
+/// Copy propagation.
+#[derive(Debug, Default)]
+pub struct CopyPropagation;
+
+impl Pass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "copy_propagation"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph, Analysis::RegisterUsage])
+    }
+}
+
+impl FunctionPass for CopyPropagation {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        copy_propagation::run(rfn.ssa_mut());
+    }
+}
+
+/// Puts the operands of commutative operations in canonical order.
+#[derive(Debug, Default)]
+pub struct SortOperands;
+
+impl Pass for SortOperands {
+    fn name(&self) -> &'static str {
+        "sort_operands"
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::SortedOperands]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[
+            Analysis::CallGraph,
+            Analysis::Bindings,
+            Analysis::RegisterUsage,
+        ])
+    }
+}
+
+impl FunctionPass for SortOperands {
+    fn run_function(&mut self, rfn: &mut RadecoFunction) {
+        Sorter::new(rfn.ssa_mut()).run();
+    }
+}
+
+/// Fixes up call sites using the stack effect of every callee.
+///
+/// If the names of the base and stack pointer are not given, they are taken
+/// from the `BP` and `SP` aliases of the register profile.
+#[derive(Debug, Default)]
+pub struct FixCalls {
+    pub bp_name: Option<String>,
+    pub sp_name: Option<String>,
+}
+
+impl Pass for FixCalls {
+    fn name(&self) -> &'static str {
+        "fix_calls"
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        &[Analysis::CallGraph]
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::Bindings, Analysis::SortedOperands]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph])
+    }
+}
+
+impl ModulePass for FixCalls {
+    fn run_module(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile) {
+        let bp_name = self
+            .bp_name
+            .clone()
+            .or_else(|| regfile.alias_info.get("BP").cloned());
+        let sp_name = self
+            .sp_name
+            .clone()
+            .or_else(|| regfile.alias_info.get("SP").cloned());
+        CallFixer::new(rmod, bp_name, sp_name).rounded_analysis();
+    }
+}
+
+/// Infers the register usage of every function, see `infer_regusage::run`.
+#[derive(Debug, Default)]
+pub struct InferRegUsage;
+
+impl Pass for InferRegUsage {
+    fn name(&self) -> &'static str {
+        "infer_regusage"
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        &[Analysis::CallGraph]
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::RegisterUsage]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph])
+    }
+}
+
+impl ModulePass for InferRegUsage {
+    fn run_module(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile) {
+        infer_regusage::run(rmod, regfile);
+    }
+}
+
+/// Builds the Memory SSA of every function.
+#[derive(Debug, Default)]
+pub struct BuildMemorySSA;
+
+impl Pass for BuildMemorySSA {
+    fn name(&self) -> &'static str {
+        "memory_ssa"
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        &[Analysis::CallGraph]
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::MemorySSA]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::All
+    }
+}
+
+impl ModulePass for BuildMemorySSA {
+    fn run_module(&mut self, rmod: &mut RadecoModule, _: &SubRegisterFile) {
+        let callgraph = &rmod.callgraph;
+        for rfn in rmod.functions.values_mut() {
+            if rfn.ftype == FunctionType::Function {
+                rfn.build_memory_ssa(callgraph);
+            }
+        }
+    }
+}
+
+/// Forwards values stored to the stack or to constant addresses to the loads
+/// reading them, see `dead_stores::forward_stores`.
+///
+/// If the names of the base and stack pointer are not given, they are taken
+/// from the `BP` and `SP` aliases of the register profile.
+#[derive(Debug, Default)]
+pub struct ForwardStores {
+    pub bp_name: Option<String>,
+    pub sp_name: Option<String>,
+}
+
+impl Pass for ForwardStores {
+    fn name(&self) -> &'static str {
+        "forward_stores"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[Analysis::CallGraph, Analysis::RegisterUsage])
+    }
+}
+
+impl ModulePass for ForwardStores {
+    fn run_module(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile) {
+        if let Some((sp_name, bp_name)) = stack_registers(&self.sp_name, &self.bp_name, regfile) {
+            for rfn in rmod.functions.values_mut() {
+                if rfn.ftype == FunctionType::Function {
+                    dead_stores::forward_stores(rfn.ssa_mut(), &sp_name, &bp_name);
+                }
+            }
+        }
+    }
+}
+
+/// Removes stores that are never read, see `dead_stores::remove_dead_stores`.
+///
+/// If the names of the base and stack pointer are not given, they are taken
+/// from the `BP` and `SP` aliases of the register profile.
+#[derive(Debug, Default)]
+pub struct DeadStores {
+    pub bp_name: Option<String>,
+    pub sp_name: Option<String>,
+}
+
+impl Pass for DeadStores {
+    fn name(&self) -> &'static str {
+        "dead_stores"
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::Only(&[
+            Analysis::CallGraph,
+            Analysis::Bindings,
+            Analysis::SortedOperands,
+            Analysis::RegisterUsage,
+        ])
+    }
+}
+
+impl ModulePass for DeadStores {
+    fn run_module(&mut self, rmod: &mut RadecoModule, regfile: &SubRegisterFile) {
+        if let Some((sp_name, bp_name)) = stack_registers(&self.sp_name, &self.bp_name, regfile) {
+            for rfn in rmod.functions.values_mut() {
+                if rfn.ftype == FunctionType::Function {
+                    dead_stores::remove_dead_stores(rfn.ssa_mut(), &sp_name, &bp_name);
+                }
+            }
+        }
+    }
+}
+
+/// Infers the types of the values of every function, see `tie::run`.
+#[derive(Debug, Default)]
+pub struct InferTypes;
+
+impl Pass for InferTypes {
+    fn name(&self) -> &'static str {
+        "tie"
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        &[Analysis::CallGraph]
+    }
+
+    fn provides(&self) -> &'static [Analysis] {
+        &[Analysis::Types]
+    }
+
+    fn preserves(&self) -> Preserved {
+        Preserved::All
+    }
+}
+
+impl ModulePass for InferTypes {
+    fn run_module(&mut self, rmod: &mut RadecoModule, _: &SubRegisterFile) {
+        tie::run(rmod);
+    }
+}
+
+// Names of the stack and base pointer, defaulting to the aliases of `regfile`.
+fn stack_registers(
+    sp_name: &Option<String>,
+    bp_name: &Option<String>,
+    regfile: &SubRegisterFile,
+) -> Option<(String, String)> {
+    let sp_name = sp_name
+        .clone()
+        .or_else(|| regfile.alias_info.get("SP").cloned());
+    let bp_name = bp_name
+        .clone()
+        .or_else(|| regfile.alias_info.get("BP").cloned());
+    match (sp_name, bp_name) {
+        (Some(sp_name), Some(bp_name)) => Some((sp_name, bp_name)),
+        _ => {
+            radeco_warn!("Stack and base pointer are unknown, memory is left untouched");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use frontend::radeco_containers::ModuleLoader;
+    use frontend::radeco_source::FileSource;
+    use middle::ir::MOpcode;
+    use middle::ssa::ssa_traits::SSA;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    struct Mock {
+        name: &'static str,
+        requires: &'static [Analysis],
+        provides: &'static [Analysis],
+        preserves: Preserved,
+        log: Log,
+    }
+
+    impl Mock {
+        fn new(name: &'static str, log: &Log) -> Mock {
+            Mock {
+                name: name,
+                requires: &[],
+                provides: &[],
+                preserves: Preserved::All,
+                log: log.clone(),
+            }
+        }
+    }
+
+    impl Pass for Mock {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn requires(&self) -> &'static [Analysis] {
+            self.requires
+        }
+
+        fn provides(&self) -> &'static [Analysis] {
+            self.provides
+        }
+
+        fn preserves(&self) -> Preserved {
+            self.preserves
+        }
+    }
+
+    impl ModulePass for Mock {
+        fn run_module(&mut self, _: &mut RadecoModule, _: &SubRegisterFile) {
+            self.log.borrow_mut().push(self.name);
+        }
+    }
+
+    impl FunctionPass for Mock {
+        fn run_function(&mut self, _: &mut RadecoFunction) {
+            self.log.borrow_mut().push(self.name);
+        }
+    }
+
+    fn module() -> RadecoModule {
+        let mut rmod = RadecoModule::new("test".to_owned());
+        rmod.functions.insert(0x10, RadecoFunction::new());
+        rmod.functions.insert(0x20, RadecoFunction::new());
+        let mut import = RadecoFunction::new();
+        import.ftype = FunctionType::Import(0);
+        rmod.functions.insert(0x30, import);
+        rmod
+    }
+
+    #[test]
+    fn runs_in_order() {
+        let log = Log::default();
+        let mut pm = PassManager::new()
+            .module_pass(Mock::new("a", &log))
+            .function_pass(Mock::new("b", &log))
+            .module_pass(Mock::new("c", &log));
+        pm.run_module(&mut module(), &SubRegisterFile::default())
+            .unwrap();
+        // Function passes skip imports.
+        assert_eq!(*log.borrow(), vec!["a", "b", "b", "c"]);
+        let timed: Vec<_> = pm.timings().iter().map(|t| t.pass).collect();
+        assert_eq!(timed, vec!["a", "b", "c"]);
+        assert!(pm.timings().iter().all(|t| t.module == "test"));
+    }
+
+    #[test]
+    fn provides_on_demand() {
+        let log = Log::default();
+        let mut bindings = Mock::new("bindings", &log);
+        bindings.requires = &[Analysis::SortedOperands];
+        bindings.provides = &[Analysis::Bindings];
+        let mut sort = Mock::new("sort", &log);
+        sort.provides = &[Analysis::SortedOperands];
+        let mut user = Mock::new("user", &log);
+        user.requires = &[Analysis::Bindings];
+        let mut invalidate = Mock::new("invalidate", &log);
+        invalidate.preserves = Preserved::Only(&[Analysis::SortedOperands]);
+
+        let mut pm = PassManager::new()
+            .module_analysis(bindings)
+            .module_analysis(sort)
+            .module_pass(Mock::new("first", &log))
+            .module_pass(user)
+            .module_pass(invalidate);
+        let mut rmod = module();
+        pm.run_module(&mut rmod, &SubRegisterFile::default())
+            .unwrap();
+        assert_eq!(
+            *log.borrow(),
+            vec!["first", "sort", "bindings", "user", "invalidate"]
+        );
+
+        // Every run starts over with only the call graph being valid.
+        log.borrow_mut().clear();
+        pm.run_module(&mut rmod, &SubRegisterFile::default())
+            .unwrap();
+        assert_eq!(
+            *log.borrow(),
+            vec!["first", "sort", "bindings", "user", "invalidate"]
+        );
+    }
+
+    #[test]
+    fn invalidated_analysis_is_missing() {
+        let log = Log::default();
+        let mut provider = Mock::new("provider", &log);
+        provider.provides = &[Analysis::Bindings];
+        let mut clobber = Mock::new("clobber", &log);
+        clobber.preserves = Preserved::Only(&[Analysis::CallGraph]);
+        let mut user = Mock::new("user", &log);
+        user.requires = &[Analysis::Bindings, Analysis::CallGraph];
+
+        let mut pm = PassManager::new()
+            .module_pass(provider)
+            .module_pass(clobber)
+            .module_pass(user);
+        match pm.run_module(&mut module(), &SubRegisterFile::default()) {
+            Err(PassError::MissingAnalysis { pass, analysis }) => {
+                assert_eq!(pass, "user");
+                assert_eq!(analysis, Analysis::Bindings);
+            }
+            r => panic!("Unexpected result: {:?}", r),
+        }
+        assert_eq!(*log.borrow(), vec!["provider", "clobber"]);
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let log = Log::default();
+        let mut a = Mock::new("a", &log);
+        a.requires = &[Analysis::RegisterUsage];
+        a.provides = &[Analysis::Bindings];
+        let mut b = Mock::new("b", &log);
+        b.requires = &[Analysis::Bindings];
+        b.provides = &[Analysis::RegisterUsage];
+        let mut user = Mock::new("user", &log);
+        user.requires = &[Analysis::Bindings];
+
+        let mut pm = PassManager::new()
+            .function_analysis(a)
+            .function_analysis(b)
+            .module_pass(user);
+        match pm.run_module(&mut module(), &SubRegisterFile::default()) {
+            Err(PassError::DependencyCycle(Analysis::Bindings)) => {}
+            r => panic!("Unexpected result: {:?}", r),
+        }
+        assert!(log.borrow().is_empty());
+    }
+
+    fn bin1() -> RadecoModule {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        ModuleLoader::default()
+            .build_ssa()
+            .build_callgraph()
+            .load(Rc::new(FileSource::open(path.to_str().unwrap())))
+    }
+
+    #[test]
+    fn callgraph_after_sccp() {
+        let mut rmod = bin1();
+        let regfile = rmod.functions[&0x40059d].ssa().regfile.clone();
+        let mut pm = PassManager::new()
+            .module_analysis(BuildCallGraph)
+            .function_pass(Sccp)
+            .module_pass(FixCalls::default())
+            .module_pass(InferRegUsage)
+            .module_pass(BuildMemorySSA)
+            .module_pass(InferTypes);
+        pm.run_module(&mut rmod, &regfile).unwrap();
+        let timed: Vec<_> = pm.timings().iter().map(|t| t.pass).collect();
+        assert_eq!(
+            timed,
+            vec![
+                "sccp",
+                "callgraph",
+                "fix_calls",
+                "infer_regusage",
+                "memory_ssa",
+                "tie"
+            ]
+        );
+
+        // Every edge leads to a call at its call site.
+        assert!(rmod.callgraph.edge_count() > 0);
+        for edge in rmod.callgraph.raw_edges() {
+            let caller = rmod.callgraph[edge.source()];
+            let ssa = match rmod.functions.get(&caller) {
+                Some(rfn) => rfn.ssa(),
+                None => continue,
+            };
+            let cctx = &edge.weight;
+            assert_eq!(ssa.opcode(cctx.csite_node), Some(MOpcode::OpCall));
+            assert_eq!(
+                ssa.address(cctx.csite_node).map(|a| a.address),
+                Some(cctx.csite)
+            );
+        }
+    }
+}